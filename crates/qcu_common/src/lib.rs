@@ -6,6 +6,181 @@
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+/// Board description for the memory map and timer of a target SoC.
+///
+/// Collects every address the firmware touches in one value so that moving
+/// from QEMU's 'virt' machine to an FPGA SoC means constructing a different
+/// `Platform` rather than editing constants across crates. Firmware selects
+/// its platform at build time; the `mmio` constants are the QEMU values.
+pub mod platform {
+    /// Addresses and timer configuration of a target platform.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Platform {
+        /// Base address of the CLINT (Core Local Interruptor).
+        pub clint_base: usize,
+
+        /// Address of the UART transmit register.
+        pub uart_base: usize,
+
+        /// Base address of the union-find decoder accelerator.
+        pub accelerator_base: usize,
+
+        /// Base address of the quantum processing unit control registers.
+        pub qpu_base: usize,
+
+        /// Start of main RAM.
+        pub ram_base: usize,
+
+        /// First byte of the region firmware may hand to bump allocators.
+        pub heap_start: usize,
+
+        /// One past the last byte of the heap region.
+        pub heap_end: usize,
+
+        /// Frequency of the machine timer (MTIME) in Hz.
+        pub timer_hz: u64,
+    }
+
+    /// Offset of hart 0's MTIMECMP register from the CLINT base.
+    pub const MTIMECMP_OFFSET: usize = 0x4000;
+
+    /// Distance in bytes between consecutive harts' MTIMECMP registers.
+    pub const MTIMECMP_STRIDE: usize = 8;
+
+    /// Offset of the MTIME register from the CLINT base.
+    pub const MTIME_OFFSET: usize = 0xBFF8;
+
+    /// QEMU's RISC-V 'virt' machine with the QCU peripherals.
+    pub const QEMU_VIRT: Platform = Platform::qemu_virt();
+
+    impl Platform {
+        /// Returns the memory map of QEMU's RISC-V 'virt' machine.
+        ///
+        /// Standard CLINT and UART addresses, a 10 MHz timer, the decoder
+        /// accelerator at 0x4000_0000, the QPU registers in the next 64 KiB
+        /// of device space, and a 64 MiB heap region in the upper half of
+        /// the default 128 MiB of RAM.
+        pub const fn qemu_virt() -> Self {
+            Self {
+                clint_base: 0x200_0000,
+                uart_base: 0x1000_0000,
+                accelerator_base: 0x4000_0000,
+                qpu_base: 0x4001_0000,
+                ram_base: 0x8000_0000,
+                heap_start: 0x8400_0000,
+                heap_end: 0x8800_0000,
+                timer_hz: 10_000_000,
+            }
+        }
+
+        /// Returns a platform with a custom memory map.
+        ///
+        /// # Arguments
+        ///
+        /// * `clint_base` - Base address of the CLINT
+        /// * `uart_base` - Address of the UART transmit register
+        /// * `accelerator_base` - Base address of the decoder accelerator
+        /// * `qpu_base` - Base address of the QPU control registers
+        /// * `ram_base` - Start of main RAM
+        /// * `heap` - Heap region as (start, end), end exclusive
+        /// * `timer_hz` - Machine timer frequency in Hz
+        pub const fn custom(
+            clint_base: usize,
+            uart_base: usize,
+            accelerator_base: usize,
+            qpu_base: usize,
+            ram_base: usize,
+            heap: (usize, usize),
+            timer_hz: u64,
+        ) -> Self {
+            Self {
+                clint_base,
+                uart_base,
+                accelerator_base,
+                qpu_base,
+                ram_base,
+                heap_start: heap.0,
+                heap_end: heap.1,
+                timer_hz,
+            }
+        }
+
+        /// Returns the address of the MTIME register.
+        pub const fn mtime_addr(&self) -> usize {
+            self.clint_base + MTIME_OFFSET
+        }
+
+        /// Returns the address of a hart's MTIMECMP register.
+        ///
+        /// # Arguments
+        ///
+        /// * `hartid` - Hardware thread ID
+        pub const fn mtimecmp_addr(&self, hartid: usize) -> usize {
+            self.clint_base + MTIMECMP_OFFSET + hartid * MTIMECMP_STRIDE
+        }
+
+        /// Returns the size of the heap region in bytes.
+        pub const fn heap_size(&self) -> usize {
+            self.heap_end - self.heap_start
+        }
+
+        /// Checks the memory map for overlapping regions.
+        ///
+        /// Intended for a compile-time assertion in firmware. Fails if the
+        /// heap is empty or starts below RAM, or if any device register base
+        /// falls inside the RAM-backed range up to the end of the heap.
+        ///
+        /// # Returns
+        ///
+        /// True if the map is consistent.
+        pub const fn is_consistent(&self) -> bool {
+            self.heap_start >= self.ram_base
+                && self.heap_start < self.heap_end
+                && !self.in_ram(self.clint_base)
+                && !self.in_ram(self.uart_base)
+                && !self.in_ram(self.accelerator_base)
+                && !self.in_ram(self.qpu_base)
+        }
+
+        /// Returns true if `addr` lies between the RAM base and heap end.
+        const fn in_ram(&self, addr: usize) -> bool {
+            addr >= self.ram_base && addr < self.heap_end
+        }
+
+        /// Reads the machine timer counter.
+        ///
+        /// # Returns
+        ///
+        /// The current value of MTIME.
+        #[cfg(target_arch = "riscv64")]
+        #[inline(always)]
+        pub fn read_mtime(&self) -> u64 {
+            // MTIME is a 64-bit aligned, always-readable CLINT register.
+            unsafe { (self.mtime_addr() as *const u64).read_volatile() }
+        }
+
+        /// Writes a hart's machine timer compare register.
+        ///
+        /// A timer interrupt is raised on that hart once MTIME reaches
+        /// `value`.
+        ///
+        /// # Arguments
+        ///
+        /// * `hartid` - Hardware thread ID whose MTIMECMP is written
+        /// * `value` - Timer value at which to raise the interrupt
+        #[cfg(target_arch = "riscv64")]
+        #[inline(always)]
+        pub fn write_mtimecmp(&self, hartid: usize, value: u64) {
+            // Each hart's MTIMECMP is a 64-bit aligned, writable CLINT
+            // register.
+            unsafe { (self.mtimecmp_addr(hartid) as *mut u64).write_volatile(value) }
+        }
+    }
+}
+
 // Memory-mapped I/O address space definitions for the system-on-chip.
 //
 // Defines the physical address layout for peripherals including interrupt
@@ -13,27 +188,75 @@
 // addresses must match the hardware memory map and are used by both firmware
 // and host-side drivers for MMIO access.
 pub mod mmio {
+    use super::platform::QEMU_VIRT;
+
     /// Base address of the CLINT (Core Local Interruptor) in QEMU 'virt' machine.
     ///
     /// The CLINT provides per-hart machine-mode timer interrupts and software
     /// interrupt generation. This address is standard for QEMU's RISC-V virt
     /// platform and must match the device tree configuration.
-    pub const CLINT_BASE: usize = 0x200_0000;
+    pub const CLINT_BASE: usize = QEMU_VIRT.clint_base;
 
     /// Memory-mapped address for the machine timer compare register.
     ///
     /// When the machine timer (MTIME) reaches this value, a timer interrupt
     /// is generated. Writing to this register schedules the next interrupt.
     /// Offset from CLINT_BASE is 0x4000 for hart 0, with 8-byte increments
-    /// per additional hart.
-    pub const MTIMECMP_ADDR: usize = CLINT_BASE + 0x4000;
+    /// per additional hart; use `mtimecmp_addr` for other harts.
+    pub const MTIMECMP_ADDR: usize = QEMU_VIRT.mtimecmp_addr(0);
+
+    pub use super::platform::MTIMECMP_STRIDE;
+
+    /// Returns the address of a hart's machine timer compare register.
+    ///
+    /// # Arguments
+    ///
+    /// * `hartid` - Hardware thread ID
+    ///
+    /// # Returns
+    ///
+    /// `MTIMECMP_ADDR + hartid * MTIMECMP_STRIDE`.
+    pub const fn mtimecmp_addr(hartid: usize) -> usize {
+        QEMU_VIRT.mtimecmp_addr(hartid)
+    }
 
     /// Memory-mapped address for the machine timer counter register.
     ///
     /// This 64-bit read-only register increments at a fixed frequency (typically
     /// 10 MHz in QEMU). Used for timestamping and scheduling periodic events
     /// in the firmware scheduler.
-    pub const MTIME_ADDR: usize = CLINT_BASE + 0xBFF8;
+    pub const MTIME_ADDR: usize = QEMU_VIRT.mtime_addr();
+
+    /// Reads the machine timer counter.
+    ///
+    /// Wraps the volatile load from `MTIME_ADDR` so callers do not open-code
+    /// pointer arithmetic. Only available on RISC-V targets, where the CLINT
+    /// is mapped.
+    ///
+    /// # Returns
+    ///
+    /// The current value of MTIME.
+    #[cfg(target_arch = "riscv64")]
+    #[inline(always)]
+    pub fn read_mtime() -> u64 {
+        // MTIME is a 64-bit aligned, always-readable CLINT register.
+        unsafe { (MTIME_ADDR as *const u64).read_volatile() }
+    }
+
+    /// Writes a hart's machine timer compare register.
+    ///
+    /// A timer interrupt is raised on that hart once MTIME reaches `value`.
+    ///
+    /// # Arguments
+    ///
+    /// * `hartid` - Hardware thread ID whose MTIMECMP is written
+    /// * `value` - Timer value at which to raise the interrupt
+    #[cfg(target_arch = "riscv64")]
+    #[inline(always)]
+    pub fn write_mtimecmp(hartid: usize, value: u64) {
+        // Each hart's MTIMECMP is a 64-bit aligned, writable CLINT register.
+        unsafe { (mtimecmp_addr(hartid) as *mut u64).write_volatile(value) }
+    }
 
     /// Base address for the Union-Find Decoder Accelerator.
     ///
@@ -41,7 +264,7 @@ pub mod mmio {
     /// structure operations. The accelerator performs path compression and
     /// union operations in hardware to reduce decoder latency. This address
     /// must match the Verilog module's MMIO base address.
-    pub const ACCELERATOR_BASE: usize = 0x4000_0000;
+    pub const ACCELERATOR_BASE: usize = QEMU_VIRT.accelerator_base;
 
     /// Base address of high RAM region.
     ///
@@ -49,7 +272,43 @@ pub mod mmio {
     /// structures, and heap allocations reside. This address is standard for
     /// QEMU virt machine and marks the transition from device memory space
     /// to general-purpose RAM.
-    pub const RAM_BASE: usize = 0x8000_0000;
+    pub const RAM_BASE: usize = QEMU_VIRT.ram_base;
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::platform::Platform;
+
+        #[test]
+        fn clint_addresses_match_qemu_virt() {
+            assert_eq!(MTIMECMP_ADDR, 0x200_4000);
+            assert_eq!(mtimecmp_addr(0), 0x200_4000);
+            assert_eq!(mtimecmp_addr(3), 0x200_4018);
+            assert_eq!(MTIME_ADDR, 0x200_BFF8);
+            for hart in 0..8 {
+                assert_eq!(mtimecmp_addr(hart), QEMU_VIRT.mtimecmp_addr(hart));
+            }
+            assert!(QEMU_VIRT.is_consistent());
+        }
+
+        #[test]
+        fn custom_platforms_offset_from_their_clint() {
+            let board = Platform::custom(
+                0x1000_0000,
+                0x2000_0000,
+                0x3000_0000,
+                0x3001_0000,
+                0x8000_0000,
+                (0x8010_0000, 0x8020_0000),
+                1_000_000,
+            );
+            assert_eq!(board.mtimecmp_addr(0), 0x1000_4000);
+            assert_eq!(board.mtimecmp_addr(2), 0x1000_4010);
+            assert_eq!(board.mtime_addr(), 0x1000_BFF8);
+            assert_eq!(board.heap_size(), 0x10_0000);
+            assert!(board.is_consistent());
+        }
+    }
 }
 
 /// Instruction Set Architecture definitions for quantum error correction operations.
@@ -82,6 +341,13 @@ pub mod isa {
         /// representation of the quantum state.
         GateS = 0x02,
 
+        /// Apply inverse Phase gate (S† gate) to the specified qubit.
+        ///
+        /// Applies a -π/2 rotation around the Z axis, undoing GateS. Its
+        /// Pauli frame update is identical to GateS because the frame does
+        /// not track operator signs.
+        GateSdg = 0x04,
+
         /// Apply CNOT gate with control and target qubits.
         ///
         /// Performs a controlled-NOT operation that entangles the control
@@ -90,6 +356,19 @@ pub mod isa {
         /// by CNOT.
         GateCNOT = 0x03,
 
+        /// Apply a Pauli X to the specified qubit.
+        ///
+        /// Used for explicit feed-forward corrections. In the Pauli frame
+        /// this toggles the qubit's X error bit rather than acting on the
+        /// hardware.
+        GateX = 0x05,
+
+        /// Apply a Pauli Z to the specified qubit.
+        ///
+        /// The Z counterpart of GateX: toggles the qubit's Z error bit in
+        /// the Pauli frame.
+        GateZ = 0x06,
+
         /// Measure a qubit and record the result.
         ///
         /// Performs a destructive measurement in the Z basis and compares
@@ -98,6 +377,21 @@ pub mod isa {
         /// for the associated detector.
         Measure = 0x10,
 
+        /// Measure a qubit in the X basis and record the result.
+        ///
+        /// Like Measure, but the measurement is flipped by Z errors rather
+        /// than X errors, so the result is compared against the Pauli
+        /// frame's Z error prediction.
+        MeasureX = 0x11,
+
+        /// Measure a qubit in the Z basis and fold it into a logical observable.
+        ///
+        /// Operand 1 is the qubit index and operand 2 is the observable index
+        /// (below 64). The frame-adjusted outcome is recorded like Measure,
+        /// but instead of firing a detector it toggles the observable's bit in
+        /// the VM's observable register.
+        ObservableInclude = 0x12,
+
         /// Execute the Union-Find decoder on accumulated syndrome data.
         ///
         /// Triggers the decoding algorithm to process all syndrome bits
@@ -106,6 +400,14 @@ pub mod isa {
         /// these corrections are applied to the Pauli frame.
         Decode = 0x20,
 
+        /// Decode and apply corrections before any further instruction.
+        ///
+        /// Operand 1 is a caller-chosen tag. Acts like Decode, except that the
+        /// corrections are always applied to the frame, and the tag is
+        /// reported so a scheduler can confirm the decode for a round finished
+        /// before the next round's gates were issued.
+        Barrier = 0x21,
+
         /// Reset the Pauli frame and clear all syndrome buffers.
         ///
         /// Clears all tracked X and Z errors in the Pauli frame, effectively
@@ -114,6 +416,49 @@ pub mod isa {
         /// cycle. Used for initialization and recovery from uncorrectable errors.
         Reset = 0x30,
 
+        /// Begin a loop body that executes a fixed number of times.
+        ///
+        /// Operand 1 is the repeat count; a count of zero skips the body.
+        /// The body extends to the matching LoopEnd. Loops may nest up to a
+        /// small fixed depth set by the executing VM.
+        LoopBegin = 0x50,
+
+        /// End the innermost loop body.
+        ///
+        /// Jumps back to the instruction after the matching LoopBegin until
+        /// its repeat count is exhausted.
+        LoopEnd = 0x51,
+
+        /// Advance the detector base used by measurement instructions.
+        ///
+        /// Operand 1 is the delta. Measure and MeasureX interpret their
+        /// detector operand relative to the running base, as Stim's
+        /// SHIFT_COORDS does for repeated rounds, so a loop body can reuse
+        /// the same detector operands on every iteration.
+        ShiftDetectors = 0x52,
+
+        /// Apply a Pauli X to a qubit if a recorded measurement was 1.
+        ///
+        /// Operand 1 is the qubit index and operand 2 is an index into the
+        /// VM's measurement record. Provides the classical feedback needed
+        /// by teleportation-style and lattice-surgery programs.
+        CondPauliX = 0x40,
+
+        /// Apply a Pauli Z to a qubit if a recorded measurement was 1.
+        ///
+        /// Operands are interpreted as for CondPauliX.
+        CondPauliZ = 0x41,
+
+        /// Prefix supplying the high 16 bits of the next instruction's operands.
+        ///
+        /// Operands 1 and 2 become bits 16..32 of the following instruction's
+        /// operands 1 and 2, so qubit and detector indices beyond 65535 can be
+        /// encoded without changing the 6-byte instruction size. The prefix and
+        /// the instruction it extends execute as one `Instruction32`; a prefix
+        /// that is last in the program or followed by another prefix is
+        /// invalid.
+        Wide = 0x60,
+
         /// Halt execution and enter idle state.
         ///
         /// Stops instruction processing and places the system in a low-power
@@ -123,14 +468,119 @@ pub mod isa {
         Halt = 0xFF,
     }
 
+    impl Opcode {
+        /// Every defined opcode, in ascending order of encoding.
+        ///
+        /// Used for table-driven dispatch, exhaustive round-trip checks, and
+        /// listing the instruction set in host tools.
+        pub const ALL: &'static [Opcode] = &[
+            Opcode::GateH,
+            Opcode::GateS,
+            Opcode::GateCNOT,
+            Opcode::GateSdg,
+            Opcode::GateX,
+            Opcode::GateZ,
+            Opcode::Measure,
+            Opcode::MeasureX,
+            Opcode::ObservableInclude,
+            Opcode::Decode,
+            Opcode::Barrier,
+            Opcode::Reset,
+            Opcode::CondPauliX,
+            Opcode::CondPauliZ,
+            Opcode::LoopBegin,
+            Opcode::LoopEnd,
+            Opcode::ShiftDetectors,
+            Opcode::Wide,
+            Opcode::Halt,
+        ];
+
+        /// Returns the assembly mnemonic for this opcode.
+        ///
+        /// Mnemonics are upper case and unique, so they can be used as the
+        /// textual form of an instruction in traces and disassembly.
+        pub fn mnemonic(&self) -> &'static str {
+            match self {
+                Opcode::GateH => "H",
+                Opcode::GateS => "S",
+                Opcode::GateSdg => "SDG",
+                Opcode::GateCNOT => "CNOT",
+                Opcode::GateX => "X",
+                Opcode::GateZ => "Z",
+                Opcode::Measure => "M",
+                Opcode::MeasureX => "MX",
+                Opcode::ObservableInclude => "OBS_INCLUDE",
+                Opcode::Decode => "DECODE",
+                Opcode::Barrier => "BARRIER",
+                Opcode::Reset => "RESET",
+                Opcode::CondPauliX => "CPX",
+                Opcode::CondPauliZ => "CPZ",
+                Opcode::LoopBegin => "LOOP",
+                Opcode::LoopEnd => "ENDLOOP",
+                Opcode::ShiftDetectors => "SHIFT_DETECTORS",
+                Opcode::Wide => "WIDE",
+                Opcode::Halt => "HALT",
+            }
+        }
+    }
+
+    /// Error returned when a byte does not correspond to any opcode.
+    ///
+    /// Carries the offending byte so callers can report it.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct UnknownOpcode(pub u8);
+
+    impl TryFrom<u8> for Opcode {
+        type Error = UnknownOpcode;
+
+        /// Decodes an opcode byte from an instruction stream.
+        ///
+        /// # Arguments
+        ///
+        /// * `byte` - Raw opcode byte
+        ///
+        /// # Returns
+        ///
+        /// The matching opcode, or `UnknownOpcode` if the byte is not assigned.
+        fn try_from(byte: u8) -> Result<Self, Self::Error> {
+            match byte {
+                0x01 => Ok(Opcode::GateH),
+                0x02 => Ok(Opcode::GateS),
+                0x03 => Ok(Opcode::GateCNOT),
+                0x04 => Ok(Opcode::GateSdg),
+                0x05 => Ok(Opcode::GateX),
+                0x06 => Ok(Opcode::GateZ),
+                0x10 => Ok(Opcode::Measure),
+                0x11 => Ok(Opcode::MeasureX),
+                0x12 => Ok(Opcode::ObservableInclude),
+                0x20 => Ok(Opcode::Decode),
+                0x21 => Ok(Opcode::Barrier),
+                0x30 => Ok(Opcode::Reset),
+                0x40 => Ok(Opcode::CondPauliX),
+                0x41 => Ok(Opcode::CondPauliZ),
+                0x50 => Ok(Opcode::LoopBegin),
+                0x51 => Ok(Opcode::LoopEnd),
+                0x52 => Ok(Opcode::ShiftDetectors),
+                0x60 => Ok(Opcode::Wide),
+                0xFF => Ok(Opcode::Halt),
+                other => Err(UnknownOpcode(other)),
+            }
+        }
+    }
+
     /// Binary instruction format for quantum error correction operations.
     ///
     /// Encodes a single quantum operation with opcode and operands in a
     /// compact 6-byte format suitable for instruction fetch and decode
     /// pipelines. The packed representation ensures efficient memory usage
     /// and cache-friendly instruction streams.
+    ///
+    /// Because the struct is packed, the operand fields are unaligned and
+    /// must never be borrowed; read and write them through `op1`, `op2`,
+    /// `set_op1`, and `set_op2`, which copy by value. `Debug` is implemented
+    /// through the same accessors.
     #[repr(C, packed)]
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Clone, Copy)]
     pub struct Instruction {
         /// Operation code identifying the instruction type.
         ///
@@ -183,5 +633,1624 @@ pub mod isa {
                 _padding: 0,
             }
         }
+
+        /// Returns the first operand.
+        ///
+        /// Copies the value out of the packed struct, so no unaligned
+        /// reference to the field is ever created.
+        #[inline(always)]
+        pub fn op1(&self) -> u16 {
+            self.operand_1
+        }
+
+        /// Returns the second operand.
+        ///
+        /// Copies the value out of the packed struct, like `op1`.
+        #[inline(always)]
+        pub fn op2(&self) -> u16 {
+            self.operand_2
+        }
+
+        /// Sets the first operand.
+        ///
+        /// Writes by value, so no unaligned reference is created.
+        ///
+        /// # Arguments
+        ///
+        /// * `value` - New first operand
+        #[inline(always)]
+        pub fn set_op1(&mut self, value: u16) {
+            self.operand_1 = value;
+        }
+
+        /// Sets the second operand.
+        ///
+        /// # Arguments
+        ///
+        /// * `value` - New second operand
+        #[inline(always)]
+        pub fn set_op2(&mut self, value: u16) {
+            self.operand_2 = value;
+        }
+
+        /// Returns the first operand.
+        #[deprecated(note = "renamed to `op1`")]
+        #[inline(always)]
+        pub fn operand_1(&self) -> u16 {
+            self.op1()
+        }
+
+        /// Returns the second operand.
+        #[deprecated(note = "renamed to `op2`")]
+        #[inline(always)]
+        pub fn operand_2(&self) -> u16 {
+            self.op2()
+        }
+
+        /// Encodes the instruction into its 6-byte wire format.
+        ///
+        /// The layout is the opcode byte, operand 1 and operand 2 as
+        /// little-endian u16 values, and the padding byte. The encoding is
+        /// independent of host endianness and never reinterprets the struct's
+        /// memory, so it is the only supported way to serialise a program.
+        ///
+        /// # Returns
+        ///
+        /// The encoded instruction.
+        pub fn to_bytes(&self) -> [u8; INSTRUCTION_SIZE] {
+            let [a1, b1] = self.op1().to_le_bytes();
+            let [a2, b2] = self.op2().to_le_bytes();
+            [self.opcode, a1, b1, a2, b2, self._padding]
+        }
+
+        /// Decodes an instruction from its 6-byte wire format.
+        ///
+        /// Inverse of `to_bytes`. The opcode must be a known `Opcode`; the
+        /// padding byte is preserved but not checked.
+        ///
+        /// # Arguments
+        ///
+        /// * `bytes` - Encoded instruction
+        ///
+        /// # Returns
+        ///
+        /// The decoded instruction, or `IsaError::UnknownOpcode` if the opcode
+        /// byte is not assigned.
+        pub fn from_bytes(bytes: &[u8; INSTRUCTION_SIZE]) -> Result<Self, IsaError> {
+            Opcode::try_from(bytes[0])?;
+            Ok(Self {
+                opcode: bytes[0],
+                operand_1: u16::from_le_bytes([bytes[1], bytes[2]]),
+                operand_2: u16::from_le_bytes([bytes[3], bytes[4]]),
+                _padding: bytes[5],
+            })
+        }
+    }
+
+    impl core::fmt::Debug for Instruction {
+        /// Formats the instruction through its copy-out accessors.
+        ///
+        /// A derived `Debug` would borrow the packed fields, so the fields are
+        /// copied into locals first.
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            let padding = self._padding;
+            f.debug_struct("Instruction")
+                .field("opcode", &self.opcode)
+                .field("operand_1", &self.op1())
+                .field("operand_2", &self.op2())
+                .field("_padding", &padding)
+                .finish()
+        }
+    }
+
+    /// Size of one encoded instruction in bytes.
+    pub const INSTRUCTION_SIZE: usize = 6;
+
+    /// Size of a Wide-prefixed instruction in bytes.
+    pub const WIDE_INSTRUCTION_SIZE: usize = 2 * INSTRUCTION_SIZE;
+
+    /// Instruction with 32-bit operands.
+    ///
+    /// The form in which the VM executes every instruction. Operands that fit
+    /// in 16 bits encode as a plain 6-byte `Instruction`; larger operands
+    /// encode as an `Opcode::Wide` prefix carrying the high halves followed by
+    /// the instruction with the low halves, 12 bytes in total. Both forms can
+    /// be mixed freely in one stream.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Instruction32 {
+        /// Operation code identifying the instruction type.
+        ///
+        /// Never `Opcode::Wide` for an instruction produced by `decode_at`.
+        pub opcode: u8,
+
+        /// Flag byte, carried in the padding byte of the base instruction.
+        ///
+        /// No flags are defined yet; always zero for instructions built with
+        /// `new`.
+        pub flags: u8,
+
+        /// First operand: qubit index, control qubit, or loop count.
+        pub operand_1: u32,
+
+        /// Second operand: target qubit, detector ID, or unused.
+        pub operand_2: u32,
+    }
+
+    impl Instruction32 {
+        /// Constructs a wide instruction with zero flags.
+        ///
+        /// # Arguments
+        ///
+        /// * `opcode` - The operation to perform
+        /// * `op1` - First operand
+        /// * `op2` - Second operand
+        pub fn new(opcode: Opcode, op1: u32, op2: u32) -> Self {
+            Self {
+                opcode: opcode as u8,
+                flags: 0,
+                operand_1: op1,
+                operand_2: op2,
+            }
+        }
+
+        /// Returns true if either operand needs a Wide prefix to encode.
+        pub fn needs_prefix(&self) -> bool {
+            self.operand_1 > u16::MAX as u32 || self.operand_2 > u16::MAX as u32
+        }
+
+        /// Returns the number of bytes `encode_into` writes.
+        pub fn encoded_len(&self) -> usize {
+            if self.needs_prefix() {
+                WIDE_INSTRUCTION_SIZE
+            } else {
+                INSTRUCTION_SIZE
+            }
+        }
+
+        /// Returns the 6-byte instruction if no prefix is needed.
+        ///
+        /// # Returns
+        ///
+        /// The narrow form, or None if an operand exceeds 16 bits.
+        pub fn narrow(&self) -> Option<Instruction> {
+            if self.needs_prefix() {
+                return None;
+            }
+            Some(Instruction {
+                opcode: self.opcode,
+                operand_1: self.operand_1 as u16,
+                operand_2: self.operand_2 as u16,
+                _padding: self.flags,
+            })
+        }
+
+        /// Splits the instruction into a Wide prefix and a base instruction.
+        ///
+        /// # Returns
+        ///
+        /// The prefix carrying the operands' high halves (always present,
+        /// even if both are zero) and the base carrying the low halves.
+        pub fn split(&self) -> (Instruction, Instruction) {
+            let prefix = Instruction::new(
+                Opcode::Wide,
+                (self.operand_1 >> 16) as u16,
+                (self.operand_2 >> 16) as u16,
+            );
+            let base = Instruction {
+                opcode: self.opcode,
+                operand_1: self.operand_1 as u16,
+                operand_2: self.operand_2 as u16,
+                _padding: self.flags,
+            };
+            (prefix, base)
+        }
+
+        /// Combines an optional Wide prefix with the instruction it extends.
+        ///
+        /// # Arguments
+        ///
+        /// * `prefix` - Wide prefix preceding `base`, if any
+        /// * `base` - Instruction supplying the opcode and low operand halves
+        ///
+        /// # Returns
+        ///
+        /// The combined instruction, or `IsaError::UnknownOpcode` carrying
+        /// `Opcode::Wide` if `base` is itself a prefix.
+        pub fn from_parts(
+            prefix: Option<Instruction>,
+            base: Instruction,
+        ) -> Result<Self, IsaError> {
+            if base.opcode == Opcode::Wide as u8 {
+                return Err(IsaError::UnknownOpcode(base.opcode));
+            }
+            let (hi1, hi2) = prefix.map_or((0, 0), |p| (p.op1(), p.op2()));
+            Ok(Self {
+                opcode: base.opcode,
+                flags: base._padding,
+                operand_1: (hi1 as u32) << 16 | base.op1() as u32,
+                operand_2: (hi2 as u32) << 16 | base.op2() as u32,
+            })
+        }
+
+        /// Encodes the instruction in its shortest form.
+        ///
+        /// # Arguments
+        ///
+        /// * `out` - Destination, at least `encoded_len()` bytes long
+        ///
+        /// # Returns
+        ///
+        /// The number of bytes written (6 or 12).
+        ///
+        /// # Panics
+        ///
+        /// Panics if `out` is shorter than `encoded_len()`.
+        pub fn encode_into(&self, out: &mut [u8]) -> usize {
+            match self.narrow() {
+                Some(instr) => {
+                    out[..INSTRUCTION_SIZE].copy_from_slice(&instr.to_bytes());
+                    INSTRUCTION_SIZE
+                }
+                None => {
+                    let (prefix, base) = self.split();
+                    out[..INSTRUCTION_SIZE].copy_from_slice(&prefix.to_bytes());
+                    out[INSTRUCTION_SIZE..WIDE_INSTRUCTION_SIZE].copy_from_slice(&base.to_bytes());
+                    WIDE_INSTRUCTION_SIZE
+                }
+            }
+        }
+
+        /// Encodes the instruction as a Wide prefix and base instruction.
+        ///
+        /// Always produces the 12-byte form, even when the operands fit in
+        /// 16 bits; `decode_at` accepts either form.
+        ///
+        /// # Returns
+        ///
+        /// The encoded prefix and instruction.
+        pub fn to_bytes(&self) -> [u8; WIDE_INSTRUCTION_SIZE] {
+            let (prefix, base) = self.split();
+            let mut out = [0u8; WIDE_INSTRUCTION_SIZE];
+            out[..INSTRUCTION_SIZE].copy_from_slice(&prefix.to_bytes());
+            out[INSTRUCTION_SIZE..].copy_from_slice(&base.to_bytes());
+            out
+        }
+
+        /// Decodes the instruction starting at a byte offset in a stream.
+        ///
+        /// Reads a plain 6-byte instruction, or a Wide prefix and the
+        /// instruction it extends.
+        ///
+        /// # Arguments
+        ///
+        /// * `bytes` - Encoded program
+        /// * `offset` - Byte offset of the instruction, a multiple of
+        ///   `INSTRUCTION_SIZE`
+        ///
+        /// # Returns
+        ///
+        /// The decoded instruction and its encoded length in bytes;
+        /// `IsaError::UnknownOpcode` for an unassigned opcode or a prefix
+        /// followed by another prefix; or `IsaError::TrailingBytes` if the
+        /// stream ends inside the instruction.
+        pub fn decode_at(bytes: &[u8], offset: usize) -> Result<(Self, usize), IsaError> {
+            let read = |at: usize| -> Result<Instruction, IsaError> {
+                let chunk = bytes
+                    .get(at..at + INSTRUCTION_SIZE)
+                    .ok_or(IsaError::TrailingBytes {
+                        count: bytes.len().saturating_sub(at),
+                    })?;
+                Instruction::from_bytes(chunk.try_into().expect("chunk is INSTRUCTION_SIZE bytes"))
+            };
+            let first = read(offset)?;
+            if first.opcode != Opcode::Wide as u8 {
+                return Ok((Self::from_parts(None, first)?, INSTRUCTION_SIZE));
+            }
+            let base = read(offset + INSTRUCTION_SIZE)?;
+            Ok((Self::from_parts(Some(first), base)?, WIDE_INSTRUCTION_SIZE))
+        }
+    }
+
+    impl From<Instruction> for Instruction32 {
+        fn from(instr: Instruction) -> Self {
+            Self {
+                opcode: instr.opcode,
+                flags: instr._padding,
+                operand_1: instr.op1() as u32,
+                operand_2: instr.op2() as u32,
+            }
+        }
+    }
+
+    /// Errors produced while decoding an instruction stream.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum IsaError {
+        /// The opcode byte does not correspond to any `Opcode`.
+        UnknownOpcode(u8),
+
+        /// The stream length is not a multiple of `INSTRUCTION_SIZE`.
+        ///
+        /// Carries the number of leftover bytes after the last whole
+        /// instruction. Distinct from `UnknownOpcode` so a transport layer
+        /// can reject (NAK) a truncated program before executing any of it.
+        TrailingBytes {
+            /// Number of bytes after the last complete instruction.
+            count: usize,
+        },
+    }
+
+    impl From<UnknownOpcode> for IsaError {
+        fn from(err: UnknownOpcode) -> Self {
+            IsaError::UnknownOpcode(err.0)
+        }
+    }
+
+    /// Iterator decoding instructions from a raw byte buffer.
+    ///
+    /// Yields one result per 6-byte chunk. If the buffer length is not a
+    /// multiple of `INSTRUCTION_SIZE`, yields a final
+    /// `IsaError::TrailingBytes` after the last complete instruction.
+    pub struct InstructionStream<'a> {
+        /// Bytes not yet decoded.
+        remaining: &'a [u8],
+    }
+
+    impl<'a> InstructionStream<'a> {
+        /// Creates a stream over an encoded program.
+        ///
+        /// # Arguments
+        ///
+        /// * `bytes` - Encoded instructions, back to back
+        pub fn new(bytes: &'a [u8]) -> Self {
+            Self { remaining: bytes }
+        }
+
+        /// Checks that a buffer holds a whole number of instructions.
+        ///
+        /// # Arguments
+        ///
+        /// * `bytes` - Encoded program
+        ///
+        /// # Returns
+        ///
+        /// Ok(()) if the length is a multiple of `INSTRUCTION_SIZE`,
+        /// otherwise `IsaError::TrailingBytes`.
+        pub fn check_length(bytes: &[u8]) -> Result<(), IsaError> {
+            match bytes.len() % INSTRUCTION_SIZE {
+                0 => Ok(()),
+                count => Err(IsaError::TrailingBytes { count }),
+            }
+        }
+    }
+
+    impl Iterator for InstructionStream<'_> {
+        type Item = Result<Instruction, IsaError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.remaining.is_empty() {
+                return None;
+            }
+            match self.remaining.split_first_chunk::<INSTRUCTION_SIZE>() {
+                Some((chunk, rest)) => {
+                    self.remaining = rest;
+                    Some(Instruction::from_bytes(chunk))
+                }
+                None => {
+                    let count = self.remaining.len();
+                    self.remaining = &[];
+                    Some(Err(IsaError::TrailingBytes { count }))
+                }
+            }
+        }
+    }
+
+    /// Magic number opening a checksummed instruction stream ("QPRG").
+    pub const STREAM_MAGIC: u32 = u32::from_le_bytes(*b"QPRG");
+
+    /// Version of the checksummed stream format written by this build.
+    pub const STREAM_VERSION: u16 = 1;
+
+    /// Size of a checksummed stream header in bytes.
+    pub const STREAM_HEADER_SIZE: usize = 16;
+
+    /// Errors produced while decoding a checksummed instruction stream.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum StreamError {
+        /// The buffer ends before the header or the declared instructions.
+        Truncated {
+            /// Bytes the stream needs.
+            needed: usize,
+            /// Bytes available.
+            available: usize,
+        },
+
+        /// The magic number does not match `STREAM_MAGIC`.
+        BadMagic(u32),
+
+        /// The stream was written by an incompatible format version.
+        UnsupportedVersion(u16),
+
+        /// The declared instruction count does not fit the receive buffer.
+        TooLong {
+            /// Instruction count from the header.
+            count: usize,
+            /// Instructions the buffer can hold.
+            capacity: usize,
+        },
+
+        /// The CRC-32 over header and instructions does not match.
+        ChecksumMismatch {
+            /// CRC carried in the header.
+            expected: u32,
+            /// CRC computed over the received bytes.
+            actual: u32,
+        },
+
+        /// An instruction in a checksum-valid stream failed to decode.
+        Isa(IsaError),
+    }
+
+    impl From<IsaError> for StreamError {
+        fn from(err: IsaError) -> Self {
+            StreamError::Isa(err)
+        }
+    }
+
+    /// Encodes the fields of a stream header other than its CRC.
+    ///
+    /// Layout, little-endian: magic (4 bytes), version (2), reserved zero
+    /// (2), instruction count (4), CRC-32 (4). The CRC covers these first
+    /// 12 bytes followed by the instruction bytes.
+    fn stream_header_prefix(count: u32) -> [u8; STREAM_HEADER_SIZE] {
+        let mut header = [0u8; STREAM_HEADER_SIZE];
+        header[0..4].copy_from_slice(&STREAM_MAGIC.to_le_bytes());
+        header[4..6].copy_from_slice(&STREAM_VERSION.to_le_bytes());
+        header[8..12].copy_from_slice(&count.to_le_bytes());
+        header
+    }
+
+    /// Checks a stream header's magic and version.
+    ///
+    /// # Returns
+    ///
+    /// The instruction count and CRC carried by the header.
+    fn parse_stream_header(header: &[u8; STREAM_HEADER_SIZE]) -> Result<(usize, u32), StreamError> {
+        let word = |at: usize| {
+            u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+        };
+        let magic = word(0);
+        if magic != STREAM_MAGIC {
+            return Err(StreamError::BadMagic(magic));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != STREAM_VERSION {
+            return Err(StreamError::UnsupportedVersion(version));
+        }
+        Ok((word(8) as usize, word(12)))
+    }
+
+    /// Encodes instructions as a checksummed stream.
+    ///
+    /// Writes a 16-byte header (magic, version, instruction count, CRC-32)
+    /// followed by each instruction in its 6-byte wire format. Suited to
+    /// lossy links such as a UART, where the receiver must be able to reject
+    /// a damaged program and find the start of the next one.
+    ///
+    /// # Arguments
+    ///
+    /// * `instrs` - Instructions to encode
+    /// * `out` - Destination for the encoded bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `u32::MAX` instructions.
+    pub fn encode_stream(instrs: &[Instruction], out: &mut impl Extend<u8>) {
+        let count = u32::try_from(instrs.len()).expect("instruction count fits in u32");
+        let mut header = stream_header_prefix(count);
+        let mut crc = crate::protocol::Crc32::new();
+        crc.update(&header[..12]);
+        for instr in instrs {
+            crc.update(&instr.to_bytes());
+        }
+        header[12..].copy_from_slice(&crc.finish().to_le_bytes());
+
+        out.extend(header);
+        for instr in instrs {
+            out.extend(instr.to_bytes());
+        }
+    }
+
+    /// Decodes a complete checksummed stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Exactly one encoded stream
+    ///
+    /// # Returns
+    ///
+    /// The instructions; `Truncated` if the buffer is shorter than the header
+    /// declares; `Isa(TrailingBytes)` if bytes follow the last instruction;
+    /// `BadMagic`, `UnsupportedVersion`, or `ChecksumMismatch` for a damaged
+    /// header or body; or `Isa(UnknownOpcode)` for an unassigned opcode.
+    #[cfg(feature = "alloc")]
+    pub fn decode_stream(bytes: &[u8]) -> Result<alloc::vec::Vec<Instruction>, StreamError> {
+        let (header, body) =
+            bytes
+                .split_first_chunk::<STREAM_HEADER_SIZE>()
+                .ok_or(StreamError::Truncated {
+                    needed: STREAM_HEADER_SIZE,
+                    available: bytes.len(),
+                })?;
+        let (count, expected) = parse_stream_header(header)?;
+        let len = count.saturating_mul(INSTRUCTION_SIZE);
+        if body.len() < len {
+            return Err(StreamError::Truncated {
+                needed: STREAM_HEADER_SIZE + len,
+                available: bytes.len(),
+            });
+        }
+        if body.len() > len {
+            return Err(IsaError::TrailingBytes {
+                count: body.len() - len,
+            }
+            .into());
+        }
+
+        let mut crc = crate::protocol::Crc32::new();
+        crc.update(&header[..12]);
+        crc.update(body);
+        let actual = crc.finish();
+        if actual != expected {
+            return Err(StreamError::ChecksumMismatch { expected, actual });
+        }
+
+        InstructionStream::new(body)
+            .map(|r| r.map_err(StreamError::from))
+            .collect()
+    }
+
+    /// Incremental decoder for checksummed streams arriving byte by byte.
+    ///
+    /// Buffers a stream in caller-provided storage and releases it only once
+    /// the whole stream has arrived and its CRC and opcodes check out, so a
+    /// firmware receiver never executes part of a damaged program. Bytes
+    /// that cannot start a header are skipped one at a time, and any error
+    /// drops back to searching for the next magic number, so the decoder
+    /// resynchronises after line noise or a lost packet. Needs no allocator.
+    pub struct StreamDecoder<'a> {
+        /// Storage for the instruction bytes of the current stream.
+        buf: &'a mut [u8],
+
+        /// Header bytes received so far.
+        header: [u8; STREAM_HEADER_SIZE],
+
+        /// Number of valid bytes in `header`.
+        header_len: usize,
+
+        /// Instruction count and CRC of the current stream, once its header
+        /// has been accepted.
+        expect: Option<(usize, u32)>,
+
+        /// Number of instruction bytes received into `buf`.
+        body_len: usize,
+
+        /// True once a complete stream has been validated.
+        ready: bool,
+    }
+
+    impl<'a> StreamDecoder<'a> {
+        /// Creates a decoder storing instruction bytes in `buf`.
+        ///
+        /// # Arguments
+        ///
+        /// * `buf` - Receive buffer; streams longer than
+        ///   `buf.len() / INSTRUCTION_SIZE` instructions are rejected
+        pub fn new(buf: &'a mut [u8]) -> Self {
+            Self {
+                buf,
+                header: [0; STREAM_HEADER_SIZE],
+                header_len: 0,
+                expect: None,
+                body_len: 0,
+                ready: false,
+            }
+        }
+
+        /// Discards any partial or completed stream and searches for a header.
+        pub fn reset(&mut self) {
+            self.header_len = 0;
+            self.expect = None;
+            self.body_len = 0;
+            self.ready = false;
+        }
+
+        /// Feeds one received byte into the decoder.
+        ///
+        /// Once a stream completes, further bytes are ignored until
+        /// `reset` is called, so the caller can consume the program first.
+        ///
+        /// # Arguments
+        ///
+        /// * `byte` - Next byte from the link
+        ///
+        /// # Returns
+        ///
+        /// `Ok(Some(count))` when a validated stream of `count` instructions
+        /// is ready, `Ok(None)` while more bytes are needed, or an error
+        /// describing a rejected stream (the decoder has already started
+        /// searching for the next header).
+        pub fn push(&mut self, byte: u8) -> Result<Option<usize>, StreamError> {
+            let result = self.step(byte);
+            if result.is_err() && self.expect.is_some() {
+                self.rescan();
+            }
+            result
+        }
+
+        /// Processes one byte.
+        ///
+        /// A stream rejected after its header was accepted is left in place,
+        /// with `expect` still set, for `push` to rescan.
+        fn step(&mut self, byte: u8) -> Result<Option<usize>, StreamError> {
+            if self.ready {
+                return Ok(None);
+            }
+            let Some((count, expected)) = self.expect else {
+                return self.push_header(byte);
+            };
+
+            self.buf[self.body_len] = byte;
+            self.body_len += 1;
+            if self.body_len < count * INSTRUCTION_SIZE {
+                return Ok(None);
+            }
+
+            let body = &self.buf[..self.body_len];
+            let mut crc = crate::protocol::Crc32::new();
+            crc.update(&self.header[..12]);
+            crc.update(body);
+            let actual = crc.finish();
+            if actual != expected {
+                return Err(StreamError::ChecksumMismatch { expected, actual });
+            }
+            if let Some(Err(err)) = InstructionStream::new(body).find(|r| r.is_err()) {
+                return Err(err.into());
+            }
+            self.ready = true;
+            Ok(Some(count))
+        }
+
+        /// Feeds a chunk of received bytes into the decoder.
+        ///
+        /// Stops at the first completed stream or error.
+        ///
+        /// # Arguments
+        ///
+        /// * `bytes` - Next bytes from the link
+        ///
+        /// # Returns
+        ///
+        /// The number of bytes consumed and the result of the last `push`.
+        pub fn push_slice(&mut self, bytes: &[u8]) -> (usize, Result<Option<usize>, StreamError>) {
+            for (i, &byte) in bytes.iter().enumerate() {
+                match self.push(byte) {
+                    Ok(None) => {}
+                    other => return (i + 1, other),
+                }
+            }
+            (bytes.len(), Ok(None))
+        }
+
+        /// Returns the instructions of the validated stream.
+        ///
+        /// Empty until `push` has reported a complete stream.
+        pub fn instructions(&self) -> impl Iterator<Item = Instruction> + '_ {
+            let body = if self.ready {
+                &self.buf[..self.body_len]
+            } else {
+                &[]
+            };
+            InstructionStream::new(body).filter_map(Result::ok)
+        }
+
+        /// Accumulates header bytes, sliding past anything that is not a
+        /// stream header.
+        fn push_header(&mut self, byte: u8) -> Result<Option<usize>, StreamError> {
+            self.header[self.header_len] = byte;
+            self.header_len += 1;
+
+            let magic = STREAM_MAGIC.to_le_bytes();
+            let matched = self.header_len.min(magic.len());
+            if self.header[..matched] != magic[..matched] {
+                self.resync();
+                return Ok(None);
+            }
+            if self.header_len < STREAM_HEADER_SIZE {
+                return Ok(None);
+            }
+
+            let result = parse_stream_header(&self.header).and_then(|(count, crc)| {
+                let capacity = self.buf.len() / INSTRUCTION_SIZE;
+                if count > capacity {
+                    return Err(StreamError::TooLong { count, capacity });
+                }
+                Ok((count, crc))
+            });
+            match result {
+                Ok((0, expected)) => {
+                    self.expect = Some((0, expected));
+                    self.push_empty(expected)
+                }
+                Ok(expect) => {
+                    self.expect = Some(expect);
+                    Ok(None)
+                }
+                Err(err) => {
+                    self.resync();
+                    Err(err)
+                }
+            }
+        }
+
+        /// Completes a stream that declares no instructions.
+        fn push_empty(&mut self, expected: u32) -> Result<Option<usize>, StreamError> {
+            let actual = crate::protocol::crc32(&self.header[..12]);
+            if actual != expected {
+                return Err(StreamError::ChecksumMismatch { expected, actual });
+            }
+            self.ready = true;
+            Ok(Some(0))
+        }
+
+        /// Searches a rejected stream for the start of another one.
+        ///
+        /// Every byte of the rejected stream after its first is fed back
+        /// through `step`, so a header inside a damaged body is still found.
+        /// The bytes are read from the header and body storage in place:
+        /// each replayed byte is stored no later than the position it was
+        /// read from. A stream found this way that is itself rejected is
+        /// rescanned in turn, after moving the bytes not yet replayed up
+        /// behind it; each round drops at least one byte, so this ends.
+        fn rescan(&mut self) {
+            let mut len = self.header_len + self.body_len;
+            let mut at = 1;
+            self.reset();
+            while at < len && !self.ready {
+                let byte = self.byte_at(at);
+                at += 1;
+                if self.step(byte).is_err() && self.expect.is_some() {
+                    let kept = self.header_len + self.body_len;
+                    for i in at..len {
+                        let byte = self.byte_at(i);
+                        self.set_byte(kept + i - at, byte);
+                    }
+                    len = kept + len - at;
+                    at = 1;
+                    self.reset();
+                }
+            }
+        }
+
+        /// Returns byte `i` of the current stream, counting the header
+        /// followed by the body.
+        fn byte_at(&self, i: usize) -> u8 {
+            match i.checked_sub(STREAM_HEADER_SIZE) {
+                Some(j) => self.buf[j],
+                None => self.header[i],
+            }
+        }
+
+        /// Sets byte `i` of the current stream, numbered as for `byte_at`.
+        fn set_byte(&mut self, i: usize, byte: u8) {
+            match i.checked_sub(STREAM_HEADER_SIZE) {
+                Some(j) => self.buf[j] = byte,
+                None => self.header[i] = byte,
+            }
+        }
+
+        /// Drops the first buffered header byte and rescans the rest for the
+        /// start of a magic number.
+        fn resync(&mut self) {
+            let magic = STREAM_MAGIC.to_le_bytes();
+            let mut start = 1;
+            while start < self.header_len {
+                let len = (self.header_len - start).min(magic.len());
+                if self.header[start..start + len] == magic[..len] {
+                    break;
+                }
+                start += 1;
+            }
+            self.header.copy_within(start..self.header_len, 0);
+            self.header_len -= start;
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        extern crate std;
+
+        use super::*;
+        use std::vec::Vec;
+
+        /// A short program exercising both operands.
+        fn program() -> Vec<Instruction> {
+            std::vec![
+                Instruction::new(Opcode::GateH, 3, 0),
+                Instruction::new(Opcode::GateCNOT, 3, 4),
+                Instruction::new(Opcode::Measure, 4, 17),
+                Instruction::new(Opcode::Halt, 0, 0),
+            ]
+        }
+
+        fn encoded(instrs: &[Instruction]) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            encode_stream(instrs, &mut bytes);
+            bytes
+        }
+
+        fn raw(instrs: impl Iterator<Item = Instruction>) -> Vec<[u8; INSTRUCTION_SIZE]> {
+            instrs.map(|i| i.to_bytes()).collect()
+        }
+
+        #[test]
+        fn every_opcode_round_trips_through_its_byte() {
+            for &op in Opcode::ALL {
+                assert_eq!(Opcode::try_from(op as u8), Ok(op));
+            }
+            for pair in Opcode::ALL.windows(2) {
+                assert!((pair[0] as u8) < (pair[1] as u8), "{pair:?}");
+            }
+            for (i, a) in Opcode::ALL.iter().enumerate() {
+                for b in &Opcode::ALL[i + 1..] {
+                    assert_ne!(a.mnemonic(), b.mnemonic());
+                }
+            }
+
+            // Every byte that decodes is listed, so `ALL` is complete.
+            let assigned = (0..=u8::MAX)
+                .filter(|&byte| Opcode::try_from(byte).is_ok())
+                .count();
+            assert_eq!(assigned, Opcode::ALL.len());
+            for byte in [0x00, 0x07, 0x13, 0x22, 0x61, 0xFE] {
+                assert_eq!(Opcode::try_from(byte), Err(UnknownOpcode(byte)));
+            }
+        }
+
+        #[test]
+        fn instruction_bytes_are_little_endian() {
+            let instr = Instruction::new(Opcode::GateCNOT, 0x1234, 0xABCD);
+            let bytes = instr.to_bytes();
+            assert_eq!(bytes, [0x03, 0x34, 0x12, 0xCD, 0xAB, 0]);
+            let back = Instruction::from_bytes(&bytes).unwrap();
+            assert_eq!(back.to_bytes(), bytes);
+            assert_eq!((back.op1(), back.op2()), (0x1234, 0xABCD));
+
+            for &op in Opcode::ALL {
+                let instr = Instruction::new(op, 0xFFFF, 1);
+                let back = Instruction::from_bytes(&instr.to_bytes()).unwrap();
+                assert_eq!(back.opcode, op as u8);
+                assert_eq!((back.op1(), back.op2()), (0xFFFF, 1));
+            }
+        }
+
+        #[test]
+        fn from_bytes_rejects_an_unknown_opcode() {
+            assert_eq!(
+                Instruction::from_bytes(&[0x07, 1, 0, 2, 0, 0]).err(),
+                Some(IsaError::UnknownOpcode(0x07))
+            );
+            // The padding byte is carried through unchecked.
+            let padded = Instruction::from_bytes(&[0xFF, 0, 0, 0, 0, 9]).unwrap();
+            assert_eq!(padded.to_bytes()[5], 9);
+        }
+
+        #[test]
+        fn narrow_operands_keep_the_six_byte_encoding() {
+            let narrow = Instruction::new(Opcode::Measure, 0xFFFF, 42);
+            let wide = Instruction32::from(narrow);
+            assert!(!wide.needs_prefix());
+            assert_eq!(wide.encoded_len(), INSTRUCTION_SIZE);
+            let mut out = [0u8; WIDE_INSTRUCTION_SIZE];
+            assert_eq!(wide.encode_into(&mut out), INSTRUCTION_SIZE);
+            assert_eq!(out[..INSTRUCTION_SIZE], narrow.to_bytes());
+            assert_eq!(
+                Instruction32::decode_at(&out[..INSTRUCTION_SIZE], 0),
+                Ok((wide, INSTRUCTION_SIZE))
+            );
+
+            // The always-prefixed form decodes to the same instruction.
+            assert_eq!(
+                Instruction32::decode_at(&wide.to_bytes(), 0),
+                Ok((wide, WIDE_INSTRUCTION_SIZE))
+            );
+        }
+
+        #[test]
+        fn wide_operands_round_trip_through_a_prefix() {
+            for (op1, op2) in [(0x1_0000, 0), (0, 0x1_0000), (u32::MAX, 0x0012_3456)] {
+                let instr = Instruction32::new(Opcode::CondPauliX, op1, op2);
+                assert!(instr.needs_prefix());
+                assert!(instr.narrow().is_none());
+
+                let (prefix, base) = instr.split();
+                assert_eq!(prefix.opcode, Opcode::Wide as u8);
+                assert_eq!(
+                    (prefix.op1(), prefix.op2()),
+                    ((op1 >> 16) as u16, (op2 >> 16) as u16)
+                );
+                assert_eq!((base.op1(), base.op2()), (op1 as u16, op2 as u16));
+
+                let mut out = [0u8; 2 * WIDE_INSTRUCTION_SIZE];
+                assert_eq!(instr.encode_into(&mut out[6..]), WIDE_INSTRUCTION_SIZE);
+                out[..6].copy_from_slice(&Instruction::new(Opcode::GateH, 1, 0).to_bytes());
+                assert_eq!(
+                    Instruction32::decode_at(&out[..18], 6),
+                    Ok((instr, WIDE_INSTRUCTION_SIZE))
+                );
+            }
+        }
+
+        #[test]
+        fn decode_at_rejects_bad_prefixes() {
+            let prefix = Instruction::new(Opcode::Wide, 1, 0).to_bytes();
+            let mut doubled = [0u8; 18];
+            doubled[..6].copy_from_slice(&prefix);
+            doubled[6..12].copy_from_slice(&prefix);
+            doubled[12..].copy_from_slice(&Instruction::new(Opcode::GateH, 1, 0).to_bytes());
+            assert_eq!(
+                Instruction32::decode_at(&doubled, 0),
+                Err(IsaError::UnknownOpcode(Opcode::Wide as u8))
+            );
+
+            assert_eq!(
+                Instruction32::decode_at(&prefix, 0),
+                Err(IsaError::TrailingBytes { count: 0 })
+            );
+            assert_eq!(
+                Instruction32::decode_at(&doubled[..10], 0),
+                Err(IsaError::TrailingBytes { count: 4 })
+            );
+        }
+
+        /// Goes through the packed operands only by value, so it also passes
+        /// under Miri, which rejects any unaligned reference.
+        #[test]
+        #[allow(deprecated)]
+        fn accessors_and_debug_copy_operands_out() {
+            // Array elements sit at odd offsets, so the operands are unaligned.
+            let mut instrs = [Instruction::new(Opcode::GateCNOT, 1, 2); 3];
+            for (i, instr) in instrs.iter_mut().enumerate() {
+                instr.set_op1(0x100 + i as u16);
+                instr.set_op2(0xBEEF);
+            }
+            for (i, instr) in instrs.iter().enumerate() {
+                assert_eq!(instr.op1(), 0x100 + i as u16);
+                assert_eq!(instr.op2(), 0xBEEF);
+                assert_eq!(instr.operand_1(), instr.op1());
+                assert_eq!(instr.operand_2(), instr.op2());
+            }
+
+            assert_eq!(
+                std::format!("{:?}", instrs[1]),
+                "Instruction { opcode: 3, operand_1: 257, operand_2: 48879, _padding: 0 }"
+            );
+        }
+
+        #[test]
+        fn decoder_accepts_a_stream_fed_byte_by_byte() {
+            let bytes = encoded(&program());
+            let mut buf = [0u8; 64];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            let (last, first) = bytes.split_last().unwrap();
+            for &byte in first {
+                assert_eq!(decoder.push(byte), Ok(None));
+                assert_eq!(decoder.instructions().count(), 0);
+            }
+            assert_eq!(decoder.push(*last), Ok(Some(4)));
+            assert_eq!(raw(decoder.instructions()), raw(program().into_iter()));
+
+            // Bytes after a completed stream are ignored until reset.
+            assert_eq!(decoder.push_slice(&bytes), (bytes.len(), Ok(None)));
+            decoder.reset();
+            assert_eq!(decoder.push_slice(&bytes), (bytes.len(), Ok(Some(4))));
+        }
+
+        #[test]
+        fn decoder_skips_garbage_before_the_magic() {
+            // Includes partial magic numbers that must not swallow the real one.
+            let mut bytes = std::vec![0x00, b'Q', b'P', 0xFF, b'Q', b'Q', b'P', b'R'];
+            bytes.extend(encoded(&program()));
+            let mut buf = [0u8; 64];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            assert_eq!(decoder.push_slice(&bytes), (bytes.len(), Ok(Some(4))));
+            assert_eq!(raw(decoder.instructions()), raw(program().into_iter()));
+        }
+
+        #[test]
+        fn decoder_resyncs_after_a_corrupted_body() {
+            let good = encoded(&program());
+            let mut bad = good.clone();
+            bad[STREAM_HEADER_SIZE + 7] ^= 0x40;
+            let mut bytes = bad.clone();
+            bytes.extend(&good);
+
+            let mut buf = [0u8; 64];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            let (used, result) = decoder.push_slice(&bytes);
+            assert_eq!(used, bad.len());
+            assert!(matches!(result, Err(StreamError::ChecksumMismatch { .. })));
+            assert_eq!(decoder.push_slice(&bytes[used..]), (good.len(), Ok(Some(4))));
+            assert_eq!(raw(decoder.instructions()), raw(program().into_iter()));
+        }
+
+        #[test]
+        fn decoder_finds_a_stream_inside_a_rejected_body() {
+            // A truncated stream whose declared body swallows a whole valid
+            // stream, followed by enough padding to complete the first.
+            let inner = encoded(&program());
+            let mut outer = encoded(&[Instruction::new(Opcode::Halt, 0, 0); 8]);
+            outer.truncate(STREAM_HEADER_SIZE + 6);
+            let mut bytes = outer.clone();
+            bytes.extend(&inner);
+            bytes.resize(outer.len() - 6 + 8 * INSTRUCTION_SIZE, 0);
+
+            let mut buf = [0u8; 64];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            let (used, result) = decoder.push_slice(&bytes);
+            assert_eq!(used, bytes.len());
+            assert!(matches!(result, Err(StreamError::ChecksumMismatch { .. })));
+            assert_eq!(raw(decoder.instructions()), raw(program().into_iter()));
+        }
+
+        #[test]
+        fn decoder_rescans_nested_rejected_streams() {
+            // The outer stream's body holds a damaged stream followed by a
+            // good one; both are only found once the outer CRC fails.
+            let good = encoded(&program());
+            let mut damaged = good.clone();
+            damaged[STREAM_HEADER_SIZE] = 0x07;
+            let mut bytes = encoded(&[Instruction::new(Opcode::Halt, 0, 0); 20]);
+            bytes.truncate(STREAM_HEADER_SIZE);
+            bytes.extend(&damaged);
+            bytes.extend(&good);
+            bytes.resize(STREAM_HEADER_SIZE + 20 * INSTRUCTION_SIZE, 0);
+
+            let mut buf = [0u8; 20 * INSTRUCTION_SIZE];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            let (_, result) = decoder.push_slice(&bytes);
+            assert!(matches!(result, Err(StreamError::ChecksumMismatch { .. })));
+            assert_eq!(raw(decoder.instructions()), raw(program().into_iter()));
+        }
+
+        #[test]
+        fn decoder_rejects_an_unknown_opcode_with_a_valid_crc() {
+            let mut instrs = program();
+            instrs[1].opcode = 0x13;
+            let bytes = encoded(&instrs);
+            let mut buf = [0u8; 64];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            let (_, result) = decoder.push_slice(&bytes);
+            assert_eq!(result, Err(StreamError::Isa(IsaError::UnknownOpcode(0x13))));
+            assert_eq!(decoder.instructions().count(), 0);
+        }
+
+        #[test]
+        fn decoder_accepts_a_zero_instruction_stream() {
+            let bytes = encoded(&[]);
+            assert_eq!(bytes.len(), STREAM_HEADER_SIZE);
+            let mut buf = [0u8; 0];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            assert_eq!(decoder.push_slice(&bytes), (bytes.len(), Ok(Some(0))));
+            assert_eq!(decoder.instructions().count(), 0);
+
+            let mut bad = bytes.clone();
+            bad[15] ^= 1;
+            decoder.reset();
+            let (_, result) = decoder.push_slice(&bad);
+            assert!(matches!(result, Err(StreamError::ChecksumMismatch { .. })));
+        }
+
+        #[test]
+        fn decoder_rejects_a_stream_longer_than_its_buffer() {
+            let bytes = encoded(&program());
+            let mut buf = [0u8; 3 * INSTRUCTION_SIZE];
+            let mut decoder = StreamDecoder::new(&mut buf);
+            let (used, result) = decoder.push_slice(&bytes);
+            assert_eq!(used, STREAM_HEADER_SIZE);
+            assert_eq!(result, Err(StreamError::TooLong { count: 4, capacity: 3 }));
+        }
+
+        #[cfg(feature = "alloc")]
+        #[test]
+        fn decode_stream_round_trips() {
+            let decoded = decode_stream(&encoded(&program())).unwrap();
+            assert_eq!(raw(decoded.into_iter()), raw(program().into_iter()));
+            assert!(decode_stream(&encoded(&[])).unwrap().is_empty());
+        }
+
+        #[cfg(feature = "alloc")]
+        #[test]
+        fn decode_stream_rejects_truncated_and_damaged_streams() {
+            let bytes = encoded(&program());
+            assert_eq!(
+                decode_stream(&bytes[..10]).err(),
+                Some(StreamError::Truncated { needed: 16, available: 10 })
+            );
+            assert_eq!(
+                decode_stream(&bytes[..bytes.len() - 1]).err(),
+                Some(StreamError::Truncated {
+                    needed: bytes.len(),
+                    available: bytes.len() - 1
+                })
+            );
+
+            let mut long = bytes.clone();
+            long.push(0);
+            assert_eq!(
+                decode_stream(&long).err(),
+                Some(StreamError::Isa(IsaError::TrailingBytes { count: 1 }))
+            );
+
+            let mut bad = bytes.clone();
+            bad[STREAM_HEADER_SIZE + 2] ^= 1;
+            assert!(matches!(
+                decode_stream(&bad),
+                Err(StreamError::ChecksumMismatch { .. })
+            ));
+
+            let mut magic = bytes.clone();
+            magic[0] = b'X';
+            assert!(matches!(decode_stream(&magic), Err(StreamError::BadMagic(_))));
+
+            let mut version = bytes;
+            version[4] = 2;
+            assert_eq!(
+                decode_stream(&version).err(),
+                Some(StreamError::UnsupportedVersion(2))
+            );
+        }
+    }
+}
+
+/// Framing shared by the host and firmware for packets exchanged over UART
+/// or virtio.
+///
+/// Every packet is a fixed 16-byte `PacketHeader` followed by `length` bytes
+/// of payload. The header's CRC-32 covers the other header fields and the
+/// payload, so a receiver that validates through `decode_packet` rejects a
+/// corrupted header as well as a corrupted payload. Both ends use this
+/// module, so a framing change is made once and picked up by both.
+pub mod protocol {
+    /// Magic number opening every packet ("QCU!" in little-endian byte order).
+    pub const MAGIC: u32 = u32::from_le_bytes(*b"QCU!");
+
+    /// Protocol version written by this build.
+    ///
+    /// Incremented on any incompatible change to the header or payload
+    /// formats. Receivers reject packets with a different version.
+    pub const VERSION: u16 = 1;
+
+    /// Size of an encoded `PacketHeader` in bytes.
+    pub const HEADER_SIZE: usize = 16;
+
+    /// Kind of payload carried by a packet.
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PacketKind {
+        /// Syndrome bits for one or more shots.
+        SyndromeData = 0x01,
+
+        /// An encoded instruction program.
+        Program = 0x02,
+
+        /// Execution or throughput statistics.
+        Stats = 0x03,
+
+        /// Positive acknowledgement of a received packet.
+        Ack = 0x10,
+
+        /// Negative acknowledgement; the packet should be resent.
+        Nak = 0x11,
+    }
+
+    impl TryFrom<u8> for PacketKind {
+        type Error = ProtocolError;
+
+        /// Decodes a packet kind byte.
+        ///
+        /// # Arguments
+        ///
+        /// * `byte` - Raw kind byte from a header
+        ///
+        /// # Returns
+        ///
+        /// The matching kind, or `ProtocolError::UnknownKind`.
+        fn try_from(byte: u8) -> Result<Self, Self::Error> {
+            match byte {
+                0x01 => Ok(PacketKind::SyndromeData),
+                0x02 => Ok(PacketKind::Program),
+                0x03 => Ok(PacketKind::Stats),
+                0x10 => Ok(PacketKind::Ack),
+                0x11 => Ok(PacketKind::Nak),
+                other => Err(ProtocolError::UnknownKind(other)),
+            }
+        }
+    }
+
+    /// Errors produced while validating a packet.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ProtocolError {
+        /// Fewer bytes than a header or the header's declared length.
+        Truncated {
+            /// Bytes needed to continue decoding.
+            needed: usize,
+            /// Bytes available.
+            available: usize,
+        },
+
+        /// The magic number does not match `MAGIC`.
+        BadMagic(u32),
+
+        /// The packet was written by an incompatible protocol version.
+        UnsupportedVersion(u16),
+
+        /// The kind byte does not correspond to any `PacketKind`.
+        UnknownKind(u8),
+
+        /// The CRC-32 over header and payload does not match.
+        ChecksumMismatch {
+            /// CRC carried in the header.
+            expected: u32,
+            /// CRC computed over the received bytes.
+            actual: u32,
+        },
+    }
+
+    /// Header preceding every packet.
+    ///
+    /// Encoded little-endian as magic (4 bytes), version (2), kind (1), a
+    /// reserved zero byte, payload length (4), and CRC-32 (4).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PacketHeader {
+        /// Must equal `MAGIC`.
+        pub magic: u32,
+
+        /// Protocol version of the sender.
+        pub version: u16,
+
+        /// Kind of payload that follows.
+        pub kind: PacketKind,
+
+        /// Payload length in bytes.
+        pub length: u32,
+
+        /// CRC-32 over the first 12 header bytes followed by the payload.
+        pub crc32: u32,
+    }
+
+    impl PacketHeader {
+        /// Builds the header for a payload with the current magic and version.
+        ///
+        /// # Arguments
+        ///
+        /// * `kind` - Kind of payload
+        /// * `payload` - Payload the header will precede
+        ///
+        /// # Returns
+        ///
+        /// The header with its length and CRC filled in.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the payload is longer than `u32::MAX` bytes.
+        pub fn new(kind: PacketKind, payload: &[u8]) -> Self {
+            let mut header = Self {
+                magic: MAGIC,
+                version: VERSION,
+                kind,
+                length: u32::try_from(payload.len()).expect("payload fits in u32"),
+                crc32: 0,
+            };
+            header.crc32 = header.compute_crc(payload);
+            header
+        }
+
+        /// Encodes the header into its 16-byte wire format.
+        pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+            let mut out = self.crc_prefix();
+            out[12..].copy_from_slice(&self.crc32.to_le_bytes());
+            out
+        }
+
+        /// Decodes a header without checking its CRC.
+        ///
+        /// Checks the magic number, version, and kind, so a receiver can
+        /// resynchronise on garbage before waiting for a payload. Use
+        /// `decode_packet` once the payload has arrived.
+        ///
+        /// # Arguments
+        ///
+        /// * `bytes` - Encoded header
+        ///
+        /// # Returns
+        ///
+        /// The header, or `BadMagic`, `UnsupportedVersion`, or `UnknownKind`.
+        pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Result<Self, ProtocolError> {
+            let word = |at: usize| {
+                u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+            };
+            let magic = word(0);
+            if magic != MAGIC {
+                return Err(ProtocolError::BadMagic(magic));
+            }
+            let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+            if version != VERSION {
+                return Err(ProtocolError::UnsupportedVersion(version));
+            }
+            Ok(Self {
+                magic,
+                version,
+                kind: PacketKind::try_from(bytes[6])?,
+                length: word(8),
+                crc32: word(12),
+            })
+        }
+
+        /// Checks a payload against the header's length and CRC.
+        ///
+        /// # Arguments
+        ///
+        /// * `payload` - Received payload, exactly `length` bytes
+        ///
+        /// # Returns
+        ///
+        /// Ok(()) if the CRC matches, otherwise `ChecksumMismatch`.
+        pub fn verify(&self, payload: &[u8]) -> Result<(), ProtocolError> {
+            let actual = self.compute_crc(payload);
+            if actual != self.crc32 {
+                return Err(ProtocolError::ChecksumMismatch {
+                    expected: self.crc32,
+                    actual,
+                });
+            }
+            Ok(())
+        }
+
+        /// Encodes every header field except the CRC, leaving it zeroed.
+        fn crc_prefix(&self) -> [u8; HEADER_SIZE] {
+            let mut out = [0u8; HEADER_SIZE];
+            out[0..4].copy_from_slice(&self.magic.to_le_bytes());
+            out[4..6].copy_from_slice(&self.version.to_le_bytes());
+            out[6] = self.kind as u8;
+            out[8..12].copy_from_slice(&self.length.to_le_bytes());
+            out
+        }
+
+        /// Computes the CRC over the header prefix and a payload.
+        fn compute_crc(&self, payload: &[u8]) -> u32 {
+            let mut crc = Crc32::new();
+            crc.update(&self.crc_prefix()[..12]);
+            crc.update(payload);
+            crc.finish()
+        }
+    }
+
+    /// Validates a complete packet and splits off its payload.
+    ///
+    /// The single entry point receivers should use: checks the header, that
+    /// enough bytes follow it, and the CRC over header and payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Buffer starting with a header
+    ///
+    /// # Returns
+    ///
+    /// The header, its payload, and any bytes after the packet; or the first
+    /// `ProtocolError` found.
+    pub fn decode_packet(bytes: &[u8]) -> Result<(PacketHeader, &[u8], &[u8]), ProtocolError> {
+        let (head, rest) = bytes
+            .split_first_chunk::<HEADER_SIZE>()
+            .ok_or(ProtocolError::Truncated {
+                needed: HEADER_SIZE,
+                available: bytes.len(),
+            })?;
+        let header = PacketHeader::from_bytes(head)?;
+        let length = header.length as usize;
+        if rest.len() < length {
+            return Err(ProtocolError::Truncated {
+                needed: length,
+                available: rest.len(),
+            });
+        }
+        let (payload, tail) = rest.split_at(length);
+        header.verify(payload)?;
+        Ok((header, payload, tail))
+    }
+
+    /// Lookup table for the reflected CRC-32 polynomial 0xEDB88320.
+    const CRC_TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    /// Incremental CRC-32 (IEEE 802.3, as used by zlib and Ethernet).
+    ///
+    /// Table-driven, one byte per step, with the 1 KiB table built at
+    /// compile time; needs no allocation, so it runs unchanged in firmware.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Crc32 {
+        /// Running CRC register, pre-inverted.
+        state: u32,
+    }
+
+    impl Crc32 {
+        /// Starts a new checksum.
+        pub const fn new() -> Self {
+            Self { state: !0 }
+        }
+
+        /// Feeds bytes into the checksum.
+        ///
+        /// # Arguments
+        ///
+        /// * `data` - Next bytes of the message
+        pub fn update(&mut self, data: &[u8]) {
+            for &byte in data {
+                let index = (self.state ^ byte as u32) as u8;
+                self.state = (self.state >> 8) ^ CRC_TABLE[index as usize];
+            }
+        }
+
+        /// Returns the checksum of all bytes fed so far.
+        pub const fn finish(&self) -> u32 {
+            !self.state
+        }
+    }
+
+    impl Default for Crc32 {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Computes the CRC-32 of a byte slice in one call.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Message to checksum
+    ///
+    /// # Returns
+    ///
+    /// The CRC-32; for example `crc32(b"123456789") == 0xCBF4_3926`.
+    pub fn crc32(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(data);
+        crc.finish()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        extern crate std;
+
+        use super::*;
+        use std::vec::Vec;
+
+        fn packet(kind: PacketKind, payload: &[u8]) -> Vec<u8> {
+            let mut bytes = PacketHeader::new(kind, payload).to_bytes().to_vec();
+            bytes.extend_from_slice(payload);
+            bytes
+        }
+
+        #[test]
+        fn crc32_matches_the_check_value() {
+            assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+            assert_eq!(crc32(b""), 0);
+
+            let mut crc = Crc32::new();
+            crc.update(b"1234");
+            crc.update(b"56789");
+            assert_eq!(crc.finish(), 0xCBF4_3926);
+        }
+
+        #[test]
+        fn decode_packet_round_trips() {
+            let mut bytes = packet(PacketKind::Program, b"payload");
+            bytes.extend_from_slice(b"next");
+            let (header, payload, tail) = decode_packet(&bytes).unwrap();
+            assert_eq!(header, PacketHeader::new(PacketKind::Program, b"payload"));
+            assert_eq!(payload, b"payload");
+            assert_eq!(tail, b"next");
+
+            let empty = packet(PacketKind::Ack, &[]);
+            assert_eq!(decode_packet(&empty).unwrap().1, b"");
+        }
+
+        #[test]
+        fn from_bytes_rejects_bad_magic_version_and_kind() {
+            let good = PacketHeader::new(PacketKind::Stats, b"abc").to_bytes();
+
+            let mut magic = good;
+            magic[3] = b'?';
+            assert_eq!(
+                PacketHeader::from_bytes(&magic),
+                Err(ProtocolError::BadMagic(u32::from_le_bytes(*b"QCU?")))
+            );
+
+            let mut version = good;
+            version[4] = 9;
+            assert_eq!(
+                PacketHeader::from_bytes(&version),
+                Err(ProtocolError::UnsupportedVersion(9))
+            );
+
+            let mut kind = good;
+            kind[6] = 0x04;
+            assert_eq!(
+                PacketHeader::from_bytes(&kind),
+                Err(ProtocolError::UnknownKind(0x04))
+            );
+            assert_eq!(decode_packet(&kind).err(), Some(ProtocolError::UnknownKind(0x04)));
+        }
+
+        #[test]
+        fn flipped_header_bytes_fail_the_checksum() {
+            let good = packet(PacketKind::SyndromeData, b"syndromes");
+            // Kind, length, and CRC bytes; a flip in the length that still
+            // fits the buffer must be caught by the CRC, not by truncation.
+            for (at, bit) in [(6, 0x02), (8, 0x01), (12, 0x80), (15, 0x10)] {
+                let mut bytes = good.clone();
+                bytes[at] ^= bit;
+                bytes.extend_from_slice(&[0; 4]);
+                assert!(
+                    matches!(
+                        decode_packet(&bytes),
+                        Err(ProtocolError::ChecksumMismatch { .. })
+                    ),
+                    "byte {at}"
+                );
+            }
+
+            let mut payload = good;
+            payload[HEADER_SIZE + 3] ^= 0x20;
+            assert!(matches!(
+                decode_packet(&payload),
+                Err(ProtocolError::ChecksumMismatch { .. })
+            ));
+        }
+
+        #[test]
+        fn decode_packet_rejects_truncated_input() {
+            let bytes = packet(PacketKind::Program, b"0123456789");
+            assert_eq!(
+                decode_packet(&bytes[..HEADER_SIZE - 1]).err(),
+                Some(ProtocolError::Truncated {
+                    needed: HEADER_SIZE,
+                    available: HEADER_SIZE - 1
+                })
+            );
+            assert_eq!(
+                decode_packet(&bytes[..bytes.len() - 3]).err(),
+                Some(ProtocolError::Truncated {
+                    needed: 10,
+                    available: 7
+                })
+            );
+        }
     }
 }
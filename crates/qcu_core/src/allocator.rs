@@ -8,6 +8,7 @@
 
 use crate::QecError;
 use core::alloc::{AllocError, Allocator, Layout};
+use core::mem::MaybeUninit;
 use core::ptr::NonNull;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
@@ -19,8 +20,11 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 /// memory, making it suitable for long-lived data structures in firmware.
 /// The atomic offset ensures thread-safe allocation without locks.
 pub struct BumpAllocator {
-    /// Base address of the managed memory region.
-    start: usize,
+    /// Base of the managed memory region.
+    ///
+    /// Kept as a pointer rather than an address so every block handed out
+    /// is derived from it and carries the region's provenance.
+    start: NonNull<u8>,
 
     /// Total size of the memory region in bytes.
     len: usize,
@@ -30,6 +34,59 @@ pub struct BumpAllocator {
     /// Tracks the next available byte in the region. Incremented atomically
     /// during allocation to prevent races between concurrent allocators.
     offset: AtomicUsize,
+
+    /// Number of marks taken and not yet restored.
+    ///
+    /// Only used to check in debug builds that marks are restored in LIFO
+    /// order.
+    marks: AtomicUsize,
+
+    /// Name of the arena, reported to the out-of-memory hook.
+    name: &'static str,
+
+    /// Function called when an allocation fails, if installed.
+    oom_hook: Option<OomHook>,
+}
+
+/// Observer called once for every allocation a `BumpAllocator` refuses.
+///
+/// A plain function pointer rather than a boxed closure, so firmware can
+/// install one without a heap. It runs on the failing thread before the
+/// error is returned, and must not allocate from the same arena.
+pub type OomHook = fn(report: &BumpReport, layout: Layout);
+
+/// Snapshot of a bump allocator's usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BumpReport {
+    /// Name given to the arena with `set_name`, or "bump" by default.
+    pub name: &'static str,
+
+    /// Bytes handed out so far, including alignment padding.
+    pub used: usize,
+
+    /// Total size of the region in bytes.
+    pub capacity: usize,
+}
+
+impl BumpReport {
+    /// Returns the number of bytes still free in the region.
+    pub fn remaining(&self) -> usize {
+        self.capacity.saturating_sub(self.used)
+    }
+}
+
+/// Saved allocation offset returned by `BumpAllocator::mark`.
+///
+/// Passing it to `restore` frees everything allocated since the mark was
+/// taken. Deliberately not `Clone`, so a mark can be restored at most once.
+#[derive(Debug)]
+#[must_use = "an unrestored mark never frees its scope"]
+pub struct BumpMark {
+    /// Allocator offset when the mark was taken.
+    offset: usize,
+
+    /// Number of outstanding marks before this one.
+    depth: usize,
 }
 
 impl BumpAllocator {
@@ -39,52 +96,235 @@ impl BumpAllocator {
     /// will allocate from this region until exhaustion. The region must be
     /// valid, writable memory for the lifetime of the allocator.
     ///
+    /// Firmware uses this for its fixed RAM window, whose address comes from
+    /// the platform memory map rather than from a Rust allocation. Host code
+    /// should prefer `from_slice`.
+    ///
     /// # Arguments
     ///
     /// * `ptr` - Base address of the memory region
     /// * `len` - Size of the region in bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr` is null and `len` is not zero. A null, empty region
+    /// gives an allocator that refuses every non-empty request.
     pub fn new(ptr: usize, len: usize) -> Self {
+        let start = core::ptr::with_exposed_provenance_mut::<u8>(ptr);
+        match NonNull::new(start) {
+            Some(start) => Self::with_start(start, len),
+            None => {
+                assert!(len == 0, "null bump region with length {}", len);
+                Self::with_start(NonNull::dangling(), 0)
+            }
+        }
+    }
+
+    /// Creates a bump allocator over a static byte buffer.
+    ///
+    /// The exclusive `'static` borrow guarantees that the region stays valid
+    /// for as long as the allocator and that no other allocator or reference
+    /// can alias it.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - Buffer to allocate from, for example a leaked `Vec` or a
+    ///   `static mut` array
+    pub fn from_slice(region: &'static mut [u8]) -> Self {
+        let len = region.len();
+        Self::with_start(NonNull::from(region).cast(), len)
+    }
+
+    /// Creates a bump allocator over a raw memory region.
+    ///
+    /// # Safety
+    ///
+    /// `[ptr, ptr + len)` must be valid for reads and writes for the whole
+    /// lifetime of the allocator and of every block it hands out. Nothing
+    /// else may access the region during that time; in particular, no two
+    /// allocators may be created over overlapping regions.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - Base of the memory region
+    /// * `len` - Size of the region in bytes
+    pub unsafe fn from_raw(ptr: NonNull<u8>, len: usize) -> Self {
+        Self::with_start(ptr, len)
+    }
+
+    /// Builds the allocator with an empty offset and no open marks.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Base of the memory region
+    /// * `len` - Size of the region in bytes
+    fn with_start(start: NonNull<u8>, len: usize) -> Self {
         Self {
-            start: ptr,
+            start,
             len,
             offset: AtomicUsize::new(0),
+            marks: AtomicUsize::new(0),
+            name: "bump",
+            oom_hook: None,
         }
     }
 
-    /// Allocates a zero-initialized slice of the specified type and length.
+    /// Names the arena in out-of-memory reports.
     ///
-    /// Convenience method that allocates memory for a slice, ensures proper
-    /// alignment for type T, and zero-initializes all elements. This is
-    /// commonly used for allocating arrays of Pauli frame registers or
-    /// decoder state vectors.
+    /// # Arguments
+    ///
+    /// * `name` - Name passed to the hook in `BumpReport::name`
+    pub fn set_name(&mut self, name: &'static str) {
+        self.name = name;
+    }
+
+    /// Installs a function to call whenever an allocation fails.
+    ///
+    /// Turns a bare `OutOfMemory` into a report of which arena ran out,
+    /// how full it was, and what layout was requested. Replaces any
+    /// previously installed hook.
     ///
     /// # Arguments
     ///
-    /// * `len` - Number of elements to allocate
+    /// * `hook` - Function called with a usage report and the failed layout
+    pub fn set_oom_hook(&mut self, hook: OomHook) {
+        self.oom_hook = Some(hook);
+    }
+
+    /// Removes the out-of-memory hook, if any.
+    pub fn clear_oom_hook(&mut self) {
+        self.oom_hook = None;
+    }
+
+    /// Returns a snapshot of the allocator's usage.
+    pub fn report(&self) -> BumpReport {
+        BumpReport {
+            name: self.name,
+            used: self.used(),
+            capacity: self.len,
+        }
+    }
+
+    /// Returns the number of bytes handed out so far, including alignment
+    /// padding.
+    ///
+    /// Callers can check this before `reset` to confirm that nothing they
+    /// still reference was allocated from the region.
+    pub fn used(&self) -> usize {
+        self.offset.load(Ordering::Acquire)
+    }
+
+    /// Rewinds the allocator to the start of its region.
+    ///
+    /// Lets firmware reuse the arena, for example to load a recalibrated
+    /// decoding graph in place of the boot-time one. The next allocation is
+    /// handed out at the same address as the first one after `new`.
+    ///
+    /// # Safety
+    ///
+    /// Invalidates every allocation made so far. The caller must ensure no
+    /// reference, `Vec`, or `Box` into the region is used after the reset,
+    /// and that no other thread is allocating concurrently.
+    pub unsafe fn reset(&self) {
+        self.marks.store(0, Ordering::Relaxed);
+        self.offset.store(0, Ordering::Release);
+    }
+
+    /// Rewinds an allocator the caller owns outright.
+    ///
+    /// Safe counterpart to `reset`: taking the allocator by value proves
+    /// that no allocation borrowing it is still alive.
     ///
     /// # Returns
     ///
-    /// A mutable slice of zero-initialized elements, or an error if allocation
-    /// fails due to insufficient memory or overflow.
-    #[allow(clippy::mut_from_ref)]
-    pub fn alloc_slice<T>(&self, len: usize) -> Result<&mut [T], QecError> {
-        let layout = Layout::array::<T>(len).map_err(|_| QecError::OutOfMemory)?;
-        let ptr = self.allocate(layout).map_err(|_| QecError::OutOfMemory)?;
-        unsafe {
-            let slice_ptr = ptr.as_ptr() as *mut T;
-            core::ptr::write_bytes(slice_ptr, 0, len);
-            Ok(core::slice::from_raw_parts_mut(slice_ptr, len))
+    /// The same allocator with its whole region free.
+    pub fn reset_owned(self) -> BumpAllocator {
+        self.marks.store(0, Ordering::Relaxed);
+        self.offset.store(0, Ordering::Relaxed);
+        self
+    }
+
+    /// Records the current offset so later allocations can be rolled back.
+    ///
+    /// Opens an arena scope: firmware can carve per-shot scratch buffers out
+    /// of the graph arena during a decode and release them with `restore`
+    /// afterwards, without a second allocator. Scopes nest and must be
+    /// closed innermost first.
+    ///
+    /// # Returns
+    ///
+    /// A mark to pass to `restore`.
+    pub fn mark(&self) -> BumpMark {
+        BumpMark {
+            offset: self.offset.load(Ordering::Acquire),
+            depth: self.marks.fetch_add(1, Ordering::Relaxed),
         }
     }
-}
 
-unsafe impl Allocator for BumpAllocator {
-    /// Allocates memory matching the requested layout.
+    /// Frees everything allocated since `mark` was taken.
     ///
-    /// Performs alignment and size calculations, then atomically updates the
-    /// offset pointer to reserve the memory. Uses compare-and-swap to handle
-    /// concurrent allocations safely. Returns an error if the allocation
-    /// would exceed the region bounds.
+    /// Debug builds assert that `mark` is the most recent unrestored mark.
+    ///
+    /// # Safety
+    ///
+    /// Invalidates every allocation made after the mark. The caller must
+    /// ensure none of them is used afterwards, and that no other hart or
+    /// thread allocates from this allocator between `mark` and `restore`:
+    /// a concurrent allocation would land inside the scope and be freed
+    /// along with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `mark` - Mark returned by `mark` on this allocator
+    pub unsafe fn restore(&self, mark: BumpMark) {
+        let open = self.marks.load(Ordering::Relaxed);
+        debug_assert_eq!(open, mark.depth + 1, "BumpMark restored out of LIFO order");
+        debug_assert!(mark.offset <= self.offset.load(Ordering::Relaxed));
+        self.marks.store(mark.depth, Ordering::Relaxed);
+        self.offset.store(mark.offset, Ordering::Release);
+    }
+
+    /// Extends the most recent allocation by advancing the bump offset.
+    ///
+    /// The block is the most recent allocation exactly when it ends at the
+    /// current offset; the compare-and-swap on the offset both checks that
+    /// and claims the extra bytes, so a racing allocation on another hart
+    /// makes this fail rather than overlap.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - Block to grow
+    /// * `old_layout` - Layout the block was allocated with
+    /// * `new_layout` - Requested layout
+    ///
+    /// # Returns
+    ///
+    /// The grown block, or None if it cannot be grown in place.
+    fn grow_in_place(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Option<NonNull<[u8]>> {
+        let (addr, base) = (ptr.addr().get(), self.start.addr().get());
+        // A zero-size block is a dangling pointer, not a position in the
+        // region, even if its address happens to fall inside it.
+        if old_layout.size() == 0 || addr < base || !addr.is_multiple_of(new_layout.align()) {
+            return None;
+        }
+        let block = addr - base;
+        let old_end = block.checked_add(old_layout.size())?;
+        let new_end = block.checked_add(new_layout.size())?;
+        if new_end > self.len {
+            return None;
+        }
+        self.offset
+            .compare_exchange(old_end, new_end, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()?;
+        Some(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
+
+    /// Reserves a block for `layout` without reporting failures.
     ///
     /// # Arguments
     ///
@@ -92,17 +332,35 @@ unsafe impl Allocator for BumpAllocator {
     ///
     /// # Returns
     ///
-    /// A pointer to the allocated memory, or AllocError if allocation fails.
-    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+    /// A pointer to the reserved block, or AllocError if it does not fit.
+    fn try_allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
         let size = layout.size();
         let align = layout.align();
 
+        if size == 0 {
+            // Zero-size blocks are never read or written, so hand out an
+            // aligned dangling pointer rather than burning padding bytes.
+            let dangling = core::ptr::without_provenance_mut::<u8>(align);
+            let ptr = unsafe { NonNull::new_unchecked(dangling) };
+            return Ok(NonNull::slice_from_raw_parts(ptr, 0));
+        }
+
         loop {
             let current_offset = self.offset.load(Ordering::Relaxed);
-            let current_ptr = self.start + current_offset;
-            let aligned_ptr = (current_ptr + align - 1) & !(align - 1);
+            let current_ptr = self
+                .start
+                .addr()
+                .get()
+                .checked_add(current_offset)
+                .ok_or(AllocError)?;
+            let aligned_ptr = current_ptr
+                .checked_next_multiple_of(align)
+                .ok_or(AllocError)?;
             let padding = aligned_ptr - current_ptr;
-            let new_offset = current_offset + padding + size;
+            let new_offset = current_offset
+                .checked_add(padding)
+                .and_then(|o| o.checked_add(size))
+                .ok_or(AllocError)?;
 
             if new_offset > self.len {
                 return Err(AllocError);
@@ -118,12 +376,89 @@ unsafe impl Allocator for BumpAllocator {
                 )
                 .is_ok()
             {
-                let ptr = unsafe { NonNull::new_unchecked(aligned_ptr as *mut u8) };
+                // In bounds: `current_offset + padding + size <= len`.
+                let ptr = unsafe { self.start.add(current_offset + padding) };
                 return Ok(NonNull::slice_from_raw_parts(ptr, size));
             }
         }
     }
 
+    /// Allocates a zero-initialized slice of the specified type and length.
+    ///
+    /// Convenience method that allocates memory for a slice, ensures proper
+    /// alignment for type T, and zero-initializes all elements. This is
+    /// commonly used for allocating arrays of Pauli frame registers or
+    /// decoder state vectors.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - Number of elements to allocate
+    ///
+    /// # Returns
+    ///
+    /// A mutable slice of zero-initialized elements, or an error if allocation
+    /// fails due to insufficient memory or overflow.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice<T>(&self, len: usize) -> Result<&mut [T], QecError> {
+        let layout = Layout::array::<T>(len).map_err(|_| QecError::OutOfMemory)?;
+        let ptr = self.allocate(layout).map_err(|_| QecError::OutOfMemory)?;
+        unsafe {
+            let slice_ptr = ptr.as_ptr() as *mut T;
+            core::ptr::write_bytes(slice_ptr, 0, len);
+            Ok(core::slice::from_raw_parts_mut(slice_ptr, len))
+        }
+    }
+
+    /// Allocates an uninitialised slice of the specified type and length.
+    ///
+    /// Skips the zeroing done by `alloc_slice`, which is wasted work for
+    /// buffers that are fully overwritten straight after allocation (CSR
+    /// targets, copied frame registers). Callers must initialise every
+    /// element before reading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - Number of elements to allocate
+    ///
+    /// # Returns
+    ///
+    /// A mutable slice of uninitialised elements, or an error if allocation
+    /// fails due to insufficient memory or overflow.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_uninit<T>(&self, len: usize) -> Result<&mut [MaybeUninit<T>], QecError> {
+        alloc_slice_uninit_in(self, len)
+    }
+}
+
+unsafe impl Allocator for BumpAllocator {
+    /// Allocates memory matching the requested layout.
+    ///
+    /// Performs alignment and size calculations, then atomically updates the
+    /// offset pointer to reserve the memory. Uses compare-and-swap to handle
+    /// concurrent allocations safely. Alignment is computed on the absolute
+    /// address, so it is honoured even when the region's base is less
+    /// aligned than the request. Zero-size requests always succeed with a
+    /// dangling, aligned pointer and consume no space. Returns an error,
+    /// after calling the out-of-memory hook if one is installed, if the
+    /// allocation would exceed the region bounds. The arithmetic is
+    /// checked, so a layout with a huge size or alignment fails instead of
+    /// wrapping around to a pointer outside the region.
+    ///
+    /// # Arguments
+    ///
+    /// * `layout` - Memory layout specifying size and alignment requirements
+    ///
+    /// # Returns
+    ///
+    /// A pointer to the allocated memory, or AllocError if allocation fails.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let result = self.try_allocate(layout);
+        if let (Err(_), Some(hook)) = (&result, self.oom_hook) {
+            hook(&self.report(), layout);
+        }
+        result
+    }
+
     /// No-op deallocation function.
     ///
     /// Bump allocators do not support deallocation. Memory is reclaimed only
@@ -135,11 +470,394 @@ unsafe impl Allocator for BumpAllocator {
     /// * `_ptr` - Pointer to deallocate (ignored)
     /// * `_layout` - Layout of the allocation (ignored)
     unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+
+    /// Grows an allocation, in place if it is the most recent one.
+    ///
+    /// When the block ends at the current bump offset, nothing has been
+    /// allocated after it, so the offset is advanced and the block keeps
+    /// its address. This is what `Vec::push` hits when filling a
+    /// bump-allocated edge list, and it avoids leaving a dead copy of the
+    /// vector in the arena at every doubling. Any other block is moved to a
+    /// fresh allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - Block to grow, allocated by this allocator with `old_layout`
+    /// * `old_layout` - Layout the block was allocated with
+    /// * `new_layout` - Requested layout, no smaller than `old_layout`
+    ///
+    /// # Returns
+    ///
+    /// The grown block, or AllocError if the region is exhausted.
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if let Some(grown) = self.grow_in_place(ptr, old_layout, new_layout) {
+            return Ok(grown);
+        }
+        let new_ptr = self.allocate(new_layout)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr() as *mut u8,
+                old_layout.size(),
+            );
+        }
+        Ok(new_ptr)
+    }
+
+    /// Grows an allocation like `grow` and zeroes the added bytes.
+    ///
+    /// The bytes are zeroed explicitly, since memory past the offset may
+    /// hold stale data after a `reset` or `restore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - Block to grow, allocated by this allocator with `old_layout`
+    /// * `old_layout` - Layout the block was allocated with
+    /// * `new_layout` - Requested layout, no smaller than `old_layout`
+    ///
+    /// # Returns
+    ///
+    /// The grown block, or AllocError if the region is exhausted.
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let grown = unsafe { self.grow(ptr, old_layout, new_layout)? };
+        unsafe {
+            let tail = (grown.as_ptr() as *mut u8).add(old_layout.size());
+            core::ptr::write_bytes(tail, 0, new_layout.size() - old_layout.size());
+        }
+        Ok(grown)
+    }
+
+    /// Shrinks an allocation without reclaiming memory.
+    ///
+    /// The block keeps its address unless it is not aligned for
+    /// `new_layout`, in which case it is moved to a fresh allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - Block to shrink, allocated by this allocator with `old_layout`
+    /// * `old_layout` - Layout the block was allocated with
+    /// * `new_layout` - Requested layout, no larger than `old_layout`
+    ///
+    /// # Returns
+    ///
+    /// The shrunk block, or AllocError if a move was needed and failed.
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let _ = old_layout;
+        if (ptr.as_ptr() as usize).is_multiple_of(new_layout.align()) {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+        let new_ptr = self.allocate(new_layout)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr() as *mut u8,
+                new_layout.size(),
+            );
+        }
+        Ok(new_ptr)
+    }
 }
 
+/// BumpAllocator can be moved between threads.
+///
+/// The region pointer is owned by the allocator, as guaranteed by its
+/// constructors, so moving the allocator moves that ownership with it.
+unsafe impl Send for BumpAllocator {}
+
 /// BumpAllocator is safe to share between threads.
 ///
 /// The atomic offset pointer ensures that concurrent allocations are handled
 /// correctly without data races. Multiple threads can allocate from the same
 /// BumpAllocator instance safely.
 unsafe impl Sync for BumpAllocator {}
+
+/// Allocates an uninitialised slice from any allocator.
+///
+/// The generic form of `BumpAllocator::alloc_slice_uninit`, for code that is
+/// generic over its allocator. The block is requested with
+/// `Layout::array::<T>(len)`, the same layout `Vec<T, A>` uses, so callers
+/// that initialise every element may hand it to `Vec::from_raw_parts_in`
+/// with the same allocator. Otherwise the block is never deallocated.
+///
+/// # Arguments
+///
+/// * `alloc` - Allocator to draw the storage from
+/// * `len` - Number of elements to allocate
+///
+/// # Returns
+///
+/// A mutable slice of uninitialised elements, or an error if allocation
+/// fails due to insufficient memory or overflow.
+#[allow(clippy::mut_from_ref)]
+pub fn alloc_slice_uninit_in<T, A: Allocator>(
+    alloc: &A,
+    len: usize,
+) -> Result<&mut [MaybeUninit<T>], QecError> {
+    let layout = Layout::array::<T>(len).map_err(|_| QecError::OutOfMemory)?;
+    let ptr = alloc.allocate(layout).map_err(|_| QecError::OutOfMemory)?;
+    // The block is sized and aligned for `len` elements of T and owned by
+    // the caller until deallocated, and MaybeUninit needs no initialisation.
+    unsafe {
+        Ok(core::slice::from_raw_parts_mut(
+            ptr.as_ptr() as *mut MaybeUninit<T>,
+            len,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Returns an allocator over a fresh, leaked region of `len` bytes.
+    fn arena(len: usize) -> BumpAllocator {
+        BumpAllocator::from_slice(Vec::leak(vec![0u8; len]))
+    }
+
+    #[test]
+    fn null_empty_region_refuses_allocations() {
+        let alloc = BumpAllocator::new(0, 0);
+        assert_eq!(alloc.report().capacity, 0);
+        assert!(alloc.alloc_slice::<u8>(0).is_ok());
+        assert!(matches!(
+            alloc.alloc_slice::<u8>(1),
+            Err(QecError::OutOfMemory)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "null bump region")]
+    fn null_region_with_length_panics() {
+        let _ = BumpAllocator::new(0, 4096);
+    }
+
+    #[test]
+    fn reset_hands_out_the_same_addresses_again() {
+        let alloc = arena(256);
+        let first: Vec<usize> = [1, 8, 24, 3]
+            .iter()
+            .map(|&n| alloc.alloc_slice::<u32>(n).unwrap().as_ptr().addr())
+            .collect();
+        assert_eq!(alloc.used(), 36 * 4);
+
+        unsafe { alloc.reset() };
+        assert_eq!(alloc.used(), 0);
+        let second: Vec<usize> = [1, 8, 24, 3]
+            .iter()
+            .map(|&n| alloc.alloc_slice::<u32>(n).unwrap().as_ptr().addr())
+            .collect();
+        assert_eq!(first, second);
+
+        let alloc = alloc.reset_owned();
+        assert_eq!(alloc.used(), 0);
+        assert_eq!(alloc.alloc_slice::<u32>(1).unwrap().as_ptr().addr(), first[0]);
+    }
+
+    #[test]
+    fn nested_marks_roll_back_innermost_first() {
+        let alloc = arena(256);
+        let _keep = alloc.alloc_slice::<u8>(10).unwrap();
+
+        let outer = alloc.mark();
+        let outer_block = alloc.alloc_slice::<u64>(4).unwrap().as_ptr().addr();
+        let after_outer = alloc.used();
+
+        let inner = alloc.mark();
+        alloc.alloc_slice::<u64>(8).unwrap();
+        assert!(alloc.used() > after_outer);
+        unsafe { alloc.restore(inner) };
+        assert_eq!(alloc.used(), after_outer);
+
+        // A second inner scope reuses the space the first one freed.
+        let inner = alloc.mark();
+        let reused = alloc.alloc_slice::<u64>(1).unwrap().as_ptr().addr();
+        assert_eq!(reused, outer_block + 32);
+        unsafe { alloc.restore(inner) };
+
+        unsafe { alloc.restore(outer) };
+        assert_eq!(alloc.used(), 10);
+        assert_eq!(
+            alloc.alloc_slice::<u64>(4).unwrap().as_ptr().addr(),
+            outer_block
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "LIFO")]
+    fn restoring_an_outer_mark_first_panics_in_debug_builds() {
+        let alloc = arena(64);
+        let outer = alloc.mark();
+        let _inner = alloc.mark();
+        unsafe { alloc.restore(outer) };
+    }
+
+    #[test]
+    fn vec_grows_in_place_in_a_tight_arena() {
+        // Room for the final 512-element buffer plus a little slack, but
+        // nowhere near the ~8 KiB that copying on every doubling would leave
+        // behind.
+        let alloc = arena(4096 + 64);
+        let mut edges: Vec<u64, &BumpAllocator> = Vec::new_in(&alloc);
+        for i in 0..500 {
+            edges.push(i);
+        }
+        assert_eq!(edges.capacity(), 512);
+        assert!(alloc.used() <= edges.capacity() * 8 + 8);
+        assert!(edges.iter().copied().eq(0..500));
+
+        // A block that is no longer the most recent one is moved instead.
+        let alloc = arena(256);
+        let mut first: Vec<u32, &BumpAllocator> = Vec::with_capacity_in(4, &alloc);
+        first.extend([1, 2, 3, 4]);
+        let blocker = alloc.alloc_slice::<u32>(1).unwrap().as_ptr().addr();
+        let before = first.as_ptr().addr();
+        first.push(5);
+        assert_ne!(first.as_ptr().addr(), before);
+        assert!(first.as_ptr().addr() > blocker);
+        assert_eq!(first, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn huge_layouts_fail_instead_of_wrapping() {
+        // Layout itself refuses a size that would overflow once rounded up
+        // to its alignment, so such a request never reaches the allocator.
+        assert!(Layout::from_size_align(usize::MAX - 16, 4096).is_err());
+
+        let alloc = arena(4096);
+        alloc.alloc_slice::<u8>(3).unwrap();
+        let used = alloc.used();
+        let base = alloc.report();
+
+        let largest = Layout::from_size_align(isize::MAX as usize - 4095, 4096).unwrap();
+        let huge_align = Layout::from_size_align(1, 1 << 62).unwrap();
+        for layout in [largest, huge_align] {
+            assert!(alloc.allocate(layout).is_err());
+            assert_eq!(alloc.used(), used);
+        }
+        assert!(matches!(
+            alloc.alloc_slice::<u64>(usize::MAX / 8),
+            Err(QecError::OutOfMemory)
+        ));
+        assert!(matches!(
+            alloc.alloc_slice::<u64>(usize::MAX),
+            Err(QecError::OutOfMemory)
+        ));
+
+        // The arena is still usable and hands out blocks inside the region.
+        let block = alloc.alloc_slice::<u64>(4).unwrap().as_ptr().addr();
+        let region = alloc.start.addr().get();
+        assert!(block.is_multiple_of(8));
+        assert!(block >= region && block + 32 <= region + base.capacity);
+        assert_eq!(alloc.used(), block + 32 - region);
+    }
+
+    #[test]
+    fn oom_hook_fires_once_per_failed_allocation() {
+        use core::sync::atomic::AtomicUsize;
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static LAST_SIZE: AtomicUsize = AtomicUsize::new(0);
+        fn hook(report: &BumpReport, layout: Layout) {
+            assert_eq!(report.name, "graph");
+            assert_eq!(report.capacity, 64);
+            assert!(report.remaining() < layout.size());
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            LAST_SIZE.store(layout.size(), Ordering::Relaxed);
+        }
+
+        let mut alloc = arena(64);
+        alloc.set_name("graph");
+        alloc.set_oom_hook(hook);
+
+        alloc.alloc_slice::<u8>(40).unwrap();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+
+        assert!(alloc.alloc_slice::<u8>(32).is_err());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert_eq!(LAST_SIZE.load(Ordering::Relaxed), 32);
+
+        // A grow that can neither extend in place nor move reports once.
+        let mut v: Vec<u8, &BumpAllocator> = Vec::with_capacity_in(8, &alloc);
+        v.extend([0; 8]);
+        assert!(v.try_reserve_exact(64).is_err());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+        assert_eq!(LAST_SIZE.load(Ordering::Relaxed), 72);
+        drop(v);
+
+        // Zero-size requests never fail and never report.
+        alloc.alloc_slice::<u8>(0).unwrap();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+
+        alloc.clear_oom_hook();
+        assert!(alloc.alloc_slice::<u8>(32).is_err());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn mixed_sizes_and_alignments_conform_to_the_allocator_contract() {
+        // Offset the base by one byte so it is less aligned than most of
+        // the requests.
+        let region = Vec::leak(vec![0u8; 4 * 4096 + 1]);
+        let base = region.as_ptr().addr() + 1;
+        let alloc = BumpAllocator::from_slice(&mut region[1..]);
+        let end = base + alloc.report().capacity;
+
+        let layouts = [
+            Layout::new::<()>(),
+            Layout::new::<u8>(),
+            Layout::new::<u64>(),
+            Layout::from_size_align(0, 4096).unwrap(),
+            Layout::from_size_align(4096, 4096).unwrap(),
+            Layout::new::<u8>(),
+            Layout::new::<[u64; 0]>(),
+            Layout::from_size_align(100, 4096).unwrap(),
+            Layout::new::<u64>(),
+            Layout::from_size_align(0, 64).unwrap(),
+        ];
+        let mut blocks: Vec<(usize, usize)> = Vec::new();
+        for layout in layouts {
+            let used = alloc.used();
+            let block = alloc.allocate(layout).unwrap();
+            let addr = block.as_ptr().addr();
+            assert_eq!(block.len(), layout.size());
+            assert!(addr.is_multiple_of(layout.align()), "{:?}", layout);
+
+            if layout.size() == 0 {
+                // Zero-size blocks are dangling and consume nothing.
+                assert_eq!(alloc.used(), used);
+                continue;
+            }
+            assert!(addr >= base && addr + layout.size() <= end);
+            unsafe { core::ptr::write_bytes(block.as_ptr() as *mut u8, 0xAB, layout.size()) };
+            blocks.push((addr, addr + layout.size()));
+        }
+
+        blocks.sort_unstable();
+        for pair in blocks.windows(2) {
+            assert!(pair[0].1 <= pair[1].0, "{:x?} overlaps", pair);
+        }
+
+        // Another 4096-aligned page does not fit in what is left.
+        let page = Layout::from_size_align(4096, 4096).unwrap();
+        assert!(alloc.allocate(page).is_err());
+        assert!(alloc.allocate(Layout::from_size_align(0, 4096).unwrap()).is_ok());
+    }
+}
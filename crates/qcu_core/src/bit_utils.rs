@@ -4,6 +4,16 @@
 //! Used throughout the decoder for managing parity bits, Pauli frame registers,
 //! and syndrome tracking. All operations are inlined for maximum performance
 //! in hot paths.
+//!
+//! Whole-array operations assume that bits past the logical length of a
+//! vector (the unused high bits of its last word) are kept zero. Code that
+//! loads words from outside, such as packed shot data, must mask them off.
+
+use crate::QecError;
+use crate::static_vec::StaticVec;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::ops::Range;
 
 /// Static utility functions for bit-level operations on u64 word arrays.
 ///
@@ -20,6 +30,10 @@ impl BitPack {
     /// the bit value. Used for reading parity flags, Pauli frame error bits,
     /// and syndrome indicators without unpacking the entire bit vector.
     ///
+    /// This is the fast path for indices the caller has already validated;
+    /// use `try_get` for indices taken from untrusted input such as a
+    /// received packet.
+    ///
     /// # Arguments
     ///
     /// * `storage` - Array of u64 words containing the bit vector
@@ -28,8 +42,13 @@ impl BitPack {
     /// # Returns
     ///
     /// True if the bit is set, false otherwise.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is past the end of `storage`.
     #[inline(always)]
     pub fn get(storage: &[u64], index: usize) -> bool {
+        Self::debug_check(storage, index);
         let word = storage[index / 64];
         let bit = index % 64;
         (word >> bit) & 1 == 1
@@ -46,8 +65,13 @@ impl BitPack {
     ///
     /// * `storage` - Mutable array of u64 words containing the bit vector
     /// * `index` - Linear bit index to toggle
+    ///
+    /// # Panics
+    ///
+    /// If `index` is past the end of `storage`.
     #[inline(always)]
     pub fn toggle(storage: &mut [u64], index: usize) {
+        Self::debug_check(storage, index);
         let word_idx = index / 64;
         let bit_idx = index % 64;
         storage[word_idx] ^= 1 << bit_idx;
@@ -65,8 +89,13 @@ impl BitPack {
     /// * `storage` - Mutable array of u64 words containing the bit vector
     /// * `index` - Linear bit index to modify
     /// * `val` - Desired bit value (true to set, false to clear)
+    ///
+    /// # Panics
+    ///
+    /// If `index` is past the end of `storage`.
     #[inline(always)]
     pub fn set(storage: &mut [u64], index: usize, val: bool) {
+        Self::debug_check(storage, index);
         if val {
             let word_idx = index / 64;
             let bit_idx = index % 64;
@@ -77,4 +106,1186 @@ impl BitPack {
             storage[word_idx] &= !(1 << bit_idx);
         }
     }
+
+    /// Reads a single bit, returning None if the index is out of range.
+    ///
+    /// Checked counterpart of `get` for indices from untrusted input, such
+    /// as detector IDs in a received or loaded packet.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `index` - Linear bit index (0-based)
+    ///
+    /// # Returns
+    ///
+    /// The bit value, or None if `index` is past the end of `storage`.
+    #[inline(always)]
+    pub fn try_get(storage: &[u64], index: usize) -> Option<bool> {
+        let word = storage.get(index / 64)?;
+        Some((word >> (index % 64)) & 1 == 1)
+    }
+
+    /// Sets a bit to a specific value, failing if the index is out of range.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Mutable array of u64 words containing the bit vector
+    /// * `index` - Linear bit index to modify
+    /// * `val` - Desired bit value (true to set, false to clear)
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if `index` is past the end of
+    /// `storage`.
+    #[inline(always)]
+    pub fn try_set(storage: &mut [u64], index: usize, val: bool) -> Result<(), QecError> {
+        let word = storage
+            .get_mut(index / 64)
+            .ok_or(QecError::NodeOutOfBounds)?;
+        let mask = 1 << (index % 64);
+        if val {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+        Ok(())
+    }
+
+    /// Toggles a single bit, failing if the index is out of range.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Mutable array of u64 words containing the bit vector
+    /// * `index` - Linear bit index to toggle
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if `index` is past the end of
+    /// `storage`.
+    #[inline(always)]
+    pub fn try_toggle(storage: &mut [u64], index: usize) -> Result<(), QecError> {
+        let word = storage
+            .get_mut(index / 64)
+            .ok_or(QecError::NodeOutOfBounds)?;
+        *word ^= 1 << (index % 64);
+        Ok(())
+    }
+
+    /// Reads a single bit without a bounds check.
+    ///
+    /// For inner loops where the index is already known to be in range,
+    /// such as the decoder's parity reads on DSU roots.
+    ///
+    /// # Safety
+    ///
+    /// `index / 64` must be less than `storage.len()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `index` - Linear bit index (0-based)
+    ///
+    /// # Returns
+    ///
+    /// True if the bit is set, false otherwise.
+    #[inline(always)]
+    pub unsafe fn get_unchecked(storage: &[u64], index: usize) -> bool {
+        Self::debug_check(storage, index);
+        let word = unsafe { *storage.get_unchecked(index / 64) };
+        (word >> (index % 64)) & 1 == 1
+    }
+
+    /// Asserts in debug builds that a bit index is within `storage`.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `index` - Linear bit index about to be accessed
+    #[inline(always)]
+    fn debug_check(storage: &[u64], index: usize) {
+        debug_assert!(
+            index / 64 < storage.len(),
+            "bit index {} out of range for a {}-bit vector",
+            index,
+            storage.len() * 64
+        );
+    }
+
+    /// Counts the set bits in a packed bit array.
+    ///
+    /// Gives the number of defects in a syndrome, whose parity tells the
+    /// decoder up front whether a boundary is needed to pair them all.
+    /// Relies on unused high bits in the last word being zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    ///
+    /// # Returns
+    ///
+    /// The number of set bits.
+    #[inline(always)]
+    pub fn count_ones(storage: &[u64]) -> usize {
+        storage.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Counts the set bits within a bit range.
+    ///
+    /// Partial words at either end of the range are masked, so bits outside
+    /// it are never counted. The range is clamped to the length of
+    /// `storage`.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to count
+    ///
+    /// # Returns
+    ///
+    /// The number of set bits in `range`.
+    pub fn count_ones_range(storage: &[u64], range: Range<usize>) -> usize {
+        let end = range.end.min(storage.len() * 64);
+        if range.start >= end {
+            return 0;
+        }
+        (range.start / 64..=(end - 1) / 64)
+            .map(|w| (storage[w] & Self::range_mask(w, range.start, end)).count_ones() as usize)
+            .sum()
+    }
+
+    /// Clears every bit in a bit range.
+    ///
+    /// Used to drop the detectors of committed rounds in windowed decoding,
+    /// and to zero the padding bits past the end of a shot loaded from
+    /// byte-aligned `.b8` data. Partial words at either end are masked and
+    /// words in between are overwritten whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Mutable array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to clear
+    ///
+    /// # Panics
+    ///
+    /// If `range.end` is past the end of `storage`.
+    pub fn clear_range(storage: &mut [u64], range: Range<usize>) {
+        Self::update_range(storage, range, |word, mask| *word &= !mask);
+    }
+
+    /// Sets every bit in a bit range.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Mutable array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to set
+    ///
+    /// # Panics
+    ///
+    /// If `range.end` is past the end of `storage`.
+    pub fn set_range(storage: &mut [u64], range: Range<usize>) {
+        Self::update_range(storage, range, |word, mask| *word |= mask);
+    }
+
+    /// Flips every bit in a bit range.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Mutable array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to flip
+    ///
+    /// # Panics
+    ///
+    /// If `range.end` is past the end of `storage`.
+    pub fn toggle_range(storage: &mut [u64], range: Range<usize>) {
+        Self::update_range(storage, range, |word, mask| *word ^= mask);
+    }
+
+    /// Applies a masked update to each word overlapping a bit range.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Mutable array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to update; may be empty
+    /// * `op` - Update applied to each word with the mask of its bits in range
+    #[inline(always)]
+    fn update_range(storage: &mut [u64], range: Range<usize>, op: impl Fn(&mut u64, u64)) {
+        assert!(
+            range.end <= storage.len() * 64,
+            "bit range end {} out of bounds for {} words",
+            range.end,
+            storage.len()
+        );
+        if range.start >= range.end {
+            return;
+        }
+        let (first, last) = (range.start / 64, (range.end - 1) / 64);
+        for (w, word) in storage[first..=last].iter_mut().enumerate() {
+            op(word, Self::range_mask(first + w, range.start, range.end));
+        }
+    }
+
+    /// Returns the bits of word `w` that fall inside `start..end`.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - Word index, overlapping the range
+    /// * `start` - First bit index of the range
+    /// * `end` - Exclusive end of the range, greater than `start`
+    #[inline(always)]
+    fn range_mask(w: usize, start: usize, end: usize) -> u64 {
+        let mut mask = u64::MAX;
+        if w == start / 64 {
+            mask &= u64::MAX << (start % 64);
+        }
+        if w == (end - 1) / 64 {
+            mask &= u64::MAX >> (63 - (end - 1) % 64);
+        }
+        mask
+    }
+
+    /// Returns the parity of a packed bit array.
+    ///
+    /// XOR-reduces the words before a single popcount, which is cheaper
+    /// than `count_ones` when only oddness matters, as in the up-front
+    /// check for a syndrome that cannot be paired without a boundary.
+    /// Relies on unused high bits in the last word being zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    ///
+    /// # Returns
+    ///
+    /// True if an odd number of bits is set.
+    #[inline(always)]
+    pub fn parity(storage: &[u64]) -> bool {
+        storage.iter().fold(0, |acc, &w| acc ^ w).count_ones() & 1 == 1
+    }
+
+    /// Returns the parity of the bits within a bit range.
+    ///
+    /// Partial words at either end are masked, so bits outside the range,
+    /// including padding past the logical length, never affect the result.
+    /// The range is clamped to the length of `storage`.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to include
+    ///
+    /// # Returns
+    ///
+    /// True if an odd number of bits in `range` is set.
+    pub fn parity_range(storage: &[u64], range: Range<usize>) -> bool {
+        let end = range.end.min(storage.len() * 64);
+        if range.start >= end {
+            return false;
+        }
+        (range.start / 64..=(end - 1) / 64)
+            .fold(0, |acc, w| acc ^ (storage[w] & Self::range_mask(w, range.start, end)))
+            .count_ones()
+            & 1
+            == 1
+    }
+
+    /// Returns true if no bit is set in a packed bit array.
+    ///
+    /// Lets firmware skip the decoder entirely for shots without defects.
+    /// Relies on unused high bits in the last word being zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    #[inline(always)]
+    pub fn is_all_zero(storage: &[u64]) -> bool {
+        storage.iter().all(|&w| w == 0)
+    }
+
+    /// Packs a slice of bools into u64 words.
+    ///
+    /// Uses the canonical LSB-first order: `bits[i]` becomes bit `i % 64`
+    /// of word `i / 64`, the same order as the bytes of a `.b8` file read
+    /// as little-endian words. Every word of `out` is written, and bits
+    /// past `bits.len()` are left zero, as whole-array operations expect.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - Bits to pack
+    /// * `out` - Destination words, at least `bits.len().div_ceil(64)` long
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if `out` is too short.
+    pub fn pack_bools(bits: &[bool], out: &mut [u64]) -> Result<(), QecError> {
+        if out.len() < bits.len().div_ceil(64) {
+            return Err(QecError::BufferOverflow);
+        }
+        out.fill(0);
+        for (word, chunk) in out.iter_mut().zip(bits.chunks(64)) {
+            for (i, &bit) in chunk.iter().enumerate() {
+                *word |= (bit as u64) << i;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unpacks u64 words into a slice of bools.
+    ///
+    /// Inverse of `pack_bools`, with the same bit order. Fills all of
+    /// `out`; bits of `words` past `out.len()` are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - Packed bit vector
+    /// * `out` - Destination bools, at most `words.len() * 64` long
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if `words` holds fewer than
+    /// `out.len()` bits.
+    pub fn unpack_bools(words: &[u64], out: &mut [bool]) -> Result<(), QecError> {
+        if words.len() < out.len().div_ceil(64) {
+            return Err(QecError::BufferOverflow);
+        }
+        for (chunk, &word) in out.chunks_mut(64).zip(words) {
+            for (i, bit) in chunk.iter_mut().enumerate() {
+                *bit = (word >> i) & 1 == 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Packs LSB-first bytes, as stored in a `.b8` shot record, into u64
+    /// words.
+    ///
+    /// Bit `i % 8` of byte `i / 8` becomes bit `i % 64` of word `i / 64`.
+    /// Every word of `out` is written, and bits from `len` on are left zero,
+    /// so the padding bits of a record's last byte never reach the words.
+    /// Needs nothing beyond `core`, so build scripts can pack shot data with
+    /// it too.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Packed bytes, at least `len.div_ceil(8)` long
+    /// * `len` - Number of bits to take from `bytes`
+    /// * `out` - Destination words, at least `len.div_ceil(64)` long
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if `bytes` or `out` is too
+    /// short.
+    pub fn pack_bytes(bytes: &[u8], len: usize, out: &mut [u64]) -> Result<(), QecError> {
+        if bytes.len() < len.div_ceil(8) || out.len() < len.div_ceil(64) {
+            return Err(QecError::BufferOverflow);
+        }
+        out.fill(0);
+        for (word, chunk) in out.iter_mut().zip(bytes[..len.div_ceil(8)].chunks(8)) {
+            let mut le = [0u8; 8];
+            le[..chunk.len()].copy_from_slice(chunk);
+            *word = u64::from_le_bytes(le);
+        }
+        Self::clear_range(out, len..out.len() * 64);
+        Ok(())
+    }
+
+    /// Packs a slice of bools into a newly allocated word vector.
+    ///
+    /// Host-side convenience over `pack_bools`, sized to exactly
+    /// `bits.len().div_ceil(64)` words.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - Bits to pack
+    ///
+    /// # Returns
+    ///
+    /// The packed words.
+    pub fn pack_bools_vec(bits: &[bool]) -> Vec<u64> {
+        let mut words = alloc::vec![0u64; bits.len().div_ceil(64)];
+        // Sized above, so the length check cannot fail.
+        let _ = Self::pack_bools(bits, &mut words);
+        words
+    }
+
+    /// Appends the index of every set bit to a static vector.
+    ///
+    /// The one place that turns a packed syndrome into the detector index
+    /// list the decoder takes, so the bit order is defined once: bit `i % 64`
+    /// of word `i / 64` is detector `i`, matching the LSB-first byte order
+    /// of `.b8` files.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `out` - Vector the indices are appended to
+    ///
+    /// # Returns
+    ///
+    /// The number of indices appended, or `BufferOverflow` if `out` filled
+    /// up first. The indices that fit are kept, lowest first.
+    pub fn extract_indices<const N: usize>(
+        storage: &[u64],
+        out: &mut StaticVec<usize, N>,
+    ) -> Result<usize, QecError> {
+        let before = out.len();
+        let mut ones = Self::iter_ones(storage);
+        let mut written = 0;
+        for (slot, index) in out.spare_capacity_mut().iter_mut().zip(&mut ones) {
+            slot.write(index);
+            written += 1;
+        }
+        // The first `written` spare slots were initialised above, and there
+        // are at most N - before of them.
+        unsafe { out.set_len(before + written) };
+
+        if ones.next().is_some() {
+            return Err(QecError::BufferOverflow);
+        }
+        Ok(written)
+    }
+
+    /// Appends the index of every set bit to a heap-allocated vector.
+    ///
+    /// Host-side counterpart of `extract_indices`, with the same bit order.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `out` - Vector the indices are appended to
+    ///
+    /// # Returns
+    ///
+    /// The number of indices appended.
+    pub fn extract_indices_vec(storage: &[u64], out: &mut Vec<usize>) -> usize {
+        let before = out.len();
+        out.extend(Self::iter_ones(storage));
+        out.len() - before
+    }
+
+    /// XORs one packed bit array into another.
+    ///
+    /// Differences two rounds of measurements into detector bits, or
+    /// applies a correction to a frame register.
+    ///
+    /// # Arguments
+    ///
+    /// * `dst` - Bit vector updated in place
+    /// * `src` - Bit vector XORed into `dst`
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the lengths differ.
+    pub fn xor_into(dst: &mut [u64], src: &[u64]) -> Result<(), QecError> {
+        Self::zip_words(dst, src, |d, s| *d ^= s)
+    }
+
+    /// ORs one packed bit array into another.
+    ///
+    /// # Arguments
+    ///
+    /// * `dst` - Bit vector updated in place
+    /// * `src` - Bit vector ORed into `dst`
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the lengths differ.
+    pub fn or_into(dst: &mut [u64], src: &[u64]) -> Result<(), QecError> {
+        Self::zip_words(dst, src, |d, s| *d |= s)
+    }
+
+    /// ANDs one packed bit array into another.
+    ///
+    /// # Arguments
+    ///
+    /// * `dst` - Bit vector updated in place
+    /// * `src` - Bit vector ANDed into `dst`
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the lengths differ.
+    pub fn and_into(dst: &mut [u64], src: &[u64]) -> Result<(), QecError> {
+        Self::zip_words(dst, src, |d, s| *d &= s)
+    }
+
+    /// Copies one packed bit array over another.
+    ///
+    /// # Arguments
+    ///
+    /// * `dst` - Bit vector to overwrite
+    /// * `src` - Bit vector to copy
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the lengths differ.
+    pub fn copy_into(dst: &mut [u64], src: &[u64]) -> Result<(), QecError> {
+        if dst.len() != src.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        dst.copy_from_slice(src);
+        Ok(())
+    }
+
+    /// Computes detector bits from two consecutive measurement rounds.
+    ///
+    /// In a multi-round code a detector fires when a stabiliser measurement
+    /// differs from the same measurement in the previous round, so the
+    /// detector vector is `prev ^ curr`. The first round has no previous
+    /// measurement and is compared against the reset value, all zeros:
+    /// pass `None` as `prev` and its detectors are `curr` itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `prev` - Previous round's measurements, or None for the first round
+    /// * `curr` - Current round's measurements
+    /// * `detectors_out` - Destination for the detector bits
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the three vectors do not
+    /// all have the same length, in which case `detectors_out` is left
+    /// unchanged.
+    pub fn diff_rounds(
+        prev: Option<&[u64]>,
+        curr: &[u64],
+        detectors_out: &mut [u64],
+    ) -> Result<(), QecError> {
+        if detectors_out.len() != curr.len() || prev.is_some_and(|p| p.len() != curr.len()) {
+            return Err(QecError::BufferOverflow);
+        }
+        detectors_out.copy_from_slice(curr);
+        if let Some(prev) = prev {
+            for (d, &p) in detectors_out.iter_mut().zip(prev) {
+                *d ^= p;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the bits that differ between two packed bit arrays.
+    ///
+    /// Equivalent to XORing them and counting the ones, in a single pass
+    /// and without a scratch buffer. Used to check a residual syndrome
+    /// against the expected one.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - First bit vector
+    /// * `b` - Second bit vector
+    ///
+    /// # Returns
+    ///
+    /// The number of differing bits, or `BufferOverflow` if the lengths
+    /// differ.
+    pub fn xor_count(a: &[u64], b: &[u64]) -> Result<usize, QecError> {
+        if a.len() != b.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        Ok(a.iter()
+            .zip(b)
+            .map(|(x, y)| (x ^ y).count_ones() as usize)
+            .sum())
+    }
+
+    /// Applies a word-wise operation between two equal-length bit arrays.
+    ///
+    /// # Arguments
+    ///
+    /// * `dst` - Bit vector updated in place
+    /// * `src` - Bit vector supplying the second operand
+    /// * `op` - Operation applied to each pair of words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the lengths differ.
+    #[inline(always)]
+    fn zip_words(
+        dst: &mut [u64],
+        src: &[u64],
+        op: impl Fn(&mut u64, u64),
+    ) -> Result<(), QecError> {
+        if dst.len() != src.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        for (d, &s) in dst.iter_mut().zip(src) {
+            op(d, s);
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the lowest set bit, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    ///
+    /// # Returns
+    ///
+    /// The smallest set index, or None if no bit is set.
+    #[inline(always)]
+    pub fn first_one(storage: &[u64]) -> Option<usize> {
+        let (w, &word) = storage.iter().enumerate().find(|&(_, &word)| word != 0)?;
+        Some(w * 64 + word.trailing_zeros() as usize)
+    }
+
+    /// Returns the index of the lowest set bit after `after`, if any.
+    ///
+    /// Lets the peeling pass and the streaming decoder step to the next
+    /// defect without building the full index list. Zero words are skipped
+    /// whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `after` - Index to search strictly after
+    ///
+    /// # Returns
+    ///
+    /// The smallest set index greater than `after`, or None if there is
+    /// none.
+    #[inline(always)]
+    pub fn next_one(storage: &[u64], after: usize) -> Option<usize> {
+        let start = after.checked_add(1)?;
+        let first = start / 64;
+        let head = storage.get(first)? & (u64::MAX << (start % 64));
+        if head != 0 {
+            return Some(first * 64 + head.trailing_zeros() as usize);
+        }
+        let rest = &storage[first + 1..];
+        let (w, &word) = rest.iter().enumerate().find(|&(_, &word)| word != 0)?;
+        Some((first + 1 + w) * 64 + word.trailing_zeros() as usize)
+    }
+
+    /// Returns the index of the highest set bit, if any.
+    ///
+    /// Relies on unused high bits in the last word being zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    ///
+    /// # Returns
+    ///
+    /// The largest set index, or None if no bit is set.
+    #[inline(always)]
+    pub fn last_one(storage: &[u64]) -> Option<usize> {
+        let (w, &word) = storage.iter().enumerate().rfind(|&(_, &word)| word != 0)?;
+        Some(w * 64 + 63 - word.leading_zeros() as usize)
+    }
+
+    /// Iterates over the indices of the set bits in a packed bit array.
+    ///
+    /// Skips zero words and visits each set bit via `trailing_zeros`, so a
+    /// sparse syndrome costs time proportional to its number of defects
+    /// rather than to its length. Indices are yielded in increasing order,
+    /// with bit 0 of word 0 as index 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the linear indices of every set bit.
+    #[inline(always)]
+    pub fn iter_ones(storage: &[u64]) -> Ones<'_> {
+        Self::iter_ones_range(storage, 0..storage.len() * 64)
+    }
+
+    /// Iterates over the indices of the set bits within a bit range.
+    ///
+    /// Used for windowed decoding, where only the detectors of the rounds
+    /// in the current window are of interest. The range is clamped to the
+    /// length of `storage`; an empty range yields nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - Array of u64 words containing the bit vector
+    /// * `range` - Linear bit indices to scan
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the linear indices of the set bits in `range`.
+    pub fn iter_ones_range(storage: &[u64], range: Range<usize>) -> Ones<'_> {
+        let end = range.end.min(storage.len() * 64);
+        if range.start >= end {
+            return Ones {
+                words: &[],
+                base: 0,
+                current: 0,
+                end: 0,
+            };
+        }
+        let first = range.start / 64;
+        Ones {
+            words: &storage[first + 1..end.div_ceil(64)],
+            base: first * 64,
+            current: storage[first] & (u64::MAX << (range.start % 64)),
+            end,
+        }
+    }
+}
+
+/// Iterator over the set bits of a packed bit array.
+///
+/// Returned by `BitPack::iter_ones` and `BitPack::iter_ones_range`.
+#[derive(Debug, Clone)]
+pub struct Ones<'a> {
+    /// Words not yet loaded into `current`.
+    words: &'a [u64],
+
+    /// Linear bit index of bit 0 of `current`.
+    base: usize,
+
+    /// Remaining set bits of the word being scanned.
+    current: u64,
+
+    /// Exclusive upper bound on the indices to yield.
+    end: usize,
+}
+
+impl Iterator for Ones<'_> {
+    type Item = usize;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            let (&word, rest) = self.words.split_first()?;
+            self.words = rest;
+            self.base += 64;
+            self.current = word;
+        }
+        let index = self.base + self.current.trailing_zeros() as usize;
+        if index >= self.end {
+            self.current = 0;
+            self.words = &[];
+            return None;
+        }
+        self.current &= self.current - 1;
+        Some(index)
+    }
+}
+
+impl FusedIterator for Ones<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+    use alloc::vec;
+
+    /// Bit vectors checked per property.
+    const CASES: usize = 200;
+
+    impl Rng {
+        /// Returns 0..=4 words, each empty, full, sparse or random.
+        fn words(&mut self) -> Vec<u64> {
+            let len = self.below(5);
+            (0..len)
+                .map(|_| match self.below(4) {
+                    0 => 0,
+                    1 => u64::MAX,
+                    2 => 1 << self.below(64),
+                    _ => self.next(),
+                })
+                .collect()
+        }
+
+        /// Returns a range within `0..=bits`, empty or reversed now and then.
+        fn range(&mut self, bits: usize) -> Range<usize> {
+            let a = self.below(bits + 1);
+            let b = self.below(bits + 1);
+            if self.below(8) == 0 {
+                b.max(a)..a.min(b)
+            } else {
+                a.min(b)..a.max(b)
+            }
+        }
+    }
+
+    /// In-place update of a bit range, as taken by `clear_range` and friends.
+    type RangeOp = fn(&mut [u64], Range<usize>);
+
+    /// Effect of a `RangeOp` on one bit inside its range.
+    type BitUpdate = fn(bool) -> bool;
+
+    /// Word-wise combination of two bit vectors, as taken by `xor_into` and friends.
+    type WordOp = fn(&mut [u64], &[u64]) -> Result<(), QecError>;
+
+    /// Effect of a `WordOp` on one `(dst, src)` bit pair.
+    type BitOp = fn(bool, bool) -> bool;
+
+    /// Unpacks a bit vector one bit at a time.
+    fn naive(storage: &[u64]) -> Vec<bool> {
+        (0..storage.len() * 64)
+            .map(|i| (storage[i / 64] >> (i % 64)) & 1 == 1)
+            .collect()
+    }
+
+    /// Indices of the set bits in `range`, found by scanning every bit.
+    fn naive_ones(storage: &[u64], range: Range<usize>) -> Vec<usize> {
+        let bits = naive(storage);
+        range.filter(|&i| i < bits.len() && bits[i]).collect()
+    }
+
+    #[test]
+    fn pack_bytes_matches_pack_bools() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for _ in 0..CASES {
+            let bytes: Vec<u8> = (0..rng.below(20)).map(|_| rng.next() as u8).collect();
+            let len = rng.below(bytes.len() * 8 + 1);
+            let bits: Vec<bool> = (0..len).map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1).collect();
+
+            // Start from garbage, including a spare word, to check that
+            // every word is written and the tail is cleared.
+            let mut out = vec![u64::MAX; len.div_ceil(64) + 1];
+            BitPack::pack_bytes(&bytes, len, &mut out).unwrap();
+            let mut expected = vec![0; out.len()];
+            BitPack::pack_bools(&bits, &mut expected).unwrap();
+            assert_eq!(out, expected, "{len} bits of {bytes:?}");
+        }
+    }
+
+    #[test]
+    fn pack_bytes_rejects_short_buffers() {
+        let mut out = [0u64; 1];
+        assert!(matches!(
+            BitPack::pack_bytes(&[0xff; 8], 65, &mut out),
+            Err(QecError::BufferOverflow)
+        ));
+        let mut out = [0u64; 2];
+        assert!(matches!(
+            BitPack::pack_bytes(&[0xff; 8], 65, &mut out),
+            Err(QecError::BufferOverflow)
+        ));
+        assert!(BitPack::pack_bytes(&[0xff; 9], 65, &mut out).is_ok());
+        assert_eq!(out, [u64::MAX, 1]);
+    }
+
+    #[test]
+    fn count_ones_range_matches_naive() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let range = rng.range(words.len() * 64 + 70);
+            assert_eq!(
+                BitPack::count_ones_range(&words, range.clone()),
+                naive_ones(&words, range.clone()).len(),
+                "{:x?} {:?}",
+                words,
+                range
+            );
+            assert_eq!(
+                BitPack::parity_range(&words, range.clone()),
+                naive_ones(&words, range).len() % 2 == 1
+            );
+        }
+    }
+
+    #[test]
+    fn range_updates_match_naive() {
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let range = rng.range(words.len() * 64);
+            let ops: [(RangeOp, BitUpdate); 3] = [
+                (BitPack::clear_range, |_| false),
+                (BitPack::set_range, |_| true),
+                (BitPack::toggle_range, |b| !b),
+            ];
+            for (op, expected) in ops {
+                let mut updated = words.clone();
+                op(&mut updated, range.clone());
+                let want: Vec<bool> = naive(&words)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| if range.contains(&i) { expected(b) } else { b })
+                    .collect();
+                assert_eq!(naive(&updated), want, "{:x?} {:?}", words, range);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn range_update_past_end_panics() {
+        BitPack::set_range(&mut [0u64; 2], 100..129);
+    }
+
+    #[test]
+    fn ones_match_naive() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let all = naive_ones(&words, 0..words.len() * 64);
+            assert_eq!(BitPack::iter_ones(&words).collect::<Vec<_>>(), all);
+            assert_eq!(BitPack::first_one(&words), all.first().copied());
+            assert_eq!(BitPack::last_one(&words), all.last().copied());
+
+            let range = rng.range(words.len() * 64 + 70);
+            let ones: Vec<_> = BitPack::iter_ones_range(&words, range.clone()).collect();
+            assert_eq!(
+                ones,
+                naive_ones(&words, range.clone()),
+                "{:x?} {:?}",
+                words,
+                range
+            );
+
+            let mut iter = BitPack::iter_ones_range(&words, range);
+            while iter.next().is_some() {}
+            assert_eq!(iter.next(), None);
+        }
+    }
+
+    #[test]
+    fn next_one_matches_naive() {
+        let mut rng = Rng(0xA076_1D64_78BD_642F);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let bits = words.len() * 64;
+            for after in (0..bits + 2).chain([usize::MAX]) {
+                let want = naive_ones(&words, after.saturating_add(1)..bits)
+                    .first()
+                    .copied();
+                assert_eq!(
+                    BitPack::next_one(&words, after),
+                    want,
+                    "{:x?} {}",
+                    words,
+                    after
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extract_indices_matches_naive() {
+        let mut rng = Rng(0xE703_7ED1_A0B4_28DB);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let all = naive_ones(&words, 0..words.len() * 64);
+
+            let mut vec_out = vec![7];
+            assert_eq!(
+                BitPack::extract_indices_vec(&words, &mut vec_out),
+                all.len()
+            );
+            assert_eq!(vec_out[1..], all[..]);
+
+            let mut out: StaticVec<usize, 16> = StaticVec::new();
+            out.push(7).unwrap();
+            let result = BitPack::extract_indices(&words, &mut out);
+            let fits = all.len().min(15);
+            assert_eq!(out.as_slice()[1..], all[..fits]);
+            if all.len() <= 15 {
+                assert!(matches!(result, Ok(n) if n == all.len()));
+            } else {
+                assert!(matches!(result, Err(QecError::BufferOverflow)));
+            }
+        }
+    }
+
+    #[test]
+    fn diff_rounds_matches_naive() {
+        let mut rng = Rng(0x8EBC_6AF0_9C88_C6E3);
+        for _ in 0..CASES {
+            let len = rng.below(5);
+            let prev: Vec<u64> = (0..len).map(|_| rng.next()).collect();
+            let curr: Vec<u64> = (0..len).map(|_| rng.next()).collect();
+            let mut out = vec![u64::MAX; len];
+
+            BitPack::diff_rounds(None, &curr, &mut out).unwrap();
+            assert_eq!(out, curr);
+
+            BitPack::diff_rounds(Some(&prev), &curr, &mut out).unwrap();
+            let want: Vec<bool> = naive(&prev)
+                .iter()
+                .zip(naive(&curr))
+                .map(|(&p, c)| p != c)
+                .collect();
+            assert_eq!(naive(&out), want);
+            let differing = want.iter().filter(|&&b| b).count();
+            assert!(matches!(BitPack::xor_count(&prev, &curr), Ok(n) if n == differing));
+        }
+
+        let mut short = [0u64; 1];
+        assert!(matches!(
+            BitPack::diff_rounds(Some(&[0; 2]), &[0; 2], &mut short),
+            Err(QecError::BufferOverflow)
+        ));
+        // A short `prev` is caught before `curr` is copied out.
+        let mut out = [7u64; 2];
+        assert!(matches!(
+            BitPack::diff_rounds(Some(&[0; 1]), &[1; 2], &mut out),
+            Err(QecError::BufferOverflow)
+        ));
+        assert_eq!(out, [7; 2]);
+    }
+
+    #[test]
+    fn checked_accessors_reject_indices_past_the_end() {
+        let mut storage = [0u64; 2];
+        let len = storage.len() * 64;
+        for index in [len, len + 1, usize::MAX] {
+            assert_eq!(BitPack::try_get(&storage, index), None);
+            assert!(matches!(
+                BitPack::try_set(&mut storage, index, true),
+                Err(QecError::NodeOutOfBounds)
+            ));
+            assert!(matches!(
+                BitPack::try_toggle(&mut storage, index),
+                Err(QecError::NodeOutOfBounds)
+            ));
+        }
+        assert_eq!(storage, [0; 2]);
+
+        // The last bit is still reachable.
+        BitPack::try_set(&mut storage, len - 1, true).unwrap();
+        assert_eq!(BitPack::try_get(&storage, len - 1), Some(true));
+        BitPack::try_toggle(&mut storage, len - 1).unwrap();
+        BitPack::try_toggle(&mut storage, 0).unwrap();
+        assert_eq!(storage, [1, 0]);
+        BitPack::try_set(&mut storage, 0, false).unwrap();
+        assert_eq!(BitPack::try_get(&storage, 0), Some(false));
+        assert_eq!(BitPack::try_get(&[], 0), None);
+    }
+
+    #[test]
+    fn unpack_inverts_pack_for_every_length() {
+        let mut rng = Rng(0x6C8E_9CF5_7093_2BD5);
+        for len in 1..=130usize {
+            let bits: Vec<bool> = (0..len).map(|_| rng.below(2) == 1).collect();
+            let words = BitPack::pack_bools_vec(&bits);
+            assert_eq!(words.len(), len.div_ceil(64));
+            assert_eq!(naive(&words)[..len], bits[..]);
+
+            let mut back = vec![false; len];
+            BitPack::unpack_bools(&words, &mut back).unwrap();
+            assert_eq!(back, bits, "length {len}");
+        }
+
+        let mut out = [false; 65];
+        assert!(matches!(
+            BitPack::unpack_bools(&[u64::MAX], &mut out),
+            Err(QecError::BufferOverflow)
+        ));
+        // Bits of the words past `out` are ignored.
+        BitPack::unpack_bools(&[u64::MAX, 1, 7], &mut out).unwrap();
+        assert!(out.iter().all(|&b| b));
+    }
+
+
+    #[test]
+    fn word_combinators_match_naive() {
+        let ops: [(WordOp, BitOp); 4] = [
+            (BitPack::xor_into, |d, s| d != s),
+            (BitPack::or_into, |d, s| d | s),
+            (BitPack::and_into, |d, s| d & s),
+            (BitPack::copy_into, |_, s| s),
+        ];
+        let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+        for _ in 0..CASES {
+            let dst = rng.words();
+            let src: Vec<u64> = (0..dst.len()).map(|_| rng.next()).collect();
+            for (op, bit) in ops {
+                let mut out = dst.clone();
+                op(&mut out, &src).unwrap();
+                let want: Vec<bool> = naive(&dst)
+                    .iter()
+                    .zip(naive(&src))
+                    .map(|(&d, s)| bit(d, s))
+                    .collect();
+                assert_eq!(naive(&out), want);
+            }
+        }
+
+        // Mismatched lengths are rejected and leave `dst` untouched.
+        for (op, _) in ops {
+            for (dst_len, src_len) in [(2, 1), (1, 2), (0, 1)] {
+                let mut dst = vec![0x5A5A_u64; dst_len];
+                let src = vec![u64::MAX; src_len];
+                assert!(matches!(op(&mut dst, &src), Err(QecError::BufferOverflow)));
+                assert_eq!(dst, vec![0x5A5A; dst_len]);
+            }
+        }
+    }
+
+
+    #[test]
+    fn parity_matches_naive_across_words() {
+        let mut rng = Rng(0xA54F_F53A_5F1D_36F1);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let ones = naive(&words).iter().filter(|&&b| b).count();
+            assert_eq!(BitPack::parity(&words), ones % 2 == 1);
+        }
+
+        // One bit on each side of a word boundary cancels out.
+        assert!(BitPack::parity(&[1 << 63, 0]));
+        assert!(!BitPack::parity(&[1 << 63, 1]));
+        assert!(!BitPack::parity(&[1, 0, 1]));
+        assert!(!BitPack::parity(&[]));
+    }
+
+    #[test]
+    fn parity_of_a_partial_last_word() {
+        let mut rng = Rng(0x510E_527F_ADE6_82D1);
+        for len in 1..=130usize {
+            let bits: Vec<bool> = (0..len).map(|_| rng.below(2) == 1).collect();
+            let odd = bits.iter().filter(|&&b| b).count() % 2 == 1;
+            let mut words = BitPack::pack_bools_vec(&bits);
+            assert_eq!(BitPack::parity(&words), odd, "length {len}");
+
+            // Stray padding bits would flip `parity`; `parity_range` masks them.
+            let padded = words.len() * 64;
+            if len < padded {
+                BitPack::toggle_range(&mut words, len..len + 1);
+                assert_ne!(BitPack::parity(&words), odd);
+                assert_eq!(BitPack::parity_range(&words, 0..len), odd);
+                BitPack::clear_range(&mut words, len..padded);
+                assert_eq!(BitPack::parity(&words), odd);
+            }
+        }
+    }
+
+
+    #[test]
+    fn count_ones_and_is_all_zero_match_naive() {
+        let mut rng = Rng(0x9B05_688C_2B3E_6C1F);
+        for _ in 0..CASES {
+            let words = rng.words();
+            let ones = naive(&words).iter().filter(|&&b| b).count();
+            assert_eq!(BitPack::count_ones(&words), ones);
+            assert_eq!(BitPack::is_all_zero(&words), ones == 0);
+        }
+        assert!(BitPack::is_all_zero(&[]));
+        assert_eq!(BitPack::count_ones(&[u64::MAX; 3]), 192);
+    }
+
+    #[test]
+    fn count_ones_relies_on_zero_padding() {
+        let mut rng = Rng(0x1F83_D9AB_FB41_BD6B);
+        for len in 1..=130usize {
+            let bits: Vec<bool> = (0..len).map(|_| rng.below(4) == 0).collect();
+            let ones = bits.iter().filter(|&&b| b).count();
+            let mut words = BitPack::pack_bools_vec(&bits);
+            assert_eq!(BitPack::count_ones(&words), ones, "length {len}");
+            assert_eq!(BitPack::is_all_zero(&words), ones == 0);
+
+            // Set padding bits are counted by the whole-array calls, but not
+            // by `count_ones_range` over the logical length, and clearing
+            // them restores the invariant.
+            let padded = words.len() * 64;
+            if len < padded {
+                BitPack::set_range(&mut words, len..padded);
+                assert_eq!(BitPack::count_ones(&words), ones + padded - len);
+                assert!(!BitPack::is_all_zero(&words));
+                assert_eq!(BitPack::count_ones_range(&words, 0..len), ones);
+                BitPack::clear_range(&mut words, len..padded);
+                assert_eq!(BitPack::count_ones(&words), ones);
+                assert_eq!(BitPack::is_all_zero(&words), ones == 0);
+            }
+        }
+    }
 }
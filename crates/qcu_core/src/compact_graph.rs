@@ -0,0 +1,370 @@
+//! Compact decoding graph with 16-bit node indices.
+//!
+//! Stores the same topology as `DecodingGraph` but with `u16` edge endpoints
+//! and CSR targets, halving edge storage for codes with at most 65,536
+//! detectors. Intended for firmware, where the graph lives in a fixed-size
+//! bump region and every byte of edge storage counts.
+
+use crate::QecError;
+use crate::allocator::alloc_slice_uninit_in;
+use crate::graph::{DecodingGraph, EdgeList};
+use alloc::alloc::Global;
+use alloc::vec::Vec;
+use core::alloc::Allocator;
+
+/// Decoding graph storing node indices as `u16`.
+///
+/// Edges are kept as a flat `(u16, u16)` list alongside `f32` weights, and the
+/// optional CSR adjacency stores `u16` neighbour targets. Offsets remain `u32`
+/// since the total neighbour count can exceed the node index range. Node
+/// numbering is taken verbatim from the source: converting a graph that has
+/// been reordered keeps its relabelled IDs, and the permutation stays with the
+/// standard graph.
+///
+/// # Type Parameters
+///
+/// * `A` - Allocator type for all graph storage. Defaults to Global for
+///   host-side usage; firmware passes its bump allocator.
+pub struct CompactDecodingGraph<A: Allocator = Global> {
+    /// Flat list of graph edges as (u, v) node pairs.
+    pub fast_edges: Vec<(u16, u16), A>,
+
+    /// Edge weights, parallel to `fast_edges`.
+    ///
+    /// Stored at single precision. Weights are negative log probabilities
+    /// and do not need more than f32 resolution for decoding.
+    pub edge_weights: Vec<f32, A>,
+
+    /// CSR adjacency offsets into `adj_targets`.
+    ///
+    /// Empty until `build_adjacency` is called. Once built, has length
+    /// `max_node_id + 1`.
+    pub adj_offsets: Vec<u32, A>,
+
+    /// Packed neighbour list in CSR order.
+    pub adj_targets: Vec<u16, A>,
+
+    /// Number of nodes referenced by the graph (maximum node ID + 1).
+    pub max_node_id: usize,
+}
+
+impl CompactDecodingGraph<Global> {
+    /// Creates a new compact graph with global allocator.
+    ///
+    /// # Arguments
+    ///
+    /// * `edge_capacity` - Number of edges to reserve storage for
+    pub fn new(edge_capacity: usize) -> Self {
+        Self::new_in(edge_capacity, Global)
+    }
+}
+
+impl<A: Allocator> CompactDecodingGraph<A> {
+    /// Largest number of nodes a compact graph can address.
+    pub const MAX_NODES: usize = u16::MAX as usize + 1;
+
+    /// Creates a new compact graph with a custom allocator.
+    ///
+    /// Reserves exactly `edge_capacity` edges and weights up front. Unlike
+    /// `DecodingGraph::new_in`, the capacity is an edge count, so callers that
+    /// have counted their edges (e.g., in a first pass over a DEM) pay for no
+    /// slack in the bump region.
+    ///
+    /// # Arguments
+    ///
+    /// * `edge_capacity` - Number of edges to reserve storage for
+    /// * `alloc` - Allocator instance for graph storage
+    pub fn new_in(edge_capacity: usize, alloc: A) -> Self
+    where
+        A: Clone,
+    {
+        Self {
+            fast_edges: Vec::with_capacity_in(edge_capacity, alloc.clone()),
+            edge_weights: Vec::with_capacity_in(edge_capacity, alloc.clone()),
+            adj_offsets: Vec::new_in(alloc.clone()),
+            adj_targets: Vec::new_in(alloc),
+            max_node_id: 0,
+        }
+    }
+
+    /// Converts a standard decoding graph into compact form.
+    ///
+    /// Copies every edge and weight into storage from `alloc`. If the source
+    /// has its adjacency list built, the compact adjacency is built too.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - Source graph
+    /// * `alloc` - Allocator instance for the compact graph's storage
+    ///
+    /// # Returns
+    ///
+    /// The compact graph; `NodeOutOfBounds` if the source references a node
+    /// that does not fit in `u16`; or `OutOfMemory` if `alloc` cannot hold
+    /// the edges or adjacency.
+    pub fn try_from_graph<B: Allocator>(graph: &DecodingGraph<B>, alloc: A) -> Result<Self, QecError>
+    where
+        A: Clone,
+    {
+        if graph.num_nodes() > Self::MAX_NODES {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        let mut compact = Self::new_in(0, alloc);
+        let len = graph.fast_edges.len();
+        compact
+            .fast_edges
+            .try_reserve_exact(len)
+            .map_err(|_| QecError::OutOfMemory)?;
+        compact
+            .edge_weights
+            .try_reserve_exact(len)
+            .map_err(|_| QecError::OutOfMemory)?;
+        for (i, &(u, v)) in graph.fast_edges.iter().enumerate() {
+            compact.add_edge(u as usize, v as usize, graph.edge_weight(i))?;
+        }
+        compact.max_node_id = compact.max_node_id.max(graph.num_nodes());
+        if !graph.adj_offsets.is_empty() {
+            compact.build_adjacency()?;
+        }
+        Ok(compact)
+    }
+
+    /// Adds an edge between nodes u and v to the graph.
+    ///
+    /// # Arguments
+    ///
+    /// * `u` - First node index
+    /// * `v` - Second node index
+    /// * `weight` - Edge weight (negative log error probability)
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, `NodeOutOfBounds` if either index does not fit in
+    /// `u16`, or an error if memory allocation fails.
+    pub fn add_edge(&mut self, u: usize, v: usize, weight: f64) -> Result<(), QecError> {
+        if u >= Self::MAX_NODES || v >= Self::MAX_NODES {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        self.fast_edges
+            .try_reserve(1)
+            .map_err(|_| QecError::OutOfMemory)?;
+        self.edge_weights
+            .try_reserve(1)
+            .map_err(|_| QecError::OutOfMemory)?;
+        self.fast_edges.push((u as u16, v as u16));
+        self.edge_weights.push(weight as f32);
+
+        let max = u.max(v);
+        if max >= self.max_node_id {
+            self.max_node_id = max + 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub fn num_nodes(&self) -> usize {
+        self.max_node_id
+    }
+
+    /// Returns the weight of edge `i`, widened to f64.
+    ///
+    /// Falls back to 1.0 for edges without a recorded weight, matching
+    /// `DecodingGraph::edge_weight`.
+    pub fn edge_weight(&self, i: usize) -> f64 {
+        self.edge_weights.get(i).map_or(1.0, |&w| w as f64)
+    }
+
+    /// Builds the CSR adjacency list from the flat edge list.
+    ///
+    /// Counts node degrees, converts them into prefix-sum offsets, then
+    /// scatters each edge into both endpoints' neighbour ranges. Storage is
+    /// taken from the graph's own allocator. The target array is left
+    /// uninitialised until the scatter, which writes every slot exactly
+    /// once, so large graphs are not zeroed first.
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `OutOfMemory` if the allocator cannot hold the
+    /// adjacency. The previous adjacency, if any, is kept on failure.
+    pub fn build_adjacency(&mut self) -> Result<(), QecError>
+    where
+        A: Clone,
+    {
+        let n = self.max_node_id;
+        let alloc = self.fast_edges.allocator().clone();
+
+        let mut offsets = Vec::new_in(alloc.clone());
+        offsets
+            .try_reserve_exact(n + 1)
+            .map_err(|_| QecError::OutOfMemory)?;
+        offsets.resize(n + 1, 0u32);
+        for &(u, v) in &self.fast_edges {
+            offsets[u as usize + 1] += 1;
+            offsets[v as usize + 1] += 1;
+        }
+        for i in 0..n {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let total = offsets[n] as usize;
+        let mut pos = Vec::new_in(alloc.clone());
+        pos.try_reserve_exact(n)
+            .map_err(|_| QecError::OutOfMemory)?;
+        pos.extend_from_slice(&offsets[..n]);
+        let slots = alloc_slice_uninit_in::<u16, _>(&alloc, total)?;
+
+        for &(u, v) in &self.fast_edges {
+            slots[pos[u as usize] as usize].write(v);
+            pos[u as usize] += 1;
+            slots[pos[v as usize] as usize].write(u);
+            pos[v as usize] += 1;
+        }
+        // Each edge bumps both endpoints' degrees once and is scattered once
+        // into each endpoint's range, so every one of the `total` slots has
+        // been written. The block was allocated from `alloc` with the layout
+        // of `total` u16s, as `Vec` expects.
+        let targets =
+            unsafe { Vec::from_raw_parts_in(slots.as_mut_ptr().cast(), total, total, alloc) };
+
+        self.adj_offsets = offsets;
+        self.adj_targets = targets;
+        Ok(())
+    }
+
+    /// Returns the neighbours of node i.
+    ///
+    /// Returns an empty slice if the adjacency list has not been built or the
+    /// node is out of range.
+    pub fn neighbors(&self, i: usize) -> &[u16] {
+        if self.adj_offsets.len() <= i + 1 {
+            return &[];
+        }
+        let start = self.adj_offsets[i] as usize;
+        let end = self.adj_offsets[i + 1] as usize;
+        &self.adj_targets[start..end]
+    }
+}
+
+impl<A: Allocator> EdgeList for CompactDecodingGraph<A> {
+    type Index = u16;
+
+    #[inline(always)]
+    fn num_nodes(&self) -> usize {
+        self.max_node_id
+    }
+
+    #[inline(always)]
+    fn edge_pairs(&self) -> &[(u16, u16)] {
+        &self.fast_edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::BumpAllocator;
+    use alloc::vec;
+
+    /// Builds a 10x10 grid with weights that differ per edge.
+    fn grid() -> DecodingGraph {
+        let mut graph = DecodingGraph::new(100);
+        for r in 0..10 {
+            for c in 0..10 {
+                let node = r * 10 + c;
+                if c + 1 < 10 {
+                    graph.add_edge(node, node + 1, 0.5 + node as f64).unwrap();
+                }
+                if r + 1 < 10 {
+                    graph.add_edge(node + 10, node, 0.25 + node as f64).unwrap();
+                }
+            }
+        }
+        graph.build_adjacency();
+        graph
+    }
+
+    fn arena(len: usize) -> BumpAllocator {
+        BumpAllocator::from_slice(Vec::leak(vec![0u8; len]))
+    }
+
+    #[test]
+    fn conversion_keeps_edges_weights_and_adjacency() {
+        let graph = grid();
+        let compact = CompactDecodingGraph::try_from_graph(&graph, Global).unwrap();
+        assert_eq!(compact.num_nodes(), graph.num_nodes());
+        assert_eq!(compact.fast_edges.len(), graph.fast_edges.len());
+        for (i, (&(u, v), &(cu, cv))) in
+            graph.fast_edges.iter().zip(&compact.fast_edges).enumerate()
+        {
+            assert_eq!((u, v), (cu as u32, cv as u32));
+            assert_eq!(compact.edge_weight(i), graph.edge_weight(i) as f32 as f64);
+        }
+        for node in 0..graph.num_nodes() {
+            let mut expected = graph.neighbors(node).to_vec();
+            let mut found: Vec<u32> = compact.neighbors(node).iter().map(|&t| t as u32).collect();
+            expected.sort_unstable();
+            found.sort_unstable();
+            assert_eq!(found, expected, "node {node}");
+        }
+        assert!(compact.neighbors(graph.num_nodes()).is_empty());
+    }
+
+    #[test]
+    fn conversion_skips_adjacency_the_source_lacks() {
+        let mut graph = DecodingGraph::new(4);
+        graph.add_edge(0, 1, 1.0).unwrap();
+        let mut compact = CompactDecodingGraph::try_from_graph(&graph, Global).unwrap();
+        assert!(compact.adj_offsets.is_empty());
+        assert!(compact.neighbors(0).is_empty());
+        compact.build_adjacency().unwrap();
+        assert_eq!(compact.neighbors(0), [1]);
+    }
+
+    #[test]
+    fn nodes_beyond_u16_are_rejected() {
+        let mut fits = DecodingGraph::new(4);
+        fits.add_edge(0, u16::MAX as usize, 1.0).unwrap();
+        let compact = CompactDecodingGraph::try_from_graph(&fits, Global).unwrap();
+        assert_eq!(
+            compact.num_nodes(),
+            CompactDecodingGraph::<Global>::MAX_NODES
+        );
+
+        let mut wide = DecodingGraph::new(4);
+        wide.add_edge(0, 1, 1.0).unwrap();
+        wide.add_edge(1, u16::MAX as usize + 1, 1.0).unwrap();
+        assert!(matches!(
+            CompactDecodingGraph::try_from_graph(&wide, Global),
+            Err(QecError::NodeOutOfBounds)
+        ));
+
+        let mut compact = CompactDecodingGraph::new(1);
+        assert!(matches!(
+            compact.add_edge(u16::MAX as usize + 1, 0, 1.0),
+            Err(QecError::NodeOutOfBounds)
+        ));
+        assert!(compact.fast_edges.is_empty());
+    }
+
+    #[test]
+    fn exhausted_arena_reports_out_of_memory() {
+        let graph = grid();
+        // 180 edges take 720 bytes of pairs and 720 of weights, leaving too
+        // little for the 1,500 bytes of adjacency.
+        let small = arena(1600);
+        assert!(matches!(
+            CompactDecodingGraph::try_from_graph(&graph, &small),
+            Err(QecError::OutOfMemory)
+        ));
+
+        let tiny = arena(64);
+        assert!(matches!(
+            CompactDecodingGraph::try_from_graph(&graph, &tiny),
+            Err(QecError::OutOfMemory)
+        ));
+
+        let roomy = arena(4096);
+        let compact = CompactDecodingGraph::try_from_graph(&graph, &roomy).unwrap();
+        assert_eq!(compact.adj_targets.len(), 2 * graph.fast_edges.len());
+    }
+}
@@ -9,7 +9,7 @@
 use crate::QecError;
 use crate::bit_utils::BitPack;
 use crate::dsu::UnionFind;
-use crate::graph::DecodingGraph;
+use crate::graph::{EdgeList, NodeIndex};
 use crate::static_vec::StaticVec;
 use core::alloc::Allocator;
 
@@ -37,6 +37,9 @@ pub trait CorrectionBuffer {
     /// Resets the buffer to empty state, typically called at the start
     /// of a new decoding cycle to prepare for fresh correction output.
     fn clear_buffer(&mut self);
+
+    /// Returns the corrections accumulated so far.
+    fn corrections(&self) -> &[(usize, usize)];
 }
 
 impl<A: Allocator> CorrectionBuffer for alloc::vec::Vec<(usize, usize), A> {
@@ -58,6 +61,11 @@ impl<A: Allocator> CorrectionBuffer for alloc::vec::Vec<(usize, usize), A> {
     fn clear_buffer(&mut self) {
         self.clear();
     }
+
+    /// Returns the vector's contents as a slice.
+    fn corrections(&self) -> &[(usize, usize)] {
+        self
+    }
 }
 
 impl<const N: usize> CorrectionBuffer for StaticVec<(usize, usize), N> {
@@ -76,6 +84,11 @@ impl<const N: usize> CorrectionBuffer for StaticVec<(usize, usize), N> {
     fn clear_buffer(&mut self) {
         self.clear();
     }
+
+    /// Returns the static vector's contents as a slice.
+    fn corrections(&self) -> &[(usize, usize)] {
+        self.as_slice()
+    }
 }
 
 /// Union-Find decoder with compile-time node capacity limit.
@@ -154,6 +167,19 @@ where
         }
     }
 
+    /// Discards all internal state from the previous decoding cycle.
+    ///
+    /// `solve_into` reinitialises its buffers on every call, so this is not
+    /// needed for correctness between shots; it guarantees that no state from
+    /// an earlier shot survives a VM reset, e.g. when replaying shots
+    /// back-to-back through one VM.
+    pub fn reset(&mut self) {
+        self.parent.clear();
+        self.rank.clear();
+        self.parity.clear();
+        self.touched.clear();
+    }
+
     /// Solves the decoding problem and outputs corrections to the buffer.
     ///
     /// Processes the provided syndrome bits through the union-find algorithm,
@@ -164,7 +190,7 @@ where
     ///
     /// # Type Parameters
     ///
-    /// * `GA` - Allocator type for the decoding graph's edge storage
+    /// * `G` - Graph type, either `DecodingGraph` or `CompactDecodingGraph`
     /// * `CB` - Correction buffer type for output
     ///
     /// # Arguments
@@ -175,33 +201,36 @@ where
     ///
     /// # Returns
     ///
-    /// Ok(()) on success, or an error if buffer overflow or invalid indices
-    /// are encountered.
-    pub fn solve_into<GA: Allocator, CB: CorrectionBuffer>(
+    /// Ok(()) on success, `NodeOutOfBounds` if the graph has more than `N`
+    /// nodes, or an error if buffer overflow is encountered.
+    pub fn solve_into<G: EdgeList, CB: CorrectionBuffer>(
         &mut self,
-        graph: &DecodingGraph<GA>,
+        graph: &G,
         syndrome_indices: &[usize],
         out_buffer: &mut CB,
     ) -> Result<(), QecError> {
         out_buffer.clear_buffer();
 
-        let num_nodes = graph.num_nodes().min(N);
+        // Edge endpoints below are used as unchecked indices into the
+        // per-node state, which only has room for `N` nodes.
+        let num_nodes = graph.num_nodes();
+        if num_nodes > N {
+            return Err(QecError::NodeOutOfBounds);
+        }
 
         self.parent.clear();
         self.rank.clear();
         self.touched.clear();
         self.parity.clear();
 
-        for i in 0..num_nodes {
-            let _ = self.parent.push(i);
-            let _ = self.rank.push(0);
-            let _ = self.touched.push(0);
-        }
-
-        let num_u64 = num_nodes.div_ceil(64);
-        for _ in 0..num_u64 {
-            let _ = self.parity.push(0);
-        }
+        // Identity parents, zero ranks, flags, and parity bits. Filled in
+        // place rather than with `StaticVec::from_fn`, which would copy the
+        // whole N-element buffer into `self` on every call. `num_nodes <= N`,
+        // so neither the extend nor the resizes can overflow.
+        self.parent.extend(0..num_nodes);
+        self.rank.resize(num_nodes, 0)?;
+        self.touched.resize(num_nodes, 0)?;
+        self.parity.resize(num_nodes.div_ceil(64), 0)?;
 
         let mut dsu = UnionFind::new(
             self.parent.as_mut_slice(),
@@ -223,9 +252,9 @@ where
         // avoiding redundant find/union calls on irrelevant edges.
         loop {
             let mut changed = false;
-            for &(u32_u, u32_v) in &graph.fast_edges {
-                let u = u32_u as usize;
-                let v = u32_v as usize;
+            for &(idx_u, idx_v) in graph.edge_pairs() {
+                let u = idx_u.index();
+                let v = idx_v.index();
 
                 if unsafe {
                     *self.touched.get_unchecked(u) == 0 && *self.touched.get_unchecked(v) == 0
@@ -237,8 +266,14 @@ where
                 let root_v = dsu.find(v);
 
                 if root_u != root_v {
-                    let u_active = BitPack::get(dsu.parity, root_u);
-                    let v_active = BitPack::get(dsu.parity, root_v);
+                    // Roots are node indices below `num_nodes`, and the
+                    // parity vector was sized with one bit per node above.
+                    let (u_active, v_active) = unsafe {
+                        (
+                            BitPack::get_unchecked(dsu.parity, root_u),
+                            BitPack::get_unchecked(dsu.parity, root_v),
+                        )
+                    };
 
                     if (u_active || v_active) && dsu.union(u, v) {
                         out_buffer.push_correction(u, v)?;
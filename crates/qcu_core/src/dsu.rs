@@ -52,6 +52,11 @@ impl<'a> UnionFind<'a> {
     /// * `rank` - Mutable slice for rank values
     /// * `parity` - Mutable slice for parity bits (u64 words)
     pub fn new(parent: &'a mut [usize], rank: &'a mut [u8], parity: &'a mut [u64]) -> Self {
+        debug_assert!(
+            parity.len() * 64 >= parent.len(),
+            "parity vector too short for {} nodes",
+            parent.len()
+        );
         for i in 0..parent.len() {
             parent[i] = i;
             rank[i] = 0;
@@ -10,6 +10,9 @@ use alloc::alloc::Global;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::alloc::Allocator;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
 /// Graph edge representation with target node and weight.
 ///
@@ -33,6 +36,244 @@ pub struct Edge {
     pub weight: f64,
 }
 
+/// Action taken when `add_edge` is given an edge that already exists.
+///
+/// Only consulted when duplicate detection has been enabled via
+/// `DecodingGraph::with_dedup` or `DecodingGraph::enable_dedup`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Return `QecError::DuplicateEdge` and leave the graph unchanged.
+    Reject,
+
+    /// Drop the duplicate silently, keeping the existing edge and weight.
+    Skip,
+
+    /// Fold the duplicate's weight into the existing edge with `merge_weights`.
+    ///
+    /// Only mechanisms that flip the same logical observables can be folded
+    /// together. A duplicate whose observable mask differs from the stored
+    /// edge's is rejected with `QecError::DuplicateEdge`, as under `Reject`.
+    Merge,
+}
+
+/// Combines the weights of two error mechanisms that flip the same edge.
+///
+/// Weights are negative log probabilities. With the `std` feature the two
+/// mechanisms are treated as independent, giving a combined probability of
+/// p1 (1 - p2) + p2 (1 - p1). Without `std` no logarithm is available, so the
+/// lower weight (the dominant mechanism) is kept instead.
+///
+/// # Arguments
+///
+/// * `w1` - Weight of the first mechanism
+/// * `w2` - Weight of the second mechanism
+///
+/// # Returns
+///
+/// The weight of the combined mechanism.
+pub fn merge_weights(w1: f64, w2: f64) -> f64 {
+    #[cfg(feature = "std")]
+    {
+        let p1 = (-w1).exp();
+        let p2 = (-w2).exp();
+        -(p1 * (1.0 - p2) + p2 * (1.0 - p1)).ln()
+    }
+
+    #[cfg(not(feature = "std"))]
+    {
+        w1.min(w2)
+    }
+}
+
+/// Duplicate edge detection state for a decoding graph.
+///
+/// With the `std` feature, every inserted edge is recorded in a hash map
+/// keyed by its (min, max) endpoints. Without `std`, edges are kept in a
+/// vector sorted by the same key and found by binary search. Inserting into
+/// the sorted vector shifts the entries after it, so `max_probe` caps its
+/// length to bound the per-insertion cost on firmware: once it is full,
+/// further edges are not indexed and later duplicates of them go undetected.
+/// Detection is exact with `std`, or without it while the index has room.
+pub struct EdgeDedup {
+    /// Action taken on a duplicate.
+    pub policy: DuplicatePolicy,
+
+    /// Maximum number of edges indexed without `std`.
+    ///
+    /// Zero indexes every edge. Ignored with `std`.
+    pub max_probe: usize,
+
+    /// Number of duplicates rejected, skipped, or merged so far.
+    pub duplicates: usize,
+
+    /// Index of each canonical edge in `fast_edges`.
+    #[cfg(feature = "std")]
+    index: HashMap<(u32, u32), usize>,
+
+    /// Canonical edges with their index in `fast_edges`, sorted by edge.
+    #[cfg(not(feature = "std"))]
+    sorted: Vec<((u32, u32), usize)>,
+}
+
+impl EdgeDedup {
+    /// Creates detection state with the given policy and probe limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Action taken on a duplicate
+    /// * `max_probe` - Maximum number of edges indexed without `std`
+    pub fn new(policy: DuplicatePolicy, max_probe: usize) -> Self {
+        Self {
+            policy,
+            max_probe,
+            duplicates: 0,
+            #[cfg(feature = "std")]
+            index: HashMap::new(),
+            #[cfg(not(feature = "std"))]
+            sorted: Vec::new(),
+        }
+    }
+
+    /// Looks up the position of an existing canonical edge in `fast_edges`.
+    fn find(&self, key: (u32, u32)) -> Option<usize> {
+        #[cfg(feature = "std")]
+        {
+            self.index.get(&key).copied()
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            self.sorted
+                .binary_search_by_key(&key, |&(k, _)| k)
+                .ok()
+                .map(|i| self.sorted[i].1)
+        }
+    }
+
+    /// Records a canonical edge stored at position `pos`, keeping the first
+    /// position if the edge is already recorded.
+    fn record(&mut self, key: (u32, u32), pos: usize) {
+        #[cfg(feature = "std")]
+        {
+            self.index.entry(key).or_insert(pos);
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            if self.max_probe != 0 && self.sorted.len() >= self.max_probe {
+                return;
+            }
+            if let Err(i) = self.sorted.binary_search_by_key(&key, |&(k, _)| k) {
+                self.sorted.insert(i, (key, pos));
+            }
+        }
+    }
+
+    /// Rebuilds the index after the edge list has been rewritten.
+    fn reindex(&mut self, edges: &[(u32, u32)]) {
+        #[cfg(feature = "std")]
+        self.index.clear();
+
+        #[cfg(not(feature = "std"))]
+        self.sorted.clear();
+
+        for (pos, &(u, v)) in edges.iter().enumerate() {
+            self.record(canonical(u, v), pos);
+        }
+    }
+}
+
+/// Orders an edge's endpoints as (min, max).
+#[inline(always)]
+fn canonical(u: u32, v: u32) -> (u32, u32) {
+    if u <= v { (u, v) } else { (v, u) }
+}
+
+/// Differences between two decoding graphs.
+///
+/// Produced by `DecodingGraph::diff`. Edges are reported in canonical
+/// (min, max) order using original detector numbering.
+#[derive(Debug, Default)]
+pub struct GraphDiff {
+    /// Node counts of (self, other).
+    pub num_nodes: (usize, usize),
+
+    /// Edges present in the first graph but not the second.
+    pub only_in_self: Vec<(usize, usize)>,
+
+    /// Edges present in the second graph but not the first.
+    pub only_in_other: Vec<(usize, usize)>,
+
+    /// Edges present in both graphs but stored a different number of times,
+    /// as (edge, count in self, count in other).
+    pub multiplicity_mismatches: Vec<((usize, usize), usize, usize)>,
+
+    /// Edges present in both graphs whose weights differ beyond the tolerance,
+    /// as (edge, weight in self, weight in other). Duplicated edges are
+    /// compared by their lowest weight.
+    pub weight_mismatches: Vec<((usize, usize), f64, f64)>,
+}
+
+impl GraphDiff {
+    /// Returns true if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.num_nodes.0 == self.num_nodes.1
+            && self.only_in_self.is_empty()
+            && self.only_in_other.is_empty()
+            && self.multiplicity_mismatches.is_empty()
+            && self.weight_mismatches.is_empty()
+    }
+}
+
+/// Edges that connect a decoding window to nodes outside it.
+///
+/// Produced by `DecodingGraph::subgraph` alongside the window graph. Each
+/// entry pairs a node index local to the window with the index of the node
+/// it connects to outside the window (in the parent graph's numbering). A
+/// streaming decoder treats these as boundary connections, since the nodes
+/// on the far side belong to an already committed or not yet decoded region.
+///
+/// # Type Parameters
+///
+/// * `A` - Allocator type for the edge list, shared with the window graph.
+pub struct CrossingEdges<A: Allocator = Global> {
+    /// Node range of the parent graph covered by the window.
+    pub window: Range<usize>,
+
+    /// Crossing edges as (window-local node, parent node outside the window).
+    pub edges: Vec<(u32, u32), A>,
+}
+
+impl<A: Allocator> CrossingEdges<A> {
+    /// Maps a window-local node index back to the parent graph's numbering.
+    ///
+    /// # Arguments
+    ///
+    /// * `local` - Node index within the window graph
+    ///
+    /// # Returns
+    ///
+    /// The corresponding node index in the parent graph.
+    #[inline(always)]
+    pub fn parent_index(&self, local: usize) -> usize {
+        self.window.start + local
+    }
+
+    /// Maps a parent graph node index into window-local numbering.
+    ///
+    /// # Returns
+    ///
+    /// Some(local index) if the node lies inside the window, None otherwise.
+    #[inline(always)]
+    pub fn local_index(&self, parent: usize) -> Option<usize> {
+        if self.window.contains(&parent) {
+            Some(parent - self.window.start)
+        } else {
+            None
+        }
+    }
+}
+
 /// Decoding graph representing the error model topology.
 ///
 /// Stores the connectivity structure of detector nodes and error locations
@@ -53,6 +294,37 @@ pub struct DecodingGraph<A: Allocator = Global> {
     /// and as the source of truth when building the adjacency list.
     pub fast_edges: Vec<(u32, u32), A>,
 
+    /// Edge weights, parallel to `fast_edges`.
+    ///
+    /// `edge_weights[i]` is the weight passed to `add_edge` for
+    /// `fast_edges[i]`. Grown on demand rather than reserved up front, so
+    /// firmware graphs with generous edge reservations do not pay for weight
+    /// storage they never fill.
+    pub edge_weights: Vec<f64, A>,
+
+    /// Logical observables flipped by each edge, as a bitmask with bit `k`
+    /// for observable `Lk`, parallel to `fast_edges`.
+    ///
+    /// Only grown as far as the last edge with a non-zero mask, so graphs
+    /// without observables store nothing; edges past the end flip none.
+    /// Read through `edge_observables`.
+    pub edge_observables: Vec<u64, A>,
+
+    /// Number of logical observables in the error model.
+    ///
+    /// One more than the highest observable index seen on any edge,
+    /// hyperedge or observable-only error, or declared with
+    /// `declare_observables`.
+    pub num_observables: usize,
+
+    /// Error mechanisms that flip logical observables but no detectors.
+    ///
+    /// Stored as (observable mask, weight). Such an error is invisible to
+    /// the decoder, like an edge between two boundary nodes, but still
+    /// flips the logical outcome, so it counts towards the logical error
+    /// rate.
+    pub observable_errors: Vec<(u64, f64)>,
+
     /// CSR adjacency offsets into `adj_targets`.
     ///
     /// `adj_offsets[i]..adj_offsets[i+1]` is the range of `adj_targets` that
@@ -80,6 +352,51 @@ pub struct DecodingGraph<A: Allocator = Global> {
     /// the actual graph size for decoder initialization. This value determines
     /// how many nodes the decoder must allocate state for.
     pub max_node_id: usize,
+
+    /// Permutation from relabelled node index to original detector index.
+    ///
+    /// Populated by `reorder_for_locality`. Empty when the graph still uses
+    /// the original detector numbering, in which case all mappings are the
+    /// identity.
+    pub node_perm: Vec<u32>,
+
+    /// Inverse of `node_perm`, mapping original detector index to the
+    /// relabelled node index used internally by the decoder.
+    pub node_perm_inv: Vec<u32>,
+
+    /// Duplicate edge detection state, if enabled.
+    ///
+    /// When set, `add_edge` checks each new edge against those already
+    /// stored and applies the configured `DuplicatePolicy`.
+    pub dedup: Option<EdgeDedup>,
+
+    /// Node lists of all hyperedges, back to back.
+    ///
+    /// A hyperedge is an error mechanism that flips three or more detectors,
+    /// which no single matching-graph edge can represent. They are stored
+    /// here, apart from `fast_edges`, so the model is kept intact for tools
+    /// that can use it; the union-find decoder ignores them.
+    pub hyperedge_nodes: Vec<u32>,
+
+    /// End of each hyperedge's node list in `hyperedge_nodes`.
+    ///
+    /// Hyperedge `i` spans `hyperedge_ends[i - 1]..hyperedge_ends[i]`, with
+    /// the first starting at 0.
+    pub hyperedge_ends: Vec<u32>,
+
+    /// Hyperedge weights, parallel to `hyperedge_ends`.
+    pub hyperedge_weights: Vec<f64>,
+
+    /// Logical observables flipped by each hyperedge, parallel to
+    /// `hyperedge_ends`.
+    pub hyperedge_observables: Vec<u64>,
+
+    /// Coordinates of each detector, indexed by original detector number.
+    ///
+    /// Taken from the error model's detector declarations, for display and
+    /// layout. An empty entry, or a detector past the end, has no
+    /// coordinates. Unaffected by `reorder_for_locality`.
+    pub detector_coords: Vec<Vec<f64>>,
 }
 
 impl DecodingGraph<Global> {
@@ -96,6 +413,22 @@ impl DecodingGraph<Global> {
         Self::new_in(capacity, Global)
     }
 
+    /// Creates a new decoding graph that detects duplicate edges on insertion.
+    ///
+    /// Intended for hand-built graphs (tests, HIL topologies) and generator
+    /// output, where writing the same lattice edge twice would otherwise
+    /// double its effective weight during decoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Estimated number of nodes (used for pre-allocation)
+    /// * `policy` - Action taken when a duplicate edge is added
+    pub fn with_dedup(capacity: usize, policy: DuplicatePolicy) -> Self {
+        let mut graph = Self::new(capacity);
+        graph.enable_dedup(policy, 0);
+        graph
+    }
+
     /// Builds the CSR adjacency list from the current edge set.
     ///
     /// Must be called once after all edges have been added via `add_edge`.
@@ -131,6 +464,116 @@ impl DecodingGraph<Global> {
         self.adj_targets = targets;
     }
 
+    /// Relabels nodes in reverse Cuthill-McKee order.
+    ///
+    /// Nodes are renumbered so that graph neighbours receive nearby indices,
+    /// meant to keep the decoder's parent and parity accesses within a small
+    /// window of memory while it scans the edge list. The edge list is then
+    /// sorted by its smaller endpoint so the scan walks the parent array
+    /// roughly sequentially. The `reorder` bench in qcu_host has not measured
+    /// a speedup from this on the d=21 graph. The decoder's output depends on
+    /// the order it scans edges in, so corrections can differ from those for
+    /// the graph as built. The permutation is stored in `node_perm` and
+    /// `node_perm_inv`; callers that work in original detector numbering must
+    /// translate through `map_syndrome_in` and `map_correction_out`.
+    ///
+    /// Calling this on an already reordered graph composes the permutations,
+    /// so the mappings always refer back to the original detector numbering.
+    pub fn reorder_for_locality(&mut self) {
+        let n = self.max_node_id;
+        if n == 0 {
+            return;
+        }
+        if self.adj_offsets.len() != n + 1 {
+            self.build_adjacency();
+        }
+
+        let degree = |i: usize| self.adj_offsets[i + 1] - self.adj_offsets[i];
+
+        let mut by_degree: Vec<u32> = (0..n as u32).collect();
+        by_degree.sort_unstable_by_key(|&i| degree(i as usize));
+
+        let mut visited = vec![false; n];
+        let mut order: Vec<u32> = Vec::with_capacity(n);
+        let mut frontier: Vec<u32> = Vec::new();
+
+        for &seed in &by_degree {
+            if visited[seed as usize] {
+                continue;
+            }
+            visited[seed as usize] = true;
+            let mut head = order.len();
+            order.push(seed);
+
+            while head < order.len() {
+                let node = order[head] as usize;
+                head += 1;
+
+                frontier.clear();
+                for &nb in self.neighbors(node) {
+                    if !visited[nb as usize] {
+                        visited[nb as usize] = true;
+                        frontier.push(nb);
+                    }
+                }
+                frontier.sort_unstable_by_key(|&i| degree(i as usize));
+                order.extend_from_slice(&frontier);
+            }
+        }
+        order.reverse();
+
+        let mut rank = vec![0u32; n];
+        for (new_id, &old_id) in order.iter().enumerate() {
+            rank[old_id as usize] = new_id as u32;
+        }
+
+        let mut relabelled: Vec<((u32, u32), f64, u64)> = self
+            .fast_edges
+            .iter()
+            .enumerate()
+            .map(|(i, &(u, v))| {
+                let edge = canonical(rank[u as usize], rank[v as usize]);
+                (edge, self.edge_weight(i), self.edge_observables(i))
+            })
+            .collect();
+        relabelled.sort_unstable_by_key(|&(edge, _, _)| edge);
+
+        for node in self.hyperedge_nodes.iter_mut() {
+            *node = rank[*node as usize];
+        }
+
+        let has_observables = !self.edge_observables.is_empty();
+        self.fast_edges.clear();
+        self.edge_weights.clear();
+        self.edge_observables.clear();
+        for (edge, weight, observables) in relabelled {
+            self.fast_edges.push(edge);
+            self.edge_weights.push(weight);
+            if has_observables {
+                self.edge_observables.push(observables);
+            }
+        }
+
+        if self.node_perm.is_empty() {
+            self.node_perm = order;
+        } else {
+            self.node_perm = order
+                .iter()
+                .map(|&prev| self.node_perm[prev as usize])
+                .collect();
+        }
+        let mut inv = vec![0u32; n];
+        for (new_id, &orig) in self.node_perm.iter().enumerate() {
+            inv[orig as usize] = new_id as u32;
+        }
+        self.node_perm_inv = inv;
+
+        if let Some(dedup) = self.dedup.as_mut() {
+            dedup.reindex(&self.fast_edges);
+        }
+
+        self.build_adjacency();
+    }
 }
 
 impl<A: Allocator> DecodingGraph<A> {
@@ -144,16 +587,55 @@ impl<A: Allocator> DecodingGraph<A> {
     ///
     /// * `capacity` - Estimated number of nodes
     /// * `alloc` - Allocator instance for edge storage
-    pub fn new_in(capacity: usize, alloc: A) -> Self {
+    pub fn new_in(capacity: usize, alloc: A) -> Self
+    where
+        A: Clone,
+    {
         Self {
-            fast_edges: Vec::with_capacity_in(capacity * 4, alloc),
+            fast_edges: Vec::with_capacity_in(capacity * 4, alloc.clone()),
+            edge_weights: Vec::new_in(alloc.clone()),
+            edge_observables: Vec::new_in(alloc),
+            num_observables: 0,
+            observable_errors: Vec::new(),
             adj_offsets: Vec::new(),
             adj_targets: Vec::new(),
             num_nodes_capacity: capacity,
             max_node_id: 0,
+            node_perm: Vec::new(),
+            node_perm_inv: Vec::new(),
+            dedup: None,
+            hyperedge_nodes: Vec::new(),
+            hyperedge_ends: Vec::new(),
+            hyperedge_weights: Vec::new(),
+            hyperedge_observables: Vec::new(),
+            detector_coords: Vec::new(),
         }
     }
 
+    /// Enables duplicate edge detection for subsequent `add_edge` calls.
+    ///
+    /// Edges already in the graph are indexed so later duplicates of them are
+    /// also caught. See `EdgeDedup` for how detection differs with and without
+    /// the `std` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Action taken when a duplicate edge is added
+    /// * `max_probe` - Maximum number of edges indexed without `std`
+    ///   (zero indexes every edge)
+    pub fn enable_dedup(&mut self, policy: DuplicatePolicy, max_probe: usize) {
+        let mut dedup = EdgeDedup::new(policy, max_probe);
+        dedup.reindex(&self.fast_edges);
+        self.dedup = Some(dedup);
+    }
+
+    /// Returns the number of duplicate edges rejected, skipped, or merged.
+    ///
+    /// Always zero when duplicate detection is disabled.
+    pub fn duplicate_count(&self) -> usize {
+        self.dedup.as_ref().map_or(0, |d| d.duplicates)
+    }
+
     /// Ensures the graph can accommodate nodes up to index n.
     ///
     /// Updates the capacity estimate if n exceeds the current value. This
@@ -171,21 +653,72 @@ impl<A: Allocator> DecodingGraph<A> {
 
     /// Adds an edge between nodes u and v to the graph.
     ///
-    /// Records a connection in the error model topology. The weight parameter
-    /// is currently ignored but stored for future use. Updates the maximum
-    /// node ID to track the graph's actual size. Edges are stored as undirected,
-    /// so (u, v) and (v, u) are equivalent.
+    /// Records a connection in the error model topology. The weight is stored
+    /// alongside the edge but is not yet used by the union-find decoder.
+    /// Updates the maximum node ID to track the graph's actual size. Edges are
+    /// stored as undirected, so (u, v) and (v, u) are equivalent. If duplicate
+    /// detection is enabled, an edge that already exists is handled according
+    /// to the configured `DuplicatePolicy` instead of being stored again.
+    ///
+    /// # Arguments
+    ///
+    /// * `u` - First node index
+    /// * `v` - Second node index
+    /// * `weight` - Edge weight (negative log error probability)
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, `DuplicateEdge` if the edge exists and the policy is
+    /// `Reject`, or an error if memory allocation fails.
+    pub fn add_edge(&mut self, u: usize, v: usize, weight: f64) -> Result<(), QecError> {
+        self.add_edge_with_observables(u, v, weight, 0)
+    }
+
+    /// Adds an edge that also flips the logical observables in `observables`.
+    ///
+    /// Behaves like `add_edge` and records the mask for `edge_observables`.
+    /// If duplicate detection catches the edge, the mask of the edge already
+    /// stored is kept; under `Merge` a duplicate with a different mask is
+    /// rejected instead.
     ///
     /// # Arguments
     ///
     /// * `u` - First node index
     /// * `v` - Second node index
-    /// * `_weight` - Edge weight (currently unused)
+    /// * `weight` - Edge weight (negative log error probability)
+    /// * `observables` - Bitmask of flipped observables, bit `k` for `Lk`
     ///
     /// # Returns
     ///
-    /// Ok(()) on success, or an error if memory allocation fails.
-    pub fn add_edge(&mut self, u: usize, v: usize, _weight: f64) -> Result<(), QecError> {
+    /// As for `add_edge`.
+    pub fn add_edge_with_observables(
+        &mut self,
+        u: usize,
+        v: usize,
+        weight: f64,
+        observables: u64,
+    ) -> Result<(), QecError> {
+        let key = canonical(u as u32, v as u32);
+        if let Some(dedup) = self.dedup.as_mut()
+            && let Some(pos) = dedup.find(key)
+        {
+            dedup.duplicates += 1;
+            let stored = self.edge_observables.get(pos).copied().unwrap_or(0);
+            match dedup.policy {
+                DuplicatePolicy::Reject => return Err(QecError::DuplicateEdge),
+                DuplicatePolicy::Skip => return Ok(()),
+                DuplicatePolicy::Merge if stored != observables => {
+                    return Err(QecError::DuplicateEdge);
+                }
+                DuplicatePolicy::Merge => {
+                    if let Some(w) = self.edge_weights.get_mut(pos) {
+                        *w = merge_weights(*w, weight);
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
         let max_idx = if u > v { u } else { v };
         self.ensure_size(max_idx + 1);
 
@@ -193,11 +726,148 @@ impl<A: Allocator> DecodingGraph<A> {
             self.max_node_id = max_idx + 1;
         }
 
+        if let Some(dedup) = self.dedup.as_mut() {
+            dedup.record(key, self.fast_edges.len());
+        }
+        if observables != 0 {
+            self.edge_observables.resize(self.fast_edges.len(), 0);
+            self.edge_observables.push(observables);
+            self.note_observables(observables);
+        }
         self.fast_edges.push((u as u32, v as u32));
+        self.edge_weights.push(weight);
 
         Ok(())
     }
 
+    /// Returns the observable mask of the edge at position `i` in
+    /// `fast_edges`, zero if it flips none.
+    #[inline(always)]
+    pub fn edge_observables(&self, i: usize) -> u64 {
+        self.edge_observables.get(i).copied().unwrap_or(0)
+    }
+
+    /// Records an error mechanism that flips observables but no detectors.
+    ///
+    /// # Arguments
+    ///
+    /// * `observables` - Bitmask of flipped observables, bit `k` for `Lk`
+    /// * `weight` - Error weight (negative log error probability)
+    pub fn add_observable_error(&mut self, observables: u64, weight: f64) {
+        self.note_observables(observables);
+        self.observable_errors.push((observables, weight));
+    }
+
+    /// Raises the observable count to at least `n`, for observables that
+    /// are declared but flipped by no error.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of observables
+    pub fn declare_observables(&mut self, n: usize) {
+        self.num_observables = self.num_observables.max(n);
+    }
+
+    /// Returns the number of logical observables in the error model.
+    pub fn num_observables(&self) -> usize {
+        self.num_observables
+    }
+
+    /// Raises the observable count to cover every bit set in `observables`.
+    fn note_observables(&mut self, observables: u64) {
+        self.declare_observables((u64::BITS - observables.leading_zeros()) as usize);
+    }
+
+    /// Adds a hyperedge flipping every node in `nodes`.
+    ///
+    /// Stored apart from the ordinary edges and ignored by the union-find
+    /// decoder; see `hyperedge_nodes`. Updates the maximum node ID like
+    /// `add_edge`, so nodes touched only by hyperedges still count towards
+    /// the graph size. Duplicate detection does not apply.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - Node indices flipped by the error mechanism
+    /// * `weight` - Hyperedge weight (negative log error probability)
+    /// * `observables` - Bitmask of flipped observables, bit `k` for `Lk`
+    pub fn add_hyperedge(&mut self, nodes: &[usize], weight: f64, observables: u64) {
+        if let Some(&max_idx) = nodes.iter().max() {
+            self.ensure_size(max_idx + 1);
+            if max_idx >= self.max_node_id {
+                self.max_node_id = max_idx + 1;
+            }
+        }
+        self.hyperedge_nodes
+            .extend(nodes.iter().map(|&node| node as u32));
+        self.hyperedge_ends.push(self.hyperedge_nodes.len() as u32);
+        self.hyperedge_weights.push(weight);
+        self.hyperedge_observables.push(observables);
+        self.note_observables(observables);
+    }
+
+    /// Counts a detector as a node of the graph even if no edge touches it.
+    ///
+    /// Raises the maximum node ID like `add_edge`, so a detector that can
+    /// fire but has no incident edges still gets decoder state and is not
+    /// lost off the end of the graph. Call before `reorder_for_locality`.
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - Detector index
+    pub fn declare_detector(&mut self, detector: usize) {
+        self.ensure_size(detector + 1);
+        if detector >= self.max_node_id {
+            self.max_node_id = detector + 1;
+        }
+    }
+
+    /// Declares a detector and records its coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - Detector index, in original numbering
+    /// * `coords` - Coordinates, typically (x, y, t)
+    pub fn set_detector_coords(&mut self, detector: usize, coords: &[f64]) {
+        self.declare_detector(detector);
+        if self.detector_coords.len() <= detector {
+            self.detector_coords.resize(detector + 1, Vec::new());
+        }
+        self.detector_coords[detector] = coords.to_vec();
+    }
+
+    /// Returns the coordinates of a detector, or None if it has none.
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - Detector index, in original numbering
+    pub fn detector_coords(&self, detector: usize) -> Option<&[f64]> {
+        self.detector_coords
+            .get(detector)
+            .filter(|coords| !coords.is_empty())
+            .map(Vec::as_slice)
+    }
+
+    /// Returns the number of stored hyperedges.
+    pub fn num_hyperedges(&self) -> usize {
+        self.hyperedge_ends.len()
+    }
+
+    /// Iterates over the stored hyperedges in the graph's node numbering.
+    ///
+    /// Yields the node list, weight and observable mask of each hyperedge in
+    /// insertion order.
+    pub fn hyperedges(&self) -> impl Iterator<Item = (&[u32], f64, u64)> + '_ {
+        let starts = core::iter::once(0).chain(self.hyperedge_ends.iter().copied());
+        starts
+            .zip(&self.hyperedge_ends)
+            .zip(&self.hyperedge_weights)
+            .zip(&self.hyperedge_observables)
+            .map(|(((start, &end), &weight), &observables)| {
+                let nodes = &self.hyperedge_nodes[start as usize..end as usize];
+                (nodes, weight, observables)
+            })
+    }
+
     /// Returns the number of nodes in the graph.
     ///
     /// Computed as the maximum node ID plus one, since node indices are
@@ -224,4 +894,726 @@ impl<A: Allocator> DecodingGraph<A> {
         let end = self.adj_offsets[i + 1] as usize;
         &self.adj_targets[start..end]
     }
+
+    /// Extracts the subgraph induced by a contiguous node range.
+    ///
+    /// Builds a new graph containing every edge with both endpoints inside
+    /// `range`, with node indices shifted so that `range.start` becomes node 0.
+    /// Edges with exactly one endpoint inside the window are returned separately
+    /// as `CrossingEdges` so a streaming decoder can treat them as boundary
+    /// connections. The window graph always spans `range.len()` nodes, even if
+    /// trailing nodes have no incident edges, so syndrome indices can be shifted
+    /// uniformly. Ranges are interpreted in this graph's node numbering, which
+    /// differs from detector numbering if the graph has been reordered.
+    ///
+    /// The adjacency list of the window graph is not built; the decoder only
+    /// requires the edge list. Edge observable masks are kept; hyperedges,
+    /// observable-only errors and detector coordinates are not carried over.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Node range to extract (clamped to the graph size)
+    /// * `alloc` - Allocator for the window graph and crossing edge list
+    ///
+    /// # Returns
+    ///
+    /// The window graph and the edges crossing its boundary.
+    pub fn subgraph<B: Allocator + Clone>(
+        &self,
+        range: Range<usize>,
+        alloc: B,
+    ) -> (DecodingGraph<B>, CrossingEdges<B>) {
+        let end = range.end.min(self.max_node_id);
+        let start = range.start.min(end);
+        let window = start..end;
+
+        let mut inner = 0;
+        let mut crossing = 0;
+        for &(u, v) in &self.fast_edges {
+            match (
+                window.contains(&(u as usize)),
+                window.contains(&(v as usize)),
+            ) {
+                (true, true) => inner += 1,
+                (true, false) | (false, true) => crossing += 1,
+                (false, false) => {}
+            }
+        }
+
+        let mut sub = DecodingGraph {
+            fast_edges: Vec::with_capacity_in(inner, alloc.clone()),
+            edge_weights: Vec::with_capacity_in(inner, alloc.clone()),
+            edge_observables: Vec::new_in(alloc.clone()),
+            num_observables: self.num_observables,
+            observable_errors: Vec::new(),
+            adj_offsets: Vec::new(),
+            adj_targets: Vec::new(),
+            num_nodes_capacity: window.len(),
+            max_node_id: window.len(),
+            node_perm: Vec::new(),
+            node_perm_inv: Vec::new(),
+            dedup: None,
+            hyperedge_nodes: Vec::new(),
+            hyperedge_ends: Vec::new(),
+            hyperedge_weights: Vec::new(),
+            hyperedge_observables: Vec::new(),
+            detector_coords: Vec::new(),
+        };
+        let mut edges = Vec::with_capacity_in(crossing, alloc);
+
+        let base = start as u32;
+        for (i, &(u, v)) in self.fast_edges.iter().enumerate() {
+            match (
+                window.contains(&(u as usize)),
+                window.contains(&(v as usize)),
+            ) {
+                (true, true) => {
+                    let observables = self.edge_observables(i);
+                    if observables != 0 {
+                        sub.edge_observables.resize(sub.fast_edges.len(), 0);
+                        sub.edge_observables.push(observables);
+                    }
+                    sub.fast_edges.push((u - base, v - base));
+                    sub.edge_weights.push(self.edge_weight(i));
+                }
+                (true, false) => edges.push((u - base, v)),
+                (false, true) => edges.push((v - base, u)),
+                (false, false) => {}
+            }
+        }
+
+        (sub, CrossingEdges { window, edges })
+    }
+
+    /// Returns the weight of the edge at position `i` in `fast_edges`.
+    ///
+    /// Falls back to 1.0 for edges pushed directly onto `fast_edges` without
+    /// a matching weight entry.
+    #[inline(always)]
+    pub fn edge_weight(&self, i: usize) -> f64 {
+        self.edge_weights.get(i).copied().unwrap_or(1.0)
+    }
+
+    /// Iterates over all edges in original detector numbering.
+    ///
+    /// Yields (u, v, weight) for every stored edge, translating through the
+    /// node permutation if the graph has been reordered. Duplicate edges are
+    /// yielded as stored. This is the allocator-independent view of the graph
+    /// used for comparisons between graphs built in different ways.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.fast_edges.iter().enumerate().map(move |(i, &(u, v))| {
+            let (u, v) = if self.node_perm.is_empty() {
+                (u as usize, v as usize)
+            } else {
+                (
+                    self.node_perm[u as usize] as usize,
+                    self.node_perm[v as usize] as usize,
+                )
+            };
+            (u, v, self.edge_weight(i))
+        })
+    }
+
+    /// Collects the canonical edge set of the graph.
+    ///
+    /// Edges are normalised to (min, max) order in original detector
+    /// numbering and sorted. Duplicates are merged into a single entry that
+    /// records how many times the edge was stored and its lowest weight.
+    fn canonical_edges(&self) -> Vec<((usize, usize), f64, usize)> {
+        let mut edges: Vec<((usize, usize), f64)> = self
+            .edges()
+            .map(|(u, v, w)| (if u <= v { (u, v) } else { (v, u) }, w))
+            .collect();
+        edges.sort_unstable_by_key(|&(edge, _)| edge);
+
+        let mut merged: Vec<((usize, usize), f64, usize)> = Vec::with_capacity(edges.len());
+        for (edge, weight) in edges {
+            match merged.last_mut() {
+                Some((last, w, count)) if *last == edge => {
+                    *w = w.min(weight);
+                    *count += 1;
+                }
+                _ => merged.push((edge, weight, 1)),
+            }
+        }
+        merged
+    }
+
+    /// Returns true if both graphs have the same node count and edge set.
+    ///
+    /// Edge orientation, storage order, duplicate multiplicity, node
+    /// reordering, and weights are ignored. Use `diff` to also compare
+    /// multiplicities and weights.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Graph to compare against, possibly using another allocator
+    pub fn structurally_equal<B: Allocator>(&self, other: &DecodingGraph<B>) -> bool {
+        if self.num_nodes() != other.num_nodes() {
+            return false;
+        }
+        let a = self.canonical_edges();
+        let b = other.canonical_edges();
+        a.len() == b.len() && a.iter().zip(&b).all(|(x, y)| x.0 == y.0)
+    }
+
+    /// Computes the differences between two graphs.
+    ///
+    /// Both graphs are reduced to their canonical edge sets in original
+    /// detector numbering and compared edge by edge. Edges present in only one
+    /// graph are listed separately; edges present in both are reported if they
+    /// were stored a different number of times or if their weights differ by
+    /// more than `tolerance`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Graph to compare against, possibly using another allocator
+    /// * `tolerance` - Maximum absolute weight difference treated as equal
+    ///
+    /// # Returns
+    ///
+    /// A `GraphDiff` describing every difference found.
+    pub fn diff<B: Allocator>(&self, other: &DecodingGraph<B>, tolerance: f64) -> GraphDiff {
+        let a = self.canonical_edges();
+        let b = other.canonical_edges();
+
+        let mut diff = GraphDiff {
+            num_nodes: (self.num_nodes(), other.num_nodes()),
+            only_in_self: Vec::new(),
+            only_in_other: Vec::new(),
+            multiplicity_mismatches: Vec::new(),
+            weight_mismatches: Vec::new(),
+        };
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            match (a.get(i), b.get(j)) {
+                (Some(&(ea, wa, ca)), Some(&(eb, wb, cb))) if ea == eb => {
+                    if ca != cb {
+                        diff.multiplicity_mismatches.push((ea, ca, cb));
+                    }
+                    if (wa - wb).abs() > tolerance {
+                        diff.weight_mismatches.push((ea, wa, wb));
+                    }
+                    i += 1;
+                    j += 1;
+                }
+                (Some(&(ea, _, _)), Some(&(eb, _, _))) if ea < eb => {
+                    diff.only_in_self.push(ea);
+                    i += 1;
+                }
+                (Some(&(ea, _, _)), None) => {
+                    diff.only_in_self.push(ea);
+                    i += 1;
+                }
+                (_, Some(&(eb, _, _))) => {
+                    diff.only_in_other.push(eb);
+                    j += 1;
+                }
+                (None, None) => break,
+            }
+        }
+
+        diff
+    }
+
+    /// Returns true if the graph has been relabelled by `reorder_for_locality`.
+    #[inline(always)]
+    pub fn is_reordered(&self) -> bool {
+        !self.node_perm.is_empty()
+    }
+
+    /// Translates syndrome indices from original detector numbering into the
+    /// graph's internal node numbering.
+    ///
+    /// Writes one translated index into `out` per entry of `syndrome`. When the
+    /// graph has not been reordered this is a plain copy. Every index must be
+    /// a node of the graph, as for `map_correction_out`.
+    ///
+    /// # Arguments
+    ///
+    /// * `syndrome` - Detector indices in original numbering
+    /// * `out` - Destination buffer, at least as long as `syndrome`
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, `BufferOverflow` if `out` is too short, or
+    /// `NodeOutOfBounds` if an index is not below `num_nodes`.
+    pub fn map_syndrome_in(&self, syndrome: &[usize], out: &mut [usize]) -> Result<(), QecError> {
+        if out.len() < syndrome.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        for (dst, &idx) in out.iter_mut().zip(syndrome) {
+            if idx >= self.max_node_id {
+                return Err(QecError::NodeOutOfBounds);
+            }
+            *dst = match self.node_perm_inv.get(idx) {
+                Some(&mapped) => mapped as usize,
+                None => idx,
+            };
+        }
+        Ok(())
+    }
+
+    /// Translates correction edges from internal node numbering back into
+    /// original detector numbering, in place.
+    ///
+    /// Intended to be applied to the buffer filled by the decoder when it ran
+    /// against a reordered graph. Leaves the edges as they are when the graph
+    /// has not been reordered.
+    ///
+    /// # Arguments
+    ///
+    /// * `corrections` - Correction edge pairs produced by the decoder
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if an endpoint is not below
+    /// `num_nodes`, in which case `corrections` is left unchanged.
+    pub fn map_correction_out(&self, corrections: &mut [(usize, usize)]) -> Result<(), QecError> {
+        if corrections
+            .iter()
+            .any(|&(u, v)| u >= self.max_node_id || v >= self.max_node_id)
+        {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        if self.node_perm.is_empty() {
+            return Ok(());
+        }
+        for (u, v) in corrections.iter_mut() {
+            *u = self.node_perm[*u] as usize;
+            *v = self.node_perm[*v] as usize;
+        }
+        Ok(())
+    }
+}
+
+/// Integer type used to store node indices in a decoding graph.
+///
+/// Lets the decoder iterate edge lists of different index widths without
+/// caring whether the graph stores `u32` or compact `u16` node IDs.
+pub trait NodeIndex: Copy {
+    /// Widens the stored index to a `usize` node ID.
+    fn index(self) -> usize;
+}
+
+impl NodeIndex for u16 {
+    #[inline(always)]
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl NodeIndex for u32 {
+    #[inline(always)]
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Read-only view of a graph's flat edge list, generic over index width.
+///
+/// Implemented by both `DecodingGraph` and `CompactDecodingGraph` so the
+/// union-find decoder can accept either representation.
+pub trait EdgeList {
+    /// Integer type of the stored node indices.
+    type Index: NodeIndex;
+
+    /// Returns the number of nodes the decoder must allocate state for.
+    fn num_nodes(&self) -> usize;
+
+    /// Returns the flat list of (u, v) edge endpoints.
+    fn edge_pairs(&self) -> &[(Self::Index, Self::Index)];
+}
+
+impl<A: Allocator> EdgeList for DecodingGraph<A> {
+    type Index = u32;
+
+    #[inline(always)]
+    fn num_nodes(&self) -> usize {
+        self.max_node_id
+    }
+
+    #[inline(always)]
+    fn edge_pairs(&self) -> &[(u32, u32)] {
+        &self.fast_edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::UnionFindDecoder;
+
+    /// Builds a 6x6 grid whose detector labels are scattered, so that the
+    /// reordering actually moves nodes.
+    fn scrambled_grid() -> DecodingGraph {
+        const SIDE: usize = 6;
+        let label = |r: usize, c: usize| (r * SIDE + c) * 7 % (SIDE * SIDE);
+        let mut graph = DecodingGraph::new(2 * SIDE * SIDE);
+        for r in 0..SIDE {
+            for c in 0..SIDE {
+                if c + 1 < SIDE {
+                    graph.add_edge(label(r, c), label(r, c + 1), 0.01).unwrap();
+                }
+                if r + 1 < SIDE {
+                    graph.add_edge(label(r, c), label(r + 1, c), 0.01).unwrap();
+                }
+            }
+        }
+        graph
+    }
+
+    /// Returns the edges with each pair in ascending order.
+    fn canonical_pairs(corrections: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        corrections.iter().map(|&(u, v)| (u.min(v), u.max(v))).collect()
+    }
+
+    #[test]
+    fn reordered_decode_maps_back_to_the_original_correction() {
+        let mut reordered = scrambled_grid();
+        reordered.reorder_for_locality();
+        assert!(reordered.is_reordered());
+
+        // The decoder's output depends on the order it scans edges in, so
+        // compare against the reordered edge list written in original
+        // numbering rather than against the graph as first built.
+        let mut original = DecodingGraph::new(reordered.fast_edges.len());
+        for (u, v, weight) in reordered.edges() {
+            original.add_edge(u, v, weight).unwrap();
+        }
+        assert_ne!(original.edge_pairs(), reordered.edge_pairs());
+
+        let mut decoder = UnionFindDecoder::<64>::new();
+        for syndrome in [vec![0, 7], vec![3, 10, 21, 35], vec![14]] {
+            let mut expected = Vec::new();
+            decoder
+                .solve_into(&original, &syndrome, &mut expected)
+                .unwrap();
+
+            let mut mapped = vec![0; syndrome.len()];
+            reordered.map_syndrome_in(&syndrome, &mut mapped).unwrap();
+            let mut corrections = Vec::new();
+            decoder
+                .solve_into(&reordered, &mapped, &mut corrections)
+                .unwrap();
+            reordered.map_correction_out(&mut corrections).unwrap();
+
+            assert!(!corrections.is_empty());
+            assert_eq!(canonical_pairs(&corrections), canonical_pairs(&expected));
+        }
+    }
+
+    #[test]
+    fn mappings_reject_indices_outside_the_graph() {
+        let mut graph = scrambled_grid();
+        let n = graph.num_nodes();
+        for reorder in [false, true] {
+            if reorder {
+                graph.reorder_for_locality();
+            }
+            let mut out = [0; 2];
+            assert!(matches!(
+                graph.map_syndrome_in(&[1, n], &mut out),
+                Err(QecError::NodeOutOfBounds)
+            ));
+            let mut corrections = [(0, 1), (2, n)];
+            assert!(matches!(
+                graph.map_correction_out(&mut corrections),
+                Err(QecError::NodeOutOfBounds)
+            ));
+            assert_eq!(corrections, [(0, 1), (2, n)]);
+        }
+    }
+
+    #[test]
+    fn subgraph_edges_map_back_to_the_parent_window() {
+        let graph = scrambled_grid();
+        for window in [0..36, 5..17, 20..21, 30..50, 12..12] {
+            let (sub, crossing) = graph.subgraph(window.clone(), Global);
+            let clamped = window.start.min(36)..window.end.min(36);
+            assert_eq!(crossing.window, clamped);
+            assert_eq!(sub.num_nodes(), clamped.len());
+
+            // Every window edge maps back to a parent edge inside the window,
+            // with its weight, and every parent edge inside it is there.
+            let mut inner: Vec<_> = sub
+                .fast_edges
+                .iter()
+                .enumerate()
+                .map(|(i, &(u, v))| {
+                    let (u, v) = (
+                        crossing.parent_index(u as usize),
+                        crossing.parent_index(v as usize),
+                    );
+                    assert_eq!(crossing.local_index(u), Some(u - clamped.start));
+                    (canonical(u as u32, v as u32), sub.edge_weight(i))
+                })
+                .collect();
+            let mut expected: Vec<_> = graph
+                .fast_edges
+                .iter()
+                .enumerate()
+                .filter(|(_, (u, v))| {
+                    clamped.contains(&(*u as usize)) && clamped.contains(&(*v as usize))
+                })
+                .map(|(i, &(u, v))| (canonical(u, v), graph.edge_weight(i)))
+                .collect();
+            inner.sort_by_key(|&(edge, _)| edge);
+            expected.sort_by_key(|&(edge, _)| edge);
+            assert_eq!(inner, expected, "{window:?}");
+        }
+    }
+
+    #[test]
+    fn crossing_edges_land_on_the_window_boundary() {
+        let graph = scrambled_grid();
+        let (_, crossing) = graph.subgraph(10..25, Global);
+
+        let mut found: Vec<(u32, u32)> = crossing
+            .edges
+            .iter()
+            .map(|&(local, outside)| {
+                assert!(crossing.local_index(outside as usize).is_none());
+                let inside = crossing.parent_index(local as usize);
+                assert!(crossing.window.contains(&inside));
+                canonical(inside as u32, outside)
+            })
+            .collect();
+        let mut expected: Vec<(u32, u32)> = graph
+            .fast_edges
+            .iter()
+            .filter(|(u, v)| {
+                crossing.window.contains(&(*u as usize)) != crossing.window.contains(&(*v as usize))
+            })
+            .map(|&(u, v)| canonical(u, v))
+            .collect();
+        found.sort_unstable();
+        expected.sort_unstable();
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+        assert_eq!(crossing.local_index(9), None);
+        assert_eq!(crossing.local_index(25), None);
+        assert_eq!(crossing.local_index(10), Some(0));
+        assert_eq!(crossing.parent_index(14), 24);
+    }
+
+    #[test]
+    fn reject_leaves_the_graph_unchanged() {
+        let mut graph = DecodingGraph::with_dedup(8, DuplicatePolicy::Reject);
+        graph.add_edge(0, 1, 1.0).unwrap();
+        graph.add_edge(1, 2, 2.0).unwrap();
+        assert!(matches!(graph.add_edge(1, 0, 3.0), Err(QecError::DuplicateEdge)));
+        assert!(matches!(graph.add_edge(2, 1, 3.0), Err(QecError::DuplicateEdge)));
+        assert_eq!(graph.fast_edges, [(0, 1), (1, 2)]);
+        assert_eq!(graph.edge_weights, [1.0, 2.0]);
+        assert_eq!(graph.duplicate_count(), 2);
+    }
+
+    #[test]
+    fn skip_keeps_the_first_edge() {
+        let mut graph = DecodingGraph::with_dedup(8, DuplicatePolicy::Skip);
+        graph.add_edge_with_observables(3, 4, 1.0, 0b01).unwrap();
+        graph.add_edge_with_observables(4, 3, 5.0, 0b10).unwrap();
+        graph.add_edge(3, 4, 6.0).unwrap();
+        assert_eq!(graph.fast_edges, [(3, 4)]);
+        assert_eq!(graph.edge_weights, [1.0]);
+        assert_eq!(graph.edge_observables(0), 0b01);
+        assert_eq!(graph.duplicate_count(), 2);
+    }
+
+    #[test]
+    fn merge_folds_weights_of_matching_observables() {
+        let mut graph = DecodingGraph::with_dedup(8, DuplicatePolicy::Merge);
+        graph.add_edge_with_observables(0, 1, 2.0, 0b1).unwrap();
+        graph.add_edge_with_observables(1, 0, 3.0, 0b1).unwrap();
+        graph.add_edge(1, 2, 4.0).unwrap();
+        graph.add_edge(2, 1, 4.0).unwrap();
+        assert_eq!(graph.fast_edges, [(0, 1), (1, 2)]);
+        assert_eq!(graph.edge_weights, [merge_weights(2.0, 3.0), merge_weights(4.0, 4.0)]);
+        assert_eq!(graph.edge_observables(0), 0b1);
+        assert_eq!(graph.duplicate_count(), 2);
+    }
+
+    #[test]
+    fn merge_rejects_a_duplicate_with_other_observables() {
+        let mut graph = DecodingGraph::with_dedup(8, DuplicatePolicy::Merge);
+        graph.add_edge_with_observables(0, 1, 2.0, 0b01).unwrap();
+        assert!(matches!(
+            graph.add_edge_with_observables(0, 1, 3.0, 0b10),
+            Err(QecError::DuplicateEdge)
+        ));
+        assert!(matches!(graph.add_edge(1, 0, 3.0), Err(QecError::DuplicateEdge)));
+        assert_eq!(graph.edge_weights, [2.0]);
+        assert_eq!(graph.edge_observables(0), 0b01);
+        assert_eq!(graph.duplicate_count(), 2);
+    }
+
+    #[test]
+    fn enable_dedup_indexes_existing_edges() {
+        let mut graph = DecodingGraph::new(8);
+        graph.add_edge(0, 1, 1.0).unwrap();
+        graph.add_edge(0, 1, 2.0).unwrap();
+        graph.add_edge(5, 2, 1.0).unwrap();
+        assert_eq!(graph.duplicate_count(), 0);
+
+        graph.enable_dedup(DuplicatePolicy::Merge, 0);
+        graph.add_edge(1, 0, 3.0).unwrap();
+        graph.add_edge(2, 5, 3.0).unwrap();
+        graph.add_edge(2, 3, 3.0).unwrap();
+        // The first copy of an edge stored twice beforehand takes the merge.
+        assert_eq!(
+            graph.edge_weights,
+            [merge_weights(1.0, 3.0), 2.0, merge_weights(1.0, 3.0), 3.0]
+        );
+        assert_eq!(graph.duplicate_count(), 2);
+    }
+
+    #[test]
+    fn dedup_survives_reordering() {
+        let mut graph = scrambled_grid();
+        graph.enable_dedup(DuplicatePolicy::Reject, 0);
+        graph.reorder_for_locality();
+        let (u, v) = graph.fast_edges[7];
+        assert!(matches!(
+            graph.add_edge(v as usize, u as usize, 1.0),
+            Err(QecError::DuplicateEdge)
+        ));
+        assert_eq!(graph.duplicate_count(), 1);
+    }
+
+    /// Adds 40 distinct edges, then a duplicate of each.
+    fn duplicate_every_edge(max_probe: usize) -> DecodingGraph {
+        let mut graph = DecodingGraph::new(64);
+        graph.enable_dedup(DuplicatePolicy::Skip, max_probe);
+        for pass in 0..2 {
+            for i in 0..40 {
+                // Scatter the keys so the sorted index inserts mid-vector.
+                let u = i * 17 % 40;
+                if pass == 0 {
+                    graph.add_edge(u, u + 1, 1.0).unwrap();
+                } else {
+                    graph.add_edge(u + 1, u, 1.0).unwrap();
+                }
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn unbounded_dedup_catches_every_duplicate() {
+        let graph = duplicate_every_edge(0);
+        assert_eq!(graph.fast_edges.len(), 40);
+        assert_eq!(graph.duplicate_count(), 40);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn hashed_dedup_ignores_max_probe() {
+        let graph = duplicate_every_edge(10);
+        assert_eq!(graph.fast_edges.len(), 40);
+        assert_eq!(graph.duplicate_count(), 40);
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn sorted_dedup_indexes_only_the_first_max_probe_edges() {
+        let graph = duplicate_every_edge(10);
+        assert_eq!(graph.fast_edges.len(), 70);
+        assert_eq!(graph.duplicate_count(), 10);
+        // Only the first ten edges added were indexed; the rest were stored
+        // a second time.
+        for (i, &(u, v)) in graph.fast_edges[..40].iter().enumerate() {
+            let copies = graph
+                .fast_edges
+                .iter()
+                .filter(|&&(a, b)| canonical(a, b) == canonical(u, v))
+                .count();
+            assert_eq!(copies, if i < 10 { 1 } else { 2 });
+        }
+    }
+
+    #[test]
+    fn diff_ignores_orientation_and_order() {
+        let mut a = DecodingGraph::new(4);
+        a.add_edge(0, 1, 1.0).unwrap();
+        a.add_edge(1, 2, 2.0).unwrap();
+        let mut b = DecodingGraph::new(4);
+        b.add_edge(2, 1, 2.0).unwrap();
+        b.add_edge(1, 0, 1.0).unwrap();
+        assert!(a.structurally_equal(&b));
+        assert!(a.diff(&b, 0.0).is_empty());
+    }
+
+    #[test]
+    fn diff_reports_each_kind_of_difference() {
+        let mut a = DecodingGraph::new(5);
+        a.add_edge(0, 1, 1.0).unwrap();
+        a.add_edge(1, 2, 2.0).unwrap();
+        a.add_edge(2, 3, 3.0).unwrap();
+        let mut b = DecodingGraph::new(5);
+        b.add_edge(1, 0, 1.05).unwrap();
+        b.add_edge(2, 1, 2.0).unwrap();
+        b.add_edge(1, 2, 2.5).unwrap();
+        b.add_edge(3, 4, 3.0).unwrap();
+
+        assert!(!a.structurally_equal(&b));
+        let diff = a.diff(&b, 0.1);
+        assert_eq!(diff.num_nodes, (4, 5));
+        assert_eq!(diff.only_in_self, [(2, 3)]);
+        assert_eq!(diff.only_in_other, [(3, 4)]);
+        assert_eq!(diff.multiplicity_mismatches, [((1, 2), 1, 2)]);
+        // (0, 1) is within tolerance; duplicates compare their lowest weight.
+        assert!(diff.weight_mismatches.is_empty());
+
+        let strict = a.diff(&b, 0.01);
+        assert_eq!(strict.weight_mismatches, [((0, 1), 1.0, 1.05)]);
+    }
+
+    #[test]
+    fn structural_equality_ignores_weights_and_multiplicity() {
+        let mut a = DecodingGraph::new(3);
+        a.add_edge(0, 1, 1.0).unwrap();
+        let mut b = DecodingGraph::new(3);
+        b.add_edge(0, 1, 9.0).unwrap();
+        b.add_edge(1, 0, 9.0).unwrap();
+        assert!(a.structurally_equal(&b));
+        assert!(!a.diff(&b, 0.0).is_empty());
+
+        // Same edges but an extra isolated node.
+        let mut c = DecodingGraph::new(3);
+        c.add_edge(0, 1, 1.0).unwrap();
+        c.max_node_id = 3;
+        assert!(!a.structurally_equal(&c));
+        assert_eq!(a.diff(&c, 0.0).num_nodes, (2, 3));
+    }
+
+    #[test]
+    fn reordered_graph_equals_the_original() {
+        let graph = scrambled_grid();
+        let mut reordered = scrambled_grid();
+        reordered.reorder_for_locality();
+        assert!(reordered.is_reordered());
+        assert_ne!(reordered.fast_edges, graph.fast_edges);
+        assert!(graph.structurally_equal(&reordered));
+        assert!(reordered.diff(&graph, 0.0).is_empty());
+    }
+
+    #[test]
+    fn graphs_in_different_allocators_compare() {
+        use crate::allocator::BumpAllocator;
+
+        let arena = BumpAllocator::from_slice(Vec::leak(vec![0u8; 1 << 14]));
+        let graph = scrambled_grid();
+        let mut copy = DecodingGraph::new_in(graph.num_nodes(), &arena);
+        for (i, &(u, v)) in graph.fast_edges.iter().enumerate().rev() {
+            copy.add_edge(v as usize, u as usize, graph.edge_weight(i)).unwrap();
+        }
+        assert!(graph.structurally_equal(&copy));
+        assert!(copy.structurally_equal(&graph));
+        assert!(graph.diff(&copy, 0.0).is_empty());
+
+        copy.add_edge(0, 35, 1.0).unwrap();
+        assert_eq!(graph.diff(&copy, 0.0).only_in_other, [(0, 35)]);
+    }
 }
@@ -5,33 +5,16 @@
 //! trigger measurements, read syndrome data, and control quantum operations
 //! through MMIO registers.
 
-/// Base address of the CLINT (Core Local Interruptor) in QEMU 'virt' machine.
-///
-/// Standard address for the RISC-V Core Local Interruptor on QEMU's virt
-/// platform. Used for timer interrupt management and inter-hart communication.
-pub const CLINT_BASE: usize = 0x200_0000;
-
-/// Memory-mapped address for machine timer compare register.
-///
-/// Writing a value to this register schedules a timer interrupt when the
-/// machine timer counter reaches that value. Offset is 0x4000 for hart 0,
-/// with 8-byte increments per additional hardware thread.
-pub const MTIMECMP_ADDR: usize = CLINT_BASE + 0x4000;
-
-/// Memory-mapped address for machine timer counter register.
-///
-/// 64-bit read-only register that increments at a fixed frequency (typically
-/// 10 MHz in QEMU). Used for timestamping and scheduling periodic events
-/// in real-time firmware.
-pub const MTIME_ADDR: usize = CLINT_BASE + 0xBFF8;
+pub use qcu_common::mmio::{CLINT_BASE, MTIME_ADDR, MTIMECMP_ADDR, MTIMECMP_STRIDE, mtimecmp_addr};
 
 /// Base address for simulated quantum processing unit MMIO interface.
 ///
 /// Memory-mapped region for accessing quantum hardware control registers.
 /// In simulation, this maps to a static buffer in firmware memory. On real
 /// FPGA hardware, this would correspond to the physical MMIO base address
-/// of the quantum accelerator. Located in high RAM region starting at 0x8000_0000.
-pub const QPU_BASE_ADDR: usize = 0x8000_0000;
+/// of the quantum accelerator. Taken from the QEMU virt platform description,
+/// which places it in device space rather than overlapping RAM.
+pub const QPU_BASE_ADDR: usize = qcu_common::platform::QEMU_VIRT.qpu_base;
 
 /// Memory-mapped interface structure for quantum processing unit control.
 ///
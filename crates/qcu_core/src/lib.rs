@@ -12,6 +12,9 @@
 
 extern crate alloc;
 
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
 /// Bump allocator for fixed-size memory regions in no_std environments.
 ///
 /// Provides a simple linear allocator that allocates from a contiguous memory
@@ -41,6 +44,13 @@ pub mod decoder;
 /// by the decoder to track connected components in the decoding graph.
 pub mod dsu;
 
+/// Compact decoding graph with 16-bit node indices.
+///
+/// Provides a reduced-footprint graph representation for codes with at most
+/// 65,536 detectors. Used in firmware where the graph must fit in a fixed
+/// bump-allocated region alongside multi-round error models.
+pub mod compact_graph;
+
 /// Decoding graph representation for quantum error correction.
 ///
 /// Represents the connectivity structure of a quantum error correction code,
@@ -49,6 +59,13 @@ pub mod dsu;
 /// determine correction operations from syndrome measurements.
 pub mod graph;
 
+/// Instruction set definitions, re-exported from `qcu_common`.
+///
+/// `qcu_common::isa` is the single definition of the opcodes and instruction
+/// encodings; this alias lets VM users name them through `qcu_core` without
+/// a separate dependency.
+pub use qcu_common::isa;
+
 /// Pauli frame tracking for quantum state updates.
 ///
 /// Maintains a representation of accumulated Pauli corrections applied to
@@ -57,11 +74,26 @@ pub mod graph;
 /// tracking in classical simulation.
 pub mod pauli_frame;
 
+/// Validated instruction programs.
+///
+/// Wraps an instruction sequence that has been checked once against the
+/// target's qubit and detector counts, so the virtual machine can execute
+/// it without per-instruction operand checks.
+pub mod program;
+
+/// Multi-producer multi-consumer queue for work distribution.
+///
+/// Variant of the job queue in `spmc` that several producers can push into
+/// concurrently, for firmware with more than one source of decoding jobs.
+pub mod mpmc;
+
 /// Lock-free ring buffer for single-producer single-consumer communication.
 ///
 /// Provides a fixed-capacity circular buffer for efficient data transfer
 /// between threads or cores without requiring mutexes. Used for streaming
 /// syndrome packets from producer to consumer threads in host-side simulations.
+/// Also holds the multi-producer variants used to collect telemetry from
+/// several workers into one consumer.
 pub mod ring_buffer;
 
 /// Single-producer multi-consumer queue for work distribution.
@@ -71,6 +103,18 @@ pub mod ring_buffer;
 /// from the primary core to worker cores via a shared job queue.
 pub mod spmc;
 
+/// Fixed-capacity binary heap built on `StaticVec`.
+///
+/// Serves as an allocation-free priority queue for searches that expand the
+/// best candidate first, such as weighted cluster growth.
+pub mod static_heap;
+
+/// Stack-allocated string buffer with compile-time fixed capacity.
+///
+/// Built on `StaticVec`, it implements `core::fmt::Write` so firmware can
+/// format a whole console line before taking the console lock.
+pub mod static_string;
+
 /// Stack-allocated vector with compile-time fixed capacity.
 ///
 /// Provides a vector-like interface without heap allocation, suitable for
@@ -79,6 +123,11 @@ pub mod spmc;
 /// unavailable or undesirable.
 pub mod static_vec;
 
+mod sync;
+
+#[cfg(test)]
+mod test_util;
+
 /// Virtual machine for executing quantum error correction operations.
 ///
 /// Provides a high-level interface for running decoding algorithms on quantum
@@ -121,4 +170,68 @@ pub enum QecError {
     /// additional elements. The caller must either use a larger buffer or
     /// implement overflow handling logic.
     BufferOverflow,
+
+    /// An edge was added that already exists in the decoding graph.
+    ///
+    /// Returned by `DecodingGraph::add_edge` when duplicate detection is
+    /// enabled with the `Reject` policy. Usually indicates a generator or
+    /// hand-built topology that writes the same lattice edge twice.
+    DuplicateEdge,
+
+    /// A measurement instruction had no hardware result to consume.
+    ///
+    /// Returned by `VirtualMachine::run_program` when the measurement
+    /// iterator is exhausted before the program's last measurement.
+    /// Indicates a mismatch between the program and the recorded shot.
+    MeasurementUnderflow,
+
+    /// An instruction could not be decoded or executed.
+    ///
+    /// The opcode byte does not correspond to any `Opcode` variant, or the
+    /// operands are structurally invalid for the opcode (e.g., a CNOT whose
+    /// control and target are the same qubit). Returned instead of treating
+    /// the instruction as a no-op, so that a corrupted
+    /// instruction stream cannot silently drift the Pauli frame out of sync
+    /// with hardware.
+    InvalidInstruction {
+        /// Raw opcode byte of the offending instruction.
+        opcode: u8,
+    },
+
+    /// An instruction operand exceeds the VM's configured qubit or detector
+    /// count.
+    ///
+    /// The instruction-level counterpart of `NodeOutOfBounds`, carrying the
+    /// offending instruction and limit so the host or firmware can report
+    /// which operand was bad. The VM state is unchanged when this is returned.
+    OperandOutOfBounds {
+        /// Raw opcode byte of the offending instruction.
+        opcode: u8,
+
+        /// Value of the out-of-range operand.
+        operand: u32,
+
+        /// Exclusive upper bound the operand was checked against.
+        limit: usize,
+    },
+
+    /// An encoded program's length is not a whole number of instructions.
+    ///
+    /// Returned by `VirtualMachine::run_bytes` before any instruction is
+    /// executed, so the transport layer can reject (NAK) the program.
+    TruncatedProgram {
+        /// Number of bytes after the last complete instruction.
+        trailing: usize,
+    },
+
+    /// A program failed structural validation.
+    ///
+    /// Returned by `Program::validate` for an unmatched LoopBegin or
+    /// LoopEnd, loops nested deeper than the VM supports, a missing final
+    /// Halt, or an instruction after Halt.
+    InvalidProgram {
+        /// Index of the offending instruction (the program length if the
+        /// final Halt is missing).
+        pc: usize,
+    },
 }
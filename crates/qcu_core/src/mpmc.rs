@@ -0,0 +1,256 @@
+//! Lock-free multi-producer multi-consumer queue with static allocation.
+//!
+//! Extends the `StaticQueue` design to several producers, for firmware that
+//! feeds one worker pool from more than one ingest source. Producers claim
+//! positions by compare-and-swap on head and consumers by compare-and-swap
+//! on tail, with per-slot sequence numbers ordering the two sides (Vyukov's
+//! bounded queue). The buffer is statically allocated, making it suitable
+//! for no_std firmware environments.
+
+use crate::ring_buffer::{PopError, Slot, mpsc_push};
+use crate::spmc::{spmc_pop, spmc_pop_batch, try_pop_closable};
+use crate::static_vec::StaticVec;
+use crate::sync::{AtomicBool, AtomicUsize, const_fn};
+use core::sync::atomic::Ordering;
+
+/// Statically allocated lock-free multi-producer multi-consumer queue.
+///
+/// Drop-in replacement for `StaticQueue` with the same `new`, `push`, and
+/// `pop` signatures, so a `static` job queue can switch types without
+/// touching its callers. Push costs one compare-and-swap more than
+/// `StaticQueue::push`, so prefer `StaticQueue` when there is only one
+/// producer.
+///
+/// # Type Parameters
+///
+/// * `T` - Element type
+/// * `N` - Buffer capacity (must be power of two; other values are rejected
+///   at compile time when `new` is instantiated)
+#[repr(C, align(64))]
+pub struct MpmcStaticQueue<T, const N: usize> {
+    buffer: [Slot<T>; N],
+    _pad0: [u8; 64],
+    head: AtomicUsize,
+    closed: AtomicBool,
+    _pad1: [u8; 64],
+    tail: AtomicUsize,
+}
+
+/// MpmcStaticQueue is safe to share between threads.
+///
+/// Producers and consumers each claim positions by compare-and-swap, and
+/// the slot sequence numbers keep either side from touching a slot the
+/// other still owns.
+unsafe impl<T: Send, const N: usize> Sync for MpmcStaticQueue<T, N> {}
+unsafe impl<T: Send, const N: usize> Send for MpmcStaticQueue<T, N> {}
+
+impl<T, const N: usize> Default for MpmcStaticQueue<T, N> {
+    /// Creates a queue with default (empty) state.
+    ///
+    /// Equivalent to calling `new()`, provided for trait compatibility.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> MpmcStaticQueue<T, N> {
+    /// Compile-time check that N is a usable capacity, as in `StaticQueue`.
+    const CAPACITY_CHECK: () = assert!(
+        N.is_power_of_two(),
+        "MpmcStaticQueue capacity N must be a nonzero power of two"
+    );
+
+    const_fn! {
+        /// Creates a new queue with empty state.
+        ///
+        /// Usable in `static` initialisers.
+        pub fn new() -> Self {
+            let () = Self::CAPACITY_CHECK;
+            Self {
+                buffer: Slot::array(),
+                _pad0: [0; 64],
+                head: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                _pad1: [0; 64],
+                tail: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Pushes an item into the queue (producer operation, any thread).
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) if the item was enqueued, Err(item) if the buffer is full or
+    /// has been closed.
+    #[inline(always)]
+    pub fn push(&self, item: T) -> Result<(), T> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(item);
+        }
+        mpsc_push(&self.buffer, &self.head, item)
+    }
+
+    /// Pops an item from the queue (consumer operation, any thread).
+    ///
+    /// # Returns
+    ///
+    /// Some(item) if an item was dequeued, None if the buffer is empty or
+    /// the oldest item is still being written by its producer.
+    #[inline(always)]
+    pub fn pop(&self) -> Option<T> {
+        spmc_pop(&self.buffer, &self.tail)
+    }
+
+    /// Pops an item, reporting whether an empty queue has been closed
+    /// (consumer operation, any thread).
+    ///
+    /// See `StaticQueue::try_pop`.
+    ///
+    /// # Returns
+    ///
+    /// Ok(item), Err(PopError::Empty), or Err(PopError::Closed).
+    pub fn try_pop(&self) -> Result<T, PopError> {
+        try_pop_closable(|| self.pop(), &self.closed, &self.head, &self.tail)
+    }
+
+    /// Closes the queue.
+    ///
+    /// Later pushes fail. With several producers, close only once they
+    /// have all stopped: a push that passed its closed check just before
+    /// the flag was set can still land afterwards, and a consumer that has
+    /// already seen `PopError::Closed` would miss it.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+
+    /// Returns true if `close` has been called.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Returns true if consumers have claimed every position producers
+    /// have claimed. See `StaticQueue::is_empty`.
+    pub fn is_empty(&self) -> bool {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.head.load(Ordering::Acquire) == tail
+    }
+
+    /// Pops up to a vector's spare capacity of items with one tail
+    /// compare-and-swap (consumer operation, any thread).
+    ///
+    /// See `StaticQueue::pop_batch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Vector to append to
+    ///
+    /// # Returns
+    ///
+    /// The number of items appended, zero if the queue is empty.
+    #[inline(always)]
+    pub fn pop_batch<const M: usize>(&self, out: &mut StaticVec<T, M>) -> usize {
+        let max = out.capacity() - out.len();
+        spmc_pop_batch(&self.buffer, &self.tail, max, |item| {
+            let _ = out.push(item);
+        })
+    }
+}
+
+impl<T, const N: usize> Drop for MpmcStaticQueue<T, N> {
+    /// Drops the items still queued.
+    ///
+    /// Exclusive access means no producer is mid-write, so every claimed
+    /// position has been published and is popped here.
+    fn drop(&mut self) {
+        while spmc_pop(&self.buffer, &self.tail).is_some() {}
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::test_util::{Counted, drop_counter, drops};
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn two_producers_three_consumers_deliver_each_item_once() {
+        const PRODUCERS: usize = 2;
+        const CONSUMERS: usize = 3;
+        const PER_PRODUCER: usize = 20_000;
+
+        let queue = Arc::new(MpmcStaticQueue::<usize, 8>::new());
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let queue = Arc::clone(&queue);
+                std::thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        let mut item = p * PER_PRODUCER + i;
+                        while let Err(back) = queue.push(item) {
+                            item = back;
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                std::thread::spawn(move || {
+                    let mut seen = Vec::new();
+                    loop {
+                        match queue.try_pop() {
+                            Ok(item) => seen.push(item),
+                            Err(PopError::Empty) => std::thread::yield_now(),
+                            Err(PopError::Closed) => return seen,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        queue.close();
+
+        let mut count = vec![0u8; PRODUCERS * PER_PRODUCER];
+        for consumer in consumers {
+            let seen = consumer.join().unwrap();
+            // Positions are claimed in order, so one consumer sees each
+            // producer's items in the order they were pushed.
+            for p in 0..PRODUCERS {
+                let own = seen.iter().filter(|&&item| item / PER_PRODUCER == p);
+                assert!(own.clone().zip(own.skip(1)).all(|(a, b)| a < b));
+            }
+            for item in seen {
+                count[item] += 1;
+            }
+        }
+        assert!(count.iter().all(|&c| c == 1));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn closed_queue_refuses_pushes_and_drops_leftovers() {
+        let counter = drop_counter();
+        let queue = MpmcStaticQueue::<Counted, 4>::new();
+        for id in 0..3 {
+            assert!(queue.push(Counted::new(id, &counter)).is_ok());
+        }
+        queue.close();
+        let refused = queue.push(Counted::new(3, &counter)).err().unwrap();
+        assert_eq!(refused.id, 3);
+        drop(refused);
+        assert_eq!(queue.try_pop().map(|item| item.id), Ok(0));
+        drop(queue);
+        assert_eq!(drops(&counter), 4);
+    }
+}
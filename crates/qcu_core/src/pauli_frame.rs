@@ -5,9 +5,37 @@
 //! enables efficient simulation of stabilizer circuits by tracking only
 //! the error operators rather than exponentially large state vectors.
 
-use crate::allocator::BumpAllocator;
+use crate::QecError;
+use crate::allocator::{BumpAllocator, alloc_slice_uninit_in};
 use crate::bit_utils::BitPack;
-use core::slice;
+use core::alloc::{Allocator, Layout};
+
+/// Basis in which a qubit is measured.
+///
+/// Determines which Pauli errors flip the measurement outcome: a Z-basis
+/// measurement is flipped by an X error, and an X-basis measurement is
+/// flipped by a Z error. Y errors flip both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasBasis {
+    /// Measurement in the X (Hadamard) basis.
+    X,
+
+    /// Measurement in the Z (computational) basis.
+    Z,
+}
+
+/// Pauli operator applied to a qubit by a correction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauliKind {
+    /// Bit flip; toggles the X register.
+    X,
+
+    /// Phase flip; toggles the Z register.
+    Z,
+
+    /// Combined bit and phase flip; toggles both registers.
+    Y,
+}
 
 /// Pauli frame tracking X and Z errors on logical qubits.
 ///
@@ -16,50 +44,193 @@ use core::slice;
 /// are applied by updating these registers according to their conjugation
 /// rules on Pauli operators. This representation is exact for stabilizer
 /// circuits and avoids the exponential memory cost of full state simulation.
-pub struct PauliFrame {
+///
+/// # Type Parameters
+///
+/// * `'a` - Lifetime of the register storage, typically borrowed from a
+///   `BumpAllocator` or from caller-owned arrays in tests
+pub struct PauliFrame<'a> {
     /// Bit vector tracking X errors on each qubit.
     ///
     /// Packed as u64 words, with each bit indicating whether the corresponding
     /// qubit has an X error. Updated when X-type gates (e.g., CNOT) are applied
     /// or when X errors are introduced by noise or corrections.
-    pub x_register: *mut u64,
+    pub x_register: &'a mut [u64],
 
     /// Bit vector tracking Z errors on each qubit.
     ///
     /// Packed as u64 words, with each bit indicating whether the corresponding
     /// qubit has a Z error. Updated when Z-type gates (e.g., phase gates) are
     /// applied or when Z errors are introduced by noise or corrections.
-    pub z_register: *mut u64,
+    pub z_register: &'a mut [u64],
 
-    /// Number of u64 words required to store the bit vectors.
+    /// Number of logical qubits tracked by the frame.
     ///
-    /// Computed as num_qubits.div_ceil(64) to accommodate all qubits with
-    /// proper alignment. Used for bounds checking and slice construction.
-    num_u64: usize,
+    /// Used to bounds-check qubit indices in the error injection APIs, since
+    /// the last register word may contain padding bits beyond the frame size.
+    num_qubits: usize,
 }
 
-impl PauliFrame {
+impl<'a> PauliFrame<'a> {
     /// Allocates and initializes a new Pauli frame for the specified number of qubits.
     ///
     /// Allocates two bit vectors (X and Z registers) from the provided allocator,
     /// both initialized to zero (no errors). The frame is ready to track errors
-    /// after construction. The allocator must provide sufficient memory for
-    /// 2 * num_u64 words.
+    /// after construction. Thin wrapper over `new_in` for firmware callers.
+    ///
+    /// # Arguments
+    ///
+    /// * `alloc` - Allocator for frame storage
+    /// * `num_qubits` - Number of logical qubits to track
+    ///
+    /// # Panics
+    ///
+    /// Panics if the allocator cannot provide 2 * num_qubits.div_ceil(64)
+    /// words. Use `new_in` to handle exhaustion instead.
+    pub fn new(alloc: &'a BumpAllocator, num_qubits: usize) -> Self {
+        Self::new_in(alloc, num_qubits).expect("bump region exhausted allocating Pauli frame")
+    }
+
+    /// Allocates a new Pauli frame from any allocator.
+    ///
+    /// Allocates zero-initialized X and Z registers through
+    /// `Allocator::allocate`. The frame borrows its storage and never returns
+    /// it to the allocator: this matches bump allocators, which cannot free
+    /// individual allocations, but means frames built over `Global` leak their
+    /// registers when dropped. That is acceptable for tests and long-lived
+    /// host VMs; use `from_slices` over owned arrays otherwise.
     ///
     /// # Arguments
     ///
     /// * `alloc` - Allocator for frame storage
     /// * `num_qubits` - Number of logical qubits to track
-    pub fn new(alloc: &BumpAllocator, num_qubits: usize) -> Self {
+    ///
+    /// # Returns
+    ///
+    /// The new frame, or `OutOfMemory` if either register cannot be allocated.
+    pub fn new_in<A: Allocator>(alloc: &'a A, num_qubits: usize) -> Result<Self, QecError> {
         let num_u64 = num_qubits.div_ceil(64);
-        let x_reg = alloc.alloc_slice::<u64>(num_u64).unwrap().as_mut_ptr();
-        let z_reg = alloc.alloc_slice::<u64>(num_u64).unwrap().as_mut_ptr();
+        Ok(Self {
+            x_register: alloc_zeroed_words(alloc, num_u64)?,
+            z_register: alloc_zeroed_words(alloc, num_u64)?,
+            num_qubits,
+        })
+    }
 
+    /// Creates a Pauli frame over caller-provided register storage.
+    ///
+    /// The frame tracks 64 qubits per word and keeps whatever bits the slices
+    /// already contain, so callers can seed a known error pattern. Intended
+    /// for tests and host-side tools that do not have a `BumpAllocator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Storage for the X register
+    /// * `z` - Storage for the Z register
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two slices differ in length.
+    pub fn from_slices(x: &'a mut [u64], z: &'a mut [u64]) -> Self {
+        assert_eq!(x.len(), z.len(), "X and Z registers must be the same length");
+        let num_qubits = x.len() * 64;
         Self {
-            x_register: x_reg,
-            z_register: z_reg,
-            num_u64,
+            x_register: x,
+            z_register: z,
+            num_qubits,
+        }
+    }
+
+    /// Returns the number of logical qubits tracked by the frame.
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    /// Returns the number of u64 words in each register.
+    ///
+    /// A snapshot buffer for `snapshot_into` must hold twice this many words.
+    pub fn num_words(&self) -> usize {
+        self.x_register.len()
+    }
+
+    /// Copies both registers into a caller-provided buffer.
+    ///
+    /// Writes the X register followed by the Z register into the first
+    /// `2 * num_words()` words of `buf`. Performs no allocation, so it can be
+    /// used to checkpoint the frame in firmware before speculative execution.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Destination buffer of at least `2 * num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the buffer is too short.
+    pub fn snapshot_into(&self, buf: &mut [u64]) -> Result<(), QecError> {
+        let n = self.num_words();
+        if buf.len() < 2 * n {
+            return Err(QecError::BufferOverflow);
+        }
+        buf[..n].copy_from_slice(self.x_register);
+        buf[n..2 * n].copy_from_slice(self.z_register);
+        Ok(())
+    }
+
+    /// Restores both registers from a buffer written by `snapshot_into`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Source buffer of at least `2 * num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the buffer is too short.
+    /// The frame is unchanged on error.
+    pub fn restore_from(&mut self, buf: &[u64]) -> Result<(), QecError> {
+        let n = self.num_words();
+        if buf.len() < 2 * n {
+            return Err(QecError::BufferOverflow);
         }
+        self.x_register.copy_from_slice(&buf[..n]);
+        self.z_register.copy_from_slice(&buf[n..2 * n]);
+        Ok(())
+    }
+
+    /// Creates an independent copy of the frame with storage from `alloc`.
+    ///
+    /// # Arguments
+    ///
+    /// * `alloc` - Allocator for the copy's register storage
+    ///
+    /// # Returns
+    ///
+    /// The copied frame, or `OutOfMemory` if the allocator is exhausted.
+    pub fn clone_frame<'b, A: Allocator>(
+        &self,
+        alloc: &'b A,
+    ) -> Result<PauliFrame<'b>, QecError> {
+        let x = alloc_copied_words(alloc, self.x_register)?;
+        let z = alloc_copied_words(alloc, self.z_register)?;
+        Ok(PauliFrame {
+            x_register: x,
+            z_register: z,
+            num_qubits: self.num_qubits,
+        })
+    }
+
+    /// Checks whether two frames track the same qubits with identical errors.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Frame to compare against
+    ///
+    /// # Returns
+    ///
+    /// True if both frames have the same size and bit-exact X and Z registers.
+    pub fn eq_state(&self, other: &PauliFrame<'_>) -> bool {
+        self.num_qubits == other.num_qubits
+            && self.x_register == other.x_register
+            && self.z_register == other.z_register
     }
 
     /// Resets the Pauli frame to the all-zero state (no errors).
@@ -68,12 +239,263 @@ impl PauliFrame {
     /// quantum state to the initial |0...0⟩ state. Used for initialization
     /// and recovery from uncorrectable errors. Does not deallocate memory.
     pub fn reset(&mut self) {
-        unsafe {
-            let x_slice = slice::from_raw_parts_mut(self.x_register, self.num_u64);
-            let z_slice = slice::from_raw_parts_mut(self.z_register, self.num_u64);
-            x_slice.fill(0);
-            z_slice.fill(0);
+        self.x_register.fill(0);
+        self.z_register.fill(0);
+    }
+
+    /// Injects an X error on the specified qubit.
+    ///
+    /// Toggles the qubit's X register bit, so injecting twice cancels. Used
+    /// to seed known errors for VM tests, noise injection, and self-tests of
+    /// the decode/correct loop.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index to inject the error on
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if q is not a tracked qubit.
+    pub fn inject_x(&mut self, q: usize) -> Result<(), QecError> {
+        if q >= self.num_qubits {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        BitPack::toggle(self.x_register, q);
+        Ok(())
+    }
+
+    /// Injects a Z error on the specified qubit.
+    ///
+    /// Toggles the qubit's Z register bit, so injecting twice cancels.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index to inject the error on
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if q is not a tracked qubit.
+    pub fn inject_z(&mut self, q: usize) -> Result<(), QecError> {
+        if q >= self.num_qubits {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        BitPack::toggle(self.z_register, q);
+        Ok(())
+    }
+
+    /// Injects a Y error on the specified qubit.
+    ///
+    /// Toggles both the X and Z register bits, since Y = iXZ up to the
+    /// global phase the frame does not track.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index to inject the error on
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if q is not a tracked qubit.
+    pub fn inject_y(&mut self, q: usize) -> Result<(), QecError> {
+        self.inject_x(q)?;
+        self.inject_z(q)
+    }
+
+    /// Injects X errors on a word of 64 qubits at once.
+    ///
+    /// XORs `mask` into word `word_idx` of the X register, so bit b of the
+    /// mask toggles qubit `word_idx * 64 + b`. Matches the packed layout of
+    /// the generator's shot data, allowing bulk noise injection without
+    /// per-qubit calls. Bits beyond the frame size in the final word are
+    /// ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `word_idx` - Index of the 64-qubit word to modify
+    /// * `mask` - Bit mask of qubits within the word to toggle
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if word_idx is past the end of
+    /// the register.
+    pub fn inject_x_mask(&mut self, word_idx: usize, mask: u64) -> Result<(), QecError> {
+        if word_idx >= self.x_register.len() {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        self.x_register[word_idx] ^= mask & self.word_mask(word_idx);
+        Ok(())
+    }
+
+    /// XORs a packed bitmap into the X register.
+    ///
+    /// Bit b of `words[i]` toggles the X error on qubit `i * 64 + b`, matching
+    /// the layout of the generator's packed shots and the firmware's syndrome
+    /// packets. Applies a whole shot's worth of errors in one XOR per word.
+    /// Bits beyond the frame size in the final word are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - Packed bitmap of exactly `num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the length does not match.
+    pub fn xor_x_register(&mut self, words: &[u64]) -> Result<(), QecError> {
+        if words.len() != self.x_register.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        for (i, &w) in words.iter().enumerate() {
+            self.x_register[i] ^= w & self.word_mask(i);
         }
+        Ok(())
+    }
+
+    /// XORs a packed bitmap into the Z register.
+    ///
+    /// Same layout and length requirements as `xor_x_register`.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - Packed bitmap of exactly `num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the length does not match.
+    pub fn xor_z_register(&mut self, words: &[u64]) -> Result<(), QecError> {
+        if words.len() != self.z_register.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        for (i, &w) in words.iter().enumerate() {
+            self.z_register[i] ^= w & self.word_mask(i);
+        }
+        Ok(())
+    }
+
+    /// Copies the X register out as a packed bitmap.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Destination of exactly `num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the length does not match.
+    pub fn read_x_register(&self, out: &mut [u64]) -> Result<(), QecError> {
+        if out.len() != self.x_register.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        out.copy_from_slice(self.x_register);
+        Ok(())
+    }
+
+    /// Copies the Z register out as a packed bitmap.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Destination of exactly `num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the length does not match.
+    pub fn read_z_register(&self, out: &mut [u64]) -> Result<(), QecError> {
+        if out.len() != self.z_register.len() {
+            return Err(QecError::BufferOverflow);
+        }
+        out.copy_from_slice(self.z_register);
+        Ok(())
+    }
+
+    /// Counts qubits with an X error (including Y errors).
+    ///
+    /// Bits beyond the frame size in the final word are masked out, so stray
+    /// high bits written through the public registers are never counted.
+    pub fn x_error_count(&self) -> usize {
+        self.x_register
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| (w & self.word_mask(i)).count_ones() as usize)
+            .sum()
+    }
+
+    /// Counts qubits with a Z error (including Y errors).
+    pub fn z_error_count(&self) -> usize {
+        self.z_register
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| (w & self.word_mask(i)).count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns the physical error weight tracked by the frame.
+    ///
+    /// Counts qubits with any non-identity Pauli, so a Y error (both bits
+    /// set) contributes one site rather than two. Used to report residual
+    /// error weight after corrections.
+    pub fn total_weight(&self) -> usize {
+        self.x_register
+            .iter()
+            .zip(self.z_register.iter())
+            .enumerate()
+            .map(|(i, (&x, &z))| ((x | z) & self.word_mask(i)).count_ones() as usize)
+            .sum()
+    }
+
+    /// Checks whether the frame tracks no errors at all.
+    ///
+    /// Fast path for self-tests after a reset: ORs every word of both
+    /// registers rather than counting bits.
+    pub fn is_clean(&self) -> bool {
+        let mut acc = 0u64;
+        for (i, (&x, &z)) in self.x_register.iter().zip(self.z_register.iter()).enumerate() {
+            acc |= (x | z) & self.word_mask(i);
+        }
+        acc == 0
+    }
+
+    /// Returns the mask of bits in register word `word_idx` that map to
+    /// tracked qubits. All ones except in a partially filled final word.
+    #[inline(always)]
+    fn word_mask(&self, word_idx: usize) -> u64 {
+        let valid_bits = self.num_qubits.saturating_sub(word_idx * 64);
+        if valid_bits < 64 {
+            (1u64 << valid_bits) - 1
+        } else {
+            u64::MAX
+        }
+    }
+
+    /// Applies decoder corrections to the frame.
+    ///
+    /// The decoder reports corrections as (u, v) detector pairs, one per
+    /// matched graph edge. `edge_to_qubit` maps each edge to the data qubit
+    /// whose error it represents and the Pauli to apply; edges that map to
+    /// None (e.g., boundary edges without a physical qubit) are skipped.
+    /// Applying a correction toggles the corresponding register bits, so a
+    /// subsequent measurement of the corrected qubit no longer reports the
+    /// original error.
+    ///
+    /// # Arguments
+    ///
+    /// * `corrections` - Correction edges produced by the decoder
+    /// * `edge_to_qubit` - Mapping from a correction edge to a qubit and Pauli
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if the mapping names a qubit
+    /// outside the frame. Corrections before the failing one remain applied.
+    pub fn apply_corrections(
+        &mut self,
+        corrections: &[(usize, usize)],
+        edge_to_qubit: &impl Fn(usize, usize) -> Option<(usize, PauliKind)>,
+    ) -> Result<(), QecError> {
+        for &(u, v) in corrections {
+            match edge_to_qubit(u, v) {
+                Some((q, PauliKind::X)) => self.inject_x(q)?,
+                Some((q, PauliKind::Z)) => self.inject_z(q)?,
+                Some((q, PauliKind::Y)) => self.inject_y(q)?,
+                None => {}
+            }
+        }
+        Ok(())
     }
 
     /// Applies a Hadamard gate to the specified qubit, updating the Pauli frame.
@@ -86,18 +508,44 @@ impl PauliFrame {
     ///
     /// * `q` - Qubit index to apply the Hadamard gate to
     pub fn apply_hadamard(&mut self, q: usize) {
-        unsafe {
-            let x_slice = slice::from_raw_parts_mut(self.x_register, self.num_u64);
-            let z_slice = slice::from_raw_parts_mut(self.z_register, self.num_u64);
+        let has_x = BitPack::get(self.x_register, q);
+        let has_z = BitPack::get(self.z_register, q);
 
-            let has_x = BitPack::get(x_slice, q);
-            let has_z = BitPack::get(z_slice, q);
+        BitPack::set(self.x_register, q, has_z);
+        BitPack::set(self.z_register, q, has_x);
+    }
 
-            BitPack::set(x_slice, q, has_z);
-            BitPack::set(z_slice, q, has_x);
+    /// Applies a Phase (S) gate to the specified qubit, updating the Pauli frame.
+    ///
+    /// S conjugates X to Y and leaves Z unchanged, so an X error picks up a Z
+    /// component: if the qubit has an X error, its Z error bit is toggled.
+    /// The frame tracks Pauli operators only up to a global phase, so the sign
+    /// distinguishing Y from -Y is deliberately discarded; it has no effect on
+    /// measurement outcomes. Applying S twice therefore returns an X error to
+    /// a bare X, matching SS = Z and Z X Z = -X.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index to apply the Phase gate to
+    pub fn apply_phase(&mut self, q: usize) {
+        if BitPack::get(self.x_register, q) {
+            BitPack::toggle(self.z_register, q);
         }
     }
 
+    /// Applies an inverse Phase (S†) gate to the specified qubit.
+    ///
+    /// S† conjugates X to -Y and leaves Z unchanged. Since the frame discards
+    /// signs (see `apply_phase`), the update is identical to S: an X error
+    /// toggles the qubit's Z error bit.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index to apply the inverse Phase gate to
+    pub fn apply_phase_dag(&mut self, q: usize) {
+        self.apply_phase(q);
+    }
+
     /// Applies a CNOT gate with control and target qubits, updating the Pauli frame.
     ///
     /// CNOT conjugates X_c to X_c X_t and Z_t to Z_c Z_t, where c is the control
@@ -110,16 +558,11 @@ impl PauliFrame {
     /// * `c` - Control qubit index
     /// * `t` - Target qubit index
     pub fn apply_cnot(&mut self, c: usize, t: usize) {
-        unsafe {
-            let x_slice = slice::from_raw_parts_mut(self.x_register, self.num_u64);
-            let z_slice = slice::from_raw_parts_mut(self.z_register, self.num_u64);
-
-            if BitPack::get(x_slice, c) {
-                BitPack::toggle(x_slice, t);
-            }
-            if BitPack::get(z_slice, t) {
-                BitPack::toggle(z_slice, c);
-            }
+        if BitPack::get(self.x_register, c) {
+            BitPack::toggle(self.x_register, t);
+        }
+        if BitPack::get(self.z_register, t) {
+            BitPack::toggle(self.z_register, c);
         }
     }
 
@@ -138,9 +581,301 @@ impl PauliFrame {
     ///
     /// True if the qubit has an X error, false otherwise.
     pub fn has_x_error(&self, q: usize) -> bool {
-        unsafe {
-            let x_slice = slice::from_raw_parts(self.x_register, self.num_u64);
-            BitPack::get(x_slice, q)
+        BitPack::get(self.x_register, q)
+    }
+
+    /// Checks whether the specified qubit has a Z error in the frame.
+    ///
+    /// A qubit with a Z error produces a flipped result when measured in the
+    /// X basis. Z-basis measurements are unaffected.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index to check
+    ///
+    /// # Returns
+    ///
+    /// True if the qubit has a Z error, false otherwise.
+    pub fn has_z_error(&self, q: usize) -> bool {
+        BitPack::get(self.z_register, q)
+    }
+
+    /// Predicts whether a measurement of the qubit will be flipped by the frame.
+    ///
+    /// Z-basis measurements anticommute with X errors and X-basis measurements
+    /// anticommute with Z errors, so the prediction reads the opposite
+    /// register from the measurement basis.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` - Qubit index being measured
+    /// * `basis` - Basis of the measurement
+    ///
+    /// # Returns
+    ///
+    /// True if the tracked errors flip the measurement outcome.
+    pub fn predict_measurement(&self, q: usize, basis: MeasBasis) -> bool {
+        match basis {
+            MeasBasis::Z => self.has_x_error(q),
+            MeasBasis::X => self.has_z_error(q),
+        }
+    }
+}
+
+/// Allocates a zero-initialized slice of u64 words from any allocator.
+///
+/// # Arguments
+///
+/// * `alloc` - Allocator to draw the storage from
+/// * `len` - Number of words to allocate
+///
+/// # Returns
+///
+/// The zeroed slice, or `OutOfMemory` if the allocation fails.
+#[allow(clippy::mut_from_ref)]
+fn alloc_zeroed_words<A: Allocator>(alloc: &A, len: usize) -> Result<&mut [u64], QecError> {
+    let layout = Layout::array::<u64>(len).map_err(|_| QecError::OutOfMemory)?;
+    let ptr = alloc
+        .allocate_zeroed(layout)
+        .map_err(|_| QecError::OutOfMemory)?;
+    // The allocation is sized and aligned for `len` u64 words, fully
+    // zeroed, and never deallocated, so it is valid for the borrow of `alloc`.
+    unsafe { Ok(core::slice::from_raw_parts_mut(ptr.as_ptr() as *mut u64, len)) }
+}
+
+/// Allocates a copy of a u64 slice from any allocator.
+///
+/// Uses an uninitialised allocation, since every word is overwritten by the
+/// copy.
+///
+/// # Arguments
+///
+/// * `alloc` - Allocator to draw the storage from
+/// * `src` - Words to copy
+///
+/// # Returns
+///
+/// The copied slice, or `OutOfMemory` if the allocation fails.
+#[allow(clippy::mut_from_ref)]
+fn alloc_copied_words<'b, A: Allocator>(
+    alloc: &'b A,
+    src: &[u64],
+) -> Result<&'b mut [u64], QecError> {
+    let words = alloc_slice_uninit_in(alloc, src.len())?;
+    for (dst, &w) in words.iter_mut().zip(src) {
+        dst.write(w);
+    }
+    // Every word was written by the loop above.
+    Ok(unsafe { core::slice::from_raw_parts_mut(words.as_mut_ptr().cast(), src.len()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+    use alloc::alloc::Global;
+
+    /// Returns the (X, Z) error bits of qubit `q`.
+    fn pauli(frame: &PauliFrame<'_>, q: usize) -> (bool, bool) {
+        (frame.has_x_error(q), frame.has_z_error(q))
+    }
+
+    #[test]
+    fn phase_gate_squares_to_z_and_cancels_its_inverse() {
+        let mut frame = PauliFrame::new_in(&Global, 3).unwrap();
+        frame.inject_x(0).unwrap();
+        frame.inject_z(1).unwrap();
+        frame.inject_y(2).unwrap();
+
+        // S maps X to Y, Y to X and fixes Z, up to sign.
+        for q in 0..3 {
+            frame.apply_phase(q);
+        }
+        assert_eq!(pauli(&frame, 0), (true, true));
+        assert_eq!(pauli(&frame, 1), (false, true));
+        assert_eq!(pauli(&frame, 2), (true, false));
+
+        // S·S = Z commutes with every Pauli up to sign, so the frame is
+        // back where it started.
+        for q in 0..3 {
+            frame.apply_phase(q);
+        }
+        assert_eq!(pauli(&frame, 0), (true, false));
+        assert_eq!(pauli(&frame, 1), (false, true));
+        assert_eq!(pauli(&frame, 2), (true, true));
+
+        // S·S† = I.
+        for q in 0..3 {
+            frame.apply_phase(q);
+            frame.apply_phase_dag(q);
+        }
+        assert_eq!(pauli(&frame, 0), (true, false));
+        assert_eq!(pauli(&frame, 1), (false, true));
+        assert_eq!(pauli(&frame, 2), (true, true));
+    }
+
+    #[test]
+    fn injection_past_the_last_qubit_is_rejected_without_side_effects() {
+        // 70 qubits leave 58 padding bits in the second register word.
+        let mut frame = PauliFrame::new_in(&Global, 70).unwrap();
+        frame.inject_x(69).unwrap();
+        frame.inject_z(0).unwrap();
+
+        for q in [70, 127, 128, usize::MAX] {
+            assert!(matches!(frame.inject_x(q), Err(QecError::NodeOutOfBounds)));
+            assert!(matches!(frame.inject_z(q), Err(QecError::NodeOutOfBounds)));
+            assert!(matches!(frame.inject_y(q), Err(QecError::NodeOutOfBounds)));
         }
+        assert_eq!(frame.x_register, [0, 1 << 5]);
+        assert_eq!(frame.z_register, [1, 0]);
+
+        // Injecting twice cancels.
+        frame.inject_x(69).unwrap();
+        frame.inject_z(0).unwrap();
+        assert!(frame.is_clean());
+    }
+
+    #[test]
+    fn frame_over_caller_slices_reads_and_writes_through_them() {
+        let mut x = [0u64, 1 << 3];
+        let mut z = [1u64 << 63, 0];
+        {
+            let mut frame = PauliFrame::from_slices(&mut x, &mut z);
+            assert_eq!(frame.num_qubits(), 128);
+            assert_eq!(frame.num_words(), 2);
+
+            // Seeded bits are visible as errors.
+            assert!(frame.has_x_error(67));
+            assert!(frame.has_z_error(63));
+
+            frame.inject_x(1).unwrap();
+            frame.apply_hadamard(63);
+            frame.apply_cnot(67, 127);
+            assert!(matches!(frame.inject_z(128), Err(QecError::NodeOutOfBounds)));
+        }
+        assert_eq!(x, [1 << 1 | 1 << 63, 1 << 3 | 1 << 63]);
+        assert_eq!(z, [0, 0]);
+    }
+
+    #[test]
+    fn restore_returns_the_frame_to_its_snapshot() {
+        let mut frame = PauliFrame::new_in(&Global, 100).unwrap();
+        frame.inject_x(3).unwrap();
+        frame.inject_y(64).unwrap();
+        frame.inject_z(99).unwrap();
+        let saved = frame.clone_frame(&Global).unwrap();
+        assert!(frame.eq_state(&saved));
+
+        let mut buf = [0u64; 4];
+        assert!(matches!(
+            frame.snapshot_into(&mut buf[..3]),
+            Err(QecError::BufferOverflow)
+        ));
+        frame.snapshot_into(&mut buf).unwrap();
+
+        frame.apply_hadamard(3);
+        frame.apply_cnot(64, 10);
+        frame.reset();
+        assert!(!frame.eq_state(&saved));
+
+        assert!(matches!(
+            frame.restore_from(&buf[..3]),
+            Err(QecError::BufferOverflow)
+        ));
+        assert!(frame.is_clean());
+
+        frame.restore_from(&buf).unwrap();
+        assert!(frame.eq_state(&saved));
+
+        // Frames of different sizes never compare equal, even when clean.
+        let small = PauliFrame::new_in(&Global, 99).unwrap();
+        let mut large = PauliFrame::new_in(&Global, 100).unwrap();
+        assert!(!small.eq_state(&large));
+        large.reset();
+        assert!(large.eq_state(&PauliFrame::new_in(&Global, 100).unwrap()));
+    }
+
+    /// Returns `n` pseudo-random words from a xorshift sequence.
+    fn random_words(seed: u64, n: usize) -> alloc::vec::Vec<u64> {
+        let mut rng = Rng(seed);
+        (0..n).map(|_| rng.next()).collect()
+    }
+
+    #[test]
+    fn packed_xor_matches_per_qubit_injection() {
+        // 150 qubits: the last word has 22 valid bits and 42 padding bits.
+        const QUBITS: usize = 150;
+        for seed in 1..=8 {
+            let words = random_words(seed, QUBITS.div_ceil(64));
+
+            let mut packed = PauliFrame::new_in(&Global, QUBITS).unwrap();
+            packed.xor_x_register(&words).unwrap();
+            packed.xor_z_register(&words).unwrap();
+            packed.xor_z_register(&words).unwrap();
+
+            let mut by_word = PauliFrame::new_in(&Global, QUBITS).unwrap();
+            for (i, &w) in words.iter().enumerate() {
+                by_word.inject_x_mask(i, w).unwrap();
+            }
+
+            let mut by_bit = PauliFrame::new_in(&Global, QUBITS).unwrap();
+            for q in 0..QUBITS {
+                if words[q / 64] >> (q % 64) & 1 == 1 {
+                    by_bit.inject_x(q).unwrap();
+                }
+            }
+
+            assert!(packed.eq_state(&by_bit));
+            assert!(by_word.eq_state(&by_bit));
+
+            let mut out = [0u64; 3];
+            packed.read_x_register(&mut out).unwrap();
+            assert_eq!(out[..2], words[..2]);
+            assert_eq!(out[2], words[2] & ((1 << 22) - 1));
+            packed.read_z_register(&mut out).unwrap();
+            assert_eq!(out, [0; 3]);
+        }
+
+        let mut frame = PauliFrame::new_in(&Global, QUBITS).unwrap();
+        assert!(matches!(frame.xor_x_register(&[0; 2]), Err(QecError::BufferOverflow)));
+        assert!(matches!(frame.xor_z_register(&[0; 4]), Err(QecError::BufferOverflow)));
+        assert!(matches!(frame.read_x_register(&mut [0; 2]), Err(QecError::BufferOverflow)));
+        assert!(matches!(frame.inject_x_mask(3, 1), Err(QecError::NodeOutOfBounds)));
+    }
+
+    #[test]
+    fn weights_ignore_padding_bits_in_the_last_word() {
+        for qubits in [1, 63, 64, 65, 130] {
+            let mut frame = PauliFrame::new_in(&Global, qubits).unwrap();
+            let words = frame.num_words();
+
+            // Set every bit of both registers, padding included.
+            frame.x_register.fill(u64::MAX);
+            frame.z_register.fill(u64::MAX);
+            assert_eq!(frame.x_error_count(), qubits);
+            assert_eq!(frame.z_error_count(), qubits);
+            assert_eq!(frame.total_weight(), qubits);
+
+            // Only padding bits set: nothing is counted.
+            frame.reset();
+            if qubits % 64 != 0 {
+                frame.x_register[words - 1] = !((1u64 << (qubits % 64)) - 1);
+                frame.z_register[words - 1] = frame.x_register[words - 1];
+            }
+            assert_eq!(frame.x_error_count(), 0);
+            assert_eq!(frame.total_weight(), 0);
+            assert!(frame.is_clean());
+        }
+
+        // A Y error counts once towards the total weight, twice towards
+        // the per-register counts.
+        let mut frame = PauliFrame::new_in(&Global, 70).unwrap();
+        frame.inject_y(69).unwrap();
+        frame.inject_x(0).unwrap();
+        frame.inject_z(64).unwrap();
+        assert_eq!(frame.x_error_count(), 2);
+        assert_eq!(frame.z_error_count(), 2);
+        assert_eq!(frame.total_weight(), 3);
+        assert!(!frame.is_clean());
     }
 }
@@ -0,0 +1,594 @@
+//! Validated instruction programs.
+//!
+//! A `Program` is an instruction sequence that has been checked once, up
+//! front, for everything the VM would otherwise check per instruction:
+//! known opcodes, balanced and shallow enough loops, operands within the
+//! declared qubit and detector counts, and a single terminating Halt.
+//! Running a `Program` lets the VM skip its per-instruction operand checks.
+
+use crate::QecError;
+use crate::vm::{MAX_LOOP_DEPTH, MAX_OBSERVABLES};
+use alloc::alloc::Global;
+use alloc::vec::Vec;
+use core::alloc::Allocator;
+use qcu_common::isa::{INSTRUCTION_SIZE, Instruction, Instruction32, Opcode};
+
+/// Instruction sequence validated against a qubit and detector count.
+///
+/// Stored in its 6-byte wire encoding, so `as_bytes` can hand the program
+/// to a transport without re-encoding.
+///
+/// # Type Parameters
+///
+/// * `A` - Allocator for the encoded instructions. Defaults to Global for
+///   host-side usage; firmware passes its bump allocator.
+pub struct Program<A: Allocator = Global> {
+    /// Encoded instructions, `INSTRUCTION_SIZE` bytes each.
+    bytes: Vec<u8, A>,
+
+    /// One past the largest qubit operand in the program.
+    qubits_used: usize,
+
+    /// One past the largest detector index any measurement can reach,
+    /// assuming execution starts with a detector base of zero.
+    detectors_used: usize,
+
+    /// Opcode of the first instruction whose qubit operand sets
+    /// `qubits_used`, reported if the program does not fit a VM.
+    qubit_opcode: u8,
+
+    /// Opcode of the first measurement that sets `detectors_used`.
+    detector_opcode: u8,
+}
+
+impl Program<Global> {
+    /// Validates an instruction sequence and wraps it in a `Program`.
+    ///
+    /// # Arguments
+    ///
+    /// * `instrs` - Instructions to validate
+    /// * `num_qubits` - Number of qubits qubit operands must stay below
+    /// * `num_detectors` - Number of detectors measurements must stay below
+    ///
+    /// # Returns
+    ///
+    /// The validated program, or the first error found (see `validate_in`).
+    pub fn validate(
+        instrs: &[Instruction],
+        num_qubits: usize,
+        num_detectors: usize,
+    ) -> Result<Self, QecError> {
+        Self::validate_in(instrs, num_qubits, num_detectors, Global)
+    }
+}
+
+impl<A: Allocator> Program<A> {
+    /// Validates an instruction sequence, storing it with a custom allocator.
+    ///
+    /// Detector operands are checked after applying every ShiftDetectors the
+    /// program can execute before them, taking loop repeat counts into
+    /// account, so the check holds for the last iteration of each loop.
+    /// Loop bodies with a repeat count of zero are still checked. A Wide
+    /// prefix is checked together with the instruction it extends.
+    ///
+    /// # Arguments
+    ///
+    /// * `instrs` - Instructions to validate
+    /// * `num_qubits` - Number of qubits qubit operands must stay below
+    /// * `num_detectors` - Number of detectors measurements must stay below
+    /// * `alloc` - Allocator for the program's storage
+    ///
+    /// # Returns
+    ///
+    /// The validated program; `InvalidInstruction` for an unknown opcode or
+    /// a CNOT whose control equals its target; `OperandOutOfBounds` for an
+    /// out-of-range qubit, detector, or observable; `InvalidProgram` for an
+    /// unbalanced or too deeply nested loop, a missing final Halt, an
+    /// instruction after Halt, or a Wide prefix with nothing to extend; or
+    /// `OutOfMemory` if storage cannot be allocated.
+    pub fn validate_in(
+        instrs: &[Instruction],
+        num_qubits: usize,
+        num_detectors: usize,
+        alloc: A,
+    ) -> Result<Self, QecError> {
+        let mut walk = Walk {
+            instrs,
+            num_qubits,
+            num_detectors,
+            qubits_used: 0,
+            detectors_used: 0,
+            qubit_opcode: 0,
+            detector_opcode: 0,
+        };
+        walk.walk(0, 0, 0, true)?;
+
+        match instrs.last().map(|i| Opcode::try_from(i.opcode)) {
+            Some(Ok(Opcode::Halt)) => {}
+            _ => {
+                return Err(QecError::InvalidProgram { pc: instrs.len() });
+            }
+        }
+
+        let mut bytes = Vec::new_in(alloc);
+        bytes
+            .try_reserve_exact(instrs.len() * INSTRUCTION_SIZE)
+            .map_err(|_| QecError::OutOfMemory)?;
+        for instr in instrs {
+            bytes.extend_from_slice(&instr.to_bytes());
+        }
+
+        Ok(Self {
+            bytes,
+            qubits_used: walk.qubits_used,
+            detectors_used: walk.detectors_used,
+            qubit_opcode: walk.qubit_opcode,
+            detector_opcode: walk.detector_opcode,
+        })
+    }
+
+    /// Returns the number of instructions in the program.
+    pub fn len(&self) -> usize {
+        self.bytes.len() / INSTRUCTION_SIZE
+    }
+
+    /// Returns true if the program has no instructions.
+    ///
+    /// Always false for a validated program, which ends in Halt.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the instruction slot at index `pc`, or None if out of range.
+    ///
+    /// A Wide-prefixed instruction occupies two slots, returned separately.
+    pub fn get(&self, pc: usize) -> Option<Instruction> {
+        let start = pc.checked_mul(INSTRUCTION_SIZE)?;
+        let chunk = self.bytes.get(start..start + INSTRUCTION_SIZE)?;
+        Instruction::from_bytes(chunk.try_into().ok()?).ok()
+    }
+
+    /// Iterates over the program's instruction slots in order.
+    pub fn iter(&self) -> impl Iterator<Item = Instruction> + '_ {
+        (0..self.len()).filter_map(|pc| self.get(pc))
+    }
+
+    /// Returns the program's wire encoding.
+    ///
+    /// Each instruction is `INSTRUCTION_SIZE` bytes with little-endian
+    /// operands, the format accepted by `VirtualMachine::run_bytes`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns one past the largest qubit operand used by the program.
+    pub fn qubits_used(&self) -> usize {
+        self.qubits_used
+    }
+
+    /// Returns one past the largest detector index the program can reach,
+    /// relative to a detector base of zero.
+    pub fn detectors_used(&self) -> usize {
+        self.detectors_used
+    }
+
+    /// Returns the opcode of the instruction using the largest qubit.
+    pub(crate) fn qubit_opcode(&self) -> u8 {
+        self.qubit_opcode
+    }
+
+    /// Returns the opcode of the measurement reaching the largest detector.
+    pub(crate) fn detector_opcode(&self) -> u8 {
+        self.detector_opcode
+    }
+}
+
+/// State carried through the recursive validation walk.
+struct Walk<'a> {
+    /// Instructions under validation.
+    instrs: &'a [Instruction],
+
+    /// Exclusive bound for qubit operands.
+    num_qubits: usize,
+
+    /// Exclusive bound for absolute detector indices.
+    num_detectors: usize,
+
+    /// One past the largest qubit operand seen so far.
+    qubits_used: usize,
+
+    /// One past the largest absolute detector index seen so far.
+    detectors_used: usize,
+
+    /// Opcode of the instruction that set `qubits_used`.
+    qubit_opcode: u8,
+
+    /// Opcode of the instruction that set `detectors_used`.
+    detector_opcode: u8,
+}
+
+impl Walk<'_> {
+    /// Validates a straight-line block starting at `start`.
+    ///
+    /// Walks until the LoopEnd closing the current block (or the end of the
+    /// program at depth zero), recursing into nested loops. Each loop body is
+    /// walked twice: once without operand checks to learn how far one
+    /// iteration shifts the detector base, and once with checks at the base
+    /// of its last iteration.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Index of the first instruction in the block
+    /// * `base` - Detector base on entry to the block
+    /// * `depth` - Current loop nesting depth
+    /// * `check` - Whether to check operands
+    ///
+    /// # Returns
+    ///
+    /// The index of the closing LoopEnd's last slot (or the program length)
+    /// and the total detector shift applied by one pass over the block.
+    fn walk(
+        &mut self,
+        start: usize,
+        base: usize,
+        depth: usize,
+        check: bool,
+    ) -> Result<(usize, usize), QecError> {
+        let len = self.instrs.len();
+        let mut shift = 0usize;
+        let mut pc = start;
+
+        while pc < len {
+            let (instr, width) = self.fetch(pc)?;
+            let opcode = Opcode::try_from(instr.opcode).map_err(|_| {
+                QecError::InvalidInstruction {
+                    opcode: instr.opcode,
+                }
+            })?;
+
+            match opcode {
+                Opcode::LoopEnd => {
+                    if depth == 0 {
+                        return Err(QecError::InvalidProgram { pc });
+                    }
+                    return Ok((pc + width - 1, shift));
+                }
+                Opcode::LoopBegin => {
+                    if depth == MAX_LOOP_DEPTH {
+                        return Err(QecError::InvalidProgram { pc });
+                    }
+                    let count = instr.operand_1 as usize;
+                    let (end, body) = self.walk(pc + width, 0, depth + 1, false)?;
+                    if check {
+                        let last = base + shift + count.saturating_sub(1) * body;
+                        self.walk(pc + width, last, depth + 1, true)?;
+                    }
+                    shift += count * body;
+                    pc = end + 1;
+                    continue;
+                }
+                Opcode::ShiftDetectors => shift += instr.operand_1 as usize,
+                Opcode::Halt if pc + width != len => {
+                    return Err(QecError::InvalidProgram { pc: pc + width });
+                }
+                _ => {}
+            }
+
+            if check {
+                self.check_operands(opcode, &instr, base + shift)?;
+            }
+            pc += width;
+        }
+
+        if depth > 0 {
+            return Err(QecError::InvalidProgram { pc: start - 1 });
+        }
+        Ok((pc, shift))
+    }
+
+    /// Reads the instruction at `pc`, folding in a Wide prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `pc` - Index of the instruction slot to read
+    ///
+    /// # Returns
+    ///
+    /// The widened instruction and the number of slots it occupies, or
+    /// `InvalidProgram` if a prefix is last or followed by another prefix.
+    fn fetch(&self, pc: usize) -> Result<(Instruction32, usize), QecError> {
+        let first = self.instrs[pc];
+        if first.opcode != Opcode::Wide as u8 {
+            return Ok((Instruction32::from(first), 1));
+        }
+        let base = *self
+            .instrs
+            .get(pc + 1)
+            .ok_or(QecError::InvalidProgram { pc })?;
+        let instr = Instruction32::from_parts(Some(first), base)
+            .map_err(|_| QecError::InvalidProgram { pc: pc + 1 })?;
+        Ok((instr, 2))
+    }
+
+    /// Checks one instruction's operands and records the extents it uses.
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - Decoded opcode of the instruction
+    /// * `instr` - Instruction to check
+    /// * `base` - Detector base in effect for the instruction
+    fn check_operands(
+        &mut self,
+        opcode: Opcode,
+        instr: &Instruction32,
+        base: usize,
+    ) -> Result<(), QecError> {
+        let (op1, op2) = (instr.operand_1, instr.operand_2);
+        let out_of_bounds = |operand: u32, limit: usize| QecError::OperandOutOfBounds {
+            opcode: instr.opcode,
+            operand,
+            limit,
+        };
+
+        let qubits: &[u32] = match opcode {
+            Opcode::GateH
+            | Opcode::GateS
+            | Opcode::GateSdg
+            | Opcode::GateX
+            | Opcode::GateZ
+            | Opcode::Measure
+            | Opcode::MeasureX
+            | Opcode::ObservableInclude
+            | Opcode::CondPauliX
+            | Opcode::CondPauliZ => &[op1],
+            Opcode::GateCNOT => {
+                if op1 == op2 {
+                    return Err(QecError::InvalidInstruction {
+                        opcode: instr.opcode,
+                    });
+                }
+                &[op1, op2]
+            }
+            _ => &[],
+        };
+        for &q in qubits {
+            if q as usize >= self.num_qubits {
+                return Err(out_of_bounds(q, self.num_qubits));
+            }
+            if q as usize >= self.qubits_used {
+                self.qubits_used = q as usize + 1;
+                self.qubit_opcode = instr.opcode;
+            }
+        }
+
+        match opcode {
+            Opcode::Measure | Opcode::MeasureX => {
+                let detector = base + op2 as usize;
+                if detector >= self.num_detectors {
+                    return Err(out_of_bounds(op2, self.num_detectors.saturating_sub(base)));
+                }
+                if detector >= self.detectors_used {
+                    self.detectors_used = detector + 1;
+                    self.detector_opcode = instr.opcode;
+                }
+            }
+            Opcode::ObservableInclude if op2 as usize >= MAX_OBSERVABLES => {
+                return Err(out_of_bounds(op2, MAX_OBSERVABLES));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::DecodingGraph;
+    use crate::pauli_frame::PauliFrame;
+    use crate::vm::{VirtualMachine, VmExit};
+    use alloc::vec;
+
+    /// Decoder capacity used by the test VMs.
+    const N: usize = 64;
+
+    fn op(opcode: Opcode, op1: u16, op2: u16) -> Instruction {
+        Instruction::new(opcode, op1, op2)
+    }
+
+    /// Builds a graph with `nodes` detectors, so a VM built on it accepts
+    /// exactly that many.
+    fn chain(nodes: usize) -> DecodingGraph {
+        let mut graph = DecodingGraph::new(nodes);
+        for u in 0..nodes - 1 {
+            graph.add_edge(u, u + 1, 1.0).unwrap();
+        }
+        graph
+    }
+
+    /// Two nested loops measuring detector operand 0, shifting by one per
+    /// inner iteration and by two more per outer iteration. Detectors
+    /// 0, 1, 4, 5, 8 and 9 are measured.
+    fn nested_shifts() -> Vec<Instruction> {
+        vec![
+            op(Opcode::LoopBegin, 3, 0),
+            op(Opcode::LoopBegin, 2, 0),
+            op(Opcode::Measure, 0, 0),
+            op(Opcode::ShiftDetectors, 1, 0),
+            op(Opcode::LoopEnd, 0, 0),
+            op(Opcode::ShiftDetectors, 2, 0),
+            op(Opcode::LoopEnd, 0, 0),
+            op(Opcode::Halt, 0, 0),
+        ]
+    }
+
+    #[test]
+    fn shifts_in_nested_loops_bound_the_last_iteration() {
+        let instrs = nested_shifts();
+        let program = Program::validate(&instrs, 1, 10).unwrap();
+        assert_eq!(program.detectors_used(), 10);
+
+        let err = Program::validate(&instrs, 1, 9).err().unwrap();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds {
+                opcode,
+                operand: 0,
+                limit: 0,
+            } if opcode == Opcode::Measure as u8
+        ));
+    }
+
+    #[test]
+    fn validated_bound_matches_execution() {
+        let graph = chain(10);
+        let frame = PauliFrame::new_in(&Global, 1).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        let program = Program::validate(&nested_shifts(), 1, 10).unwrap();
+
+        let exit = vm
+            .run_program(&program, &mut core::iter::repeat(true))
+            .unwrap();
+        assert!(matches!(
+            exit,
+            VmExit::Halted {
+                measurements: 6,
+                ..
+            }
+        ));
+        assert_eq!(vm.syndrome_buffer, [0, 1, 4, 5, 8, 9]);
+
+        // The per-instruction checks agree: one detector fewer fails on the
+        // last measurement.
+        let graph = chain(9);
+        let frame = PauliFrame::new_in(&Global, 1).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        let err = vm
+            .run_instructions(&nested_shifts(), &mut core::iter::repeat(true))
+            .unwrap_err();
+        assert!(matches!(err, QecError::OperandOutOfBounds { .. }));
+        assert_eq!(vm.syndrome_buffer, [0, 1, 4, 5, 8]);
+    }
+
+    #[test]
+    fn zero_count_loops_are_checked_but_do_not_shift() {
+        let out_of_range = [
+            op(Opcode::LoopBegin, 0, 0),
+            op(Opcode::Measure, 0, 50),
+            op(Opcode::LoopEnd, 0, 0),
+            op(Opcode::Halt, 0, 0),
+        ];
+        assert!(matches!(
+            Program::validate(&out_of_range, 1, 10),
+            Err(QecError::OperandOutOfBounds { operand: 50, .. })
+        ));
+
+        let skipped_shift = [
+            op(Opcode::LoopBegin, 0, 0),
+            op(Opcode::ShiftDetectors, 100, 0),
+            op(Opcode::LoopEnd, 0, 0),
+            op(Opcode::Measure, 0, 5),
+            op(Opcode::Halt, 0, 0),
+        ];
+        let program = Program::validate(&skipped_shift, 1, 10).unwrap();
+        assert_eq!(program.detectors_used(), 6);
+
+        let graph = chain(10);
+        let frame = PauliFrame::new_in(&Global, 1).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.run_program(&program, &mut core::iter::repeat(true))
+            .unwrap();
+        assert_eq!(vm.syndrome_buffer, [5]);
+    }
+
+    #[test]
+    fn observable_index_must_fit_the_register() {
+        let last = [op(Opcode::ObservableInclude, 0, 63), op(Opcode::Halt, 0, 0)];
+        assert!(Program::validate(&last, 1, 0).is_ok());
+
+        let past = [op(Opcode::ObservableInclude, 0, 64), op(Opcode::Halt, 0, 0)];
+        let err = Program::validate(&past, 1, 0).err().unwrap();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds {
+                opcode,
+                operand: 64,
+                limit: MAX_OBSERVABLES,
+            } if opcode == Opcode::ObservableInclude as u8
+        ));
+    }
+
+    #[test]
+    fn halt_must_be_last() {
+        let early = [
+            op(Opcode::GateH, 0, 0),
+            op(Opcode::Halt, 0, 0),
+            op(Opcode::GateX, 0, 0),
+            op(Opcode::Halt, 0, 0),
+        ];
+        assert!(matches!(
+            Program::validate(&early, 1, 0),
+            Err(QecError::InvalidProgram { pc: 2 })
+        ));
+
+        let in_loop = [
+            op(Opcode::LoopBegin, 2, 0),
+            op(Opcode::Halt, 0, 0),
+            op(Opcode::LoopEnd, 0, 0),
+            op(Opcode::Halt, 0, 0),
+        ];
+        assert!(matches!(
+            Program::validate(&in_loop, 1, 0),
+            Err(QecError::InvalidProgram { pc: 2 })
+        ));
+
+        let missing = [op(Opcode::GateH, 0, 0)];
+        assert!(matches!(
+            Program::validate(&missing, 1, 0),
+            Err(QecError::InvalidProgram { pc: 1 })
+        ));
+    }
+
+    #[test]
+    fn run_program_names_the_offending_opcode() {
+        let graph = chain(10);
+        let frame = PauliFrame::new_in(&Global, 4).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+
+        let wide_qubits = [
+            op(Opcode::GateH, 1, 0),
+            op(Opcode::GateCNOT, 0, 7),
+            op(Opcode::Halt, 0, 0),
+        ];
+        let program = Program::validate(&wide_qubits, 8, 10).unwrap();
+        let err = vm
+            .run_program(&program, &mut core::iter::empty())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds {
+                opcode,
+                operand: 7,
+                limit: 4,
+            } if opcode == Opcode::GateCNOT as u8
+        ));
+
+        let wide_detectors = [
+            op(Opcode::Measure, 0, 3),
+            op(Opcode::MeasureX, 1, 15),
+            op(Opcode::Halt, 0, 0),
+        ];
+        let program = Program::validate(&wide_detectors, 4, 20).unwrap();
+        let err = vm
+            .run_program(&program, &mut core::iter::repeat(false))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds {
+                opcode,
+                operand: 15,
+                limit: 10,
+            } if opcode == Opcode::MeasureX as u8
+        ));
+    }
+}
@@ -4,10 +4,82 @@
 //! producer thread and a single consumer thread without locks. Uses atomic
 //! operations on head and tail pointers to coordinate access, enabling
 //! low-latency communication in real-time systems.
+//!
+//! `MpscRingBuffer` and its fixed-capacity counterpart
+//! `StaticMpscRingBuffer` relax the producer side to any number of threads
+//! for cases such as several workers reporting to one collector.
+
+use alloc::boxed::Box;
+use core::mem::MaybeUninit;
+use core::sync::atomic::Ordering;
+use crate::sync::{AtomicBool, AtomicUsize, UnsafeCell, const_fn};
+#[cfg(feature = "std")]
+use core::sync::atomic::fence;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::thread::{self, Thread};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// Number of failed pops `pop_blocking` spins through before parking.
+///
+/// Long enough to ride out the gap between packets at high rates without a
+/// park/unpark round trip, short enough that an idle consumer stops burning
+/// its core within a few microseconds.
+#[cfg(feature = "std")]
+const SPIN_LIMIT: usize = 1024;
+
+/// Reason a pop returned no item.
+///
+/// Returned by the `try_pop` methods of `RingBuffer` and the static queues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopError {
+    /// Nothing is queued right now, but the producer may push more.
+    Empty,
+
+    /// Nothing is queued and the producer has called `close`, so nothing
+    /// ever will be.
+    Closed,
+}
+
+/// Snapshot of a ring buffer's traffic counters.
+///
+/// Returned by `RingBuffer::stats` when the `stats` feature is enabled.
+/// The high-water mark is the number to look at when sizing the buffer.
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RingBufferStats {
+    /// Items accepted by `push`, `try_push`, `push_slice`, or `force_push`.
+    pub pushes: usize,
+
+    /// Items returned by `pop`, `pop_blocking`, or `pop_into`.
+    pub pops: usize,
+
+    /// Items rejected because the buffer was full, or evicted or handed back
+    /// by `force_push`.
+    pub rejects: usize,
+
+    /// Largest number of items queued at once, observed after each push.
+    pub high_water: usize,
+}
+
+/// Live counters behind `RingBufferStats`, updated with relaxed atomics.
+#[cfg(feature = "stats")]
+#[derive(Default)]
+struct Counters {
+    /// Items accepted by the producer.
+    pushes: AtomicUsize,
 
-use alloc::vec::Vec;
-use core::cell::UnsafeCell;
-use core::sync::atomic::{AtomicUsize, Ordering};
+    /// Items taken by the consumer.
+    pops: AtomicUsize,
+
+    /// Items rejected because the buffer was full, or evicted by `force_push`.
+    rejects: AtomicUsize,
+
+    /// Largest occupancy observed after a push.
+    high_water: AtomicUsize,
+}
 
 /// Lock-free ring buffer for single-producer single-consumer communication.
 ///
@@ -17,16 +89,36 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 /// visibility of written data. Capacity must be a power of two to enable
 /// efficient modulo operations via bit masking.
 ///
+/// Items are moved in on push and out on pop, so owned payloads such as a
+/// `Vec` of syndrome indices can be handed from producer to consumer. Items
+/// still queued when the buffer is dropped are dropped with it.
+///
+/// Each slot carries a sequence number, as in the MPSC buffers. It equals
+/// the slot's position plus one while the item at that position is queued
+/// and unclaimed. Whoever takes the oldest item, the consumer popping or
+/// `force_push` evicting, first claims it by moving the sequence number
+/// back to the position, so the two can never read a slot the other is
+/// rewriting.
+///
+/// As in `StaticQueue`, head and tail are separated by cache line padding so
+/// the two ends do not invalidate each other's line on every update. Each
+/// end also keeps a private copy of the other's index and only reloads the
+/// shared one when the copy says the buffer is full (producer) or empty
+/// (consumer).
+///
 /// # Type Parameters
 ///
-/// * `T` - Element type, must be Default and Copy for initialization
+/// * `T` - Element type
+#[repr(C, align(64))]
 pub struct RingBuffer<T> {
     /// Backing storage for the circular buffer.
     ///
-    /// Wrapped in UnsafeCell to allow mutable access from immutable references,
-    /// which is safe because the SPSC contract ensures only one thread accesses
-    /// each end of the buffer.
-    buffer: UnsafeCell<Vec<T>>,
+    /// Each value is wrapped in UnsafeCell to allow mutable access from
+    /// immutable references, which is safe because the SPSC contract ensures
+    /// only one thread accesses each end of the buffer, and the sequence
+    /// numbers decide who owns the oldest item. Slots in `tail..head` hold
+    /// initialised items; the rest are uninitialised.
+    buffer: Box<[Slot<T>]>,
 
     /// Fixed capacity of the buffer (must be power of two).
     capacity: usize,
@@ -37,17 +129,54 @@ pub struct RingBuffer<T> {
     /// enabling faster index calculation via bitwise AND.
     mask: usize,
 
+    _pad0: [u8; 64],
+
     /// Atomic head pointer (producer index).
     ///
     /// Points to the next slot where data will be written. Incremented by
     /// the producer thread after writing an item.
     head: AtomicUsize,
 
+    /// Producer's last observed value of `tail`.
+    ///
+    /// Never ahead of the real tail, so it can only under-report free space.
+    /// Only the producer touches it; it is atomic just to keep the type Sync.
+    cached_tail: AtomicUsize,
+
+    /// Set by the producer in `close` once it will push no more items.
+    closed: AtomicBool,
+
+    _pad1: [u8; 64],
+
     /// Atomic tail pointer (consumer index).
     ///
     /// Points to the next slot to read from. Incremented by the consumer
     /// thread after reading an item.
     tail: AtomicUsize,
+
+    /// Consumer's last observed value of `head`.
+    ///
+    /// Never ahead of the real head, so it can only under-report queued
+    /// items. Only the consumer touches it.
+    cached_head: AtomicUsize,
+
+    _pad2: [u8; 64],
+
+    /// Whether the consumer is parked in `pop_blocking`, or about to be.
+    ///
+    /// Checked by the producer after each push so that it only pays for an
+    /// unpark when someone is waiting.
+    #[cfg(feature = "std")]
+    parked: AtomicBool,
+
+    /// Handle of the consumer thread last parked in `pop_blocking`.
+    #[cfg(feature = "std")]
+    consumer: Mutex<Option<Thread>>,
+
+    /// Traffic counters, only present with the `stats` feature so the
+    /// firmware hot path pays nothing for them.
+    #[cfg(feature = "stats")]
+    counters: Counters,
 }
 
 /// RingBuffer is safe to share between threads under SPSC constraints.
@@ -58,12 +187,12 @@ pub struct RingBuffer<T> {
 unsafe impl<T: Send> Sync for RingBuffer<T> {}
 unsafe impl<T: Send> Send for RingBuffer<T> {}
 
-impl<T: Default + Copy> RingBuffer<T> {
+impl<T> RingBuffer<T> {
     /// Creates a new ring buffer with the specified capacity.
     ///
     /// The capacity must be a power of two to enable efficient modulo operations.
-    /// All buffer slots are initialized to the default value of T. The buffer
-    /// is ready for use after construction, with head and tail both at zero.
+    /// Slots are left uninitialised until items are pushed. The buffer is
+    /// ready for use after construction, with head and tail both at zero.
     ///
     /// # Arguments
     ///
@@ -74,16 +203,183 @@ impl<T: Default + Copy> RingBuffer<T> {
     /// Panics if capacity is zero or not a power of two.
     pub fn new(capacity: usize) -> Self {
         assert!(capacity > 0 && capacity.is_power_of_two());
-        let mut vec = Vec::with_capacity(capacity);
-        vec.resize_with(capacity, Default::default);
-
         Self {
-            buffer: UnsafeCell::new(vec),
+            buffer: (0..capacity).map(Slot::new).collect(),
             capacity,
             mask: capacity - 1,
+            _pad0: [0; 64],
             head: AtomicUsize::new(0),
+            cached_tail: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            _pad1: [0; 64],
             tail: AtomicUsize::new(0),
+            cached_head: AtomicUsize::new(0),
+            _pad2: [0; 64],
+            #[cfg(feature = "std")]
+            parked: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            consumer: Mutex::new(None),
+            #[cfg(feature = "stats")]
+            counters: Counters::default(),
+        }
+    }
+
+    /// Returns the number of slots in the buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of items currently queued.
+    ///
+    /// Safe to call from any thread, but only a snapshot: the producer and
+    /// consumer may change it immediately afterwards. Tail is read before
+    /// head, so the difference is never negative, and it is clamped to the
+    /// capacity in case the consumer and producer both moved in between.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Relaxed);
+        head.wrapping_sub(tail).min(self.capacity)
+    }
+
+    /// Returns true if no items are queued, subject to the same staleness
+    /// as `len`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if every slot is occupied, subject to the same staleness
+    /// as `len`.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+
+    /// Returns the number of items that could be pushed right now, subject
+    /// to the same staleness as `len`.
+    pub fn free_space(&self) -> usize {
+        self.capacity - self.len()
+    }
+
+    /// Returns a snapshot of the buffer's traffic counters.
+    ///
+    /// Each counter is read separately with a relaxed load, so a snapshot
+    /// taken while both ends are active may be slightly inconsistent.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> RingBufferStats {
+        RingBufferStats {
+            pushes: self.counters.pushes.load(Ordering::Relaxed),
+            pops: self.counters.pops.load(Ordering::Relaxed),
+            rejects: self.counters.rejects.load(Ordering::Relaxed),
+            high_water: self.counters.high_water.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Records a push in the traffic counters.
+    ///
+    /// # Arguments
+    ///
+    /// * `accepted` - Items enqueued
+    /// * `rejected` - Items turned away because the buffer was full
+    ///
+    /// The occupancy for the high-water mark is read from the shared tail
+    /// rather than the producer's cached copy, which may be far behind.
+    #[cfg(feature = "stats")]
+    #[inline(always)]
+    fn record_push(&self, accepted: usize, rejected: usize) {
+        self.counters.pushes.fetch_add(accepted, Ordering::Relaxed);
+        if rejected > 0 {
+            self.counters.rejects.fetch_add(rejected, Ordering::Relaxed);
         }
+        self.counters.high_water.fetch_max(self.len(), Ordering::Relaxed);
+    }
+
+    /// Returns the number of free slots as seen by the producer.
+    ///
+    /// Uses the cached tail if it shows at least `want` free slots, and
+    /// reloads the shared tail otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `head` - Current head, as loaded by the producer
+    /// * `want` - Number of slots the caller would like to fill
+    #[inline(always)]
+    fn producer_free(&self, head: usize, want: usize) -> usize {
+        let cached = self.cached_tail.load(Ordering::Relaxed);
+        let free = self.capacity - head.wrapping_sub(cached);
+        if free >= want {
+            return free;
+        }
+        let tail = self.tail.load(Ordering::Acquire);
+        self.cached_tail.store(tail, Ordering::Relaxed);
+        self.capacity - head.wrapping_sub(tail)
+    }
+
+    /// Returns the number of queued items as seen by the consumer.
+    ///
+    /// Uses the cached head if it shows at least `want` items, and reloads
+    /// the shared head otherwise. The items counted from the cache were
+    /// published by an earlier acquire load on this thread, so their slots
+    /// are already visible. A cached head that `force_push` has let tail
+    /// overtake shows up as more than `capacity` items and is reloaded too.
+    ///
+    /// # Arguments
+    ///
+    /// * `tail` - Current tail, as loaded by the consumer
+    /// * `want` - Number of items the caller would like to take
+    #[inline(always)]
+    fn consumer_available(&self, tail: usize, want: usize) -> usize {
+        let cached = self.cached_head.load(Ordering::Relaxed);
+        let available = cached.wrapping_sub(tail);
+        if available >= want && available <= self.capacity {
+            return available;
+        }
+        let head = self.head.load(Ordering::Acquire);
+        self.cached_head.store(head, Ordering::Relaxed);
+        head.wrapping_sub(tail)
+    }
+
+    /// Claims the oldest item, at position `tail`, for reading.
+    ///
+    /// Moves the slot's sequence number from "queued" back to the position.
+    /// Exactly one of the consumer and an evicting `force_push` can win, and
+    /// the claim fails while the other side holds it.
+    ///
+    /// # Arguments
+    ///
+    /// * `tail` - Position of the oldest item
+    #[inline(always)]
+    fn claim(&self, tail: usize) -> bool {
+        self.buffer[tail & self.mask]
+            .seq
+            .compare_exchange(tail.wrapping_add(1), tail, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Writes an item into the slot for position `pos` and marks it queued.
+    ///
+    /// The item is visible to the consumer once head moves past `pos`.
+    ///
+    /// # Safety
+    ///
+    /// Producer only, with `pos` in `head..tail + capacity`, so the slot is
+    /// free and nobody else reads it.
+    #[inline(always)]
+    unsafe fn write_slot(&self, pos: usize, item: T) {
+        let slot = &self.buffer[pos & self.mask];
+        slot.value.with_mut(|value| unsafe { (*value).write(item) });
+        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Moves the item at position `pos` out of its slot.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold an initialised item that the caller has claimed,
+    /// or that lies past the claimed oldest item in `tail..head`.
+    #[inline(always)]
+    unsafe fn read_slot(&self, pos: usize) -> T {
+        self.buffer[pos & self.mask]
+            .value
+            .with(|value| unsafe { (*value).assume_init_read() })
     }
 
     /// Pushes an item into the buffer (producer operation).
@@ -100,22 +396,42 @@ impl<T: Default + Copy> RingBuffer<T> {
     ///
     /// # Returns
     ///
-    /// True if the item was successfully enqueued, false if the buffer is full.
+    /// True if the item was successfully enqueued, false if the buffer is
+    /// full. A rejected item is dropped; use `try_push` to get it back.
     pub fn push(&self, item: T) -> bool {
+        self.try_push(item).is_ok()
+    }
+
+    /// Pushes an item, handing it back if the buffer is full.
+    ///
+    /// Same as `push`, but returns a rejected item instead of dropping it,
+    /// so an owned payload can be retried or reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) if the item was enqueued, Err(item) if the buffer is full.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
         let head = self.head.load(Ordering::Relaxed);
-        let tail = self.tail.load(Ordering::Acquire);
 
-        if head.wrapping_sub(tail) >= self.capacity {
-            return false;
+        if self.producer_free(head, 1) == 0 {
+            #[cfg(feature = "stats")]
+            self.counters.rejects.fetch_add(1, Ordering::Relaxed);
+            return Err(item);
         }
 
-        unsafe {
-            let ptr = (*self.buffer.get()).as_mut_ptr();
-            ptr.add(head & self.mask).write(item);
-        }
+        // The slot is free and owned by the producer until head is advanced.
+        unsafe { self.write_slot(head, item) };
 
         self.head.store(head.wrapping_add(1), Ordering::Release);
-        true
+        #[cfg(feature = "stats")]
+        self.record_push(1, 0);
+        #[cfg(feature = "std")]
+        self.wake_consumer();
+        Ok(())
     }
 
     /// Pops an item from the buffer (consumer operation).
@@ -128,21 +444,1224 @@ impl<T: Default + Copy> RingBuffer<T> {
     ///
     /// # Returns
     ///
-    /// Some(item) if an item was dequeued, None if the buffer is empty.
+    /// Some(item) if an item was dequeued, None if the buffer is empty. Use
+    /// `try_pop` to tell an empty buffer from a closed one.
     pub fn pop(&self) -> Option<T> {
-        let tail = self.tail.load(Ordering::Relaxed);
-        let head = self.head.load(Ordering::Acquire);
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+
+            if self.consumer_available(tail, 1) == 0 {
+                return None;
+            }
+
+            // A failed claim means `force_push` is evicting this item and is
+            // about to advance tail, so look again.
+            if !self.claim(tail) {
+                core::hint::spin_loop();
+                continue;
+            }
+
+            // The slot is in `tail..head`, so it was initialised by push, and
+            // the claim keeps the producer from evicting it.
+            let item = unsafe { self.read_slot(tail) };
+
+            // Advancing tail hands the slot back to the producer.
+            self.tail.store(tail.wrapping_add(1), Ordering::Release);
+            #[cfg(feature = "stats")]
+            self.counters.pops.fetch_add(1, Ordering::Relaxed);
+            return Some(item);
+        }
+    }
+
+    /// Pops an item, waiting up to `timeout` for one to arrive
+    /// (consumer operation).
+    ///
+    /// Spins for a bounded number of attempts first, then parks the thread
+    /// until the producer's next push unparks it or the timeout expires,
+    /// so an idle consumer does not keep a core busy. Host-only; no_std
+    /// builds poll `pop` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Longest time to wait for an item
+    ///
+    /// # Returns
+    ///
+    /// Ok(item) if one was dequeued in time, Err(PopError::Empty) on timeout,
+    /// or Err(PopError::Closed) as soon as the buffer is closed and empty.
+    #[cfg(feature = "std")]
+    pub fn pop_blocking(&self, timeout: Duration) -> Result<T, PopError> {
+        for _ in 0..SPIN_LIMIT {
+            match self.try_pop() {
+                Err(PopError::Empty) => core::hint::spin_loop(),
+                result => return result,
+            }
+        }
+
+        let deadline = Instant::now() + timeout;
+        *self.consumer.lock().unwrap_or_else(|e| e.into_inner()) = Some(thread::current());
+        loop {
+            self.parked.store(true, Ordering::Relaxed);
+            // Pairs with the fence in `wake_consumer`: either the producer
+            // sees `parked` and unparks us, or this pop sees its item.
+            fence(Ordering::SeqCst);
+            let result = self.try_pop();
+            if !matches!(result, Err(PopError::Empty)) {
+                self.parked.store(false, Ordering::Relaxed);
+                return result;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                self.parked.store(false, Ordering::Relaxed);
+                return result;
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+
+    /// Pops an item, reporting whether an empty buffer has been closed
+    /// (consumer operation).
+    ///
+    /// # Returns
+    ///
+    /// Ok(item) if an item was dequeued, Err(PopError::Empty) if the buffer
+    /// is empty but still open, or Err(PopError::Closed) if it is empty and
+    /// the producer has closed it.
+    pub fn try_pop(&self) -> Result<T, PopError> {
+        if let Some(item) = self.pop() {
+            return Ok(item);
+        }
+        if !self.closed.load(Ordering::Acquire) {
+            return Err(PopError::Empty);
+        }
+        // Items pushed before `close` are visible once the flag is, so look
+        // once more before reporting the end of the stream.
+        self.pop().ok_or(PopError::Closed)
+    }
+
+    /// Marks the buffer closed (producer operation).
+    ///
+    /// Tells the consumer that no more items are coming: once the items
+    /// already queued are gone, `try_pop` and `pop_blocking` report
+    /// `PopError::Closed` instead of `PopError::Empty`, and a consumer
+    /// parked in `pop_blocking` is woken. The producer must not push after
+    /// closing. Closing does not discard queued items; use `drain` to
+    /// collect them.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        #[cfg(feature = "std")]
+        self.wake_consumer();
+    }
+
+    /// Returns true if the producer has called `close`.
+    ///
+    /// Items may still be queued; see `drain`.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Returns an iterator that pops every queued item (consumer operation).
+    ///
+    /// Meant for shutdown, after the producer has stopped, so that leftover
+    /// items can be counted, reported, or dropped rather than abandoned in
+    /// the buffer. The iterator ends at the first empty pop; items it does
+    /// not reach stay queued.
+    pub fn drain(&self) -> Drain<'_, T> {
+        Drain { buffer: self }
+    }
+
+    /// Unparks the consumer if it is waiting in `pop_blocking`.
+    ///
+    /// Called by the producer after publishing new items.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn wake_consumer(&self) {
+        fence(Ordering::SeqCst);
+        // Plain load first so the common no-waiter case does not take the
+        // line exclusive.
+        if self.parked.load(Ordering::Relaxed)
+            && self.parked.swap(false, Ordering::Relaxed)
+            && let Some(consumer) = &*self.consumer.lock().unwrap_or_else(|e| e.into_inner())
+        {
+            consumer.unpark();
+        }
+    }
+
+    /// Returns a reference to the next item without consuming it
+    /// (consumer operation).
+    ///
+    /// Lets the consumer inspect a packet, for example its timestamp, before
+    /// deciding whether to process or discard it. Prefer `peek_with` or
+    /// `peek_copy`, which cannot outlive the item.
+    ///
+    /// # Safety
+    ///
+    /// Must be called from the consumer thread, and the returned reference
+    /// must not be used after the next `pop` or `pop_into` on this buffer:
+    /// popping moves the item out and frees its slot for the producer to
+    /// overwrite. Unlike `peek_with`, this does not claim the item, so the
+    /// producer must also not call `force_push` while the reference is in
+    /// use.
+    ///
+    /// # Returns
+    ///
+    /// Some(reference) to the oldest item, or None if the buffer is empty.
+    pub unsafe fn peek(&self) -> Option<&T> {
+        let tail = self.tail.load(Ordering::Acquire);
 
-        if tail == head {
+        if self.consumer_available(tail, 1) == 0 {
             return None;
         }
 
-        let item = unsafe {
-            let ptr = (*self.buffer.get()).as_ptr();
-            ptr.add(tail & self.mask).read()
+        // The slot is in `tail..head`, so it is initialised, and the producer
+        // leaves it alone until the consumer advances tail.
+        Some(
+            self.buffer[tail & self.mask]
+                .value
+                .with(|value| unsafe { (*value).assume_init_ref() }),
+        )
+    }
+
+    /// Applies a function to the next item without consuming it
+    /// (consumer operation).
+    ///
+    /// Safe alternative to `peek`: the item is only borrowed for the
+    /// duration of the call, so no reference can outlive a later pop. The
+    /// item is claimed while `f` runs, so `force_push` cannot evict it, and
+    /// the claim is released afterwards, even if `f` panics. Like `push` and
+    /// `pop`, it relies on the SPSC contract, so `f` must not pop from this
+    /// buffer itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Function to apply to the oldest item
+    ///
+    /// # Returns
+    ///
+    /// Some(result of `f`), or None if the buffer is empty.
+    pub fn peek_with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+
+            if self.consumer_available(tail, 1) == 0 {
+                return None;
+            }
+            if !self.claim(tail) {
+                core::hint::spin_loop();
+                continue;
+            }
+
+            let slot = &self.buffer[tail & self.mask];
+            let _release = Unclaim {
+                seq: &slot.seq,
+                queued: tail.wrapping_add(1),
+            };
+            // The claim keeps the producer away from the slot, and under the
+            // SPSC contract nothing else pops while `f` runs.
+            return Some(slot.value.with(|value| f(unsafe { (*value).assume_init_ref() })));
+        }
+    }
+
+    /// Pushes an item, evicting the oldest one if the buffer is full
+    /// (producer operation).
+    ///
+    /// For telemetry such as latency samples, where the newest value matters
+    /// more than the oldest. This relaxes the SPSC contract: to evict, the
+    /// producer claims the oldest item the same way the consumer does before
+    /// reading it, then advances tail itself. Only one side can hold the
+    /// claim, so an item is never read while it is being rewritten.
+    ///
+    /// If the consumer holds the oldest item at that moment, inside
+    /// `peek_with` or partway through a pop, it cannot be evicted. Rather
+    /// than wait for the consumer, the new item is handed back.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// Ok(Some(evicted)) if the buffer was full, Ok(None) if there was room,
+    /// or Err(item) if the buffer was full and the consumer held the oldest
+    /// item.
+    pub fn force_push(&self, item: T) -> Result<Option<T>, T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let mut evicted = None;
+
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            if head.wrapping_sub(tail) < self.capacity {
+                self.cached_tail.store(tail, Ordering::Relaxed);
+                break;
+            }
+            // Full: the oldest item sits in the slot the new one goes into.
+            if self.claim(tail) {
+                evicted = Some(unsafe { self.read_slot(tail) });
+                self.tail.store(tail.wrapping_add(1), Ordering::Release);
+                self.cached_tail.store(tail.wrapping_add(1), Ordering::Relaxed);
+                break;
+            }
+            // The consumer holds the oldest item. If it has finished popping
+            // by now there is room; otherwise give up.
+            if self.tail.load(Ordering::Acquire) == tail {
+                #[cfg(feature = "stats")]
+                self.counters.rejects.fetch_add(1, Ordering::Relaxed);
+                return Err(item);
+            }
+        }
+
+        // The slot is free: either tail showed room, or the eviction above
+        // emptied it.
+        unsafe { self.write_slot(head, item) };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        #[cfg(feature = "stats")]
+        self.record_push(1, evicted.is_some() as usize);
+        #[cfg(feature = "std")]
+        self.wake_consumer();
+        Ok(evicted)
+    }
+}
+
+impl<T: Copy> RingBuffer<T> {
+    /// Returns a copy of the next item without consuming it
+    /// (consumer operation).
+    ///
+    /// # Returns
+    ///
+    /// Some(copy of the oldest item), or None if the buffer is empty.
+    pub fn peek_copy(&self) -> Option<T> {
+        self.peek_with(|item| *item)
+    }
+}
+
+impl<T: Copy> RingBuffer<T> {
+    /// Pushes as many items from a slice as fit (producer operation).
+    ///
+    /// Fills a contiguous run of slots and publishes them with a single
+    /// head update, saving one atomic round trip per item compared with
+    /// calling `push` in a loop.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Items to enqueue, in order
+    ///
+    /// # Returns
+    ///
+    /// The number of leading items that were enqueued; the rest did not fit.
+    pub fn push_slice(&self, items: &[T]) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+
+        let n = items.len().min(self.producer_free(head, items.len()));
+
+        // Slots `head..head + n` are free and owned by the producer until
+        // head is advanced.
+        for (i, &item) in items[..n].iter().enumerate() {
+            unsafe { self.write_slot(head.wrapping_add(i), item) };
+        }
+
+        self.head.store(head.wrapping_add(n), Ordering::Release);
+        #[cfg(feature = "stats")]
+        self.record_push(n, items.len() - n);
+        #[cfg(feature = "std")]
+        if n > 0 {
+            self.wake_consumer();
+        }
+        n
+    }
+
+    /// Pops as many items as fit into a slice (consumer operation).
+    ///
+    /// Counterpart of `push_slice`: copies out up to `out.len()` items and
+    /// releases their slots with a single tail update. Only the oldest item
+    /// needs claiming, since `force_push` only ever evicts the item at tail,
+    /// which does not move until the copies are done.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Destination for the dequeued items, filled from the front
+    ///
+    /// # Returns
+    ///
+    /// The number of items written to `out`.
+    pub fn pop_into(&self, out: &mut [T]) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+
+            let n = out.len().min(self.consumer_available(tail, out.len()));
+            if n == 0 {
+                return 0;
+            }
+            // As in `pop`, a failed claim means `force_push` is evicting the
+            // oldest item.
+            if !self.claim(tail) {
+                core::hint::spin_loop();
+                continue;
+            }
+
+            // Slots `tail..tail + n` were initialised by the producer and are
+            // not reused until tail is advanced.
+            for (i, slot) in out[..n].iter_mut().enumerate() {
+                *slot = unsafe { self.read_slot(tail.wrapping_add(i)) };
+            }
+
+            self.tail.store(tail.wrapping_add(n), Ordering::Release);
+            #[cfg(feature = "stats")]
+            self.counters.pops.fetch_add(n, Ordering::Relaxed);
+            return n;
+        }
+    }
+}
+
+/// Iterator over the items left in a ring buffer.
+///
+/// Returned by `RingBuffer::drain`. Each step is a `pop`.
+pub struct Drain<'a, T> {
+    buffer: &'a RingBuffer<T>,
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.buffer.pop()
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    /// Drops the items still queued in the buffer.
+    ///
+    /// Exclusive access means neither end is in use, so every slot in
+    /// `tail..head` holds an item no one else will read.
+    fn drop(&mut self) {
+        // Relaxed loads rather than `get_mut`, which loom's atomics lack.
+        let head = self.head.load(Ordering::Relaxed);
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        while tail != head {
+            self.buffer[tail & self.mask]
+                .value
+                .with_mut(|value| unsafe { (*value).assume_init_drop() });
+            tail = tail.wrapping_add(1);
+        }
+    }
+}
+
+/// Releases a `peek_with` claim when dropped, so that a panicking closure
+/// does not leave the oldest item claimed forever.
+struct Unclaim<'a> {
+    /// Sequence number of the claimed slot.
+    seq: &'a AtomicUsize,
+
+    /// Value that marks the item queued again.
+    queued: usize,
+}
+
+impl Drop for Unclaim<'_> {
+    fn drop(&mut self) {
+        self.seq.store(self.queued, Ordering::Release);
+    }
+}
+
+/// One slot of an MPSC ring buffer.
+///
+/// The sequence number says who may touch the slot next: it equals the
+/// slot's position while the slot is free for the producer claiming that
+/// position, and the position plus one once the item is written and ready
+/// for the consumer. Also used by `StaticQueue`, and by `RingBuffer` to
+/// arbitrate between the consumer and `force_push`.
+pub(crate) struct Slot<T> {
+    pub(crate) seq: AtomicUsize,
+    pub(crate) value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    const_fn! {
+        /// Creates an empty slot for the given initial position.
+        pub(crate) fn new(position: usize) -> Self {
+            Self {
+                seq: AtomicUsize::new(position),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }
+    }
+
+    const_fn! {
+        /// Creates an array of empty slots, slot `i` starting at position `i`.
+        ///
+        /// Usable in `static` initialisers.
+        pub(crate) fn array<const N: usize>() -> [Self; N] {
+            let mut slots = MaybeUninit::<[Self; N]>::uninit();
+            let first = slots.as_mut_ptr() as *mut Self;
+            let mut i = 0;
+            while i < N {
+                // `i < N`, so the write stays inside the array.
+                unsafe { first.add(i).write(Self::new(i)) };
+                i += 1;
+            }
+            // Every element was written by the loop above.
+            unsafe { slots.assume_init() }
+        }
+    }
+}
+
+/// Lock-free ring buffer for multi-producer single-consumer communication.
+///
+/// Lets several threads push concurrently into one collector, for example
+/// worker threads reporting latency samples. Producers claim a position by
+/// compare-and-swap on head; each slot carries a sequence number (Vyukov's
+/// bounded queue) so the consumer only reads a slot after its producer has
+/// finished writing it, even when producers finish out of order. A producer
+/// that has claimed a slot but not yet filled it holds up the consumer
+/// until it does, so `pop` may report empty while later items are ready.
+///
+/// Capacity must be a power of two. See `StaticMpscRingBuffer` for a
+/// fixed-capacity variant that can live in a `static`.
+///
+/// # Type Parameters
+///
+/// * `T` - Element type
+#[repr(C, align(64))]
+pub struct MpscRingBuffer<T> {
+    /// Backing storage; slot `i & mask` holds position `i`.
+    slots: Box<[Slot<T>]>,
+
+    _pad0: [u8; 64],
+
+    /// Next position to be claimed by a producer.
+    head: AtomicUsize,
+
+    _pad1: [u8; 64],
+
+    /// Next position to be read by the consumer.
+    tail: AtomicUsize,
+}
+
+/// MpscRingBuffer is safe to share between threads under MPSC constraints.
+///
+/// Producers coordinate through the head CAS and the slot sequence numbers,
+/// and the single-consumer contract ensures only one thread pops.
+unsafe impl<T: Send> Sync for MpscRingBuffer<T> {}
+unsafe impl<T: Send> Send for MpscRingBuffer<T> {}
+
+impl<T> MpscRingBuffer<T> {
+    /// Creates a new MPSC ring buffer with the specified capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Buffer size (must be power of two and greater than zero)
+    ///
+    /// # Panics
+    ///
+    /// Panics if capacity is zero or not a power of two.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0 && capacity.is_power_of_two());
+        Self {
+            slots: (0..capacity).map(Slot::new).collect(),
+            _pad0: [0; 64],
+            head: AtomicUsize::new(0),
+            _pad1: [0; 64],
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of slots in the buffer.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of items claimed by producers and not yet popped.
+    ///
+    /// A snapshot only, like `RingBuffer::len`, and it counts items whose
+    /// producers are still writing them.
+    pub fn len(&self) -> usize {
+        mpsc_len(&self.slots, &self.head, &self.tail)
+    }
+
+    /// Returns true if no items are queued, subject to the same staleness
+    /// as `len`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes an item into the buffer (producer operation, any thread).
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// True if the item was enqueued, false if the buffer is full. A
+    /// rejected item is dropped; use `try_push` to get it back.
+    pub fn push(&self, item: T) -> bool {
+        self.try_push(item).is_ok()
+    }
+
+    /// Pushes an item, handing it back if the buffer is full
+    /// (producer operation, any thread).
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) if the item was enqueued, Err(item) if the buffer is full.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        mpsc_push(&self.slots, &self.head, item)
+    }
+
+    /// Pops the oldest item (consumer operation, one thread only).
+    ///
+    /// # Returns
+    ///
+    /// Some(item) if an item was dequeued, None if the buffer is empty or
+    /// the oldest item is still being written.
+    pub fn pop(&self) -> Option<T> {
+        mpsc_pop(&self.slots, &self.tail)
+    }
+}
+
+impl<T> Drop for MpscRingBuffer<T> {
+    /// Drops the items still queued in the buffer.
+    fn drop(&mut self) {
+        while mpsc_pop(&self.slots, &self.tail).is_some() {}
+    }
+}
+
+/// Fixed-capacity MPSC ring buffer that can be placed in a `static`.
+///
+/// Same algorithm as `MpscRingBuffer`, with the slots stored inline so
+/// firmware can share one between harts without an allocator, for example
+/// worker harts pushing latency samples to hart 0.
+///
+/// # Type Parameters
+///
+/// * `T` - Element type
+/// * `N` - Buffer capacity (must be power of two; other values are rejected
+///   at compile time when `new` is instantiated)
+#[repr(C, align(64))]
+pub struct StaticMpscRingBuffer<T, const N: usize> {
+    /// Backing storage; slot `i & (N - 1)` holds position `i`.
+    slots: [Slot<T>; N],
+
+    _pad0: [u8; 64],
+
+    /// Next position to be claimed by a producer.
+    head: AtomicUsize,
+
+    _pad1: [u8; 64],
+
+    /// Next position to be read by the consumer.
+    tail: AtomicUsize,
+}
+
+/// StaticMpscRingBuffer is safe to share between threads under MPSC
+/// constraints, for the same reasons as `MpscRingBuffer`.
+unsafe impl<T: Send, const N: usize> Sync for StaticMpscRingBuffer<T, N> {}
+unsafe impl<T: Send, const N: usize> Send for StaticMpscRingBuffer<T, N> {}
+
+impl<T, const N: usize> Default for StaticMpscRingBuffer<T, N> {
+    /// Creates an empty buffer, equivalent to `new()`.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> StaticMpscRingBuffer<T, N> {
+    /// Compile-time check that N is a usable capacity, as in `StaticQueue`.
+    const CAPACITY_CHECK: () = assert!(
+        N.is_power_of_two(),
+        "StaticMpscRingBuffer capacity N must be a nonzero power of two"
+    );
+
+    const_fn! {
+        /// Creates an empty buffer.
+        ///
+        /// Usable in `static` initialisers.
+        pub fn new() -> Self {
+            let () = Self::CAPACITY_CHECK;
+            Self {
+                slots: Slot::array(),
+                _pad0: [0; 64],
+                head: AtomicUsize::new(0),
+                _pad1: [0; 64],
+                tail: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Returns the number of slots in the buffer.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of items claimed by producers and not yet popped.
+    ///
+    /// See `MpscRingBuffer::len`.
+    pub fn len(&self) -> usize {
+        mpsc_len(&self.slots, &self.head, &self.tail)
+    }
+
+    /// Returns true if no items are queued, subject to the same staleness
+    /// as `len`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes an item into the buffer (producer operation, any hart).
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// True if the item was enqueued, false if the buffer is full.
+    pub fn push(&self, item: T) -> bool {
+        self.try_push(item).is_ok()
+    }
+
+    /// Pushes an item, handing it back if the buffer is full
+    /// (producer operation, any hart).
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Item to enqueue
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) if the item was enqueued, Err(item) if the buffer is full.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        mpsc_push(&self.slots, &self.head, item)
+    }
+
+    /// Pops the oldest item (consumer operation, one hart only).
+    ///
+    /// # Returns
+    ///
+    /// Some(item) if an item was dequeued, None if the buffer is empty or
+    /// the oldest item is still being written.
+    pub fn pop(&self) -> Option<T> {
+        mpsc_pop(&self.slots, &self.tail)
+    }
+}
+
+impl<T, const N: usize> Drop for StaticMpscRingBuffer<T, N> {
+    /// Drops the items still queued in the buffer.
+    fn drop(&mut self) {
+        while mpsc_pop(&self.slots, &self.tail).is_some() {}
+    }
+}
+
+/// Claims the next position and writes an item into it.
+///
+/// Shared by the MPSC buffers and `MpmcStaticQueue`. A slot whose sequence
+/// number equals the claimed position is free; one behind it still holds
+/// an unread item from the previous lap, meaning the buffer is full; one
+/// ahead of it means another producer claimed the position first, so head
+/// is reloaded.
+///
+/// # Arguments
+///
+/// * `slots` - Slot storage, with a power-of-two length
+/// * `head` - Shared producer position
+/// * `item` - Item to enqueue
+///
+/// # Returns
+///
+/// Ok(()) if the item was enqueued, Err(item) if the buffer is full.
+#[inline(always)]
+pub(crate) fn mpsc_push<T>(slots: &[Slot<T>], head: &AtomicUsize, item: T) -> Result<(), T> {
+    let mask = slots.len() - 1;
+    let mut pos = head.load(Ordering::Relaxed);
+    loop {
+        let slot = &slots[pos & mask];
+        let seq = slot.seq.load(Ordering::Acquire);
+        let lag = seq.wrapping_sub(pos) as isize;
+
+        if lag == 0 {
+            match head.compare_exchange_weak(
+                pos,
+                pos.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    // The CAS gave this producer sole ownership of the slot
+                    // until it publishes the new sequence number.
+                    slot.value.with_mut(|value| unsafe { (*value).write(item) });
+                    slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                    return Ok(());
+                }
+                Err(current) => pos = current,
+            }
+        } else if lag < 0 {
+            return Err(item);
+        } else {
+            pos = head.load(Ordering::Relaxed);
+        }
+    }
+}
+
+/// Takes the item at the consumer position, if it has been published.
+///
+/// Shared by both MPSC buffers. After reading, the slot's sequence number
+/// is advanced by one lap to hand it back to the producer that will claim
+/// it next.
+///
+/// # Arguments
+///
+/// * `slots` - Slot storage, with a power-of-two length
+/// * `tail` - Consumer position, only written by the consumer
+///
+/// # Returns
+///
+/// Some(item), or None if the slot at the consumer position is not ready.
+#[inline(always)]
+fn mpsc_pop<T>(slots: &[Slot<T>], tail: &AtomicUsize) -> Option<T> {
+    let pos = tail.load(Ordering::Relaxed);
+    let slot = &slots[pos & (slots.len() - 1)];
+
+    if slot.seq.load(Ordering::Acquire) != pos.wrapping_add(1) {
+        return None;
+    }
+
+    // The acquire load above saw the producer's release of this position,
+    // so the item is initialised and no producer touches it until the
+    // sequence number moves on.
+    let item = slot.value.with(|value| unsafe { (*value).assume_init_read() });
+    slot.seq.store(pos.wrapping_add(slots.len()), Ordering::Release);
+    tail.store(pos.wrapping_add(1), Ordering::Relaxed);
+    Some(item)
+}
+
+/// Returns the occupancy of an MPSC buffer, clamped to its capacity.
+///
+/// # Arguments
+///
+/// * `slots` - Slot storage
+/// * `head` - Shared producer position
+/// * `tail` - Consumer position
+#[inline(always)]
+fn mpsc_len<T>(slots: &[Slot<T>], head: &AtomicUsize, tail: &AtomicUsize) -> usize {
+    let tail = tail.load(Ordering::Relaxed);
+    let head = head.load(Ordering::Relaxed);
+    head.wrapping_sub(tail).min(slots.len())
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::test_util::{Counted, drop_counter, drops};
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn head_and_tail_sit_on_separate_cache_lines() {
+        assert!(core::mem::align_of::<RingBuffer<u64>>() >= 64);
+        let head = core::mem::offset_of!(RingBuffer<u64>, head);
+        let tail = core::mem::offset_of!(RingBuffer<u64>, tail);
+        let cached_head = core::mem::offset_of!(RingBuffer<u64>, cached_head);
+        assert!(tail - head >= 64);
+        assert!(cached_head - head >= 64);
+    }
+
+    #[test]
+    fn cached_indices_catch_up_when_stale() {
+        let buffer = RingBuffer::new(4);
+        for i in 0..4 {
+            assert!(buffer.try_push(i).is_ok());
+        }
+        assert!(buffer.try_push(4).is_err());
+        // The producer only sees the freed slots once it reloads the tail.
+        assert_eq!(buffer.pop(), Some(0));
+        assert_eq!(buffer.pop(), Some(1));
+        assert!(buffer.try_push(4).is_ok());
+        assert!(buffer.try_push(5).is_ok());
+        assert!(buffer.try_push(6).is_err());
+        for i in 2..6 {
+            assert_eq!(buffer.pop(), Some(i));
+        }
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn drops_each_item_once() {
+        let counter = drop_counter();
+        let buffer = RingBuffer::new(8);
+        for id in 0..8 {
+            assert!(buffer.try_push(Counted::new(id, &counter)).is_ok());
+        }
+        let rejected = buffer.try_push(Counted::new(8, &counter)).err().unwrap();
+        assert_eq!(drops(&counter), 0);
+        drop(rejected);
+        assert_eq!(drops(&counter), 1);
+
+        for id in 0..3 {
+            assert_eq!(buffer.pop().unwrap().id, id);
+        }
+        assert_eq!(drops(&counter), 4);
+        // Wrap head past the end of the storage before dropping the rest.
+        for id in 9..12 {
+            assert!(buffer.push(Counted::new(id, &counter)));
+        }
+        drop(buffer);
+        assert_eq!(drops(&counter), 12);
+    }
+
+    #[test]
+    fn push_slice_and_pop_into_split_across_the_wrap() {
+        let buffer = RingBuffer::new(8);
+        // Move head and tail to slot 6, two short of the end of storage.
+        assert_eq!(buffer.push_slice(&[0u32; 6]), 6);
+        assert_eq!(buffer.pop_into(&mut [0; 6]), 6);
+        assert!(buffer.is_empty());
+
+        assert_eq!(buffer.push_slice(&[1, 2, 3, 4, 5]), 5);
+        let mut out = [0; 3];
+        assert_eq!(buffer.pop_into(&mut out), 3);
+        assert_eq!(out, [1, 2, 3]);
+        assert_eq!(buffer.push_slice(&[6, 7, 8]), 3);
+
+        let mut out = [0; 8];
+        assert_eq!(buffer.pop_into(&mut out), 5);
+        assert_eq!(out[..5], [4, 5, 6, 7, 8]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn push_slice_and_pop_into_return_short_counts() {
+        let buffer = RingBuffer::new(4);
+        assert_eq!(buffer.pop_into(&mut [0u32; 4]), 0);
+        assert_eq!(buffer.push_slice(&[]), 0);
+
+        assert_eq!(buffer.push_slice(&[1, 2, 3]), 3);
+        // Only one slot is left; the rest of the slice is not enqueued.
+        assert_eq!(buffer.push_slice(&[4, 5, 6]), 1);
+        assert!(buffer.is_full());
+        assert_eq!(buffer.push_slice(&[7]), 0);
+
+        let mut out = [0; 6];
+        assert_eq!(buffer.pop_into(&mut out[..2]), 2);
+        assert_eq!(out[..2], [1, 2]);
+        assert_eq!(buffer.push_slice(&[5, 6, 7]), 2);
+        assert_eq!(buffer.pop_into(&mut out), 4);
+        assert_eq!(out[..4], [3, 4, 5, 6]);
+        assert_eq!(buffer.pop_into(&mut out), 0);
+    }
+
+    #[test]
+    fn concurrent_transfer_drops_each_item_once() {
+        const ITEMS: usize = 20_000;
+        const POPPED: usize = 15_000;
+
+        let counter = drop_counter();
+        let buffer = Arc::new(RingBuffer::new(64));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let producer = {
+            let buffer = Arc::clone(&buffer);
+            let counter = Arc::clone(&counter);
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                for id in 0..ITEMS {
+                    let mut item = Counted::new(id, &counter);
+                    while let Err(back) = buffer.try_push(item) {
+                        // Stop once the consumer has left and the rest of
+                        // the buffer can never drain.
+                        if done.load(Ordering::Acquire) {
+                            return id + 1;
+                        }
+                        item = back;
+                        std::thread::yield_now();
+                    }
+                }
+                ITEMS
+            })
         };
 
-        self.tail.store(tail.wrapping_add(1), Ordering::Release);
-        Some(item)
+        let mut next = 0;
+        while next < POPPED {
+            match buffer.pop() {
+                Some(item) => {
+                    assert_eq!(item.id, next);
+                    next += 1;
+                }
+                None => std::thread::yield_now(),
+            }
+        }
+        done.store(true, Ordering::Release);
+        let created = producer.join().unwrap();
+        let queued: Vec<usize> = core::iter::from_fn(|| buffer.pop().map(|i| i.id))
+            .take(10)
+            .collect();
+        assert_eq!(queued, (POPPED..POPPED + queued.len()).collect::<Vec<_>>());
+        drop(buffer);
+        assert_eq!(drops(&counter), created);
+    }
+
+    #[test]
+    fn len_stays_within_capacity_under_load() {
+        const ITEMS: u32 = 20_000;
+
+        let buffer = Arc::new(RingBuffer::new(8));
+        let producer = {
+            let buffer = Arc::clone(&buffer);
+            std::thread::spawn(move || {
+                for i in 0..ITEMS {
+                    // Evictions move tail from the producer side as well.
+                    if i % 3 == 0 {
+                        let _ = buffer.force_push(i);
+                    } else {
+                        while buffer.try_push(i).is_err() {
+                            std::thread::yield_now();
+                        }
+                    }
+                }
+                buffer.close();
+            })
+        };
+        let observer = {
+            let buffer = Arc::clone(&buffer);
+            std::thread::spawn(move || {
+                let mut samples = 0;
+                while !buffer.is_closed() {
+                    let len = buffer.len();
+                    assert!(len <= buffer.capacity(), "len {len}");
+                    assert!(buffer.free_space() <= buffer.capacity());
+                    samples += 1;
+                }
+                samples
+            })
+        };
+
+        let mut out = [0u32; 3];
+        loop {
+            match buffer.try_pop() {
+                Ok(_) => {}
+                Err(PopError::Empty) => std::thread::yield_now(),
+                Err(PopError::Closed) => break,
+            }
+            buffer.pop_into(&mut out);
+        }
+        producer.join().unwrap();
+        assert!(observer.join().unwrap() > 0);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_a_scripted_session() {
+        let buffer = RingBuffer::new(4);
+        assert_eq!(buffer.stats(), RingBufferStats::default());
+
+        for i in 1..=3u32 {
+            assert!(buffer.push(i));
+        }
+        assert_eq!(buffer.push_slice(&[4, 5, 6]), 1);
+        assert_eq!(buffer.try_push(7), Err(7));
+        assert_eq!(buffer.pop(), Some(1));
+        assert_eq!(buffer.force_push(8), Ok(None));
+        assert_eq!(buffer.force_push(9), Ok(Some(2)));
+        assert_eq!(buffer.pop_into(&mut [0; 8]), 4);
+        assert!(buffer.push(10));
+
+        assert_eq!(
+            buffer.stats(),
+            RingBufferStats {
+                pushes: 7,
+                pops: 5,
+                rejects: 4,
+                high_water: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn close_then_drain_yields_queued_items_in_order() {
+        let buffer = RingBuffer::new(8);
+        for i in 0..5u32 {
+            assert!(buffer.push(i));
+        }
+        assert_eq!(buffer.try_pop(), Ok(0));
+        assert!(buffer.push(5));
+        buffer.close();
+
+        // Closing keeps what is queued; only an empty closed buffer is done.
+        assert!(buffer.is_closed());
+        assert_eq!(buffer.len(), 5);
+        assert_eq!(buffer.drain().collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        assert_eq!(buffer.try_pop(), Err(PopError::Closed));
+        assert_eq!(buffer.try_pop(), Err(PopError::Closed));
+        assert_eq!(buffer.drain().count(), 0);
+    }
+
+    #[test]
+    fn try_pop_reports_empty_until_closed() {
+        let buffer = RingBuffer::<u32>::new(4);
+        assert_eq!(buffer.try_pop(), Err(PopError::Empty));
+        assert!(buffer.push(7));
+        buffer.close();
+        assert_eq!(buffer.try_pop(), Ok(7));
+        assert_eq!(buffer.try_pop(), Err(PopError::Closed));
+    }
+
+    #[test]
+    fn force_push_evicts_oldest_when_full() {
+        let buffer = RingBuffer::new(4);
+        for i in 0..4u32 {
+            assert_eq!(buffer.force_push(i), Ok(None));
+        }
+        assert_eq!(buffer.force_push(4), Ok(Some(0)));
+        assert_eq!(buffer.force_push(5), Ok(Some(1)));
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.drain().collect::<Vec<_>>(), [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn force_push_refuses_item_held_by_peek() {
+        let buffer = RingBuffer::new(2);
+        buffer.push(0u32);
+        buffer.push(1);
+        let refused = buffer.peek_with(|&oldest| {
+            assert_eq!(oldest, 0);
+            buffer.force_push(2)
+        });
+        assert_eq!(refused, Some(Err(2)));
+        // Once the peek has ended the claim is released and eviction works.
+        assert_eq!(buffer.force_push(2), Ok(Some(0)));
+        assert_eq!(buffer.drain().collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn concurrent_force_push_hands_out_each_item_once() {
+        const ITEMS: usize = 50_000;
+
+        let counter = drop_counter();
+        let buffer = Arc::new(RingBuffer::new(4));
+
+        let producer = {
+            let buffer = Arc::clone(&buffer);
+            let counter = Arc::clone(&counter);
+            std::thread::spawn(move || {
+                let mut last_evicted = None;
+                for id in 0..ITEMS {
+                    if let Ok(Some(evicted)) = buffer.force_push(Counted::new(id, &counter)) {
+                        assert!(last_evicted < Some(evicted.id));
+                        last_evicted = Some(evicted.id);
+                    }
+                }
+                buffer.close();
+            })
+        };
+
+        // Alternate peeks and pops so both kinds of claim race evictions.
+        let mut last = None;
+        loop {
+            let peeked = buffer.peek_with(|item| item.id);
+            match buffer.try_pop() {
+                Ok(item) => {
+                    assert!(last < Some(item.id));
+                    assert!(peeked.is_none_or(|id| id <= item.id));
+                    last = Some(item.id);
+                }
+                Err(PopError::Empty) => std::thread::yield_now(),
+                Err(PopError::Closed) => break,
+            }
+        }
+        producer.join().unwrap();
+        drop(buffer);
+        assert_eq!(drops(&counter), ITEMS);
+    }
+
+    #[test]
+    fn concurrent_pop_into_sees_increasing_samples() {
+        const ITEMS: u64 = 100_000;
+
+        let buffer = Arc::new(RingBuffer::new(8));
+        let producer = {
+            let buffer = Arc::clone(&buffer);
+            std::thread::spawn(move || {
+                for sample in 0..ITEMS {
+                    // Retry samples refused during a pop, so the last one
+                    // is sure to arrive.
+                    while buffer.force_push(sample).is_err() {}
+                }
+                buffer.close();
+            })
+        };
+
+        let mut out = [0u64; 3];
+        let mut last = None;
+        while !(buffer.is_closed() && buffer.is_empty()) {
+            let n = buffer.pop_into(&mut out);
+            for &sample in &out[..n] {
+                assert!(last < Some(sample));
+                last = Some(sample);
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(last, Some(ITEMS - 1));
+    }
+
+    #[test]
+    fn mpsc_delivers_every_item_in_per_producer_order() {
+        const PRODUCERS: usize = 4;
+        const PER_PRODUCER: usize = 25_000;
+
+        let counter = drop_counter();
+        let buffer = Arc::new(MpscRingBuffer::new(16));
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let buffer = Arc::clone(&buffer);
+                let counter = Arc::clone(&counter);
+                std::thread::spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        let mut item = Counted::new(p * PER_PRODUCER + i, &counter);
+                        while let Err(back) = buffer.try_push(item) {
+                            item = back;
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // Items from different producers interleave, but each producer's
+        // own items must arrive in the order it pushed them.
+        let mut next = [0; PRODUCERS];
+        let mut received = 0;
+        while received < PRODUCERS * PER_PRODUCER {
+            match buffer.pop() {
+                Some(item) => {
+                    let p = item.id / PER_PRODUCER;
+                    assert_eq!(item.id % PER_PRODUCER, next[p]);
+                    next[p] += 1;
+                    received += 1;
+                }
+                None => std::thread::yield_now(),
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(next, [PER_PRODUCER; PRODUCERS]);
+        assert!(buffer.pop().is_none());
+        assert_eq!(drops(&counter), PRODUCERS * PER_PRODUCER);
+    }
+
+    #[test]
+    fn static_mpsc_drops_items_left_behind() {
+        let counter = drop_counter();
+        let buffer = StaticMpscRingBuffer::<Counted, 4>::new();
+        for id in 0..4 {
+            assert!(buffer.try_push(Counted::new(id, &counter)).is_ok());
+        }
+        let rejected = buffer.try_push(Counted::new(4, &counter)).err().unwrap();
+        assert_eq!(rejected.id, 4);
+        drop(rejected);
+        assert_eq!(buffer.pop().unwrap().id, 0);
+        assert_eq!(buffer.len(), 3);
+        drop(buffer);
+        assert_eq!(drops(&counter), 5);
     }
 }
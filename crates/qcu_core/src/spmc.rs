@@ -5,9 +5,10 @@
 //! pointer to handle concurrent consumers safely. The buffer is statically
 //! allocated at compile time, making it suitable for no_std firmware environments.
 
-use core::cell::UnsafeCell;
-use core::mem::MaybeUninit;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::ring_buffer::{PopError, Slot};
+use crate::static_vec::StaticVec;
+use crate::sync::{AtomicBool, AtomicUsize, const_fn};
+use core::sync::atomic::Ordering;
 
 /// Statically allocated lock-free single-producer multi-consumer queue.
 ///
@@ -18,14 +19,45 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 /// Cache line padding is included to reduce false sharing between head and
 /// tail pointers.
 ///
+/// Each slot carries a sequence number, as in `MpscRingBuffer`: the
+/// producer publishes an item by setting it to the position plus one, and
+/// the consumer that claims the position hands the slot back by advancing
+/// it a full lap only after reading the item. Winning the tail CAS alone
+/// is therefore not enough for the producer to reuse a slot, so it cannot
+/// overwrite an item a slow consumer is still reading.
+///
 /// # Type Parameters
 ///
-/// * `T` - Element type, must be Copy for efficient reads
-/// * `N` - Buffer capacity (must be power of two)
+/// * `T` - Element type; items are moved in and out, so owned payloads
+///   work, and any left queued are dropped with the queue
+/// * `N` - Buffer capacity (must be power of two; other values are rejected
+///   at compile time when `new` is instantiated)
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::spmc::StaticQueue;
+///
+/// static JOBS: StaticQueue<u32, 4> = StaticQueue::new();
+///
+/// JOBS.push(7).unwrap();
+/// assert_eq!(JOBS.pop(), Some(7));
+/// assert_eq!(JOBS.pop(), None);
+/// ```
+///
+/// A capacity that is not a power of two does not build:
+///
+/// ```compile_fail
+/// use qcu_core::spmc::StaticQueue;
+///
+/// let queue = StaticQueue::<u32, 6>::new();
+/// # let _ = queue.push(0);
+/// ```
 pub struct StaticQueue<T, const N: usize> {
-    buffer: [UnsafeCell<MaybeUninit<T>>; N],
+    buffer: [Slot<T>; N],
     _pad0: [u8; 64],
     head: AtomicUsize,
+    closed: AtomicBool,
     _pad1: [u8; 64],
     tail: AtomicUsize,
 }
@@ -37,7 +69,7 @@ pub struct StaticQueue<T, const N: usize> {
 unsafe impl<T: Send, const N: usize> Sync for StaticQueue<T, N> {}
 unsafe impl<T: Send, const N: usize> Send for StaticQueue<T, N> {}
 
-impl<T: Copy, const N: usize> Default for StaticQueue<T, N> {
+impl<T, const N: usize> Default for StaticQueue<T, N> {
     /// Creates a queue with default (empty) state.
     ///
     /// Equivalent to calling `new()`, provided for trait compatibility.
@@ -46,29 +78,45 @@ impl<T: Copy, const N: usize> Default for StaticQueue<T, N> {
     }
 }
 
-impl<T: Copy, const N: usize> StaticQueue<T, N> {
-    /// Creates a new static queue with empty state.
-    ///
-    /// The buffer slots are left uninitialized until items are pushed. Head
-    /// and tail are both initialized to zero. The capacity N must be a power
-    /// of two for correct operation.
-    pub const fn new() -> Self {
-        Self {
-            buffer: unsafe { MaybeUninit::uninit().assume_init() },
-            _pad0: [0; 64],
-            head: AtomicUsize::new(0),
-            _pad1: [0; 64],
-            tail: AtomicUsize::new(0),
+impl<T, const N: usize> StaticQueue<T, N> {
+    /// Compile-time check that N is a usable capacity.
+    ///
+    /// Positions are mapped to slots with `& (N - 1)`, which aliases slots
+    /// unless N is a power of two. Evaluated by `new`, so a bad N fails the
+    /// build rather than corrupting the queue at run time.
+    const CAPACITY_CHECK: () = assert!(
+        N.is_power_of_two(),
+        "StaticQueue capacity N must be a nonzero power of two"
+    );
+
+    const_fn! {
+        /// Creates a new static queue with empty state.
+        ///
+        /// The buffer slots are left uninitialized until items are pushed, with
+        /// slot `i` marked free for position `i`. Head and tail are both
+        /// initialized to zero. The capacity N must be a power
+        /// of two for correct operation.
+        pub fn new() -> Self {
+            let () = Self::CAPACITY_CHECK;
+            Self {
+                buffer: Slot::array(),
+                _pad0: [0; 64],
+                head: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                _pad1: [0; 64],
+                tail: AtomicUsize::new(0),
+            }
         }
     }
 
     /// Pushes an item into the queue (producer operation).
     ///
     /// Writes the item at the current head position and increments head. Returns
-    /// an error if the buffer is full (head has wrapped around and caught up
-    /// to tail). Uses acquire ordering when reading tail to ensure visibility
-    /// of consumer updates, and release ordering when updating head to make
-    /// the written data visible to consumers.
+    /// an error if the buffer is full, meaning the head slot has not yet been
+    /// handed back by the consumer that claimed it on the previous lap. Uses
+    /// acquire ordering when reading the slot's sequence number so the
+    /// consumer's read is finished, and release ordering when publishing the
+    /// new one to make the written data visible to consumers.
     ///
     /// # Arguments
     ///
@@ -76,22 +124,23 @@ impl<T: Copy, const N: usize> StaticQueue<T, N> {
     ///
     /// # Returns
     ///
-    /// Ok(()) if the item was enqueued, Err(item) if the buffer is full.
+    /// Ok(()) if the item was enqueued, Err(item) if the buffer is full or
+    /// has been closed.
     #[inline(always)]
     pub fn push(&self, item: T) -> Result<(), T> {
         let head = self.head.load(Ordering::Relaxed);
-        let tail = self.tail.load(Ordering::Acquire);
+        let slot = &self.buffer[head & (N - 1)];
 
-        if head.wrapping_sub(tail) >= N {
+        // Only the producer sets `closed`, so a relaxed load sees its own
+        // store.
+        if self.closed.load(Ordering::Relaxed) || slot.seq.load(Ordering::Acquire) != head {
             return Err(item);
         }
 
-        unsafe {
-            let slot = self.buffer[head & (N - 1)].get();
-            (*slot).write(item);
-        }
+        slot.value.with_mut(|value| unsafe { (*value).write(item) });
 
-        self.head.store(head.wrapping_add(1), Ordering::Release);
+        slot.seq.store(head.wrapping_add(1), Ordering::Release);
+        self.head.store(head.wrapping_add(1), Ordering::Relaxed);
         Ok(())
     }
 
@@ -99,40 +148,515 @@ impl<T: Copy, const N: usize> StaticQueue<T, N> {
     ///
     /// Attempts to claim and read an item from the current tail position using
     /// compare-and-swap to handle concurrent consumers. Returns None if the
-    /// buffer is empty (tail has caught up to head). The compare-and-swap loop
-    /// retries if another consumer claimed the slot first, ensuring each item
-    /// is consumed exactly once.
+    /// buffer is empty (the tail slot holds no published item). The
+    /// compare-and-swap loop retries if another consumer claimed the slot
+    /// first, ensuring each item is consumed exactly once. The slot is only
+    /// released to the producer after the item has been read out.
     ///
     /// # Returns
     ///
-    /// Some(item) if an item was dequeued, None if the buffer is empty.
+    /// Some(item) if an item was dequeued, None if the buffer is empty. Use
+    /// `try_pop` to tell an empty queue from a closed one.
     #[inline(always)]
     pub fn pop(&self) -> Option<T> {
-        let mut tail = self.tail.load(Ordering::Relaxed);
-        loop {
-            let head = self.head.load(Ordering::Acquire);
+        spmc_pop(&self.buffer, &self.tail)
+    }
+
+    /// Pops an item, reporting whether an empty queue has been closed
+    /// (consumer operation).
+    ///
+    /// # Returns
+    ///
+    /// Ok(item) if an item was dequeued, Err(PopError::Empty) if nothing is
+    /// waiting but more may come, or Err(PopError::Closed) once the queue is
+    /// closed and every item has been claimed. Every consumer that keeps
+    /// calling this after close eventually sees Closed.
+    pub fn try_pop(&self) -> Result<T, PopError> {
+        try_pop_closable(|| self.pop(), &self.closed, &self.head, &self.tail)
+    }
+
+    /// Closes the queue (producer operation).
+    ///
+    /// Later pushes fail, and consumers see `PopError::Closed` from
+    /// `try_pop` once the items already queued have been taken, so workers
+    /// can finish their backlog and stop without a side-channel flag.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+
+    /// Returns true if the producer has called `close`.
+    ///
+    /// Items may still be queued; see `is_empty`.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Returns true if every pushed item has been claimed by a consumer.
+    ///
+    /// A snapshot only while the producer is active. Once `is_closed`
+    /// returns true it is final: an empty closed queue stays empty.
+    pub fn is_empty(&self) -> bool {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.head.load(Ordering::Acquire) == tail
+    }
+
+    /// Pops up to a vector's spare capacity of items at once (consumer
+    /// operation).
+    ///
+    /// Claims a run of consecutive published items with a single tail
+    /// compare-and-swap and appends them to `out` in queue order, so a
+    /// worker draining a backlog pays one CAS per batch rather than per
+    /// item. When only one item is waiting this behaves like `pop`.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Vector to append to; at most `out.capacity() - out.len()`
+    ///   items are taken
+    ///
+    /// # Returns
+    ///
+    /// The number of items appended, zero if the queue is empty.
+    #[inline(always)]
+    pub fn pop_batch<const M: usize>(&self, out: &mut StaticVec<T, M>) -> usize {
+        let max = out.capacity() - out.len();
+        spmc_pop_batch(&self.buffer, &self.tail, max, |item| {
+            // At most `max` items are passed in, so there is room.
+            let _ = out.push(item);
+        })
+    }
+}
 
-            if tail == head {
-                return None;
+impl<T, const N: usize> Drop for StaticQueue<T, N> {
+    /// Drops the items still queued.
+    ///
+    /// Exclusive access means no producer or consumer is mid-operation, so
+    /// every slot in `tail..head` holds a published item no one else will
+    /// read.
+    fn drop(&mut self) {
+        while spmc_pop(&self.buffer, &self.tail).is_some() {}
+    }
+}
+
+/// Pops from a closable queue, telling an empty queue from a finished one.
+///
+/// Shared by `StaticQueue` and `MpmcStaticQueue`. Checks the closed flag
+/// only after a failed pop, then pops once more: the flag is published
+/// after the last push, so anything pushed before close is visible by then.
+/// Closed is reported only when head and tail meet, meaning every pushed
+/// item has been claimed.
+///
+/// # Arguments
+///
+/// * `pop` - The queue's pop operation
+/// * `closed` - The queue's closed flag
+/// * `head` - Shared producer position
+/// * `tail` - Shared consumer position
+///
+/// # Returns
+///
+/// Ok(item), Err(PopError::Empty), or Err(PopError::Closed).
+#[inline(always)]
+pub(crate) fn try_pop_closable<T>(
+    pop: impl Fn() -> Option<T>,
+    closed: &AtomicBool,
+    head: &AtomicUsize,
+    tail: &AtomicUsize,
+) -> Result<T, PopError> {
+    if let Some(item) = pop() {
+        return Ok(item);
+    }
+    if !closed.load(Ordering::Acquire) {
+        return Err(PopError::Empty);
+    }
+    if let Some(item) = pop() {
+        return Ok(item);
+    }
+    let tail = tail.load(Ordering::Acquire);
+    if head.load(Ordering::Acquire) == tail {
+        Err(PopError::Closed)
+    } else {
+        // Claimed but not yet published by a producer, or read by another
+        // consumer between our pop and the loads above.
+        Err(PopError::Empty)
+    }
+}
+
+/// Claims the item at the consumer position and reads it out.
+///
+/// Shared by `StaticQueue` and `MpmcStaticQueue`. A slot whose sequence
+/// number is the position plus one holds a published item; one behind that
+/// has not been filled for this lap, meaning the queue is empty; one ahead
+/// means another consumer took the position first, so tail is reloaded.
+/// After reading, the sequence number is advanced by one lap to hand the
+/// slot back to the producer side.
+///
+/// # Arguments
+///
+/// * `slots` - Slot storage, with a power-of-two length
+/// * `tail` - Shared consumer position
+///
+/// # Returns
+///
+/// Some(item), or None if no published item is waiting.
+#[inline(always)]
+pub(crate) fn spmc_pop<T>(slots: &[Slot<T>], tail: &AtomicUsize) -> Option<T> {
+    let mask = slots.len() - 1;
+    let mut pos = tail.load(Ordering::Relaxed);
+    loop {
+        let slot = &slots[pos & mask];
+        let lag = slot.seq.load(Ordering::Acquire).wrapping_sub(pos.wrapping_add(1)) as isize;
+
+        if lag < 0 {
+            // Not yet published for this lap: the queue is empty.
+            return None;
+        }
+        if lag > 0 {
+            // Another consumer already took this position.
+            pos = tail.load(Ordering::Relaxed);
+            continue;
+        }
+
+        match tail.compare_exchange_weak(
+            pos,
+            pos.wrapping_add(1),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => {
+                // The CAS made this consumer the only reader of the slot,
+                // and producers leave it alone until the sequence number
+                // moves on a lap.
+                let item = slot.value.with(|value| unsafe { (*value).assume_init_read() });
+                slot.seq.store(pos.wrapping_add(slots.len()), Ordering::Release);
+                return Some(item);
             }
+            Err(actual_tail) => {
+                pos = actual_tail;
+            }
+        }
+    }
+}
 
-            match self.tail.compare_exchange_weak(
-                tail,
-                tail.wrapping_add(1),
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => {
-                    let item = unsafe {
-                        let slot = self.buffer[tail & (N - 1)].get();
-                        (*slot).assume_init()
-                    };
-                    return Some(item);
-                }
-                Err(actual_tail) => {
-                    tail = actual_tail;
+/// Claims up to `max` consecutive published items and hands them to `sink`.
+///
+/// Batch counterpart of `spmc_pop`. Counts how many slots from the
+/// consumer position on are published for the current lap, claims them all
+/// with one tail compare-and-swap, then reads each out in order and hands
+/// its slot back. If another consumer moves tail first, the count is redone
+/// from the new position.
+///
+/// # Arguments
+///
+/// * `slots` - Slot storage, with a power-of-two length
+/// * `tail` - Shared consumer position
+/// * `max` - Largest number of items to take
+/// * `sink` - Receives the claimed items, oldest first
+///
+/// # Returns
+///
+/// The number of items passed to `sink`.
+#[inline(always)]
+pub(crate) fn spmc_pop_batch<T>(
+    slots: &[Slot<T>],
+    tail: &AtomicUsize,
+    max: usize,
+    mut sink: impl FnMut(T),
+) -> usize {
+    let mask = slots.len() - 1;
+    let max = max.min(slots.len());
+    let mut pos = tail.load(Ordering::Relaxed);
+    loop {
+        let mut ready = 0;
+        while ready < max {
+            let p = pos.wrapping_add(ready);
+            if slots[p & mask].seq.load(Ordering::Acquire) != p.wrapping_add(1) {
+                break;
+            }
+            ready += 1;
+        }
+
+        if ready == 0 {
+            let lag = slots[pos & mask]
+                .seq
+                .load(Ordering::Relaxed)
+                .wrapping_sub(pos.wrapping_add(1)) as isize;
+            if lag <= 0 {
+                return 0;
+            }
+            // Another consumer already took this position.
+            pos = tail.load(Ordering::Relaxed);
+            continue;
+        }
+
+        match tail.compare_exchange_weak(
+            pos,
+            pos.wrapping_add(ready),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => {
+                for i in 0..ready {
+                    let p = pos.wrapping_add(i);
+                    let slot = &slots[p & mask];
+                    // Claimed by the CAS above and published for this lap,
+                    // as checked before it.
+                    let item = slot.value.with(|value| unsafe { (*value).assume_init_read() });
+                    slot.seq.store(p.wrapping_add(slots.len()), Ordering::Release);
+                    sink(item);
                 }
+                return ready;
+            }
+            Err(actual_tail) => {
+                pos = actual_tail;
             }
         }
     }
 }
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn pop_batch_takes_the_oldest_items_up_to_spare_capacity() {
+        let queue = StaticQueue::<u32, 8>::new();
+        for i in 0..6 {
+            queue.push(i).unwrap();
+        }
+        let mut out = StaticVec::<u32, 4>::new();
+        out.push(100).unwrap();
+        assert_eq!(queue.pop_batch(&mut out), 3);
+        assert_eq!(out.as_slice(), [100, 0, 1, 2]);
+        // A full vector takes nothing and leaves the queue alone.
+        assert_eq!(queue.pop_batch(&mut out), 0);
+        assert_eq!(queue.pop(), Some(3));
+        out.clear();
+        assert_eq!(queue.pop_batch(&mut out), 2);
+        assert_eq!(out.as_slice(), [4, 5]);
+        assert_eq!(queue.pop_batch(&mut out), 0);
+    }
+
+    #[test]
+    fn mixed_single_and_batch_pops_never_duplicate() {
+        const ITEMS: usize = 50_000;
+        const CONSUMERS: usize = 3;
+
+        let queue = Arc::new(StaticQueue::<usize, 16>::new());
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|c| {
+                let queue = Arc::clone(&queue);
+                std::thread::spawn(move || {
+                    let mut seen = Vec::new();
+                    let mut batch = StaticVec::<usize, 4>::new();
+                    let mut round = c;
+                    loop {
+                        round += 1;
+                        let got = if round % 2 == 0 {
+                            let item = queue.pop();
+                            seen.extend(item);
+                            item.is_some()
+                        } else {
+                            batch.clear();
+                            let n = queue.pop_batch(&mut batch);
+                            seen.extend_from_slice(batch.as_slice());
+                            n > 0
+                        };
+                        if !got {
+                            if queue.is_closed() && queue.is_empty() {
+                                return seen;
+                            }
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for item in 0..ITEMS {
+            let mut item = item;
+            while let Err(back) = queue.push(item) {
+                item = back;
+                std::thread::yield_now();
+            }
+        }
+        queue.close();
+
+        let mut count = vec![0u8; ITEMS];
+        for consumer in consumers {
+            let seen = consumer.join().unwrap();
+            // Batches and single pops both claim positions in order.
+            assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+            for item in seen {
+                count[item] += 1;
+            }
+        }
+        assert!(count.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn every_consumer_sees_closed_after_the_backlog() {
+        const ITEMS: usize = 100;
+        const CONSUMERS: usize = 3;
+
+        let queue = Arc::new(StaticQueue::<usize, 8>::new());
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                std::thread::spawn(move || {
+                    let mut seen = Vec::new();
+                    loop {
+                        match queue.try_pop() {
+                            Ok(item) => seen.push(item),
+                            Err(PopError::Empty) => std::thread::yield_now(),
+                            Err(PopError::Closed) => break,
+                        }
+                    }
+                    // Closed is final: later calls keep reporting it.
+                    assert_eq!(queue.try_pop(), Err(PopError::Closed));
+                    seen
+                })
+            })
+            .collect();
+
+        for item in 0..ITEMS {
+            let mut item = item;
+            while let Err(back) = queue.push(item) {
+                item = back;
+                std::thread::yield_now();
+            }
+        }
+        queue.close();
+        assert_eq!(queue.push(ITEMS), Err(ITEMS));
+
+        let mut all: Vec<usize> = consumers
+            .into_iter()
+            .flat_map(|consumer| consumer.join().unwrap())
+            .collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..ITEMS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn try_pop_reports_empty_until_closed_and_drained() {
+        let queue = StaticQueue::<u32, 4>::new();
+        assert_eq!(queue.try_pop(), Err(PopError::Empty));
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+        queue.close();
+        assert!(queue.is_closed());
+        assert_eq!(queue.try_pop(), Ok(1));
+        assert_eq!(queue.try_pop(), Ok(2));
+        assert_eq!(queue.try_pop(), Err(PopError::Closed));
+    }
+
+    /// Heap-owning payload like the syndrome packets workers receive.
+    struct Packet {
+        words: Box<[u64]>,
+        drops: Arc<AtomicUsize>,
+    }
+
+    impl Packet {
+        fn new(seed: u64, drops: &Arc<AtomicUsize>) -> Self {
+            Self {
+                words: (seed..seed + 4).collect(),
+                drops: Arc::clone(drops),
+            }
+        }
+    }
+
+    impl Drop for Packet {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn owned_payloads_move_through_and_leftovers_drop_once() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let queue = StaticQueue::<Packet, 4>::new();
+
+        // Two laps over the four slots before leaving items queued.
+        for seed in 0..6 {
+            queue.push(Packet::new(seed * 10, &drops)).ok().unwrap();
+            if seed < 4 {
+                let packet = queue.pop().unwrap();
+                let base = seed * 10;
+                assert_eq!(*packet.words, [base, base + 1, base + 2, base + 3]);
+            }
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+
+        let mut batch = StaticVec::<Packet, 1>::new();
+        assert_eq!(queue.pop_batch(&mut batch), 1);
+        assert_eq!(batch.as_slice()[0].words[0], 40);
+        drop(batch);
+        assert_eq!(drops.load(Ordering::Relaxed), 5);
+
+        queue.push(Packet::new(99, &drops)).ok().unwrap();
+        queue.close();
+        let after_close = queue.push(Packet::new(100, &drops)).err().unwrap();
+        assert_eq!(after_close.words[0], 100);
+        drop(after_close);
+
+        // Items 50 and 99 are still queued and go with the queue.
+        drop(queue);
+        assert_eq!(drops.load(Ordering::Relaxed), 8);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn two_consumers_on_two_slots_receive_each_item_once() {
+        // Three items through two slots makes the producer reuse slot 0
+        // while a consumer may still be reading it, which is the race the
+        // per-slot sequence numbers close. Consumers make a fixed number of
+        // attempts rather than spinning, so every execution is finite.
+        // Two preemptions keep the run to seconds and are enough to put the
+        // producer's write inside a consumer's read if the slot is handed
+        // back too early; set LOOM_MAX_PREEMPTIONS to search deeper.
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound.get_or_insert(2);
+        model.check(|| {
+            let queue = Arc::new(StaticQueue::<usize, 2>::new());
+            let consumers: Vec<_> = (0..2)
+                .map(|_| {
+                    let queue = Arc::clone(&queue);
+                    thread::spawn(move || (0..2).filter_map(|_| queue.pop()).collect::<Vec<_>>())
+                })
+                .collect();
+
+            let mut seen = Vec::new();
+            assert!(queue.push(0).is_ok());
+            assert!(queue.push(1).is_ok());
+            // Make room by popping here if the consumers have not, which
+            // is allowed since pop is safe from any number of threads.
+            while queue.push(2).is_err() {
+                match queue.pop() {
+                    Some(item) => seen.push(item),
+                    None => thread::yield_now(),
+                }
+            }
+            queue.close();
+
+            for consumer in consumers {
+                seen.extend(consumer.join().unwrap());
+            }
+            while let Ok(item) = queue.try_pop() {
+                seen.push(item);
+            }
+            assert_eq!(queue.try_pop(), Err(PopError::Closed));
+            seen.sort_unstable();
+            assert_eq!(seen, [0, 1, 2]);
+        });
+    }
+}
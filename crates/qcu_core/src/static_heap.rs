@@ -0,0 +1,224 @@
+//! Fixed-capacity priority queue built on StaticVec.
+//!
+//! Provides a binary max-heap for algorithms that repeatedly take the best
+//! candidate, such as growing clusters along the lightest frontier edge,
+//! without heap allocation.
+
+use crate::static_vec::StaticVec;
+use core::fmt;
+
+/// Binary max-heap with a compile-time fixed capacity.
+///
+/// Orders elements like `std::collections::BinaryHeap`: `pop` returns the
+/// greatest element. Wrap keys in `core::cmp::Reverse` for a min-heap, as
+/// shortest-path and lightest-edge searches need.
+///
+/// When the heap is full, `push` rejects the new element and hands it back,
+/// even if it outranks elements already queued. Callers that must not lose
+/// candidates should size N for the worst case or treat the rejection as an
+/// overflow, the same contract as `StaticVec::push`.
+///
+/// # Type Parameters
+///
+/// * `T` - Element type, ordered by `Ord`
+/// * `N` - Maximum capacity (compile-time constant)
+pub struct StaticBinaryHeap<T, const N: usize> {
+    /// Heap-ordered storage: each element is no less than its children at
+    /// `2i + 1` and `2i + 2`.
+    data: StaticVec<T, N>,
+}
+
+impl<T: Ord, const N: usize> Default for StaticBinaryHeap<T, N> {
+    /// Creates an empty heap, equivalent to `new()`.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, const N: usize> StaticBinaryHeap<T, N> {
+    /// Creates an empty heap.
+    pub const fn new() -> Self {
+        Self {
+            data: StaticVec::new(),
+        }
+    }
+
+    /// Inserts an element.
+    ///
+    /// Appends it at the bottom and sifts it up past smaller parents, in
+    /// O(log N) comparisons.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - Element to insert
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) if the item was inserted, Err(item) if the heap is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        self.data.push(item)?;
+        self.sift_up(self.data.len() - 1);
+        Ok(())
+    }
+
+    /// Removes and returns the greatest element.
+    ///
+    /// Moves the last element to the root and sifts it down, in O(log N)
+    /// comparisons.
+    ///
+    /// # Returns
+    ///
+    /// Some(element) if the heap was non-empty, None otherwise.
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.data.len().checked_sub(1)?;
+        self.data.swap(0, last);
+        let top = self.data.pop();
+        self.sift_down(0);
+        top
+    }
+
+    /// Returns the greatest element without removing it.
+    #[inline(always)]
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    /// Returns the number of queued elements.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if no elements are queued.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the maximum number of elements the heap can hold (N).
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Drops all queued elements.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns the queued elements in heap order, which is not sorted
+    /// beyond the greatest element coming first.
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[T] {
+        self.data.as_slice()
+    }
+
+    /// Moves the element at `pos` up until its parent is no smaller.
+    fn sift_up(&mut self, mut pos: usize) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.data[pos] <= self.data[parent] {
+                break;
+            }
+            self.data.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    /// Moves the element at `pos` down until no child is greater.
+    fn sift_down(&mut self, mut pos: usize) {
+        let len = self.data.len();
+        loop {
+            let left = 2 * pos + 1;
+            if left >= len {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < len && self.data[right] > self.data[left] {
+                right
+            } else {
+                left
+            };
+            if self.data[pos] >= self.data[child] {
+                break;
+            }
+            self.data.swap(pos, child);
+            pos = child;
+        }
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for StaticBinaryHeap<T, N> {
+    /// Clones the storage, preserving heap order.
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+        }
+    }
+}
+
+/// Formats the elements in heap order.
+impl<T: fmt::Debug, const N: usize> fmt::Debug for StaticBinaryHeap<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.data.as_slice(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+    use core::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    #[test]
+    fn random_operations_match_std_binary_heap() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let mut heap = StaticBinaryHeap::<u16, 16>::new();
+            let mut reference = BinaryHeap::new();
+            for _ in 0..64 {
+                let r = rng.next();
+                if r.is_multiple_of(3) {
+                    assert_eq!(heap.pop(), reference.pop());
+                } else {
+                    let item = (r >> 8) as u16 % 50;
+                    match heap.push(item) {
+                        Ok(()) => reference.push(item),
+                        Err(back) => {
+                            assert_eq!(back, item);
+                            assert_eq!(reference.len(), 16);
+                        }
+                    }
+                }
+                assert_eq!(heap.len(), reference.len());
+                assert_eq!(heap.peek(), reference.peek());
+            }
+        }
+    }
+
+    #[test]
+    fn reverse_keys_give_a_min_heap() {
+        let mut heap = StaticBinaryHeap::<Reverse<u32>, 8>::new();
+        for weight in [5, 1, 4, 1, 3] {
+            heap.push(Reverse(weight)).unwrap();
+        }
+        let order: alloc::vec::Vec<u32> =
+            core::iter::from_fn(|| heap.pop().map(|r| r.0)).collect();
+        assert_eq!(order, [1, 1, 3, 4, 5]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn full_heap_rejects_even_a_larger_item() {
+        let mut heap = StaticBinaryHeap::<u8, 2>::new();
+        heap.push(1).unwrap();
+        heap.push(2).unwrap();
+        assert_eq!(heap.push(9), Err(9));
+        assert_eq!(heap.peek(), Some(&2));
+        heap.clear();
+        assert!(heap.is_empty());
+        assert_eq!(heap.capacity(), 2);
+    }
+}
@@ -0,0 +1,194 @@
+//! Fixed-capacity string buffer for formatting without heap allocation.
+//!
+//! Lets firmware format a complete message, such as a status line, into a
+//! buffer before touching the console, so the console lock is held only for
+//! the final write rather than for the whole formatting pass.
+
+use crate::static_vec::StaticVec;
+use core::fmt;
+use core::ops::Deref;
+use core::str;
+
+/// UTF-8 string with a compile-time fixed capacity in bytes.
+///
+/// Implements `fmt::Write`, so `write!` formats straight into it. Output
+/// that does not fit is dropped silently, the way a fixed-width display
+/// would clip it: the buffer keeps the longest prefix that ends on a
+/// character boundary, raises a truncation flag, and ignores all later
+/// writes until cleared, so the contents are always a clean prefix of what
+/// was written.
+///
+/// # Type Parameters
+///
+/// * `N` - Capacity in bytes
+pub struct StaticString<const N: usize> {
+    /// Contents; always valid UTF-8.
+    bytes: StaticVec<u8, N>,
+
+    /// Set once a write did not fit.
+    truncated: bool,
+}
+
+impl<const N: usize> Default for StaticString<N> {
+    /// Creates an empty string, equivalent to `new()`.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> StaticString<N> {
+    /// Creates an empty string.
+    pub const fn new() -> Self {
+        Self {
+            bytes: StaticVec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Appends as much of `s` as fits.
+    ///
+    /// If `s` does not fit, the part up to the last character boundary that
+    /// does is appended and the string is marked truncated. Nothing is
+    /// appended to a string that is already truncated.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Text to append
+    ///
+    /// # Returns
+    ///
+    /// True if all of `s` was appended.
+    pub fn push_str(&mut self, s: &str) -> bool {
+        if self.truncated {
+            return s.is_empty();
+        }
+        let room = N - self.bytes.len();
+        let mut cut = s.len().min(room);
+        while !s.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        self.bytes.extend(&s.as_bytes()[..cut]);
+        if cut < s.len() {
+            self.truncated = true;
+        }
+        !self.truncated
+    }
+
+    /// Returns the contents as a string slice.
+    #[inline(always)]
+    pub fn as_str(&self) -> &str {
+        // Only whole characters taken from `&str` values are ever appended.
+        unsafe { str::from_utf8_unchecked(self.bytes.as_slice()) }
+    }
+
+    /// Returns the number of bytes stored.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns true if nothing is stored.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the capacity in bytes (N).
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns true if some written text was dropped for lack of space.
+    #[inline(always)]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Empties the string and clears the truncation flag.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.truncated = false;
+    }
+}
+
+impl<const N: usize> fmt::Write for StaticString<N> {
+    /// Appends formatted text, truncating as described on the type.
+    ///
+    /// Never returns an error, so a long message is clipped rather than
+    /// abandoned partway through `write!`.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl<const N: usize> Deref for StaticString<N> {
+    type Target = str;
+
+    /// Returns the contents as a string slice.
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Display for StaticString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for StaticString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn write_formats_into_the_buffer() {
+        let mut line = StaticString::<32>::new();
+        write!(line, "T={:>3}s | Q:{:>4}", 1, 3).unwrap();
+        assert_eq!(line.as_str(), "T=  1s | Q:   3");
+        assert!(!line.is_truncated());
+        assert_eq!(line.len(), 15);
+    }
+
+    #[test]
+    fn overflow_keeps_a_whole_character_prefix_and_stops() {
+        let mut s = StaticString::<6>::new();
+        assert!(s.push_str("ab"));
+        // "é" is two bytes: "abcdé" needs 6 bytes and fits, "ü" does not.
+        assert!(!s.push_str("cdéü"));
+        assert_eq!(s.as_str(), "abcdé");
+        assert!(s.is_truncated());
+
+        let mut s = StaticString::<4>::new();
+        assert!(!s.push_str("abc€"));
+        // The three-byte "€" would straddle the end, so it is cut whole.
+        assert_eq!(s.as_str(), "abc");
+
+        // Nothing lands after a truncation, even if it would fit.
+        assert!(!s.push_str("d"));
+        assert_eq!(s.as_str(), "abc");
+        assert!(s.push_str(""));
+    }
+
+    #[test]
+    fn write_never_errors_and_clear_resets_the_flag() {
+        let mut s = StaticString::<8>::new();
+        let status = "a long status line";
+        assert!(write!(s, "{status}").is_ok());
+        assert_eq!(&*s, "a long s");
+        s.clear();
+        assert!(s.is_empty());
+        assert!(!s.is_truncated());
+        assert!(s.push_str("ok"));
+        assert_eq!(alloc::format!("{s} {s:?}"), "ok \"ok\"");
+    }
+}
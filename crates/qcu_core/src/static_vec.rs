@@ -5,8 +5,11 @@
 //! the stack or in static memory, making this suitable for real-time firmware
 //! where heap allocation is unavailable or undesirable.
 
-use core::mem::MaybeUninit;
-use core::ops::{Deref, DerefMut};
+use crate::QecError;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Bound, Deref, DerefMut, RangeBounds};
+use core::ptr;
 use core::slice;
 
 /// Fixed-capacity vector allocated on the stack or in static memory.
@@ -17,6 +20,11 @@ use core::slice;
 /// can be used as a slice via Deref, making it compatible with standard
 /// Rust collection APIs.
 ///
+/// Elements are dropped when they are removed by `clear` or `truncate` and
+/// when the vector itself is dropped. For types without drop glue, such as
+/// the Copy indices used by the decoder, these paths compile to a length
+/// update only.
+///
 /// # Type Parameters
 ///
 /// * `T` - Element type
@@ -36,6 +44,15 @@ pub struct StaticVec<T, const N: usize> {
     len: usize,
 }
 
+/// Compile-time length check for `StaticVec::from_array`.
+struct ArrayFits<const M: usize, const N: usize>;
+
+impl<const M: usize, const N: usize> ArrayFits<M, N> {
+    /// Fails the build when an array of length M is turned into a
+    /// StaticVec of capacity N < M.
+    const CHECK: () = assert!(M <= N, "array is longer than the StaticVec capacity");
+}
+
 impl<T, const N: usize> Default for StaticVec<T, N> {
     /// Creates a vector with default (empty) state.
     ///
@@ -57,14 +74,279 @@ impl<T, const N: usize> StaticVec<T, N> {
         }
     }
 
+    /// Creates a vector holding `len` elements produced by `f`.
+    ///
+    /// Element `i` is `f(i)`, called in index order.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - Number of elements
+    /// * `f` - Produces the element for each index
+    ///
+    /// # Returns
+    ///
+    /// The filled vector, or QecError::BufferOverflow if `len` exceeds N,
+    /// in which case `f` is not called.
+    pub fn from_fn(len: usize, mut f: impl FnMut(usize) -> T) -> Result<Self, QecError> {
+        if len > N {
+            return Err(QecError::BufferOverflow);
+        }
+        let mut out = Self::new();
+        for i in 0..len {
+            // `i < len <= N`. Bumping `len` per element means a panic in
+            // `f` drops exactly the elements written so far.
+            unsafe { out.data.get_unchecked_mut(i).write(f(i)) };
+            out.len = i + 1;
+        }
+        Ok(out)
+    }
+
+    /// Creates a vector from an array, checking at compile time that it
+    /// fits.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Elements, in order; M must not exceed N
+    ///
+    /// # Examples
+    ///
+    /// An array longer than the capacity does not build:
+    ///
+    /// ```compile_fail
+    /// use qcu_core::static_vec::StaticVec;
+    ///
+    /// let vec = StaticVec::<u8, 2>::from_array([1, 2, 3]);
+    /// # drop(vec);
+    /// ```
+    pub fn from_array<const M: usize>(items: [T; M]) -> Self {
+        let () = ArrayFits::<M, N>::CHECK;
+        let mut out = Self::new();
+        for item in items {
+            // M <= N, so every push fits.
+            let _ = out.push(item);
+        }
+        out
+    }
+
     /// Clears the vector by resetting the length to zero.
     ///
-    /// Does not drop or deallocate elements; they remain in memory but are
-    /// marked as uninitialized. This is a constant-time operation that prepares
-    /// the vector for reuse without reallocation.
+    /// Drops the elements in place and marks their slots uninitialized,
+    /// leaving the storage ready for reuse. For types that need no drop this
+    /// is a constant-time operation.
     #[inline(always)]
     pub fn clear(&mut self) {
-        self.len = 0;
+        self.truncate(0);
+    }
+
+    /// Shortens the vector to `new_len` elements, dropping the rest.
+    ///
+    /// Has no effect if `new_len` is not less than the current length. The
+    /// length is updated before the removed elements are dropped, so a
+    /// panicking destructor cannot cause a double drop.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_len` - Number of leading elements to keep
+    #[inline(always)]
+    pub fn truncate(&mut self, new_len: usize) {
+        let old_len = self.len;
+        if new_len >= old_len {
+            return;
+        }
+        self.len = new_len;
+        if mem::needs_drop::<T>() {
+            // Slots `new_len..old_len` were initialised and are no longer
+            // covered by `len`, so nothing else will read or drop them.
+            unsafe {
+                let tail = self.data.as_mut_ptr().add(new_len) as *mut T;
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(tail, old_len - new_len));
+            }
+        }
+    }
+
+    /// Resizes the vector to `new_len`, filling new slots with `f()`.
+    ///
+    /// Shrinking drops the removed elements, as `truncate` does. Growing
+    /// calls `f` once per new slot, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_len` - Length after the call
+    /// * `f` - Produces each added element
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or QecError::BufferOverflow if `new_len` exceeds
+    /// N, in which case the vector is unchanged.
+    pub fn resize_with(
+        &mut self,
+        new_len: usize,
+        mut f: impl FnMut() -> T,
+    ) -> Result<(), QecError> {
+        if new_len > N {
+            return Err(QecError::BufferOverflow);
+        }
+        self.truncate(new_len);
+        while self.len < new_len {
+            // `len < new_len <= N`, so the slot exists and is uninitialised.
+            unsafe { self.data.get_unchecked_mut(self.len).write(f()) };
+            self.len += 1;
+        }
+        Ok(())
+    }
+
+    /// Resizes the vector to `new_len`, filling new slots with clones of
+    /// `value`.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_len` - Length after the call
+    /// * `value` - Value to clone into each added slot
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or QecError::BufferOverflow if `new_len` exceeds
+    /// N, in which case the vector is unchanged.
+    pub fn resize(&mut self, new_len: usize, value: T) -> Result<(), QecError>
+    where
+        T: Clone,
+    {
+        self.resize_with(new_len, || value.clone())
+    }
+
+    /// Overwrites every current element with a clone of `value`.
+    ///
+    /// The length is unchanged; combine with `clear` and `resize` to reset
+    /// a buffer to a given length.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value to store
+    #[inline(always)]
+    pub fn fill(&mut self, value: T)
+    where
+        T: Clone,
+    {
+        self.as_mut_slice().fill(value);
+    }
+
+    /// Keeps only the elements for which `f` returns true.
+    ///
+    /// Compacts the kept elements to the front in their original order and
+    /// drops the rest, without a second buffer. If `f` panics, the elements
+    /// are leaked rather than risk dropping one twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Predicate called once per element, in order
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let len = mem::replace(&mut self.len, 0);
+        let base = self.data.as_mut_ptr() as *mut T;
+        let mut kept = 0;
+        for i in 0..len {
+            // Slot `i` is initialised and not yet moved or dropped; slots
+            // below `kept` hold the compacted elements, and `kept <= i`.
+            unsafe {
+                let current = base.add(i);
+                if f(&*current) {
+                    if kept != i {
+                        ptr::copy_nonoverlapping(current, base.add(kept), 1);
+                    }
+                    kept += 1;
+                } else {
+                    ptr::drop_in_place(current);
+                }
+            }
+        }
+        self.len = kept;
+    }
+
+    /// Removes consecutive repeated elements, keeping the first of each run.
+    ///
+    /// On sorted contents this leaves every value exactly once; call
+    /// `sort_unstable` first if the order does not matter. Removed elements
+    /// are dropped. As with `retain`, a panicking comparison leaks the
+    /// elements.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        let len = mem::replace(&mut self.len, 0);
+        if len <= 1 {
+            self.len = len;
+            return;
+        }
+        let base = self.data.as_mut_ptr() as *mut T;
+        let mut kept = 1;
+        for i in 1..len {
+            // Same invariant as in `retain`, with `kept >= 1`.
+            unsafe {
+                let current = base.add(i);
+                if *current == *base.add(kept - 1) {
+                    ptr::drop_in_place(current);
+                } else {
+                    if kept != i {
+                        ptr::copy_nonoverlapping(current, base.add(kept), 1);
+                    }
+                    kept += 1;
+                }
+            }
+        }
+        self.len = kept;
+    }
+
+    /// Sorts the elements in place without allocating.
+    ///
+    /// Same as calling `sort_unstable` on the slice through `DerefMut`,
+    /// listed here so it shows up next to `dedup`.
+    #[inline(always)]
+    pub fn sort_unstable(&mut self)
+    where
+        T: Ord,
+    {
+        self.as_mut_slice().sort_unstable();
+    }
+
+    /// Removes a range of elements, yielding them through an iterator.
+    ///
+    /// The elements after the range move down to close the gap when the
+    /// iterator is dropped, whether or not it was run to the end; elements
+    /// in the range that were not yielded are dropped at that point.
+    ///
+    /// The vector is cut back to the start of the range while the iterator
+    /// is alive. If the iterator is leaked (for example with
+    /// `mem::forget`), the vector keeps only the elements before the range
+    /// and the rest are leaked, never dropped twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Indices to remove
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends or ends past `len()`.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, N> {
+        let len = self.len;
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i + 1,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => len,
+        };
+        assert!(start <= end, "drain range starts after it ends");
+        assert!(end <= len, "drain range ends past the StaticVec length");
+        self.len = start;
+        Drain {
+            vec: self,
+            index: start,
+            end,
+            tail_start: end,
+            tail_len: len - end,
+        }
     }
 
     /// Appends an element to the end of the vector.
@@ -151,6 +433,39 @@ impl<T, const N: usize> StaticVec<T, N> {
         N
     }
 
+    /// Returns the unused slots past the current length.
+    ///
+    /// Lets a producer such as a bit scan or a device readout write
+    /// elements in place; follow up with `set_len` to make them part of the
+    /// vector. Writing here alone does not change the length.
+    ///
+    /// # Returns
+    ///
+    /// The `N - len` uninitialised slots, in order.
+    #[inline(always)]
+    pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        &mut self.data[self.len..]
+    }
+
+    /// Sets the length without initialising or dropping anything.
+    ///
+    /// # Safety
+    ///
+    /// * `new_len` must not exceed N.
+    /// * If growing, the slots `len..new_len` must have been initialised,
+    ///   for example through `spare_capacity_mut`.
+    /// * If shrinking, the elements `new_len..len` are forgotten rather than
+    ///   dropped; use `truncate` unless that is intended.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_len` - Number of leading slots that hold valid elements
+    #[inline(always)]
+    pub unsafe fn set_len(&mut self, new_len: usize) {
+        debug_assert!(new_len <= N, "set_len past StaticVec capacity");
+        self.len = new_len;
+    }
+
     /// Returns a slice view of the initialized elements.
     ///
     /// Creates a slice covering elements [0..len], which are guaranteed to be
@@ -178,6 +493,94 @@ impl<T, const N: usize> StaticVec<T, N> {
     pub fn as_mut_slice(&mut self) -> &mut [T] {
         unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut T, self.len) }
     }
+
+    /// Returns the elements as an array reference if the vector is full.
+    ///
+    /// # Returns
+    ///
+    /// Some(array) if `len == N`, None otherwise.
+    #[inline(always)]
+    pub fn try_as_array(&self) -> Option<&[T; N]> {
+        self.as_slice().try_into().ok()
+    }
+
+    /// Converts a full vector into an array without copying element by
+    /// element.
+    ///
+    /// # Returns
+    ///
+    /// Ok(array) if `len == N`, or Err(self) unchanged if the vector is only
+    /// partly filled.
+    pub fn into_array(self) -> Result<[T; N], Self> {
+        if self.len != N {
+            return Err(self);
+        }
+        let this = mem::ManuallyDrop::new(self);
+        // All N slots are initialised, `MaybeUninit<T>` has the layout of
+        // `T`, and ManuallyDrop stops the vector dropping the moved-out
+        // elements.
+        Ok(unsafe { ptr::read(this.data.as_ptr() as *const [T; N]) })
+    }
+}
+
+impl<T, const N: usize> Drop for StaticVec<T, N> {
+    /// Drops the initialized elements.
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: Copy, const N: usize> StaticVec<T, N> {
+    /// Creates a full vector with every slot set to `value`.
+    ///
+    /// Usable in `static` and `const` initialisers.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value to store in all N slots
+    pub const fn new_filled(value: T) -> Self {
+        Self {
+            data: [MaybeUninit::new(value); N],
+            len: N,
+        }
+    }
+
+    /// Creates a vector holding a copy of a slice.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Elements to copy, in order
+    ///
+    /// # Returns
+    ///
+    /// The filled vector, or QecError::BufferOverflow if the slice is
+    /// longer than N.
+    pub fn from_slice(items: &[T]) -> Result<Self, QecError> {
+        if items.len() > N {
+            return Err(QecError::BufferOverflow);
+        }
+        let mut out = Self::new();
+        // The slice fits, and Copy elements can be duplicated bitwise.
+        unsafe {
+            ptr::copy_nonoverlapping(items.as_ptr(), out.data.as_mut_ptr() as *mut T, items.len());
+        }
+        out.len = items.len();
+        Ok(out)
+    }
+
+    /// Creates a vector holding `len` copies of `elem`.
+    ///
+    /// # Arguments
+    ///
+    /// * `elem` - Value to repeat
+    /// * `len` - Number of copies
+    ///
+    /// # Returns
+    ///
+    /// The filled vector, or QecError::BufferOverflow if `len` exceeds N.
+    pub fn from_elem(elem: T, len: usize) -> Result<Self, QecError> {
+        Self::from_fn(len, |_| elem)
+    }
 }
 
 /// Enables indexing and slice operations via Deref.
@@ -245,10 +648,294 @@ impl<T, const N: usize> IntoIterator for StaticVec<T, N> {
     ///
     /// The iterator will yield elements in order from index 0 to len-1.
     /// After iteration completes, the vector is consumed and cannot be used.
-    fn into_iter(self) -> Self::IntoIter {
+    /// Ownership of the elements passes to the iterator, which drops any
+    /// that are not yielded.
+    fn into_iter(mut self) -> Self::IntoIter {
+        let end = mem::replace(&mut self.len, 0);
         StaticVecIntoIter {
             vec: self,
             index: 0,
+            end,
+        }
+    }
+}
+
+/// Enables borrowed iteration, as in `for x in &vec`.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u32, 4>::new();
+/// vec.extend([1, 2, 3]);
+/// let mut sum = 0;
+/// for x in &vec {
+///     sum += x;
+/// }
+/// assert_eq!(sum, 6);
+/// assert_eq!(vec.len(), 3);
+/// ```
+impl<'a, T, const N: usize> IntoIterator for &'a StaticVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    /// Returns an iterator over references to the elements, in order.
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+/// Enables mutable borrowed iteration, as in `for x in &mut vec`.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u32, 4>::new();
+/// vec.extend([1, 2, 3]);
+/// for x in &mut vec {
+///     *x *= 10;
+/// }
+/// assert_eq!(vec, [10, 20, 30][..]);
+/// ```
+impl<'a, T, const N: usize> IntoIterator for &'a mut StaticVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    /// Returns an iterator over mutable references to the elements, in order.
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_mut_slice().iter_mut()
+    }
+}
+
+/// Clones the elements, leaving the original untouched.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<String, 2>::new();
+/// vec.push("x".to_string()).unwrap();
+/// let mut copy = vec.clone();
+/// copy[0].push('y');
+/// assert_eq!(vec[0], "x");
+/// assert_eq!(copy[0], "xy");
+/// ```
+impl<T: Clone, const N: usize> Clone for StaticVec<T, N> {
+    /// Clones each element into a new vector of the same capacity.
+    fn clone(&self) -> Self {
+        let mut out = Self::new();
+        out.extend(self.iter().cloned());
+        out
+    }
+}
+
+/// Compares element-wise; the capacities may differ.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut small = StaticVec::<u32, 2>::new();
+/// let mut large = StaticVec::<u32, 8>::new();
+/// small.extend([4, 5]);
+/// large.extend([4, 5]);
+/// assert_eq!(small, large);
+/// large.push(6).unwrap();
+/// assert_ne!(small, large);
+/// ```
+impl<T: PartialEq<U>, U, const N: usize, const M: usize> PartialEq<StaticVec<U, M>>
+    for StaticVec<T, N>
+{
+    fn eq(&self, other: &StaticVec<U, M>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for StaticVec<T, N> {}
+
+/// Compares the elements with a slice.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u8, 4>::new();
+/// vec.extend([1, 2]);
+/// assert_eq!(vec, [1, 2][..]);
+/// assert_ne!(vec, [1][..]);
+/// ```
+impl<T: PartialEq<U>, U, const N: usize> PartialEq<[U]> for StaticVec<T, N> {
+    fn eq(&self, other: &[U]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+/// Compares the elements with a slice reference.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u8, 4>::new();
+/// vec.extend([1, 2]);
+/// let expected: &[u8] = &[1, 2];
+/// assert_eq!(vec, expected);
+/// ```
+impl<T: PartialEq<U>, U, const N: usize> PartialEq<&[U]> for StaticVec<T, N> {
+    fn eq(&self, other: &&[U]) -> bool {
+        self.as_slice() == *other
+    }
+}
+
+/// Formats the elements like a slice.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u8, 4>::new();
+/// vec.extend([1, 2]);
+/// assert_eq!(format!("{vec:?}"), "[1, 2]");
+/// ```
+impl<T: fmt::Debug, const N: usize> fmt::Debug for StaticVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+/// Appends items until the vector is full.
+///
+/// `Extend` cannot report failure, so items that do not fit are truncated:
+/// once the vector reaches capacity, iteration stops and the rest of the
+/// iterator is left unconsumed. Compare `len()` before and after, or use
+/// `push`, when overflow must be detected.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u32, 3>::new();
+/// let mut rest = 0..10;
+/// vec.extend(&mut rest);
+/// assert_eq!(vec, [0, 1, 2][..]);
+/// // Nothing past the capacity was pulled from the iterator.
+/// assert_eq!(rest.next(), Some(3));
+/// ```
+impl<T, const N: usize> Extend<T> for StaticVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter.into_iter().take(N - self.len) {
+            // `take` bounds the count by the free space, so this fits.
+            let _ = self.push(item);
+        }
+    }
+}
+
+/// Appends copies of borrowed items until the vector is full, with the same
+/// truncation as `Extend<T>`.
+///
+/// # Examples
+///
+/// ```
+/// use qcu_core::static_vec::StaticVec;
+///
+/// let mut vec = StaticVec::<u32, 3>::new();
+/// vec.extend(&[7, 8, 9, 10]);
+/// assert_eq!(vec, [7, 8, 9][..]);
+/// ```
+impl<'a, T: Copy + 'a, const N: usize> Extend<&'a T> for StaticVec<T, N> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+/// Iterator returned by `StaticVec::drain`.
+///
+/// Yields the removed elements by value from either end. Dropping it drops
+/// the elements not yet yielded and moves the tail of the vector down to
+/// close the gap.
+pub struct Drain<'a, T, const N: usize> {
+    /// The vector being drained, with its length cut back to the start of
+    /// the range.
+    vec: &'a mut StaticVec<T, N>,
+
+    /// Next slot to yield from the front.
+    index: usize,
+
+    /// One past the next slot to yield from the back.
+    end: usize,
+
+    /// First slot after the drained range.
+    tail_start: usize,
+
+    /// Number of elements after the drained range.
+    tail_len: usize,
+}
+
+impl<T, const N: usize> Iterator for Drain<'_, T, N> {
+    type Item = T;
+
+    /// Moves the next element of the range out of the vector.
+    fn next(&mut self) -> Option<T> {
+        if self.index < self.end {
+            // Slots `index..end` are initialised and owned by the iterator.
+            let item = unsafe { self.vec.data.get_unchecked(self.index).assume_init_read() };
+            self.index += 1;
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Drain<'_, T, N> {
+    /// Moves the last remaining element of the range out of the vector.
+    fn next_back(&mut self) -> Option<T> {
+        if self.index < self.end {
+            self.end -= 1;
+            // Same ownership as in `next`.
+            Some(unsafe { self.vec.data.get_unchecked(self.end).assume_init_read() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Drain<'_, T, N> {}
+
+impl<T, const N: usize> Drop for Drain<'_, T, N> {
+    /// Drops the elements not yielded and closes the gap.
+    ///
+    /// If one of those destructors panics, the tail is leaked and the vector
+    /// keeps only the elements before the range, as after `mem::forget`.
+    fn drop(&mut self) {
+        let base = self.vec.data.as_mut_ptr() as *mut T;
+        // Slots `index..end` are still initialised and owned here; the tail
+        // slots are initialised and not covered by the vector's length, and
+        // `ptr::copy` handles the overlap when the range is short.
+        unsafe {
+            if mem::needs_drop::<T>() {
+                let rest = base.add(self.index);
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(rest, self.end - self.index));
+            }
+            let start = self.vec.len;
+            if self.tail_len > 0 && self.tail_start != start {
+                ptr::copy(base.add(self.tail_start), base.add(start), self.tail_len);
+            }
+            self.vec.len = start + self.tail_len;
         }
     }
 }
@@ -259,13 +946,19 @@ impl<T, const N: usize> IntoIterator for StaticVec<T, N> {
 /// in the process. Elements are yielded in order from first to last.
 pub struct StaticVecIntoIter<T, const N: usize> {
     /// The vector being iterated over.
+    ///
+    /// Its length is zero, so it never drops the elements itself; the
+    /// iterator owns `data[index..end]`.
     vec: StaticVec<T, N>,
 
     /// Current iteration index.
     ///
-    /// Points to the next element to yield. When index >= vec.len, iteration
+    /// Points to the next element to yield. When index >= end, iteration
     /// is complete.
     index: usize,
+
+    /// Number of elements the vector held when iteration started.
+    end: usize,
 }
 
 impl<T, const N: usize> Iterator for StaticVecIntoIter<T, N> {
@@ -280,7 +973,7 @@ impl<T, const N: usize> Iterator for StaticVecIntoIter<T, N> {
     /// Moves the element out of the vector and advances the index. Returns
     /// None when all elements have been consumed.
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.vec.len {
+        if self.index < self.end {
             let item = unsafe { self.vec.data.get_unchecked(self.index).assume_init_read() };
             self.index += 1;
             Some(item)
@@ -289,3 +982,298 @@ impl<T, const N: usize> Iterator for StaticVecIntoIter<T, N> {
         }
     }
 }
+
+impl<T, const N: usize> Drop for StaticVecIntoIter<T, N> {
+    /// Drops the elements that were not yielded.
+    fn drop(&mut self) {
+        if mem::needs_drop::<T>() {
+            // Slots `index..end` are still initialised and owned by the
+            // iterator alone.
+            unsafe {
+                let rest = self.vec.data.as_mut_ptr().add(self.index) as *mut T;
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(rest, self.end - self.index));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Counted, drop_counter, drops};
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use core::sync::atomic::AtomicUsize;
+
+    fn filled(counter: &Arc<AtomicUsize>) -> StaticVec<Counted, 8> {
+        StaticVec::from_fn(8, |id| Counted::new(id, counter)).unwrap()
+    }
+
+    #[test]
+    fn removal_paths_drop_each_element_once() {
+        let counter = drop_counter();
+        let mut vec = filled(&counter);
+
+        vec.truncate(6);
+        assert_eq!(drops(&counter), 2);
+        drop(vec.pop());
+        assert_eq!(drops(&counter), 3);
+        vec.retain(|item| item.id % 2 == 0);
+        assert_eq!(drops(&counter), 5);
+        assert_eq!(
+            vec.iter().map(|item| item.id).collect::<Vec<_>>(),
+            [0, 2, 4]
+        );
+        vec.clear();
+        assert_eq!(drops(&counter), 8);
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn drain_and_into_iter_drop_unconsumed_elements() {
+        let counter = drop_counter();
+        let mut vec = filled(&counter);
+
+        let mut drain = vec.drain(2..6);
+        assert_eq!(drain.next().map(|item| item.id), Some(2));
+        drop(drain);
+        assert_eq!(drops(&counter), 4);
+        assert_eq!(
+            vec.iter().map(|item| item.id).collect::<Vec<_>>(),
+            [0, 1, 6, 7]
+        );
+
+        let mut iter = vec.into_iter();
+        assert_eq!(iter.next().map(|item| item.id), Some(0));
+        drop(iter);
+        assert_eq!(drops(&counter), 8);
+
+        let vec = filled(&counter);
+        drop(vec);
+        assert_eq!(drops(&counter), 16);
+    }
+
+    #[test]
+    fn retain_every_other_keeps_order_and_drops_the_rest() {
+        let counter = drop_counter();
+        let mut vec = filled(&counter);
+
+        let mut keep = false;
+        vec.retain(|_| {
+            keep = !keep;
+            keep
+        });
+        assert_eq!(
+            vec.iter().map(|item| item.id).collect::<Vec<_>>(),
+            [0, 2, 4, 6]
+        );
+        assert_eq!(drops(&counter), 4);
+
+        vec.retain(|_| true);
+        assert_eq!(vec.len(), 4);
+        vec.retain(|_| false);
+        assert!(vec.is_empty());
+        assert_eq!(drops(&counter), 8);
+    }
+
+    #[test]
+    fn dedup_collapses_sorted_runs_to_one_each() {
+        let counter = drop_counter();
+        let ids = [3, 1, 3, 3, 0, 1, 7, 3];
+        let mut vec: StaticVec<Counted, 8> =
+            StaticVec::from_fn(ids.len(), |i| Counted::new(ids[i], &counter)).unwrap();
+
+        // Unsorted, only adjacent repeats go.
+        vec.dedup();
+        assert_eq!(
+            vec.iter().map(|item| item.id).collect::<Vec<_>>(),
+            [3, 1, 3, 0, 1, 7, 3]
+        );
+        assert_eq!(drops(&counter), 1);
+
+        vec.as_mut_slice().sort_unstable_by_key(|item| item.id);
+        vec.dedup();
+        assert_eq!(
+            vec.iter().map(|item| item.id).collect::<Vec<_>>(),
+            [0, 1, 3, 7]
+        );
+        assert_eq!(drops(&counter), 4);
+        drop(vec);
+        assert_eq!(drops(&counter), 8);
+    }
+
+    #[test]
+    fn dedup_handles_short_and_uniform_vectors() {
+        let mut empty = StaticVec::<u32, 4>::new();
+        empty.dedup();
+        assert!(empty.is_empty());
+
+        let mut single = StaticVec::<u32, 4>::from_array([5]);
+        single.dedup();
+        assert_eq!(single, [5][..]);
+
+        let mut same = StaticVec::<u32, 4>::from_array([2, 2, 2, 2]);
+        same.dedup();
+        assert_eq!(same, [2][..]);
+
+        let mut runs = StaticVec::<u32, 8>::from_array([9, 4, 4, 1, 9, 1, 4, 9]);
+        runs.sort_unstable();
+        runs.dedup();
+        assert_eq!(runs, [1, 4, 9][..]);
+    }
+
+    #[test]
+    fn constructors_reject_sources_longer_than_the_capacity() {
+        let fits = StaticVec::<u16, 4>::from_slice(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(fits, [1, 2, 3, 4][..]);
+        assert!(matches!(
+            StaticVec::<u16, 4>::from_slice(&[1, 2, 3, 4, 5]),
+            Err(QecError::BufferOverflow)
+        ));
+        assert!(matches!(
+            StaticVec::<u16, 4>::from_elem(9, 5),
+            Err(QecError::BufferOverflow)
+        ));
+
+        // The length is checked before `f` runs, so nothing is built and
+        // then thrown away.
+        let mut calls = 0;
+        let too_long = StaticVec::<u16, 4>::from_fn(5, |i| {
+            calls += 1;
+            i as u16
+        });
+        assert!(matches!(too_long, Err(QecError::BufferOverflow)));
+        assert_eq!(calls, 0);
+        assert_eq!(StaticVec::<u16, 4>::from_fn(0, |_| unreachable!()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn from_fn_drops_the_built_prefix_when_f_panics() {
+        let counter = drop_counter();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            StaticVec::<Counted, 8>::from_fn(6, |id| {
+                assert!(id < 3, "boom");
+                Counted::new(id, &counter)
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops(&counter), 3);
+    }
+
+    #[test]
+    fn spare_capacity_then_set_len_appends_in_place() {
+        let mut vec = StaticVec::<u32, 8>::new();
+        vec.push(1).unwrap();
+        vec.push(2).unwrap();
+
+        let spare = vec.spare_capacity_mut();
+        assert_eq!(spare.len(), 6);
+        // Writing without `set_len` changes nothing visible.
+        spare[0].write(99);
+        assert_eq!(vec, [1, 2][..]);
+
+        // Scan-style producer: write a prefix of the spare slots, then
+        // publish exactly that many.
+        let set_bits = [3u32, 5, 6];
+        let spare = vec.spare_capacity_mut();
+        for (slot, &bit) in spare.iter_mut().zip(&set_bits) {
+            slot.write(bit);
+        }
+        // The first three spare slots were written just above.
+        unsafe { vec.set_len(vec.len() + set_bits.len()) };
+        assert_eq!(vec, [1, 2, 3, 5, 6][..]);
+        assert_eq!(vec.spare_capacity_mut().len(), 3);
+
+        vec.extend([7, 8, 9]);
+        assert!(vec.spare_capacity_mut().is_empty());
+    }
+
+    #[test]
+    fn set_len_hands_ownership_in_and_out_without_double_drops() {
+        let counter = drop_counter();
+        let mut vec = StaticVec::<Counted, 4>::new();
+        for (id, slot) in vec.spare_capacity_mut().iter_mut().take(3).enumerate() {
+            slot.write(Counted::new(id, &counter));
+        }
+        // Slots 0..3 were initialised by the loop.
+        unsafe { vec.set_len(3) };
+        assert_eq!(vec.iter().map(|item| item.id).collect::<Vec<_>>(), [0, 1, 2]);
+
+        // Take the last element out by hand, then shrink past it so the
+        // vector no longer owns it.
+        let last = unsafe { ptr::read(vec.as_ptr().add(2)) };
+        unsafe { vec.set_len(2) };
+        assert_eq!(last.id, 2);
+        drop(last);
+        assert_eq!(drops(&counter), 1);
+
+        drop(vec);
+        assert_eq!(drops(&counter), 3);
+    }
+
+    #[test]
+    fn full_vectors_convert_to_arrays_and_partial_ones_come_back() {
+        const FILLED: StaticVec<u8, 3> = StaticVec::new_filled(7);
+        assert_eq!(FILLED.try_as_array(), Some(&[7, 7, 7]));
+        assert_eq!(FILLED.into_array().ok(), Some([7, 7, 7]));
+
+        let counter = drop_counter();
+        let vec = StaticVec::<Counted, 4>::from_fn(3, |id| Counted::new(id, &counter)).unwrap();
+        assert!(vec.try_as_array().is_none());
+        // A partial vector is handed back whole, with nothing dropped.
+        let mut vec = vec.into_array().err().unwrap();
+        assert_eq!(vec.len(), 3);
+        assert_eq!(drops(&counter), 0);
+
+        vec.push(Counted::new(3, &counter)).ok().unwrap();
+        let ids = vec.try_as_array().unwrap().each_ref().map(|item| item.id);
+        assert_eq!(ids, [0, 1, 2, 3]);
+        let array = vec.into_array().ok().unwrap();
+        // Ownership moved to the array: converting dropped nothing.
+        assert_eq!(drops(&counter), 0);
+        drop(array);
+        assert_eq!(drops(&counter), 4);
+    }
+
+    #[test]
+    fn drain_yields_from_both_ends_and_closes_the_gap() {
+        let mut vec = StaticVec::<u32, 8>::from_array([0, 1, 2, 3, 4, 5, 6, 7]);
+        let mut drain = vec.drain(1..=5);
+        assert_eq!(drain.len(), 5);
+        assert_eq!(drain.next(), Some(1));
+        assert_eq!(drain.next_back(), Some(5));
+        assert_eq!(drain.next(), Some(2));
+        assert_eq!(drain.len(), 2);
+        drop(drain);
+        assert_eq!(vec, [0, 6, 7][..]);
+
+        assert_eq!(vec.drain(..).collect::<Vec<_>>(), [0, 6, 7]);
+        assert!(vec.is_empty());
+
+        let mut vec = StaticVec::<u32, 4>::from_array([1, 2, 3]);
+        assert_eq!(vec.drain(3..).count(), 0);
+        assert_eq!(vec.drain(1..1).count(), 0);
+        assert_eq!(vec, [1, 2, 3][..]);
+    }
+
+    #[test]
+    fn forgotten_drain_leaks_the_rest_instead_of_double_dropping() {
+        let counter = drop_counter();
+        let mut vec = filled(&counter);
+        let mut drain = vec.drain(2..5);
+        assert_eq!(drain.next().map(|item| item.id), Some(2));
+        core::mem::forget(drain);
+        // Only the head before the range survives; 3..8 are leaked.
+        assert_eq!(vec.iter().map(|item| item.id).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(drops(&counter), 1);
+        drop(vec);
+        assert_eq!(drops(&counter), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn drain_past_the_end_panics() {
+        let mut vec = StaticVec::<u32, 4>::from_array([1, 2]);
+        vec.drain(1..3);
+    }
+}
@@ -0,0 +1,66 @@
+//! Atomic and cell types used by the lock-free queues.
+//!
+//! Normally these are the `core` types. Building with `--cfg loom` swaps
+//! in loom's instrumented versions, so the loom tests in `spmc` can explore
+//! the interleavings of the queue operations and flag a slot that is
+//! written while another thread is still reading it:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release -p qcu_core --lib loom
+//! ```
+//!
+//! Loom atomics cannot be created in a const context, so constructors that
+//! build queues in `static` initialisers are declared with `const_fn!` and
+//! lose their `const` under loom.
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicUsize};
+
+#[cfg(loom)]
+pub(crate) use loom::cell::UnsafeCell;
+
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicUsize};
+
+/// `core::cell::UnsafeCell` behind loom's closure-based access API.
+///
+/// Loom can only track accesses made inside `with` and `with_mut`, so the
+/// queues go through these even when loom is not in use; they compile
+/// down to a plain pointer access.
+#[cfg(not(loom))]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    /// Wraps a value.
+    pub(crate) const fn new(data: T) -> Self {
+        Self(core::cell::UnsafeCell::new(data))
+    }
+
+    /// Calls `f` with a shared pointer to the contents.
+    #[inline(always)]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    /// Calls `f` with a mutable pointer to the contents.
+    #[inline(always)]
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// Declares a function that is `const` except under `--cfg loom`.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])*
+        $vis fn $($rest)*
+    };
+}
+
+pub(crate) use const_fn;
@@ -0,0 +1,72 @@
+//! Helpers shared by the unit tests of the queue, vector and bit-array
+//! modules.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Payload that counts its drops, for checking that containers neither leak
+/// nor double-drop the items they hold.
+///
+/// Carries an id so tests can also check order, and a heap allocation so a
+/// double drop is caught by the allocator as well as the counter.
+#[derive(Debug)]
+pub(crate) struct Counted {
+    pub(crate) id: usize,
+    drops: Arc<AtomicUsize>,
+    _payload: alloc::boxed::Box<usize>,
+}
+
+impl Counted {
+    /// Creates a payload that bumps `drops` when dropped.
+    pub(crate) fn new(id: usize, drops: &Arc<AtomicUsize>) -> Self {
+        Self {
+            id,
+            drops: Arc::clone(drops),
+            _payload: alloc::boxed::Box::new(id),
+        }
+    }
+}
+
+/// Compares ids only, so tests of `dedup` can count the drops it causes.
+impl PartialEq for Counted {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns a fresh drop counter.
+pub(crate) fn drop_counter() -> Arc<AtomicUsize> {
+    Arc::new(AtomicUsize::new(0))
+}
+
+/// Reads a drop counter.
+pub(crate) fn drops(counter: &Arc<AtomicUsize>) -> usize {
+    counter.load(Ordering::Relaxed)
+}
+
+/// Xorshift generator, so randomised tests are repeatable.
+///
+/// The seed must be non-zero. Modules add their own sampling methods in an
+/// `impl Rng` block inside their tests.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    /// Returns the next 64-bit value.
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a value in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        self.next() as usize % n
+    }
+}
@@ -8,11 +8,332 @@
 
 use crate::QecError;
 use crate::allocator::BumpAllocator;
-use crate::decoder::UnionFindDecoder;
-use crate::graph::DecodingGraph;
-use crate::pauli_frame::PauliFrame;
+use crate::decoder::{CorrectionBuffer, UnionFindDecoder};
+use crate::graph::{DecodingGraph, EdgeList};
+use crate::pauli_frame::{MeasBasis, PauliFrame, PauliKind};
+use crate::program::Program;
+use crate::static_vec::StaticVec;
 use alloc::vec::Vec;
-use qcu_common::isa::{Instruction, Opcode};
+use core::alloc::Allocator;
+use qcu_common::isa::{
+    INSTRUCTION_SIZE, Instruction, Instruction32, InstructionStream, IsaError, Opcode,
+};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// Mapping from a (u, v) correction edge to the data qubit and Pauli it flips.
+///
+/// Returns None for edges that do not correspond to a physical qubit.
+pub type CorrectionMap = fn(usize, usize) -> Option<(usize, PauliKind)>;
+
+/// Mapping from a (u, v) correction edge to the logical observables it flips.
+///
+/// Returns a bitmask with bit k set if applying the correction flips
+/// observable k, i.e. the `L` targets of the corresponding DEM error.
+pub type ObservableMap = fn(usize, usize) -> u64;
+
+/// Maximum nesting depth of LoopBegin/LoopEnd pairs.
+///
+/// The loop stack is a fixed-size array so that program execution needs no
+/// heap in firmware.
+pub const MAX_LOOP_DEPTH: usize = 4;
+
+/// Number of logical observables tracked by the VM's observable register.
+pub const MAX_OBSERVABLES: usize = u64::BITS as usize;
+
+/// Output buffer for syndrome bits collected by measurement instructions.
+///
+/// Abstracts over heap-allocated vectors (host) and fixed-capacity static
+/// vectors (firmware), mirroring `CorrectionBuffer` on the decoder side.
+pub trait SyndromeBuffer {
+    /// Appends the index of a detector that fired.
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - Detector index to record
+    fn push_syndrome(&mut self, detector: usize) -> Result<(), QecError>;
+
+    /// Clears all accumulated syndrome bits.
+    fn clear_syndromes(&mut self);
+
+    /// Returns the detector indices accumulated so far.
+    fn syndromes(&self) -> &[usize];
+}
+
+impl<A: Allocator> SyndromeBuffer for Vec<usize, A> {
+    /// Pushes a detector index, returning `OutOfMemory` if the vector
+    /// cannot grow.
+    fn push_syndrome(&mut self, detector: usize) -> Result<(), QecError> {
+        self.try_reserve(1).map_err(|_| QecError::OutOfMemory)?;
+        self.push(detector);
+        Ok(())
+    }
+
+    fn clear_syndromes(&mut self) {
+        self.clear();
+    }
+
+    fn syndromes(&self) -> &[usize] {
+        self
+    }
+}
+
+impl<const M: usize> SyndromeBuffer for StaticVec<usize, M> {
+    /// Pushes a detector index, returning `BufferOverflow` if the static
+    /// vector is full.
+    fn push_syndrome(&mut self, detector: usize) -> Result<(), QecError> {
+        self.push(detector).map_err(|_| QecError::BufferOverflow)
+    }
+
+    fn clear_syndromes(&mut self) {
+        self.clear();
+    }
+
+    fn syndromes(&self) -> &[usize] {
+        self.as_slice()
+    }
+}
+
+/// Storage for the frame-adjusted outcome of every measurement.
+///
+/// Abstracts over heap-allocated vectors (host) and fixed-capacity static
+/// vectors (firmware).
+pub trait MeasurementRecord {
+    /// Appends a measurement outcome.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - Frame-adjusted measurement result
+    fn record_outcome(&mut self, outcome: bool) -> Result<(), QecError>;
+
+    /// Returns the outcome at position `index`, if recorded.
+    fn outcome(&self, index: usize) -> Option<bool>;
+
+    /// Returns the number of recorded outcomes.
+    fn outcome_count(&self) -> usize;
+
+    /// Clears all recorded outcomes.
+    fn clear_outcomes(&mut self);
+}
+
+impl<A: Allocator> MeasurementRecord for Vec<bool, A> {
+    fn record_outcome(&mut self, outcome: bool) -> Result<(), QecError> {
+        self.try_reserve(1).map_err(|_| QecError::OutOfMemory)?;
+        self.push(outcome);
+        Ok(())
+    }
+
+    fn outcome(&self, index: usize) -> Option<bool> {
+        self.get(index).copied()
+    }
+
+    fn outcome_count(&self) -> usize {
+        self.len()
+    }
+
+    fn clear_outcomes(&mut self) {
+        self.clear();
+    }
+}
+
+impl<const M: usize> MeasurementRecord for StaticVec<bool, M> {
+    fn record_outcome(&mut self, outcome: bool) -> Result<(), QecError> {
+        self.push(outcome).map_err(|_| QecError::BufferOverflow)
+    }
+
+    fn outcome(&self, index: usize) -> Option<bool> {
+        self.as_slice().get(index).copied()
+    }
+
+    fn outcome_count(&self) -> usize {
+        self.len()
+    }
+
+    fn clear_outcomes(&mut self) {
+        self.clear();
+    }
+}
+
+/// Observable effect of an executed instruction, reported to a trace hook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A gate or fired conditional Pauli updated the frame.
+    GateApplied,
+
+    /// A conditional Pauli was skipped because its record bit was 0.
+    ConditionSkipped,
+
+    /// A measurement completed with the given frame-adjusted outcome.
+    Measured {
+        /// Frame-adjusted measurement outcome.
+        outcome: bool,
+    },
+
+    /// An ObservableInclude measurement fired and toggled observables.
+    ObservableFlipped {
+        /// Observable register after the toggle.
+        observables: u64,
+    },
+
+    /// A measurement disagreed with the frame and fired a detector.
+    SyndromeRecorded {
+        /// Detector index that fired.
+        detector: usize,
+    },
+
+    /// The decoder ran and produced corrections.
+    DecodeCompleted {
+        /// Number of correction edges produced.
+        num_corrections: usize,
+    },
+
+    /// A Barrier decoded and applied all pending syndromes.
+    Barrier {
+        /// Tag carried in the Barrier's first operand.
+        tag: u16,
+    },
+
+    /// The detector base was advanced by a ShiftDetectors instruction.
+    DetectorsShifted {
+        /// Detector base after the shift.
+        base: usize,
+    },
+
+    /// The frame, buffers, measurement record, and decoder were cleared.
+    FrameReset,
+
+    /// A Halt instruction was reached.
+    Halted,
+}
+
+/// Observer called after each traced VM event.
+///
+/// A plain function pointer plus an opaque `usize` context (e.g., a hart ID
+/// or the address of caller-owned state) rather than a boxed closure, so it
+/// can be installed in firmware without a heap. The instruction is passed in
+/// its widened form, with any Wide prefix already folded in.
+pub type TraceHook = fn(context: usize, instr: &Instruction32, event: TraceEvent);
+
+/// Execution counters accumulated by the VM.
+///
+/// Answers how much of a shot's work is frame updates versus decoding
+/// without an external profiler. Counts only successfully executed
+/// instructions; an instruction rejected by validation is not counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VmStats {
+    /// Gate instructions executed (H, S, S†, X, Z, CNOT).
+    pub gates: u64,
+
+    /// Measurement instructions executed (Measure, MeasureX,
+    /// ObservableInclude).
+    pub measurements: u64,
+
+    /// Decode instructions executed.
+    pub decodes: u64,
+
+    /// Barrier instructions executed.
+    pub barriers: u64,
+
+    /// Tag of the most recently completed Barrier, if any.
+    pub last_barrier_tag: Option<u16>,
+
+    /// Conditional Pauli instructions executed, whether or not they fired.
+    pub conditionals: u64,
+
+    /// Control instructions executed (Reset, Halt, LoopBegin, LoopEnd,
+    /// ShiftDetectors). Loop instructions are counted once per execution.
+    pub control: u64,
+
+    /// Measurements that disagreed with the frame and fired a detector.
+    pub syndromes_recorded: u64,
+
+    /// Correction edges applied to the frame after decoding.
+    pub corrections_applied: u64,
+
+    /// Wall-clock time spent inside the decoder, for Decode and Barrier.
+    #[cfg(feature = "std")]
+    pub decode_time: Duration,
+}
+
+impl VmStats {
+    /// Returns the total number of instructions executed.
+    pub fn instructions(&self) -> u64 {
+        self.gates
+            + self.measurements
+            + self.decodes
+            + self.barriers
+            + self.conditionals
+            + self.control
+    }
+}
+
+/// Reason a program run stopped.
+///
+/// Both variants report how many instructions were executed, counting the
+/// Halt instruction itself when present, and the length of the VM's
+/// measurement record when execution stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmExit {
+    /// Execution reached an `Opcode::Halt` instruction at index `pc`.
+    Halted {
+        /// Index of the Halt instruction within the program.
+        pc: usize,
+
+        /// Number of instructions executed, including the Halt.
+        executed: usize,
+
+        /// Length of the measurement record at exit.
+        measurements: usize,
+    },
+
+    /// Execution reached the end of the program without a Halt.
+    RanOffEnd {
+        /// Number of instructions executed.
+        executed: usize,
+
+        /// Length of the measurement record at exit.
+        measurements: usize,
+    },
+}
+
+impl VmExit {
+    /// Returns the number of instructions executed before stopping.
+    pub fn executed(&self) -> usize {
+        match *self {
+            VmExit::Halted { executed, .. } | VmExit::RanOffEnd { executed, .. } => executed,
+        }
+    }
+
+    /// Returns the length of the measurement record when execution stopped.
+    pub fn measurements(&self) -> usize {
+        match *self {
+            VmExit::Halted { measurements, .. } | VmExit::RanOffEnd { measurements, .. } => {
+                measurements
+            }
+        }
+    }
+}
+
+/// Control-flow signal returned by `VirtualMachine::execute`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// The instruction completed; fetch the next one.
+    Continue,
+
+    /// The instruction was `Opcode::Halt`; stop fetching.
+    Halt,
+
+    /// The instruction was `Opcode::LoopBegin`; the caller runs the body
+    /// `count` times.
+    LoopBegin {
+        /// Number of times to execute the loop body.
+        count: u32,
+    },
+
+    /// The instruction was `Opcode::LoopEnd`; the caller jumps back to the
+    /// start of the innermost loop body if iterations remain.
+    LoopEnd,
+}
 
 /// Virtual machine for quantum error correction instruction execution.
 ///
@@ -24,11 +345,21 @@ use qcu_common::isa::{Instruction, Opcode};
 ///
 /// # Type Parameters
 ///
-/// * `'a` - Lifetime of the decoding graph reference
+/// * `'a` - Lifetime of the decoding graph reference and Pauli frame storage
 /// * `N` - Maximum number of nodes the decoder can handle (must satisfy
 ///   the constraint that N.div_ceil(64) is a valid array size)
-pub struct VirtualMachine<'a, const N: usize>
-where
+/// * `G` - Graph type, either `DecodingGraph` or `CompactDecodingGraph`
+/// * `S` - Syndrome buffer type; `Vec` on the host, `StaticVec` in firmware
+/// * `C` - Correction buffer type; `Vec` on the host, `StaticVec` in firmware
+/// * `R` - Measurement record type; `Vec` on the host, `StaticVec` in firmware
+pub struct VirtualMachine<
+    'a,
+    const N: usize,
+    G: EdgeList = DecodingGraph,
+    S: SyndromeBuffer = Vec<usize>,
+    C: CorrectionBuffer = Vec<(usize, usize)>,
+    R: MeasurementRecord = Vec<bool>,
+> where
     [(); N.div_ceil(64)]:,
 {
     /// Pauli frame tracking X and Z errors on logical qubits.
@@ -36,7 +367,7 @@ where
     /// Maintains the stabilizer state representation, updated by gate
     /// operations and used to predict measurement outcomes for syndrome
     /// generation.
-    pub frame: PauliFrame,
+    pub frame: PauliFrame<'a>,
 
     /// Union-Find decoder instance for processing syndrome data.
     ///
@@ -49,21 +380,85 @@ where
     ///
     /// Used by the decoder to find correction paths. The graph is shared
     /// and not modified by the VM, so a reference is sufficient.
-    pub graph: &'a DecodingGraph,
+    /// Replace it with `set_graph` so the detector limit stays in sync.
+    pub graph: &'a G,
 
     /// Buffer accumulating detector indices that fired (syndrome bits).
     ///
     /// Collects detector IDs from measurement instructions where the actual
     /// measurement differs from the Pauli frame prediction. This buffer is
     /// passed to the decoder when a decode instruction is executed.
-    pub syndrome_buffer: Vec<usize>,
+    pub syndrome_buffer: S,
 
     /// Buffer receiving correction edge pairs from the decoder.
     ///
     /// Populated by the decoder's solve_into method with (u, v) edge pairs
     /// that should be flipped to correct detected errors. The corrections
     /// are applied to the Pauli frame to restore the logical state.
-    pub correction_buffer: Vec<(usize, usize)>,
+    pub correction_buffer: C,
+
+    /// Frame-adjusted outcome of every measurement, in execution order.
+    ///
+    /// Each entry is the hardware result XORed with the Pauli frame's
+    /// prediction, i.e. the outcome the ideal circuit would have produced
+    /// given the tracked errors. Used by the host to reconstruct logical
+    /// observables. Drained by `take_measurements`.
+    pub measurement_record: R,
+
+    /// Mapping from correction edges to the data qubit and Pauli they flip.
+    ///
+    /// Used by decode instructions when `auto_apply_corrections` is set.
+    /// When None, the fallback convention described on
+    /// `auto_apply_corrections` is used instead.
+    pub correction_map: Option<CorrectionMap>,
+
+    /// Mapping from correction edges to the logical observables they flip.
+    ///
+    /// When set, every applied correction XORs its mask into the observable
+    /// register. When None, corrections leave the register untouched.
+    pub observable_map: Option<ObservableMap>,
+
+    /// Whether decode instructions apply their corrections to the frame.
+    ///
+    /// When true (the default), each decode applies the corrections it
+    /// produced and then clears `correction_buffer`, so a subsequent
+    /// measurement no longer reports the corrected errors. Corrections are
+    /// applied through `correction_map` if one is set; otherwise each
+    /// correction edge (u, v) toggles the X error on qubits u and v, treating
+    /// detector indices as the indices of their adjacent qubits and skipping
+    /// endpoints beyond the frame (boundary nodes). When false, corrections
+    /// are left in `correction_buffer` for the caller.
+    pub auto_apply_corrections: bool,
+
+    /// Number of detectors addressable by measurement instructions.
+    ///
+    /// Initialised from the graph's node count. Measurement instructions whose
+    /// detector (operand plus `detector_base`) is not below this limit, or
+    /// below the decoder capacity `N` if that is smaller, are rejected with
+    /// `OperandOutOfBounds`. Qubit operands are checked against the frame's
+    /// `num_qubits`.
+    pub num_detectors: usize,
+
+    /// Offset added to the detector operand of measurement instructions.
+    ///
+    /// Advanced by ShiftDetectors and cleared by reset, so looped rounds can
+    /// address fresh detectors with identical instructions.
+    pub detector_base: usize,
+
+    /// Optional execution observer and its context.
+    ///
+    /// Checked once per event; when None, tracing costs a single branch.
+    pub trace_hook: Option<(TraceHook, usize)>,
+
+    /// Execution counters, read with `stats` and cleared with `reset_stats`.
+    stats: VmStats,
+
+    /// Logical observable register, read with `observables`.
+    ///
+    /// Bit k holds the accumulated parity of observable k: toggled by each
+    /// ObservableInclude measurement that fires and by each applied
+    /// correction whose `observable_map` mask includes k.
+    observables: u64,
 }
 
 impl<'a, const N: usize> VirtualMachine<'a, N>
@@ -82,24 +477,701 @@ where
     /// * `alloc` - Allocator for Pauli frame storage
     /// * `graph` - Decoding graph reference (must outlive the VM)
     /// * `num_qubits` - Number of logical qubits to track in the Pauli frame
-    pub fn new(alloc: &BumpAllocator, graph: &'a DecodingGraph, num_qubits: usize) -> Self {
-        Self {
-            frame: PauliFrame::new(alloc, num_qubits),
+    ///
+    /// # Returns
+    ///
+    /// The new VM, or `NodeOutOfBounds` if the graph has more nodes than the
+    /// decoder capacity `N`.
+    pub fn new(
+        alloc: &'a BumpAllocator,
+        graph: &'a DecodingGraph,
+        num_qubits: usize,
+    ) -> Result<Self, QecError> {
+        Self::with_frame(PauliFrame::new(alloc, num_qubits), graph)
+    }
+
+    /// Creates a new virtual machine around an existing Pauli frame.
+    ///
+    /// Lets host tools build the frame with `PauliFrame::new_in` over any
+    /// allocator instead of setting up a bump region.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Pauli frame to track errors in
+    /// * `graph` - Decoding graph reference (must outlive the VM)
+    ///
+    /// # Returns
+    ///
+    /// The new VM, or `NodeOutOfBounds` if the graph has more nodes than the
+    /// decoder capacity `N`.
+    pub fn with_frame(frame: PauliFrame<'a>, graph: &'a DecodingGraph) -> Result<Self, QecError> {
+        check_capacity::<N>(graph)?;
+        Ok(Self {
+            frame,
             decoder: UnionFindDecoder::new(),
             graph,
             syndrome_buffer: Vec::with_capacity(256),
             correction_buffer: Vec::with_capacity(256),
+            measurement_record: Vec::with_capacity(256),
+            correction_map: None,
+            observable_map: None,
+            auto_apply_corrections: true,
+            num_detectors: graph.num_nodes(),
+            detector_base: 0,
+            trace_hook: None,
+            stats: VmStats::default(),
+            observables: 0,
+        })
+    }
+}
+
+impl<'a, const N: usize, G, S, C, R> VirtualMachine<'a, N, G, S, C, R>
+where
+    [(); N.div_ceil(64)]:,
+    G: EdgeList,
+    S: SyndromeBuffer,
+    C: CorrectionBuffer,
+    R: MeasurementRecord,
+{
+    /// Creates a virtual machine with fixed-capacity buffers.
+    ///
+    /// Intended for firmware, where the global allocator is unavailable: the
+    /// Pauli frame is allocated from `alloc` and the syndrome, correction, and
+    /// measurement buffers start empty via `Default`, so `StaticVec` buffers
+    /// need no heap at all. Each worker hart can own one such VM.
+    ///
+    /// # Arguments
+    ///
+    /// * `alloc` - Allocator for Pauli frame storage
+    /// * `graph` - Decoding graph reference (must outlive the VM)
+    /// * `num_qubits` - Number of logical qubits to track in the Pauli frame
+    ///
+    /// # Returns
+    ///
+    /// The new VM, `NodeOutOfBounds` if the graph has more nodes than the
+    /// decoder capacity `N`, or `OutOfMemory` if the frame cannot be
+    /// allocated.
+    pub fn new_static(
+        alloc: &'a BumpAllocator,
+        graph: &'a G,
+        num_qubits: usize,
+    ) -> Result<Self, QecError>
+    where
+        S: Default,
+        C: Default,
+        R: Default,
+    {
+        check_capacity::<N>(graph)?;
+        Ok(Self {
+            frame: PauliFrame::new_in(alloc, num_qubits)?,
+            decoder: UnionFindDecoder::new(),
+            graph,
+            syndrome_buffer: S::default(),
+            correction_buffer: C::default(),
+            measurement_record: R::default(),
+            correction_map: None,
+            observable_map: None,
+            auto_apply_corrections: true,
+            num_detectors: graph.num_nodes(),
+            detector_base: 0,
+            trace_hook: None,
+            stats: VmStats::default(),
+            observables: 0,
+        })
+    }
+
+    /// Replaces the decoding graph used by subsequent decode instructions.
+    ///
+    /// Supports calibration workflows that switch to a re-weighted graph
+    /// mid-program without rebuilding the frame or buffers. The detector
+    /// limit is taken from the new graph and the decoder's internal state is
+    /// cleared. Syndromes already collected are kept and will be decoded
+    /// against the new graph, so they must be valid detectors in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - New decoding graph (must outlive the VM)
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if the graph has more nodes
+    /// than the decoder capacity `N` or a pending syndrome is not a node of
+    /// the new graph. On error the VM is left unchanged.
+    pub fn set_graph(&mut self, graph: &'a G) -> Result<(), QecError> {
+        check_capacity::<N>(graph)?;
+        let num_nodes = graph.num_nodes();
+        if self.syndrome_buffer.syndromes().iter().any(|&d| d >= num_nodes) {
+            return Err(QecError::NodeOutOfBounds);
+        }
+        self.graph = graph;
+        self.num_detectors = num_nodes;
+        self.decoder.reset();
+        Ok(())
+    }
+
+    /// Sets the mapping used to apply decoder corrections to the Pauli frame.
+    ///
+    /// Replaces the fallback convention used by `auto_apply_corrections` with
+    /// an explicit edge-to-qubit mapping, typically derived from the code's
+    /// layout or the graph's fault metadata.
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - Function mapping a (u, v) correction edge to a qubit and Pauli
+    pub fn set_correction_map(&mut self, map: CorrectionMap) {
+        self.correction_map = Some(map);
+    }
+
+    /// Sets the mapping used to update the observable register on correction.
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - Function mapping a (u, v) correction edge to an observable mask
+    pub fn set_observable_map(&mut self, map: ObservableMap) {
+        self.observable_map = Some(map);
+    }
+
+    /// Returns the logical observable register.
+    ///
+    /// Bit k is the parity of observable k accumulated since the last reset.
+    /// Comparing it against the generator's ground truth after a shot tells
+    /// whether decoding introduced a logical error.
+    pub fn observables(&self) -> u64 {
+        self.observables
+    }
+
+    /// Applies the contents of `correction_buffer` to the frame and clears it.
+    ///
+    /// Uses `correction_map` if set, otherwise the fallback convention
+    /// documented on `auto_apply_corrections`. If `observable_map` is set,
+    /// each correction's mask is XORed into the observable register. Called
+    /// automatically after each decode when `auto_apply_corrections` is true.
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `NodeOutOfBounds` if the correction map names a
+    /// qubit outside the frame.
+    pub fn apply_pending_corrections(&mut self) -> Result<(), QecError> {
+        match self.correction_map {
+            Some(map) => self
+                .frame
+                .apply_corrections(self.correction_buffer.corrections(), &map)?,
+            None => {
+                let num_qubits = self.frame.num_qubits();
+                for &(u, v) in self.correction_buffer.corrections() {
+                    for q in [u, v] {
+                        if q < num_qubits {
+                            self.frame.inject_x(q)?;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(map) = self.observable_map {
+            for &(u, v) in self.correction_buffer.corrections() {
+                self.observables ^= map(u, v);
+            }
+        }
+        self.stats.corrections_applied += self.correction_buffer.corrections().len() as u64;
+        self.correction_buffer.clear_buffer();
+        Ok(())
+    }
+
+    /// Returns the number of detectors measurement instructions may address.
+    ///
+    /// `num_detectors`, capped at the decoder capacity `N` in case the field
+    /// was raised by hand after construction.
+    #[inline(always)]
+    fn detector_limit(&self) -> usize {
+        self.num_detectors.min(N)
+    }
+
+    /// Validates an instruction's operands against the VM's configuration.
+    ///
+    /// Qubit operands must be below the frame's qubit count, detector
+    /// operands plus `detector_base` below `num_detectors`, and observable
+    /// operands below
+    /// `MAX_OBSERVABLES`. Checked before any state is touched,
+    /// so a rejected instruction leaves the frame and buffers unmodified.
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - Decoded opcode of the instruction
+    /// * `instr` - Instruction whose operands are checked
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) if all operands used by the opcode are in range, otherwise
+    /// `OperandOutOfBounds` naming the first offending operand.
+    fn check_operands(&self, opcode: Opcode, instr: &Instruction32) -> Result<(), QecError> {
+        let num_qubits = self.frame.num_qubits();
+        let (op1, op2) = (instr.operand_1, instr.operand_2);
+        let (qubits, index): (&[u32], Option<(u32, usize)>) = match opcode {
+            Opcode::GateH
+            | Opcode::GateS
+            | Opcode::GateSdg
+            | Opcode::GateX
+            | Opcode::GateZ
+            | Opcode::CondPauliX
+            | Opcode::CondPauliZ => (&[op1], None),
+            Opcode::GateCNOT => (&[op1, op2], None),
+            Opcode::Measure | Opcode::MeasureX => (
+                &[op1],
+                Some((op2, self.detector_limit().saturating_sub(self.detector_base))),
+            ),
+            Opcode::ObservableInclude => (&[op1], Some((op2, MAX_OBSERVABLES))),
+            Opcode::Decode
+            | Opcode::Barrier
+            | Opcode::Reset
+            | Opcode::Halt
+            | Opcode::LoopBegin
+            | Opcode::LoopEnd
+            | Opcode::ShiftDetectors
+            | Opcode::Wide => (&[], None),
+        };
+
+        let out_of_bounds = |operand: u32, limit: usize| QecError::OperandOutOfBounds {
+            opcode: instr.opcode,
+            operand,
+            limit,
+        };
+        for &q in qubits {
+            if q as usize >= num_qubits {
+                return Err(out_of_bounds(q, num_qubits));
+            }
+        }
+        if let Some((i, limit)) = index
+            && i as usize >= limit
+        {
+            return Err(out_of_bounds(i, limit));
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the measurement record accumulated so far.
+    ///
+    /// # Returns
+    ///
+    /// The frame-adjusted outcomes of all measurements since the last call.
+    pub fn take_measurements(&mut self) -> R
+    where
+        R: Default,
+    {
+        core::mem::take(&mut self.measurement_record)
+    }
+
+    /// Returns the VM to its initial state without executing an instruction.
+    ///
+    /// Clears the Pauli frame, the syndrome and correction buffers, the
+    /// measurement record, the observable register, the detector base, and
+    /// the decoder's internal state, so that no correction from a previous shot can leak
+    /// into the next decode. Has the same effect as executing `Opcode::Reset`.
+    /// Execution counters, the trace hook, and configuration are kept.
+    pub fn full_reset(&mut self) {
+        self.frame.reset();
+        self.syndrome_buffer.clear_syndromes();
+        self.correction_buffer.clear_buffer();
+        self.measurement_record.clear_outcomes();
+        self.observables = 0;
+        self.detector_base = 0;
+        self.decoder.reset();
+    }
+
+    /// Returns the execution counters accumulated since the last reset.
+    pub fn stats(&self) -> &VmStats {
+        &self.stats
+    }
+
+    /// Clears all execution counters.
+    pub fn reset_stats(&mut self) {
+        self.stats = VmStats::default();
+    }
+
+    /// Installs an observer called for every traced VM event.
+    ///
+    /// Used to debug divergence between firmware and host runs by logging
+    /// each instruction's effect. Replaces any previously installed hook.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - Function called with the context, instruction, and event
+    /// * `context` - Opaque value passed through to every hook call
+    pub fn set_trace_hook(&mut self, hook: TraceHook, context: usize) {
+        self.trace_hook = Some((hook, context));
+    }
+
+    /// Removes the trace hook, if any.
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+    }
+
+    /// Reports an event to the trace hook if one is installed.
+    #[inline(always)]
+    fn trace(&self, instr: &Instruction32, event: TraceEvent) {
+        if let Some((hook, context)) = self.trace_hook {
+            hook(context, instr, event);
+        }
+    }
+
+    /// Checkpoints the Pauli frame into a caller-provided buffer.
+    ///
+    /// Lets the host replay an instruction window after a decode failure by
+    /// restoring the frame with `load_state`. Pending syndromes and
+    /// corrections are not saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Destination buffer of at least `2 * frame.num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the buffer is too short.
+    pub fn save_state(&self, buf: &mut [u64]) -> Result<(), QecError> {
+        self.frame.snapshot_into(buf)
+    }
+
+    /// Restores the Pauli frame from a buffer written by `save_state`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Source buffer of at least `2 * frame.num_words()` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or `BufferOverflow` if the buffer is too short.
+    pub fn load_state(&mut self, buf: &[u64]) -> Result<(), QecError> {
+        self.frame.restore_from(buf)
+    }
+
+    /// Runs the decoder over the pending syndromes.
+    ///
+    /// Fills `correction_buffer`, clears the syndrome buffer, and reports
+    /// `DecodeCompleted`. Corrections are not applied here; the caller decides
+    /// whether to apply them.
+    ///
+    /// # Arguments
+    ///
+    /// * `instr` - Instruction that triggered the decode, for tracing
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or the decoder's error.
+    fn decode_pending(&mut self, instr: &Instruction32) -> Result<(), QecError> {
+        #[cfg(feature = "std")]
+        let start = Instant::now();
+        let result = self.decoder.solve_into(
+            self.graph,
+            self.syndrome_buffer.syndromes(),
+            &mut self.correction_buffer,
+        );
+        #[cfg(feature = "std")]
+        {
+            self.stats.decode_time += start.elapsed();
+        }
+        result?;
+        self.syndrome_buffer.clear_syndromes();
+        let num_corrections = self.correction_buffer.corrections().len();
+        self.trace(instr, TraceEvent::DecodeCompleted { num_corrections });
+        Ok(())
+    }
+
+    /// Executes a validated program until it halts.
+    ///
+    /// The program's qubit and detector extents are checked against the
+    /// VM's frame, `num_detectors` (capped at `N`), and current
+    /// `detector_base` once, up front; the per-instruction operand checks
+    /// done by `execute` are then skipped. Otherwise behaves like
+    /// `run_instructions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - Program validated with `Program::validate`
+    /// * `measurements` - Hardware measurement results, in program order
+    ///
+    /// # Returns
+    ///
+    /// The exit reason on success, `OperandOutOfBounds` naming the opcode of
+    /// the instruction with the largest qubit or detector if the program
+    /// addresses more than the VM provides, or any error `run_instructions`
+    /// can return other than operand and loop-structure errors.
+    pub fn run_program<PA: Allocator>(
+        &mut self,
+        program: &Program<PA>,
+        measurements: &mut impl Iterator<Item = bool>,
+    ) -> Result<VmExit, QecError> {
+        let num_qubits = self.frame.num_qubits();
+        if program.qubits_used() > num_qubits {
+            return Err(QecError::OperandOutOfBounds {
+                opcode: program.qubit_opcode(),
+                operand: (program.qubits_used() - 1) as u32,
+                limit: num_qubits,
+            });
+        }
+        let num_detectors = self.detector_limit();
+        if self.detector_base + program.detectors_used() > num_detectors {
+            return Err(QecError::OperandOutOfBounds {
+                opcode: program.detector_opcode(),
+                operand: (program.detectors_used() - 1) as u32,
+                limit: num_detectors.saturating_sub(self.detector_base),
+            });
+        }
+
+        let fetch = |pc: usize| program.get(pc).ok_or(QecError::InvalidProgram { pc });
+        self.run_fetched(program.len(), fetch, measurements, true)
+    }
+
+    /// Executes an instruction sequence until it halts or runs off the end.
+    ///
+    /// Fetches instructions in order, stopping at the first `Opcode::Halt`.
+    /// Each Measure, MeasureX, or ObservableInclude instruction consumes the
+    /// next hardware result from `measurements`; other instructions consume
+    /// nothing. LoopBegin/LoopEnd pairs repeat their body, nesting up to
+    /// `MAX_LOOP_DEPTH` deep. An `Opcode::Wide` prefix is folded into the
+    /// instruction after it, and the pair executes as one instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - Instructions to execute
+    /// * `measurements` - Hardware measurement results, in program order
+    ///
+    /// # Returns
+    ///
+    /// The exit reason on success, `MeasurementUnderflow` if `measurements`
+    /// runs out, `BufferOverflow` if loops nest too deeply,
+    /// `InvalidInstruction` for an unmatched LoopBegin or LoopEnd or a Wide
+    /// prefix that does not precede an instruction, or the first error
+    /// returned by `execute`.
+    pub fn run_instructions(
+        &mut self,
+        program: &[Instruction],
+        measurements: &mut impl Iterator<Item = bool>,
+    ) -> Result<VmExit, QecError> {
+        self.run_fetched(program.len(), |pc| Ok(program[pc]), measurements, false)
+    }
+
+    /// Executes an encoded program until it halts or runs off the end.
+    ///
+    /// Decodes instructions from their 6-byte wire encoding (little-endian
+    /// operands) as they are fetched, for programs received as raw bytes
+    /// over UART or virtio. Plain 6-byte instructions and 12-byte
+    /// Wide-prefixed ones may be mixed freely. The length is checked up
+    /// front, so a truncated program executes nothing; an unknown opcode
+    /// stops execution at that instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - Encoded program
+    /// * `measurements` - Hardware measurement results, in program order
+    ///
+    /// # Returns
+    ///
+    /// The exit reason on success, `TruncatedProgram` if the length is not a
+    /// multiple of the instruction size, `InvalidInstruction` for an unknown
+    /// opcode, or any error `run_instructions` can return.
+    pub fn run_bytes(
+        &mut self,
+        bytes: &[u8],
+        measurements: &mut impl Iterator<Item = bool>,
+    ) -> Result<VmExit, QecError> {
+        InstructionStream::check_length(bytes).map_err(isa_to_qec)?;
+
+        let len = bytes.len() / INSTRUCTION_SIZE;
+        let fetch = |pc: usize| {
+            let start = pc * INSTRUCTION_SIZE;
+            let chunk = bytes[start..start + INSTRUCTION_SIZE]
+                .try_into()
+                .expect("chunk is INSTRUCTION_SIZE bytes");
+            Instruction::from_bytes(chunk).map_err(isa_to_qec)
+        };
+        self.run_fetched(len, fetch, measurements, false)
+    }
+
+    /// Shared fetch-execute loop behind the `run_*` methods.
+    ///
+    /// Keeps the loop stack as (body start, remaining iterations) pairs in a
+    /// fixed-capacity vector. A LoopBegin with a count of zero skips forward
+    /// past its matching LoopEnd without executing the body. Program indices
+    /// count 6-byte slots, so a Wide-prefixed instruction advances `pc` by
+    /// two.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - Number of instruction slots in the program
+    /// * `fetch` - Returns the instruction slot at a program index below `len`
+    /// * `measurements` - Hardware measurement results, in program order
+    /// * `validated` - Skip per-instruction operand checks because the
+    ///   program was validated as a whole
+    ///
+    /// # Returns
+    ///
+    /// The exit reason, or the first error raised while fetching or
+    /// executing.
+    fn run_fetched(
+        &mut self,
+        len: usize,
+        mut fetch: impl FnMut(usize) -> Result<Instruction, QecError>,
+        measurements: &mut impl Iterator<Item = bool>,
+        validated: bool,
+    ) -> Result<VmExit, QecError> {
+        let mut loops: StaticVec<(usize, u32), MAX_LOOP_DEPTH> = StaticVec::new();
+        let mut executed = 0;
+        let mut pc = 0;
+
+        while pc < len {
+            let (instr, width) = Self::fetch_wide(pc, len, &mut fetch)?;
+            let opcode = Opcode::try_from(instr.opcode).map_err(|_| {
+                QecError::InvalidInstruction {
+                    opcode: instr.opcode,
+                }
+            })?;
+            let hw_measure = match opcode {
+                Opcode::Measure | Opcode::MeasureX | Opcode::ObservableInclude => {
+                    measurements.next().ok_or(QecError::MeasurementUnderflow)?
+                }
+                _ => false,
+            };
+            if !validated {
+                self.check_operands(opcode, &instr)?;
+            }
+            let step = self.dispatch(opcode, &instr, hw_measure)?;
+            executed += 1;
+
+            match step {
+                Step::Continue => pc += width,
+                Step::Halt => {
+                    return Ok(VmExit::Halted {
+                        pc,
+                        executed,
+                        measurements: self.measurement_record.outcome_count(),
+                    });
+                }
+                Step::LoopBegin { count: 0 } => {
+                    pc = Self::skip_loop_body(pc + width, len, &mut fetch)?;
+                }
+                Step::LoopBegin { count } => {
+                    loops
+                        .push((pc + width, count))
+                        .map_err(|_| QecError::BufferOverflow)?;
+                    pc += width;
+                }
+                Step::LoopEnd => {
+                    let (start, remaining) = loops.pop().ok_or(QecError::InvalidInstruction {
+                        opcode: instr.opcode,
+                    })?;
+                    if remaining > 1 {
+                        loops
+                            .push((start, remaining - 1))
+                            .map_err(|_| QecError::BufferOverflow)?;
+                        pc = start;
+                    } else {
+                        pc += width;
+                    }
+                }
+            }
+        }
+
+        if !loops.is_empty() {
+            return Err(QecError::InvalidInstruction {
+                opcode: Opcode::LoopBegin as u8,
+            });
+        }
+        Ok(VmExit::RanOffEnd {
+            executed,
+            measurements: self.measurement_record.outcome_count(),
+        })
+    }
+
+    /// Fetches the instruction at `pc`, folding in a Wide prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `pc` - Index of the instruction slot to fetch
+    /// * `len` - Number of instruction slots in the program
+    /// * `fetch` - Instruction fetch function
+    ///
+    /// # Returns
+    ///
+    /// The widened instruction and the number of slots it occupies (1, or 2
+    /// with a prefix), or `InvalidInstruction` carrying `Opcode::Wide` if a
+    /// prefix is last in the program or followed by another prefix.
+    fn fetch_wide(
+        pc: usize,
+        len: usize,
+        fetch: &mut impl FnMut(usize) -> Result<Instruction, QecError>,
+    ) -> Result<(Instruction32, usize), QecError> {
+        let first = fetch(pc)?;
+        if first.opcode != Opcode::Wide as u8 {
+            return Ok((Instruction32::from(first), 1));
         }
+        if pc + 1 >= len {
+            return Err(QecError::InvalidInstruction {
+                opcode: first.opcode,
+            });
+        }
+        let instr = Instruction32::from_parts(Some(first), fetch(pc + 1)?).map_err(isa_to_qec)?;
+        Ok((instr, 2))
+    }
+
+    /// Finds the instruction after the LoopEnd matching a LoopBegin.
+    ///
+    /// Steps over the body with `fetch_wide`, so a Wide-prefixed instruction
+    /// is read as one and only its real opcode is matched against the loop
+    /// markers.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - Index of the first slot after the LoopBegin (and its
+    ///   prefix, if any)
+    /// * `len` - Number of instructions in the program
+    /// * `fetch` - Instruction fetch function
+    ///
+    /// # Returns
+    ///
+    /// The index following the matching LoopEnd, `InvalidInstruction` if
+    /// the loop is never closed, or the error `fetch_wide` returns for a
+    /// malformed prefix.
+    fn skip_loop_body(
+        body: usize,
+        len: usize,
+        fetch: &mut impl FnMut(usize) -> Result<Instruction, QecError>,
+    ) -> Result<usize, QecError> {
+        let mut depth = 1usize;
+        let mut pc = body;
+        while pc < len {
+            let (instr, width) = Self::fetch_wide(pc, len, fetch)?;
+            match Opcode::try_from(instr.opcode) {
+                Ok(Opcode::LoopBegin) => depth += 1,
+                Ok(Opcode::LoopEnd) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(pc + width);
+                    }
+                }
+                _ => {}
+            }
+            pc += width;
+        }
+        Err(QecError::InvalidInstruction {
+            opcode: Opcode::LoopBegin as u8,
+        })
     }
 
     /// Executes a single quantum error correction instruction.
     ///
     /// Dispatches to the appropriate handler based on the instruction opcode:
     /// gate operations update the Pauli frame, measurements compare against
-    /// the frame to generate syndromes, decode triggers the decoder, and
-    /// reset clears all state. The hw_measure parameter provides the actual
-    /// measurement result from hardware, which is compared against the
-    /// Pauli frame prediction to detect errors.
+    /// the frame to generate syndromes, decode triggers the decoder (and
+    /// applies its corrections if `auto_apply_corrections` is set), barrier
+    /// decodes and always applies the corrections,
+    /// conditional Paulis consult the measurement record, ObservableInclude
+    /// folds a measurement into the observable register, and reset clears
+    /// all state (see `full_reset`). Halt does nothing but signal the caller
+    /// to stop fetching.
+    /// A conditional Pauli whose record index has not been measured yet
+    /// fails with `OperandOutOfBounds`, the limit being the number of
+    /// measurements recorded so far. The hw_measure parameter provides the
+    /// actual measurement result from hardware, which is compared against
+    /// the Pauli frame prediction to detect errors.
     ///
     /// # Arguments
     ///
@@ -109,33 +1181,1090 @@ where
     ///
     /// # Returns
     ///
-    /// Ok(()) on success, or an error if decoding fails or buffers overflow.
-    pub fn execute(&mut self, instr: &Instruction, hw_measure: bool) -> Result<(), QecError> {
+    /// `Step::Halt` for a Halt instruction, `Step::LoopBegin` or
+    /// `Step::LoopEnd` for loop instructions (which the caller must
+    /// interpret), `Step::Continue` for any other successfully executed
+    /// instruction, `InvalidInstruction` if the opcode
+    /// byte is not recognised or its operands are structurally invalid,
+    /// `OperandOutOfBounds` if a qubit or detector operand is out of range,
+    /// or an error if decoding fails or buffers overflow. An invalid instruction
+    /// leaves the VM state unchanged.
+    pub fn execute(&mut self, instr: &Instruction, hw_measure: bool) -> Result<Step, QecError> {
+        self.execute_wide(&Instruction32::from(*instr), hw_measure)
+    }
+
+    /// Executes a single instruction with 32-bit operands.
+    ///
+    /// Behaves like `execute`; used for detector and qubit indices beyond
+    /// 65535. A bare `Opcode::Wide` is rejected with `InvalidInstruction`,
+    /// since the prefix only has meaning inside an instruction stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `instr` - Instruction to execute
+    /// * `hw_measure` - Actual measurement result from hardware
+    ///
+    /// # Returns
+    ///
+    /// As for `execute`.
+    pub fn execute_wide(
+        &mut self,
+        instr: &Instruction32,
+        hw_measure: bool,
+    ) -> Result<Step, QecError> {
         let op = instr.opcode;
+        let opcode =
+            Opcode::try_from(op).map_err(|_| QecError::InvalidInstruction { opcode: op })?;
+        self.check_operands(opcode, instr)?;
+        self.dispatch(opcode, instr, hw_measure)
+    }
 
-        if op == Opcode::GateH as u8 {
-            self.frame.apply_hadamard(instr.operand_1 as usize);
-        } else if op == Opcode::GateCNOT as u8 {
-            self.frame
-                .apply_cnot(instr.operand_1 as usize, instr.operand_2 as usize);
-        } else if op == Opcode::Measure as u8 {
-            let expected = self.frame.has_x_error(instr.operand_1 as usize);
-            let is_syndrome = expected ^ hw_measure;
-            if is_syndrome {
-                self.syndrome_buffer.push(instr.operand_2 as usize);
-            }
-        } else if op == Opcode::Decode as u8 {
-            self.decoder.solve_into(
-                self.graph,
-                &self.syndrome_buffer,
-                &mut self.correction_buffer,
-            )?;
-            self.syndrome_buffer.clear();
-        } else if op == Opcode::Reset as u8 {
-            self.frame.reset();
-            self.syndrome_buffer.clear();
+    /// Executes an instruction whose operands have already been checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - Decoded opcode of `instr`
+    /// * `instr` - Instruction to execute
+    /// * `hw_measure` - Actual measurement result from hardware
+    ///
+    /// # Returns
+    ///
+    /// As for `execute`, except that operands are not bounds-checked.
+    fn dispatch(
+        &mut self,
+        opcode: Opcode,
+        instr: &Instruction32,
+        hw_measure: bool,
+    ) -> Result<Step, QecError> {
+        let op = instr.opcode;
+        match opcode {
+            Opcode::GateH => {
+                self.frame.apply_hadamard(instr.operand_1 as usize);
+                self.stats.gates += 1;
+                self.trace(instr, TraceEvent::GateApplied);
+            }
+            Opcode::GateS => {
+                self.frame.apply_phase(instr.operand_1 as usize);
+                self.stats.gates += 1;
+                self.trace(instr, TraceEvent::GateApplied);
+            }
+            Opcode::GateSdg => {
+                self.frame.apply_phase_dag(instr.operand_1 as usize);
+                self.stats.gates += 1;
+                self.trace(instr, TraceEvent::GateApplied);
+            }
+            Opcode::GateX => {
+                self.frame.inject_x(instr.operand_1 as usize)?;
+                self.stats.gates += 1;
+                self.trace(instr, TraceEvent::GateApplied);
+            }
+            Opcode::GateZ => {
+                self.frame.inject_z(instr.operand_1 as usize)?;
+                self.stats.gates += 1;
+                self.trace(instr, TraceEvent::GateApplied);
+            }
+            Opcode::GateCNOT => {
+                let (control, target) = (instr.operand_1, instr.operand_2);
+                if control == target {
+                    return Err(QecError::InvalidInstruction { opcode: op });
+                }
+                self.frame.apply_cnot(control as usize, target as usize);
+                self.stats.gates += 1;
+                self.trace(instr, TraceEvent::GateApplied);
+            }
+            Opcode::Measure | Opcode::MeasureX => {
+                let basis = if opcode == Opcode::MeasureX {
+                    MeasBasis::X
+                } else {
+                    MeasBasis::Z
+                };
+                let expected = self.frame.predict_measurement(instr.operand_1 as usize, basis);
+                let is_syndrome = expected ^ hw_measure;
+                self.measurement_record.record_outcome(is_syndrome)?;
+                self.stats.measurements += 1;
+                self.trace(instr, TraceEvent::Measured { outcome: is_syndrome });
+                if is_syndrome {
+                    let detector = self.detector_base + instr.operand_2 as usize;
+                    self.syndrome_buffer.push_syndrome(detector)?;
+                    self.stats.syndromes_recorded += 1;
+                    self.trace(instr, TraceEvent::SyndromeRecorded { detector });
+                }
+            }
+            Opcode::ObservableInclude => {
+                let expected = self
+                    .frame
+                    .predict_measurement(instr.operand_1 as usize, MeasBasis::Z);
+                let outcome = expected ^ hw_measure;
+                self.measurement_record.record_outcome(outcome)?;
+                self.stats.measurements += 1;
+                self.trace(instr, TraceEvent::Measured { outcome });
+                if outcome {
+                    self.observables ^= 1 << instr.operand_2;
+                    let observables = self.observables;
+                    self.trace(instr, TraceEvent::ObservableFlipped { observables });
+                }
+            }
+            Opcode::Decode => {
+                self.decode_pending(instr)?;
+                self.stats.decodes += 1;
+                if self.auto_apply_corrections {
+                    self.apply_pending_corrections()?;
+                }
+            }
+            Opcode::Barrier => {
+                self.decode_pending(instr)?;
+                self.apply_pending_corrections()?;
+                let tag = instr.operand_1 as u16;
+                self.stats.barriers += 1;
+                self.stats.last_barrier_tag = Some(tag);
+                self.trace(instr, TraceEvent::Barrier { tag });
+            }
+            Opcode::CondPauliX | Opcode::CondPauliZ => {
+                let record_idx = instr.operand_2 as usize;
+                let fired = self.measurement_record.outcome(record_idx).ok_or(
+                    QecError::OperandOutOfBounds {
+                        opcode: op,
+                        operand: instr.operand_2,
+                        limit: self.measurement_record.outcome_count(),
+                    },
+                )?;
+                self.stats.conditionals += 1;
+                if fired {
+                    let q = instr.operand_1 as usize;
+                    if opcode == Opcode::CondPauliX {
+                        self.frame.inject_x(q)?;
+                    } else {
+                        self.frame.inject_z(q)?;
+                    }
+                    self.trace(instr, TraceEvent::GateApplied);
+                } else {
+                    self.trace(instr, TraceEvent::ConditionSkipped);
+                }
+            }
+            Opcode::Reset => {
+                self.full_reset();
+                self.stats.control += 1;
+                self.trace(instr, TraceEvent::FrameReset);
+            }
+            Opcode::Halt => {
+                self.stats.control += 1;
+                self.trace(instr, TraceEvent::Halted);
+                return Ok(Step::Halt);
+            }
+            Opcode::LoopBegin => {
+                self.stats.control += 1;
+                return Ok(Step::LoopBegin {
+                    count: instr.operand_1,
+                });
+            }
+            Opcode::LoopEnd => {
+                self.stats.control += 1;
+                return Ok(Step::LoopEnd);
+            }
+            Opcode::Wide => return Err(QecError::InvalidInstruction { opcode: op }),
+            Opcode::ShiftDetectors => {
+                self.detector_base += instr.operand_1 as usize;
+                self.stats.control += 1;
+                let base = self.detector_base;
+                self.trace(instr, TraceEvent::DetectorsShifted { base });
+            }
         }
 
-        Ok(())
+        Ok(Step::Continue)
+    }
+}
+
+/// Checks that a graph fits in a decoder of capacity `N`.
+///
+/// The decoder indexes its per-node state without bounds checks, so every
+/// way of giving the VM a graph goes through this.
+///
+/// # Returns
+///
+/// Ok(()) if the graph has at most `N` nodes, `NodeOutOfBounds` otherwise.
+fn check_capacity<const N: usize>(graph: &impl EdgeList) -> Result<(), QecError> {
+    if graph.num_nodes() > N {
+        return Err(QecError::NodeOutOfBounds);
+    }
+    Ok(())
+}
+
+/// Converts an instruction stream error into the VM's error type.
+fn isa_to_qec(err: IsaError) -> QecError {
+    match err {
+        IsaError::UnknownOpcode(opcode) => QecError::InvalidInstruction { opcode },
+        IsaError::TrailingBytes { count } => QecError::TruncatedProgram { trailing: count },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+    use alloc::alloc::Global;
+    use qcu_common::isa::WIDE_INSTRUCTION_SIZE;
+
+    /// Decoder capacity used by the test VMs.
+    const N: usize = 256;
+
+    /// Decoder capacity for the tests that need detector indices past the
+    /// 16-bit operand range.
+    const WIDE_N: usize = 100_032;
+
+    /// Runs `f` on a thread with a stack large enough for a `WIDE_N` VM.
+    fn on_big_stack(f: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    /// Builds a 1D chain of `nodes` detectors.
+    fn chain(nodes: usize) -> DecodingGraph {
+        let mut graph = DecodingGraph::new(nodes);
+        for u in 0..nodes - 1 {
+            graph.add_edge(u, u + 1, 1.0).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn out_of_range_qubit_is_rejected_without_touching_the_frame() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 100).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.frame.inject_x(7).unwrap();
+        vm.frame.inject_z(42).unwrap();
+        let before = vm.frame.clone_frame(&Global).unwrap();
+
+        for opcode in [Opcode::GateH, Opcode::GateS, Opcode::GateX, Opcode::GateZ] {
+            let err = vm
+                .execute(&Instruction::new(opcode, 0xFFFF, 0), false)
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                QecError::OperandOutOfBounds { opcode: op, operand: 0xFFFF, limit: 100 }
+                    if op == opcode as u8
+            ));
+        }
+        let err = vm
+            .execute(&Instruction::new(Opcode::GateCNOT, 3, 0xFFFF), false)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds {
+                operand: 0xFFFF,
+                ..
+            }
+        ));
+
+        assert!(vm.frame.eq_state(&before));
+        assert_eq!(vm.stats().gates, 0);
+    }
+
+    #[test]
+    fn conditional_pauli_on_unrecorded_measurement_reports_operand() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 4).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.execute(&Instruction::new(Opcode::Measure, 0, 0), true)
+            .unwrap();
+
+        for opcode in [Opcode::CondPauliX, Opcode::CondPauliZ] {
+            let err = vm
+                .execute(&Instruction::new(opcode, 1, 5), false)
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                QecError::OperandOutOfBounds { opcode: op, operand: 5, limit: 1 }
+                    if op == opcode as u8
+            ));
+        }
+        assert!(vm.frame.is_clean());
+
+        vm.execute(&Instruction::new(Opcode::CondPauliX, 1, 0), false)
+            .unwrap();
+        assert!(vm.frame.has_x_error(1));
+    }
+
+    /// Encodes instructions, each in its shortest form.
+    fn encode(instrs: &[Instruction32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for instr in instrs {
+            let mut buf = [0; WIDE_INSTRUCTION_SIZE];
+            let n = instr.encode_into(&mut buf);
+            bytes.extend_from_slice(&buf[..n]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn wide_detector_index_runs_against_a_large_graph() {
+        on_big_stack(|| {
+            let graph = chain(100_010);
+            let frame = PauliFrame::new_in(&Global, 2).unwrap();
+            let mut vm = VirtualMachine::<WIDE_N>::with_frame(frame, &graph).unwrap();
+
+            let program = encode(&[
+                Instruction32::new(Opcode::Measure, 1, 100_000),
+                Instruction32::new(Opcode::Halt, 0, 0),
+            ]);
+            assert_eq!(program.len(), WIDE_INSTRUCTION_SIZE + INSTRUCTION_SIZE);
+
+            let exit = vm.run_bytes(&program, &mut core::iter::once(true)).unwrap();
+            assert!(matches!(
+                exit,
+                VmExit::Halted {
+                    pc: 2,
+                    executed: 2,
+                    measurements: 1,
+                }
+            ));
+            assert_eq!(vm.syndrome_buffer, [100_000]);
+
+            // Pair it with its neighbour and decode against the full graph.
+            vm.auto_apply_corrections = false;
+            let program = encode(&[
+                Instruction32::new(Opcode::Measure, 1, 100_001),
+                Instruction32::new(Opcode::Decode, 0, 0),
+                Instruction32::new(Opcode::Halt, 0, 0),
+            ]);
+            vm.run_bytes(&program, &mut core::iter::once(true)).unwrap();
+            assert!(vm.syndrome_buffer.is_empty());
+            assert!(vm.correction_buffer.contains(&(100_000, 100_001)));
+            assert!(
+                vm.correction_buffer
+                    .iter()
+                    .all(|&(u, v)| (99_999..=100_002).contains(&u.min(v)))
+            );
+            assert_eq!(vm.stats().decodes, 1);
+
+            let program = encode(&[Instruction32::new(Opcode::Measure, 1, 200_000)]);
+            let err = vm
+                .run_bytes(&program, &mut core::iter::once(true))
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                QecError::OperandOutOfBounds {
+                    operand: 200_000,
+                    limit: 100_010,
+                    ..
+                }
+            ));
+        });
+    }
+
+    #[test]
+    fn zero_count_loop_skips_wide_instructions() {
+        on_big_stack(|| {
+            let graph = chain(100_010);
+            let frame = PauliFrame::new_in(&Global, 2).unwrap();
+            let mut vm = VirtualMachine::<WIDE_N>::with_frame(frame, &graph).unwrap();
+
+            // The skipped LoopBegin carries an explicit prefix, so its base slot
+            // must not be counted as a second, nested LoopBegin.
+            let mut program = Instruction32::new(Opcode::LoopBegin, 0, 0)
+                .to_bytes()
+                .to_vec();
+            program.extend(encode(&[
+                Instruction32::new(Opcode::LoopBegin, 70_000, 0),
+                Instruction32::new(Opcode::Measure, 1, 100_000),
+                Instruction32::new(Opcode::LoopEnd, 0, 0),
+                Instruction32::new(Opcode::LoopEnd, 0, 0),
+                Instruction32::new(Opcode::Measure, 0, 7),
+                Instruction32::new(Opcode::Halt, 0, 0),
+            ]));
+
+            let exit = vm
+                .run_bytes(&program, &mut core::iter::repeat(true))
+                .unwrap();
+            assert!(matches!(
+                exit,
+                VmExit::Halted {
+                    measurements: 1,
+                    ..
+                }
+            ));
+            assert_eq!(vm.syndrome_buffer, [7]);
+        });
+    }
+
+    #[test]
+    fn constructors_reject_a_graph_larger_than_the_decoder() {
+        let graph = chain(N + 1);
+        let frame = PauliFrame::new_in(&Global, 2).unwrap();
+        assert!(matches!(
+            VirtualMachine::<N>::with_frame(frame, &graph),
+            Err(QecError::NodeOutOfBounds)
+        ));
+        let alloc = BumpAllocator::from_slice(Vec::leak(alloc::vec![0u8; 256]));
+        assert!(matches!(
+            VirtualMachine::<N>::new(&alloc, &graph, 2),
+            Err(QecError::NodeOutOfBounds)
+        ));
+        assert!(matches!(
+            VirtualMachine::<N>::new_static(&alloc, &graph, 2),
+            Err(QecError::NodeOutOfBounds)
+        ));
+
+        let fits = chain(N);
+        assert!(VirtualMachine::<N>::new_static(&alloc, &fits, 2).is_ok());
+    }
+
+    #[test]
+    fn detector_limit_is_capped_at_the_decoder_capacity() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 2).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.num_detectors = N + 100;
+
+        let err = vm
+            .execute(&Instruction::new(Opcode::Measure, 0, N as u16), true)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds { limit, .. } if limit == N
+        ));
+
+        let instrs = [
+            Instruction::new(Opcode::Measure, 0, N as u16),
+            Instruction::new(Opcode::Halt, 0, 0),
+        ];
+        let program = Program::validate(&instrs, 2, N + 100).unwrap();
+        let err = vm
+            .run_program(&program, &mut core::iter::repeat(true))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds { limit, .. } if limit == N
+        ));
+    }
+
+    #[test]
+    fn program_runs_every_opcode_and_stops_at_halt() {
+        use Opcode::*;
+
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 4).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+
+        let program = [
+            Instruction::new(Reset, 0, 0),
+            Instruction::new(GateX, 0, 0),
+            Instruction::new(GateH, 0, 0),
+            Instruction::new(GateS, 1, 0),
+            Instruction::new(GateSdg, 1, 0),
+            Instruction::new(GateZ, 1, 0),
+            Instruction::new(GateCNOT, 2, 1),
+            Instruction::new(Measure, 3, 0),
+            Instruction::new(MeasureX, 2, 1),
+            Instruction::new(ObservableInclude, 3, 0),
+            Instruction::new(CondPauliX, 3, 0),
+            Instruction::new(CondPauliZ, 3, 2),
+            Instruction::new(LoopBegin, 2, 0),
+            Instruction::new(ShiftDetectors, 5, 0),
+            Instruction::new(LoopEnd, 0, 0),
+            Instruction::new(Decode, 0, 0),
+            Instruction::new(Barrier, 7, 0),
+            Instruction::new(Halt, 0, 0),
+            Instruction::new(GateX, 3, 0),
+        ];
+        let mut measurements = [true, false, false].into_iter();
+        let exit = vm.run_instructions(&program, &mut measurements).unwrap();
+
+        // Two loop iterations execute the body twice.
+        assert_eq!(
+            exit,
+            VmExit::Halted {
+                pc: 17,
+                executed: 20,
+                measurements: 3,
+            }
+        );
+        assert_eq!(measurements.next(), None);
+
+        // H moved the X on qubit 0 to Z, GateZ and the CNOT put Z on 1 and
+        // 2, CondPauliX fired on the first record entry, and the decoded
+        // (0, 1) correction toggled X on qubits 0 and 1. The GateX after
+        // Halt did not run.
+        assert_eq!(vm.frame.x_register, [0b1011]);
+        assert_eq!(vm.frame.z_register, [0b0111]);
+        assert_eq!(vm.measurement_record, [true, true, false]);
+        assert!(vm.syndrome_buffer.is_empty());
+        assert!(vm.correction_buffer.is_empty());
+        assert_eq!(vm.detector_base, 10);
+        assert_eq!(vm.observables(), 0);
+
+        let stats = vm.stats();
+        assert_eq!(stats.gates, 6);
+        assert_eq!(stats.measurements, 3);
+        assert_eq!(stats.syndromes_recorded, 2);
+        assert_eq!(stats.conditionals, 2);
+        assert_eq!(stats.decodes, 1);
+        assert_eq!(stats.barriers, 1);
+        assert_eq!(stats.last_barrier_tag, Some(7));
+        assert_eq!(stats.corrections_applied, 1);
+
+        // Without a Halt the run ends at the last instruction.
+        let exit = vm
+            .run_instructions(&program[..3], &mut core::iter::empty())
+            .unwrap();
+        assert_eq!(
+            exit,
+            VmExit::RanOffEnd {
+                executed: 3,
+                measurements: 0,
+            }
+        );
+        assert_eq!(vm.frame.x_register, [0]);
+        assert_eq!(vm.frame.z_register, [1]);
+    }
+
+    #[test]
+    fn every_declared_opcode_is_dispatched() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 4).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+
+        for &opcode in Opcode::ALL {
+            if matches!(opcode, Opcode::CondPauliX | Opcode::CondPauliZ) {
+                // Reset comes earlier in `ALL`, so record an entry to read.
+                vm.execute(&Instruction::new(Opcode::Measure, 0, 0), false)
+                    .unwrap();
+            }
+            let instr = match opcode {
+                Opcode::GateCNOT => Instruction::new(opcode, 0, 1),
+                Opcode::LoopBegin => Instruction::new(opcode, 3, 0),
+                _ => Instruction::new(opcode, 1, 0),
+            };
+            let before = vm.stats().clone();
+            let result = vm.execute(&instr, false);
+
+            let expected = match opcode {
+                Opcode::Halt => Step::Halt,
+                Opcode::LoopBegin => Step::LoopBegin { count: 3 },
+                Opcode::LoopEnd => Step::LoopEnd,
+                Opcode::Wide => {
+                    assert!(matches!(
+                        result,
+                        Err(QecError::InvalidInstruction { opcode: 0x60 })
+                    ));
+                    assert_eq!(*vm.stats(), before);
+                    continue;
+                }
+                _ => Step::Continue,
+            };
+            assert_eq!(result.unwrap(), expected, "{:?}", opcode);
+            assert_eq!(
+                vm.stats().instructions(),
+                before.instructions() + 1,
+                "{:?} was not counted",
+                opcode
+            );
+        }
+
+        // GateS acts on the frame rather than being skipped.
+        vm.full_reset();
+        vm.frame.inject_x(2).unwrap();
+        vm.execute(&Instruction::new(Opcode::GateS, 2, 0), false)
+            .unwrap();
+        assert!(vm.frame.has_z_error(2));
+
+        for byte in (0..=u8::MAX).filter(|&b| Opcode::try_from(b).is_err()) {
+            let instr = Instruction32 {
+                opcode: byte,
+                flags: 0,
+                operand_1: 0,
+                operand_2: 0,
+            };
+            assert!(matches!(
+                vm.execute_wide(&instr, false),
+                Err(QecError::InvalidInstruction { opcode }) if opcode == byte
+            ));
+        }
+    }
+
+    impl Rng {
+        /// Returns a 16-bit operand, small most of the time so that valid
+        /// instructions are generated as well as out-of-range ones.
+        fn operand(&mut self) -> u16 {
+            let raw = self.next() as u16;
+            if self.next().is_multiple_of(4) {
+                raw
+            } else {
+                raw % 8
+            }
+        }
+    }
+
+    #[test]
+    fn random_instructions_are_classified_without_panicking() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 4).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+
+        for _ in 0..20_000 {
+            let mut bytes = [0u8; INSTRUCTION_SIZE];
+            bytes[0] = rng.next() as u8;
+            bytes[2..4].copy_from_slice(&rng.operand().to_le_bytes());
+            bytes[4..6].copy_from_slice(&rng.operand().to_le_bytes());
+
+            let known = Opcode::try_from(bytes[0]).ok();
+            let instr = match Instruction::from_bytes(&bytes) {
+                Ok(instr) => instr,
+                Err(_) => {
+                    assert!(known.is_none());
+                    continue;
+                }
+            };
+            let frame = vm.frame.clone_frame(&Global).unwrap();
+            let record = vm.measurement_record.len();
+
+            match vm.execute(&instr, rng.next() & 1 == 1) {
+                Ok(_) => assert!(known.is_some_and(|op| op != Opcode::Wide)),
+                Err(QecError::InvalidInstruction { opcode }) => {
+                    assert_eq!(opcode, bytes[0]);
+                    assert!(
+                        matches!(known, None | Some(Opcode::Wide))
+                            || (known == Some(Opcode::GateCNOT)
+                                && instr.operand_1 == instr.operand_2)
+                    );
+                }
+                Err(QecError::OperandOutOfBounds { opcode, .. }) => {
+                    assert_eq!(opcode, bytes[0]);
+                }
+                Err(other) => panic!("{:?} for {:02x?}", other, bytes),
+            }
+            if vm.stats().instructions() == 0 {
+                assert!(vm.frame.eq_state(&frame));
+                assert_eq!(vm.measurement_record.len(), record);
+            }
+            vm.reset_stats();
+        }
+    }
+
+    #[test]
+    fn random_byte_streams_are_rejected_or_run_without_panicking() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 4).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+        for _ in 0..2_000 {
+            let slots = (rng.next() % 16) as usize;
+            let len = slots * INSTRUCTION_SIZE + rng.next().is_multiple_of(4) as usize;
+            let mut bytes = Vec::with_capacity(len);
+            while bytes.len() < len {
+                // Half the opcodes are valid ones. Wide prefixes are left out
+                // so that loop counts stay small.
+                let opcode = if rng.next() & 1 == 0 {
+                    Opcode::ALL[rng.next() as usize % Opcode::ALL.len()] as u8
+                } else {
+                    rng.next() as u8
+                };
+                let opcode = if opcode == Opcode::Wide as u8 {
+                    0
+                } else {
+                    opcode
+                };
+                bytes.push(opcode);
+                bytes.push(0);
+                bytes.extend_from_slice(&rng.operand().to_le_bytes());
+                bytes.extend_from_slice(&rng.operand().to_le_bytes());
+            }
+            bytes.truncate(len);
+
+            vm.full_reset();
+            match vm.run_bytes(&bytes, &mut core::iter::repeat(true)) {
+                Ok(exit) => assert!(exit.executed() > 0 || bytes.is_empty()),
+                Err(QecError::TruncatedProgram { trailing }) => {
+                    assert_eq!(trailing, len % INSTRUCTION_SIZE);
+                }
+                Err(QecError::InvalidInstruction { opcode }) => {
+                    assert!(
+                        Opcode::try_from(opcode).is_err()
+                            || matches!(
+                                Opcode::try_from(opcode),
+                                Ok(Opcode::GateCNOT | Opcode::LoopBegin | Opcode::LoopEnd)
+                            )
+                    );
+                }
+                Err(
+                    QecError::OperandOutOfBounds { .. }
+                    | QecError::BufferOverflow
+                    | QecError::MeasurementUnderflow,
+                ) => {}
+                Err(other) => panic!("{:?} for {:02x?}", other, bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn measurement_record_holds_frame_adjusted_outcomes() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 3).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.frame.inject_x(1).unwrap();
+        vm.frame.inject_z(2).unwrap();
+
+        let program = [
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::Measure, 1, 1),
+            Instruction::new(Opcode::MeasureX, 2, 2),
+            Instruction::new(Opcode::Halt, 0, 0),
+        ];
+        // Qubit 1's flip is predicted by the frame, qubit 2's is not
+        // reported by the hardware, so only those outcomes differ from the
+        // raw results.
+        let mut raw = [false, true, false].into_iter();
+        let exit = vm.run_instructions(&program, &mut raw).unwrap();
+        assert_eq!(exit.measurements(), 3);
+        assert_eq!(vm.syndrome_buffer, [2]);
+
+        assert_eq!(vm.take_measurements(), [false, false, true]);
+        assert!(vm.measurement_record.is_empty());
+
+        // The record restarts after being taken.
+        let exit = vm
+            .run_instructions(&program[..1], &mut core::iter::once(true))
+            .unwrap();
+        assert_eq!(exit.measurements(), 1);
+        assert_eq!(vm.take_measurements(), [true]);
+    }
+
+    #[test]
+    fn conditional_paulis_feed_forward_both_outcomes() {
+        // Teleportation-style correction: X on qubit 2 if the Z measurement
+        // of qubit 0 fired, Z if the X measurement of qubit 1 fired.
+        let program = [
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::MeasureX, 1, 1),
+            Instruction::new(Opcode::CondPauliX, 2, 0),
+            Instruction::new(Opcode::CondPauliZ, 2, 1),
+            Instruction::new(Opcode::Halt, 0, 0),
+        ];
+        let graph = chain(16);
+        for (m0, m1) in [(false, false), (true, false), (false, true), (true, true)] {
+            let frame = PauliFrame::new_in(&Global, 3).unwrap();
+            let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+            vm.run_instructions(&program, &mut [m0, m1].into_iter())
+                .unwrap();
+
+            assert_eq!(vm.frame.has_x_error(2), m0);
+            assert_eq!(vm.frame.has_z_error(2), m1);
+            assert_eq!(vm.frame.total_weight(), (m0 || m1) as usize);
+            assert_eq!(vm.stats().conditionals, 2);
+        }
+
+        // A record index that has not been measured yet is an error.
+        let frame = PauliFrame::new_in(&Global, 3).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        let err = vm
+            .run_instructions(&program[2..], &mut core::iter::empty())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds { operand: 0, limit: 0, .. }
+        ));
+        assert!(vm.frame.is_clean());
+    }
+
+    /// VM with fixed-capacity buffers, as a firmware hart would own.
+    type StaticVm<'a> = VirtualMachine<
+        'a,
+        N,
+        DecodingGraph,
+        StaticVec<usize, 8>,
+        StaticVec<(usize, usize), 8>,
+        StaticVec<bool, 8>,
+    >;
+
+    #[test]
+    fn static_buffer_vm_decodes_from_an_arena() {
+        let alloc = BumpAllocator::from_slice(Vec::leak(alloc::vec![0u8; 64]));
+        let graph = chain(16);
+        let mut vm = StaticVm::new_static(&alloc, &graph, 16).unwrap();
+        // Two 16-qubit registers, one word each.
+        assert_eq!(alloc.used(), 16);
+
+        let program = [
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::Measure, 1, 1),
+            Instruction::new(Opcode::Decode, 0, 0),
+            Instruction::new(Opcode::Halt, 0, 0),
+        ];
+        let exit = vm
+            .run_instructions(&program, &mut core::iter::repeat(true))
+            .unwrap();
+        assert_eq!(exit.measurements(), 2);
+        assert!(vm.syndrome_buffer.is_empty());
+        assert!(vm.correction_buffer.is_empty());
+        assert_eq!(vm.frame.x_register, [0b11]);
+        assert_eq!(vm.measurement_record.as_slice(), [true, true]);
+
+        // The fixed capacities are enforced rather than grown.
+        vm.full_reset();
+        let mut overflow = Ok(Step::Continue);
+        for q in 0..9 {
+            overflow = vm.execute(&Instruction::new(Opcode::Measure, q, q), true);
+        }
+        assert!(matches!(overflow, Err(QecError::BufferOverflow)));
+        assert_eq!(vm.syndrome_buffer.len(), 8);
+    }
+
+    #[test]
+    fn applied_corrections_silence_the_next_round() {
+        // Qubits 0 and 1 carry a persistent X error, so the hardware
+        // reports both measurements flipped on every round.
+        let round = [
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::Measure, 1, 1),
+            Instruction::new(Opcode::Decode, 0, 0),
+        ];
+        let graph = chain(16);
+
+        let frame = PauliFrame::new_in(&Global, 2).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.run_instructions(&round, &mut core::iter::repeat(true))
+            .unwrap();
+        assert_eq!(vm.stats().syndromes_recorded, 2);
+        assert_eq!(vm.stats().corrections_applied, 1);
+        assert!(vm.correction_buffer.is_empty());
+        assert_eq!(vm.frame.x_register, [0b11]);
+
+        vm.run_instructions(&round, &mut core::iter::repeat(true))
+            .unwrap();
+        assert_eq!(vm.stats().syndromes_recorded, 2);
+        assert_eq!(vm.stats().corrections_applied, 1);
+        assert_eq!(vm.take_measurements(), [true, true, false, false]);
+
+        // Without automatic application the frame never learns about the
+        // error and the same syndrome comes back.
+        let frame = PauliFrame::new_in(&Global, 2).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.auto_apply_corrections = false;
+        for _ in 0..2 {
+            vm.run_instructions(&round, &mut core::iter::repeat(true))
+                .unwrap();
+        }
+        assert_eq!(vm.stats().syndromes_recorded, 4);
+        assert_eq!(vm.stats().corrections_applied, 0);
+        assert_eq!(vm.correction_buffer, [(0, 1)]);
+        assert!(vm.frame.is_clean());
+    }
+
+    #[test]
+    fn reset_leaves_nothing_for_the_next_shot() {
+        let shot_a = [
+            Instruction::new(Opcode::ShiftDetectors, 2, 0),
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::Measure, 1, 1),
+            Instruction::new(Opcode::ObservableInclude, 1, 3),
+            Instruction::new(Opcode::Decode, 0, 0),
+            Instruction::new(Opcode::Measure, 0, 5),
+        ];
+        let shot_b = [Instruction::new(Opcode::Decode, 0, 0)];
+        let graph = chain(16);
+
+        for via_instruction in [false, true] {
+            let frame = PauliFrame::new_in(&Global, 2).unwrap();
+            let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+            vm.auto_apply_corrections = false;
+            vm.frame.inject_z(0).unwrap();
+            vm.run_instructions(&shot_a, &mut core::iter::repeat(true))
+                .unwrap();
+            assert!(!vm.correction_buffer.is_empty());
+            assert_eq!(vm.syndrome_buffer, [7]);
+            assert_eq!(vm.observables(), 1 << 3);
+
+            if via_instruction {
+                vm.execute(&Instruction::new(Opcode::Reset, 0, 0), false)
+                    .unwrap();
+            } else {
+                vm.full_reset();
+            }
+            assert!(vm.frame.is_clean());
+            assert!(vm.syndrome_buffer.is_empty());
+            assert!(vm.correction_buffer.is_empty());
+            assert!(vm.measurement_record.is_empty());
+            assert_eq!(vm.observables(), 0);
+            assert_eq!(vm.detector_base, 0);
+
+            vm.run_instructions(&shot_b, &mut core::iter::empty())
+                .unwrap();
+            assert!(vm.correction_buffer.is_empty());
+            assert!(vm.frame.is_clean());
+        }
+    }
+
+    /// Observable map in which only edge (0, 1) crosses observable 2.
+    fn edge_01_flips_observable_2(u: usize, v: usize) -> u64 {
+        if (u.min(v), u.max(v)) == (0, 1) {
+            1 << 2
+        } else {
+            0
+        }
+    }
+
+    #[test]
+    fn observables_follow_corrections_and_included_measurements() {
+        let graph = chain(16);
+        let frame = PauliFrame::new_in(&Global, 2).unwrap();
+        let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+        vm.set_observable_map(edge_01_flips_observable_2);
+
+        // An error between detectors 0 and 1 is decoded and corrected
+        // across observable 2.
+        let round = [
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::Measure, 1, 1),
+            Instruction::new(Opcode::Decode, 0, 0),
+        ];
+        vm.run_instructions(&round, &mut core::iter::repeat(true))
+            .unwrap();
+        assert_eq!(vm.observables(), 1 << 2);
+
+        // The correction put X on qubit 1, so a raw 0 from the hardware is
+        // a frame-adjusted 1 and flips observable 5; a raw 1 does not.
+        let include = Instruction::new(Opcode::ObservableInclude, 1, 5);
+        vm.execute(&include, false).unwrap();
+        assert_eq!(vm.observables(), 1 << 2 | 1 << 5);
+        vm.execute(&include, true).unwrap();
+        assert_eq!(vm.observables(), 1 << 2 | 1 << 5);
+        assert!(vm.syndrome_buffer.is_empty());
+
+        let err = vm
+            .execute(&Instruction::new(Opcode::ObservableInclude, 1, 64), false)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            QecError::OperandOutOfBounds { operand: 64, limit: 64, .. }
+        ));
+        assert_eq!(vm.observables(), 1 << 2 | 1 << 5);
+    }
+
+    #[test]
+    fn x_stabiliser_round_detects_z_errors_only() {
+        // Data qubits 0..3; ancilla 3 checks X0 X1 and ancilla 4 checks
+        // X1 X2. A Z error on a data qubit is copied onto every ancilla
+        // controlling it and shows up in that ancilla's X measurement.
+        let round = [
+            Instruction::new(Opcode::GateCNOT, 3, 0),
+            Instruction::new(Opcode::GateCNOT, 3, 1),
+            Instruction::new(Opcode::GateCNOT, 4, 1),
+            Instruction::new(Opcode::GateCNOT, 4, 2),
+            Instruction::new(Opcode::MeasureX, 3, 0),
+            Instruction::new(Opcode::MeasureX, 4, 1),
+        ];
+        let graph = chain(16);
+        let cases: [(&[Instruction], &[usize]); 6] = [
+            (&[], &[]),
+            (&[Instruction::new(Opcode::GateZ, 0, 0)], &[0]),
+            (&[Instruction::new(Opcode::GateZ, 1, 0)], &[0, 1]),
+            (&[Instruction::new(Opcode::GateZ, 2, 0)], &[1]),
+            (&[Instruction::new(Opcode::GateX, 1, 0)], &[]),
+            (
+                &[
+                    Instruction::new(Opcode::GateZ, 1, 0),
+                    Instruction::new(Opcode::GateX, 1, 0),
+                    Instruction::new(Opcode::GateZ, 1, 0),
+                ],
+                &[],
+            ),
+        ];
+
+        for (errors, detectors) in cases {
+            let frame = PauliFrame::new_in(&Global, 5).unwrap();
+            let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+            let mut program = errors.to_vec();
+            program.extend_from_slice(&round);
+            // The hardware reports no flips, so every detector that fires
+            // comes from an error the frame knows about.
+            let exit = vm
+                .run_instructions(&program, &mut core::iter::repeat(false))
+                .unwrap();
+            assert_eq!(exit.measurements(), 2);
+            assert_eq!(vm.syndrome_buffer, detectors, "{:?}", errors);
+        }
+    }
+
+    #[test]
+    fn looped_rounds_match_the_unrolled_program() {
+        let looped = [
+            Instruction::new(Opcode::LoopBegin, 3, 0),
+            Instruction::new(Opcode::Measure, 0, 0),
+            Instruction::new(Opcode::Measure, 1, 1),
+            Instruction::new(Opcode::ShiftDetectors, 2, 0),
+            Instruction::new(Opcode::LoopEnd, 0, 0),
+            Instruction::new(Opcode::Halt, 0, 0),
+        ];
+        let mut unrolled = Vec::new();
+        for round in 0..3 {
+            unrolled.push(Instruction::new(Opcode::Measure, 0, 2 * round));
+            unrolled.push(Instruction::new(Opcode::Measure, 1, 2 * round + 1));
+        }
+        unrolled.push(Instruction::new(Opcode::Halt, 0, 0));
+
+        let graph = chain(16);
+        let raw = [true, true, false, true, true, false];
+        let run = |program: &[Instruction], validated: bool| {
+            let frame = PauliFrame::new_in(&Global, 2).unwrap();
+            let mut vm = VirtualMachine::<N>::with_frame(frame, &graph).unwrap();
+            vm.frame.inject_x(1).unwrap();
+            let exit = if validated {
+                let program = Program::validate(program, 2, 16).unwrap();
+                vm.run_program(&program, &mut raw.into_iter())
+            } else {
+                vm.run_instructions(program, &mut raw.into_iter())
+            }
+            .unwrap();
+            assert_eq!(exit.measurements(), 6);
+            (vm.syndrome_buffer.clone(), vm.take_measurements())
+        };
+
+        let expected = run(&unrolled, false);
+        assert_eq!(expected.0, [0, 4, 5]);
+        assert_eq!(run(&looped, false), expected);
+        assert_eq!(run(&looped, true), expected);
+        assert_eq!(run(&unrolled, true), expected);
+    }
+
+
+    #[test]
+    fn h_then_measure_x_matches_measure_on_the_pre_h_frame() {
+        // H maps Z to X, so measuring X after H reads the same bit as
+        // measuring Z before it, for every Pauli error and raw outcome.
+        let graph = chain(16);
+        let errors: [fn(&mut PauliFrame<'_>); 4] = [
+            |_| {},
+            |f| f.inject_x(0).unwrap(),
+            |f| f.inject_z(0).unwrap(),
+            |f| f.inject_y(0).unwrap(),
+        ];
+        for inject in errors {
+            for raw in [false, true] {
+                let vm = || {
+                    let mut frame = PauliFrame::new_in(&Global, 1).unwrap();
+                    inject(&mut frame);
+                    VirtualMachine::<N>::with_frame(frame, &graph).unwrap()
+                };
+                let (mut rotated, mut direct) = (vm(), vm());
+                rotated
+                    .execute(&Instruction::new(Opcode::GateH, 0, 0), false)
+                    .unwrap();
+                rotated
+                    .execute(&Instruction::new(Opcode::MeasureX, 0, 3), raw)
+                    .unwrap();
+                direct
+                    .execute(&Instruction::new(Opcode::Measure, 0, 3), raw)
+                    .unwrap();
+
+                assert_eq!(rotated.measurement_record, direct.measurement_record);
+                assert_eq!(rotated.syndrome_buffer, direct.syndrome_buffer);
+            }
+        }
     }
 }
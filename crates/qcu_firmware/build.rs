@@ -4,11 +4,71 @@
 /// data from .b8 and .dem files. Converts binary measurement data into a
 /// Rust array of u64 words for efficient firmware access. If benchmark data
 /// files are missing, generates empty dummy data to allow compilation.
+use qcu_core::bit_utils::BitPack;
 use std::env;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+/// Environment variables read for a custom platform, in the argument order
+/// of `Platform::custom` with the heap range split into start and end.
+const PLATFORM_VARS: [&str; 8] = [
+    "QCU_CLINT_BASE",
+    "QCU_UART_BASE",
+    "QCU_ACCELERATOR_BASE",
+    "QCU_QPU_BASE",
+    "QCU_RAM_BASE",
+    "QCU_HEAP_START",
+    "QCU_HEAP_END",
+    "QCU_TIMER_HZ",
+];
+
+/// Generates the firmware's platform selection.
+///
+/// `QCU_PLATFORM` selects the target: unset or `qemu_virt` uses
+/// `Platform::qemu_virt()`, and `custom` builds a `Platform::custom` from the
+/// `PLATFORM_VARS` environment variables, each a decimal or 0x-prefixed
+/// hexadecimal integer. The output defines `PLATFORM` for inclusion in the
+/// firmware's `board` module.
+///
+/// # Arguments
+///
+/// * `dest` - Path of the generated source file
+fn write_platform(dest: &Path) {
+    println!("cargo:rerun-if-env-changed=QCU_PLATFORM");
+    for var in PLATFORM_VARS {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+
+    let platform = env::var("QCU_PLATFORM").unwrap_or_else(|_| "qemu_virt".into());
+    let expr = match platform.as_str() {
+        "qemu_virt" => "Platform::qemu_virt()".to_string(),
+        "custom" => {
+            let v: Vec<u64> = PLATFORM_VARS
+                .iter()
+                .map(|var| {
+                    let raw = env::var(var)
+                        .unwrap_or_else(|_| panic!("QCU_PLATFORM=custom requires {}", var));
+                    let parsed = match raw.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+                        None => raw.replace('_', "").parse(),
+                    };
+                    parsed.unwrap_or_else(|_| panic!("{}={:?} is not an integer", var, raw))
+                })
+                .collect();
+            format!(
+                "Platform::custom({:#x}, {:#x}, {:#x}, {:#x}, {:#x}, ({:#x}, {:#x}), {})",
+                v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]
+            )
+        }
+        other => panic!("unknown QCU_PLATFORM {:?} (expected qemu_virt or custom)", other),
+    };
+
+    let mut f = fs::File::create(dest).unwrap();
+    writeln!(f, "// GENERATED BY build.rs").unwrap();
+    writeln!(f, "pub const PLATFORM: Platform = {};", expr).unwrap();
+}
+
 /// Build script entry point for firmware compilation.
 ///
 /// Configures the linker with the memory layout script and generates embedded
@@ -24,6 +84,8 @@ fn main() {
     println!("cargo:rerun-if-changed=memory.x");
     println!("cargo:rustc-link-arg=-Tmemory.x");
 
+    write_platform(&out_dir.join("platform.rs"));
+
     let dest_path = out_dir.join("bench_data.rs");
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     let output_dir = Path::new(&manifest_dir).join("../../output");
@@ -40,12 +102,6 @@ fn main() {
     // The value of 160 words supports up to 10,240 detectors per shot.
     const WORDS_PER_SHOT: usize = 160;
 
-    // Size of a syndrome packet in bytes.
-    //
-    // Calculated as WORDS_PER_SHOT * 8 (bytes per u64 word). Used to allocate
-    // buffers for reading and processing syndrome data from the .b8 file.
-    const PACKET_SIZE: usize = WORDS_PER_SHOT * 8;
-
     if !b8_path.exists() || !dem_path.exists() {
         println!(
             "cargo:warning=Benchmark data not found at {}. Using empty dummy data.",
@@ -53,6 +109,7 @@ fn main() {
         );
         let mut f = fs::File::create(&dest_path).unwrap();
         writeln!(f, "pub const TOTAL_SHOTS: usize = 0;").unwrap();
+        writeln!(f, "pub const DETECTORS_PER_SHOT: usize = 0;").unwrap();
         writeln!(f, "pub const WORDS_PER_SHOT: usize = {};", WORDS_PER_SHOT).unwrap();
         writeln!(f, "#[unsafe(link_section = \".rodata\")]").unwrap();
         writeln!(f, "pub static BENCH_DATA: [u64; 0] = [];").unwrap();
@@ -72,20 +129,24 @@ fn main() {
     }
     let num_detectors = max_node_id + 1;
 
-    let raw_data = fs::read(&b8_path).expect("Failed to read .b8 file");
-
-    let bytes_per_shot_file = num_detectors.div_ceil(8);
-
-    let total_shots = if bytes_per_shot_file > 0 {
-        raw_data.len() / bytes_per_shot_file
-    } else {
-        0
-    };
+    // Packed with qcu_core rather than qcu_io's loader, so the firmware
+    // build does not pull in the host crate's file-format dependencies.
+    let b8 = fs::read(&b8_path).expect("Failed to read .b8 file");
+    let shots: Vec<Vec<u64>> = b8
+        .chunks_exact(num_detectors.div_ceil(8))
+        .map(|record| {
+            let mut words = vec![0; num_detectors.div_ceil(64)];
+            BitPack::pack_bytes(record, num_detectors, &mut words).unwrap();
+            words
+        })
+        .collect();
+    let total_shots = shots.len();
 
     let mut f = fs::File::create(&dest_path).unwrap();
 
     writeln!(f, "// GENERATED BY build.rs").unwrap();
     writeln!(f, "pub const TOTAL_SHOTS: usize = {};", total_shots).unwrap();
+    writeln!(f, "pub const DETECTORS_PER_SHOT: usize = {};", num_detectors).unwrap();
     writeln!(f, "pub const WORDS_PER_SHOT: usize = {};", WORDS_PER_SHOT).unwrap();
     writeln!(f, "#[unsafe(link_section = \".rodata\")]").unwrap();
     writeln!(
@@ -95,22 +156,11 @@ fn main() {
     )
     .unwrap();
 
-    for i in 0..total_shots {
-        let start = i * bytes_per_shot_file;
-        let end = start + bytes_per_shot_file;
-
-        if end > raw_data.len() {
-            break;
-        }
-
-        let shot_slice = &raw_data[start..end];
-        let mut buffer = [0u8; PACKET_SIZE];
-        let copy_len = shot_slice.len().min(PACKET_SIZE);
-        buffer[0..copy_len].copy_from_slice(&shot_slice[0..copy_len]);
-
+    for shot in &shots {
+        // Shots are zero-padded, or truncated, to the fixed packet width.
         write!(f, "    ").unwrap();
-        for chunk in buffer.chunks(8) {
-            let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        for i in 0..WORDS_PER_SHOT {
+            let word = shot.get(i).copied().unwrap_or(0);
             write!(f, "0x{:016x}, ", word).unwrap();
         }
         writeln!(f).unwrap();
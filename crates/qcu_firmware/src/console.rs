@@ -1,7 +1,7 @@
 //! UART console implementation for firmware debugging output.
 //!
-//! Provides a simple console interface that writes to the QEMU UART device
-//! at address 0x1000_0000. Uses a spinlock to ensure thread-safe output
+//! Provides a simple console interface that writes to the UART device
+//! at the selected platform's `uart_base`. Uses a spinlock to ensure thread-safe output
 //! when multiple cores attempt to print simultaneously.
 
 use core::cell::UnsafeCell;
@@ -130,11 +130,12 @@ impl<'a, T> Drop for SpinLockGuard<'a, T> {
     }
 }
 
-/// Memory-mapped address of the QEMU UART device.
+/// Memory-mapped address of the UART transmit register.
 ///
-/// Standard address for the UART on QEMU's RISC-V virt platform. Writing
-/// a byte to this address transmits it over the serial console.
-const UART0: *mut u8 = 0x1000_0000 as *mut u8;
+/// Taken from the build-time platform (0x1000_0000 on QEMU's RISC-V virt
+/// machine). Writing a byte to this address transmits it over the serial
+/// console.
+const UART0: *mut u8 = crate::board::PLATFORM.uart_base as *mut u8;
 
 /// Global spinlock protecting UART access.
 ///
@@ -195,6 +196,23 @@ pub fn _print(args: fmt::Arguments) {
     let _ = Uart.write_fmt(args);
 }
 
+/// Writes a pre-formatted line to the console in one locked burst.
+///
+/// Unlike `println!`, which formats while holding the console lock, this
+/// expects the caller to have formatted the text already (typically into a
+/// `StaticString`), so the lock is held only for the UART writes and the
+/// text and its trailing newline cannot be split by another core's output.
+///
+/// # Arguments
+///
+/// * `line` - Text to print, without the trailing newline
+pub fn write_line(line: &str) {
+    use fmt::Write;
+    let _guard = CONSOLE_LOCK.lock();
+    let _ = Uart.write_str(line);
+    let _ = Uart.write_str("\n");
+}
+
 /// Macro for printing a line to the console.
 ///
 /// Formats the arguments and prints them followed by a newline. Thread-safe
@@ -4,7 +4,7 @@
 //! operations. The accelerator performs path compression and union operations
 //! in hardware to reduce decoder latency compared to software implementations.
 
-use qcu_common::mmio::ACCELERATOR_BASE;
+use crate::board::PLATFORM;
 
 /// Hardware accelerator interface for decoder operations.
 ///
@@ -28,7 +28,7 @@ impl DecoderAccelerator {
     ///   detector indices that fired.
     /// * `result_ptr` must be a valid pointer to a writable result buffer large
     ///   enough to hold the correction edge pairs.
-    /// * The hardware accelerator must be mapped at `ACCELERATOR_BASE` and
+    /// * The hardware accelerator must be mapped at `PLATFORM.accelerator_base` and
     ///   ready to accept commands.
     ///
     /// # Arguments
@@ -37,7 +37,7 @@ impl DecoderAccelerator {
     /// * `result_ptr` - Pointer to the result buffer for corrections
     #[inline(always)]
     pub unsafe fn trigger_decode(syndrome_ptr: *const usize, result_ptr: *mut usize) {
-        let base = ACCELERATOR_BASE as *mut u32;
+        let base = PLATFORM.accelerator_base as *mut u32;
         let s_addr = syndrome_ptr as usize;
         let r_addr = result_ptr as usize;
 
@@ -60,7 +60,7 @@ impl DecoderAccelerator {
     /// blocking operation that spins until completion.
     #[inline(always)]
     pub fn poll_complete() {
-        let base = ACCELERATOR_BASE as *const u32;
+        let base = PLATFORM.accelerator_base as *const u32;
         unsafe {
             while base.add(1).read_volatile() & 1 == 1 {
                 core::hint::spin_loop();
@@ -15,11 +15,18 @@
 extern crate alloc;
 
 use core::cell::UnsafeCell;
+use core::fmt::Write;
 use core::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
-use qcu_core::allocator::BumpAllocator;
-use qcu_core::decoder::UnionFindDecoder;
+use board::PLATFORM;
+use qcu_core::QecError;
+use qcu_core::allocator::{BumpAllocator, BumpReport};
+use qcu_core::bit_utils::BitPack;
+use qcu_core::compact_graph::CompactDecodingGraph;
+use qcu_core::decoder::{CorrectionBuffer, UnionFindDecoder};
 use qcu_core::graph::DecodingGraph;
+use qcu_core::ring_buffer::StaticMpscRingBuffer;
 use qcu_core::spmc::StaticQueue;
+use qcu_core::static_string::StaticString;
 use qcu_core::static_vec::StaticVec;
 
 #[cfg(feature = "use_fpga_mmio")]
@@ -56,6 +63,20 @@ mod bench_data {
     include!(concat!(env!("OUT_DIR"), "/bench_data.rs"));
 }
 
+/// Build-time selected platform description.
+///
+/// Generated by build.rs from the `QCU_PLATFORM` environment variable.
+/// Every MMIO address and the timer frequency used by the firmware come from
+/// `PLATFORM`, so targeting another SoC is a rebuild rather than a code edit.
+mod board {
+    use qcu_common::platform::Platform;
+
+    include!(concat!(env!("OUT_DIR"), "/platform.rs"));
+
+    // Reject overlapping memory maps at compile time.
+    const _: () = assert!(PLATFORM.is_consistent());
+}
+
 /// Embedded decoding graph description in DEM format.
 ///
 /// Loaded at compile time from the output directory. Used to initialize
@@ -136,6 +157,13 @@ pub struct SyndromePacket {
     /// corresponding to detector 0, bit 1 to detector 1, etc. The array
     /// size is WORDS_PER_SHOT, supporting up to WORDS_PER_SHOT * 64 detectors.
     pub syndromes: [u64; WORDS_PER_SHOT],
+
+    /// Barrier tag carried by this packet, if it ends a round.
+    ///
+    /// When set, the worker that decodes the packet acknowledges it by
+    /// publishing the tag to `BARRIER_ACK` once decoding completes, so the
+    /// primary core can hold back the next round until it is observed.
+    pub barrier: Option<u16>,
 }
 
 /// Global job queue for distributing decoding work to worker cores.
@@ -168,26 +196,28 @@ static SYSTEM_READY: AtomicBool = AtomicBool::new(false);
 /// primary core's statistics loop.
 static TOTAL_PROCESSED: AtomicU64 = AtomicU64::new(0);
 
-/// Sum of all decoding latencies (for average calculation).
+/// Highest barrier tag acknowledged by a worker core, plus one.
 ///
-/// Accumulates the latency (in machine timer ticks) for each decoded packet.
-/// Reset periodically by the primary core when computing statistics, then
-/// divided by the number of processed packets to compute average latency.
-static LATENCY_SUM: AtomicU64 = AtomicU64::new(0);
+/// Zero means no barrier has completed yet. Workers raise it with
+/// `fetch_max` after decoding a packet that carries a barrier tag, using
+/// Release ordering so the primary core's Acquire load also observes the
+/// decode's side effects. With several workers, an acknowledged tag means
+/// that packet was decoded, not that every earlier packet was.
+pub static BARRIER_ACK: AtomicU64 = AtomicU64::new(0);
 
-/// Maximum decoding latency observed.
+/// Decoding latencies (in machine timer ticks) reported by worker cores.
 ///
-/// Tracks the worst-case latency across all decoded packets. Reset periodically
-/// by the primary core when computing statistics. Used to identify performance
-/// outliers and worst-case timing behavior.
-static LATENCY_MAX: AtomicU64 = AtomicU64::new(0);
+/// Every worker pushes one sample per decoded packet and the primary core
+/// drains them between shots, folding them into the min/avg/max on its
+/// status line. Replaces three shared atomics that every worker hit with a
+/// read-modify-write per packet.
+static LATENCY_SAMPLES: StaticMpscRingBuffer<u64, 256> = StaticMpscRingBuffer::new();
 
-/// Minimum decoding latency observed.
+/// Latency samples lost because `LATENCY_SAMPLES` was full.
 ///
-/// Tracks the best-case latency across all decoded packets. Reset periodically
-/// by the primary core when computing statistics. Initialized to u64::MAX
-/// so the first measurement becomes the minimum.
-static LATENCY_MIN: AtomicU64 = AtomicU64::new(u64::MAX);
+/// Nonzero only if the primary core falls behind on draining; the status
+/// line's latency figures then cover a subset of the decoded packets.
+static SAMPLES_LOST: AtomicU64 = AtomicU64::new(0);
 
 /// Thread-safe wrapper for global mutable state.
 ///
@@ -256,13 +286,81 @@ impl<T> GlobalCell<T> {
 /// and is never deallocated during firmware execution.
 static GRAPH_ALLOC: GlobalCell<Option<BumpAllocator>> = GlobalCell::new(None);
 
+/// Size of the graph allocator's region at the start of the platform heap.
+const GRAPH_REGION_SIZE: usize = 0x40_0000;
+
+// The graph region must fit inside the platform's heap.
+const _: () = assert!(GRAPH_REGION_SIZE <= PLATFORM.heap_size());
+
+/// Out-of-memory hook for firmware bump arenas.
+///
+/// Prints which arena ran out, how full it was, and the failed layout, so
+/// an arena overflow at boot is reported instead of surfacing only as a
+/// bare `OutOfMemory` or a hang.
+///
+/// # Arguments
+///
+/// * `report` - Usage of the arena at the time of the failure
+/// * `layout` - Layout of the refused allocation
+fn report_oom(report: &BumpReport, layout: Layout) {
+    console::println!(
+        "[OOM] {} arena: {} bytes (align {}) requested, {} used, {} of {} remaining",
+        report.name,
+        layout.size(),
+        layout.align(),
+        report.used,
+        report.remaining(),
+        report.capacity
+    );
+}
+
+/// Decoding graph loaded at boot, in whichever representation fits.
+///
+/// The compact form halves edge storage in the bump region and is used
+/// whenever every detector index fits in 16 bits. Larger models fall back
+/// to the standard 32-bit graph.
+enum FirmwareGraph {
+    /// Graph with 32-bit node indices.
+    Standard(DecodingGraph<&'static BumpAllocator>),
+
+    /// Graph with 16-bit node indices.
+    Compact(CompactDecodingGraph<&'static BumpAllocator>),
+}
+
+impl FirmwareGraph {
+    /// Decodes a syndrome against whichever graph representation is loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder` - Worker-local decoder state
+    /// * `syndrome_indices` - List of detector node indices that fired
+    /// * `out_buffer` - Buffer to receive correction edge pairs
+    ///
+    /// # Returns
+    ///
+    /// The decoder's result for the selected graph.
+    fn solve_into<const N: usize, CB: CorrectionBuffer>(
+        &self,
+        decoder: &mut UnionFindDecoder<N>,
+        syndrome_indices: &[usize],
+        out_buffer: &mut CB,
+    ) -> Result<(), QecError>
+    where
+        [(); N.div_ceil(64)]:,
+    {
+        match self {
+            FirmwareGraph::Standard(graph) => decoder.solve_into(graph, syndrome_indices, out_buffer),
+            FirmwareGraph::Compact(graph) => decoder.solve_into(graph, syndrome_indices, out_buffer),
+        }
+    }
+}
+
 /// Global reference to the loaded decoding graph.
 ///
 /// Stored as a static reference after being leaked from a Box to extend
 /// its lifetime beyond the allocation scope. The graph is shared read-only
 /// by all worker cores, so a single reference is sufficient.
-static GRAPH_REF: GlobalCell<Option<&'static DecodingGraph<&'static BumpAllocator>>> =
-    GlobalCell::new(None);
+static GRAPH_REF: GlobalCell<Option<&'static FirmwareGraph>> = GlobalCell::new(None);
 
 use core::arch::global_asm;
 global_asm!(include_str!("entry.S"));
@@ -305,25 +403,26 @@ fn primary_main() -> ! {
     console::println!("[BOOT] Core 0 Online");
 
     unsafe {
-        *GRAPH_ALLOC.get_mut() = Some(BumpAllocator::new(0x8400_0000, 0x400000));
+        let mut graph_alloc = BumpAllocator::new(PLATFORM.heap_start, GRAPH_REGION_SIZE);
+        graph_alloc.set_name("graph");
+        graph_alloc.set_oom_hook(report_oom);
+        *GRAPH_ALLOC.get_mut() = Some(graph_alloc);
         let alloc_ref = GRAPH_ALLOC.get().as_ref().unwrap();
 
+        let load_start = PLATFORM.read_mtime();
         let (graph, _) = parse_graph_dem(alloc_ref);
         let leaked_graph = alloc::boxed::Box::leak(alloc::boxed::Box::new_in(graph, alloc_ref));
+        console::println!(
+            "[BOOT] Graph loaded in {} ticks ({} bytes of arena)",
+            PLATFORM.read_mtime().wrapping_sub(load_start),
+            alloc_ref.used()
+        );
 
         *GRAPH_REF.get_mut() = Some(leaked_graph);
     }
 
     SYSTEM_READY.store(true, Ordering::Release);
 
-    // Memory-mapped address of the machine timer register.
-    //
-    // The machine timer (mtime) provides a 64-bit counter that increments
-    // at a fixed frequency. Used for timestamping syndrome packets and
-    // measuring decoding latency. The address is defined in the common
-    // MMIO module to ensure consistency across firmware and host code.
-    const MTIME_ADDR: usize = qcu_common::mmio::MTIME_ADDR;
-
     // Target interval between syndrome packet generations (machine timer ticks).
     //
     // Controls the rate at which the primary core generates and enqueues
@@ -334,12 +433,23 @@ fn primary_main() -> ! {
     const TARGET_INTERVAL: u64 = 222;
 
     let mut data_idx = 0;
-    let mut last_print_time = unsafe { (MTIME_ADDR as *const u64).read_volatile() };
+    let mut last_print_time = PLATFORM.read_mtime();
     let mut last_processed = 0;
-    let mut next_shot_time = unsafe { (MTIME_ADDR as *const u64).read_volatile() };
+    let mut next_shot_time = PLATFORM.read_mtime();
+    let mut lat_sum = 0u64;
+    let mut lat_count = 0u64;
+    let mut lat_max = 0u64;
+    let mut lat_min = u64::MAX;
 
     loop {
-        let now = unsafe { (MTIME_ADDR as *const u64).read_volatile() };
+        while let Some(latency) = LATENCY_SAMPLES.pop() {
+            lat_sum += latency;
+            lat_count += 1;
+            lat_max = lat_max.max(latency);
+            lat_min = lat_min.min(latency);
+        }
+
+        let now = PLATFORM.read_mtime();
 
         if now < next_shot_time {
             core::hint::spin_loop();
@@ -352,6 +462,10 @@ fn primary_main() -> ! {
 
         if offset + WORDS_PER_SHOT <= bench_data::BENCH_DATA.len() {
             syndromes.copy_from_slice(&bench_data::BENCH_DATA[offset..offset + WORDS_PER_SHOT]);
+            // Shots are byte-aligned in the .b8 file, so the last byte can
+            // carry padding bits past the final detector.
+            let detectors = bench_data::DETECTORS_PER_SHOT.min(WORDS_PER_SHOT * 64);
+            BitPack::clear_range(&mut syndromes, detectors..WORDS_PER_SHOT * 64);
         } else {
             data_idx = 0;
         }
@@ -360,6 +474,7 @@ fn primary_main() -> ! {
             shot_id: data_idx as u64,
             timestamp: now,
             syndromes,
+            barrier: None,
         };
 
         if JOB_QUEUE.push(packet).is_ok() {
@@ -367,38 +482,54 @@ fn primary_main() -> ! {
             data_idx = (data_idx + 1) % bench_data::TOTAL_SHOTS;
         }
 
-        if now.wrapping_sub(last_print_time) >= 10_000_000 {
+        if now.wrapping_sub(last_print_time) >= PLATFORM.timer_hz {
             let total = TOTAL_PROCESSED.load(Ordering::Relaxed);
             let depth = QUEUE_DEPTH.load(Ordering::Relaxed);
-            let sum = LATENCY_SUM.swap(0, Ordering::Relaxed);
-            let max = LATENCY_MAX.swap(0, Ordering::Relaxed);
-            let min = LATENCY_MIN.swap(u64::MAX, Ordering::Relaxed);
+            let lost = SAMPLES_LOST.load(Ordering::Relaxed);
 
             let delta = total.wrapping_sub(last_processed);
-            let avg = if delta > 0 { sum / delta } else { 0 };
+            let avg = if lat_count > 0 { lat_sum / lat_count } else { 0 };
 
-            console::println!(
-                "T={:3}s | Rate: {:6}/s | Lat: {:4}/{:4}/{:4} | Q: {:4}",
-                now / 10_000_000,
+            let mut line: StaticString<256> = StaticString::new();
+            let _ = write!(
+                line,
+                "T={:3}s | Rate: {:6}/s | Lat: {:4}/{:4}/{:4} | Q: {:4} | Lost: {}",
+                now / PLATFORM.timer_hz,
                 delta,
-                min,
+                lat_min,
                 avg,
-                max,
-                depth
+                lat_max,
+                depth,
+                lost
             );
+            console::write_line(line.as_str());
 
             last_print_time = now;
             last_processed = total;
+            lat_sum = 0;
+            lat_count = 0;
+            lat_max = 0;
+            lat_min = u64::MAX;
         }
     }
 }
 
+/// Largest number of packets a worker claims from the job queue at once.
+///
+/// Lets a worker work through a backlog with one queue CAS per batch. A
+/// shallow queue still yields single packets, so latency is unchanged when
+/// the workers keep up.
+const WORKER_BATCH: usize = 4;
+
 /// Worker core main function (hart 1+).
 ///
 /// Waits for system initialization to complete, then enters a loop that pops
-/// syndrome packets from the job queue, unpacks the syndrome bits, runs the
-/// decoder, and records latency statistics. Each worker core operates independently,
-/// processing jobs in parallel to maximize throughput. This function never returns.
+/// batches of up to `WORKER_BATCH` syndrome packets from the job queue,
+/// unpacks the syndrome bits, runs the decoder, and records latency
+/// statistics. Each worker core operates independently, processing jobs in
+/// parallel to maximize throughput. If the job queue is closed, the worker
+/// finishes the remaining packets, reports that it is done, and idles. This
+/// function never returns.
 ///
 /// # Arguments
 ///
@@ -413,60 +544,113 @@ fn worker_main(hartid: usize) -> ! {
     let mut decoder = UnionFindDecoder::<MAX_NODES>::new();
     let mut syndrome_indices: StaticVec<usize, 1024> = StaticVec::new();
     let mut corrections: StaticVec<(usize, usize), 1024> = StaticVec::new();
+    let mut batch: StaticVec<SyndromePacket, WORKER_BATCH> = StaticVec::new();
 
     console::println!("[WORKER] Core {} Ready", hartid);
 
-    // Memory-mapped address of the machine timer register.
-    //
-    // Used by worker cores to read the current time for latency measurement.
-    // The timer value is compared against packet timestamps to compute
-    // decoding latency statistics.
-    const MTIME_ADDR: usize = qcu_common::mmio::MTIME_ADDR;
-
     loop {
-        if let Some(packet) = JOB_QUEUE.pop() {
-            QUEUE_DEPTH.fetch_sub(1, Ordering::Relaxed);
+        batch.clear();
+        let popped = JOB_QUEUE.pop_batch(&mut batch);
+        if popped == 0 {
+            if JOB_QUEUE.is_closed() && JOB_QUEUE.is_empty() {
+                break;
+            }
+            core::hint::spin_loop();
+            continue;
+        }
+        QUEUE_DEPTH.fetch_sub(popped as i64, Ordering::Relaxed);
+
+        for packet in &batch {
 
             syndrome_indices.clear();
-            for (i, &word) in packet.syndromes.iter().enumerate() {
-                let mut w = word;
-                let mut bit = 0;
-                while w > 0 {
-                    if w & 1 == 1 {
-                        let _ = syndrome_indices.push(i * 64 + bit);
-                    }
-                    w >>= 1;
-                    bit += 1;
-                }
-            }
+            let _ = BitPack::extract_indices(&packet.syndromes, &mut syndrome_indices);
 
             #[cfg(not(feature = "use_fpga_mmio"))]
             {
-                if decoder
-                    .solve_into(graph, &syndrome_indices, &mut corrections)
-                    .is_ok()
-                {
-                    let now = unsafe { (MTIME_ADDR as *const u64).read_volatile() };
+                // A shot without defects needs no correction, so skip the
+                // decoder setup entirely.
+                let result = if BitPack::is_all_zero(&packet.syndromes) {
+                    corrections.clear();
+                    Ok(())
+                } else {
+                    graph.solve_into(&mut decoder, &syndrome_indices, &mut corrections)
+                };
+                if let Err(e) = result {
+                    console::println!(
+                        "[WORKER] Core {} skipped shot {}: {:?}",
+                        hartid,
+                        packet.shot_id,
+                        e
+                    );
+                } else {
+                    let now = PLATFORM.read_mtime();
                     let latency = now.wrapping_sub(packet.timestamp);
 
                     TOTAL_PROCESSED.fetch_add(1, Ordering::Relaxed);
-                    LATENCY_SUM.fetch_add(latency, Ordering::Relaxed);
-                    LATENCY_MAX.fetch_max(latency, Ordering::Relaxed);
-                    LATENCY_MIN.fetch_min(latency, Ordering::Relaxed);
+                    if !LATENCY_SAMPLES.push(latency) {
+                        SAMPLES_LOST.fetch_add(1, Ordering::Relaxed);
+                    }
+
+                    if let Some(tag) = packet.barrier {
+                        BARRIER_ACK.fetch_max(tag as u64 + 1, Ordering::Release);
+                    }
                 }
             }
-        } else {
-            core::hint::spin_loop();
         }
     }
+
+    // The primary core closed the job queue and every packet has been
+    // taken, so this worker is done.
+    console::println!("[WORKER] Core {} Drained", hartid);
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Iterates over the detector indices of one line of the embedded DEM data.
+///
+/// Yields every "D<n>" target of an "error" line, in order, and nothing for
+/// any other line.
+///
+/// # Arguments
+///
+/// * `line` - One line of the DEM data
+///
+/// # Returns
+///
+/// An iterator over the line's detector indices.
+fn dem_detectors(line: &str) -> impl Iterator<Item = usize> + '_ {
+    line.trim()
+        .strip_prefix("error")
+        .into_iter()
+        .flat_map(str::split_whitespace)
+        .filter_map(|part| part.strip_prefix('D')?.parse().ok())
+}
+
+/// Iterates over the two-detector edges in the embedded DEM data.
+///
+/// Parses "error" lines of the form "error(probability) D<u> D<v>" and yields
+/// the first two detector indices of each. Lines with fewer than two
+/// detectors are skipped.
+///
+/// # Returns
+///
+/// An iterator over (u, v) detector index pairs.
+fn dem_edges() -> impl Iterator<Item = (usize, usize)> {
+    DEM_DATA.split('\n').filter_map(|line| {
+        let mut detectors = dem_detectors(line);
+        Some((detectors.next()?, detectors.next()?))
+    })
 }
 
 /// Parses a decoding graph from DEM (Detector Error Model) format.
 ///
-/// Reads the embedded DEM data string and constructs a DecodingGraph by
-/// parsing "error" lines that specify edges between detector nodes. The
-/// format is: "error(probability) D<u> D<v>" where u and v are detector
-/// node indices. Returns the graph and the maximum node ID encountered.
+/// Makes a first pass over the embedded DEM data to count edges and find the
+/// largest detector index on any error line, including those past the first
+/// two that no edge is built from, then builds the graph with exactly that
+/// many edges reserved. If every index fits in 16 bits the compact
+/// representation is built; otherwise the standard graph is used. Returns
+/// the graph and the number of nodes.
 ///
 /// # Arguments
 ///
@@ -475,42 +659,35 @@ fn worker_main(hartid: usize) -> ! {
 /// # Returns
 ///
 /// A tuple containing the constructed graph and the number of nodes (max_id + 1).
-fn parse_graph_dem(alloc: &BumpAllocator) -> (DecodingGraph<&BumpAllocator>, usize) {
-    let mut graph = DecodingGraph::new_in(120_000, alloc);
-    let mut max_node_id = 0;
-
-    for line in DEM_DATA.split('\n') {
-        let trimmed: &str = line.trim();
-        if trimmed.starts_with("error") {
-            let mut parts = trimmed.split_whitespace();
-            parts.next();
-            let mut u = usize::MAX;
-            let mut v = usize::MAX;
-
-            for part in parts {
-                if let Some(Ok(idx)) = part.strip_prefix('D').map(core::str::FromStr::from_str) {
-                    if idx > max_node_id {
-                        max_node_id = idx;
-                    }
-                    if u == usize::MAX {
-                        u = idx;
-                    } else if v == usize::MAX {
-                        v = idx;
-                    }
-                }
-            }
-            if u != usize::MAX && v != usize::MAX {
-                let _ = graph.add_edge(u, v, 1.0);
-            }
+fn parse_graph_dem(alloc: &'static BumpAllocator) -> (FirmwareGraph, usize) {
+    let num_edges = dem_edges().count();
+    let max_node_id = DEM_DATA
+        .split('\n')
+        .flat_map(dem_detectors)
+        .max()
+        .unwrap_or(0);
+
+    let graph = if max_node_id < CompactDecodingGraph::<&BumpAllocator>::MAX_NODES {
+        let mut graph = CompactDecodingGraph::new_in(num_edges, alloc);
+        for (u, v) in dem_edges() {
+            let _ = graph.add_edge(u, v, 1.0);
         }
-    }
+        FirmwareGraph::Compact(graph)
+    } else {
+        let mut graph = DecodingGraph::new_in(num_edges.div_ceil(4), alloc);
+        for (u, v) in dem_edges() {
+            let _ = graph.add_edge(u, v, 1.0);
+        }
+        FirmwareGraph::Standard(graph)
+    };
     (graph, max_node_id + 1)
 }
 
 /// Panic handler for firmware error conditions.
 ///
 /// Called when a panic occurs (e.g., assertion failure, out-of-bounds access).
-/// Prints panic information to the console, then attempts to exit QEMU by
+/// Formats the panic information into a fixed-size buffer (clipped if it
+/// does not fit) and prints it in one locked write, then attempts to exit QEMU by
 /// writing a magic value to the QEMU exit device. If QEMU exit fails or is
 /// unavailable, enters an infinite loop to halt execution.
 ///
@@ -519,7 +696,9 @@ fn parse_graph_dem(alloc: &BumpAllocator) -> (DecodingGraph<&BumpAllocator>, usi
 /// * `info` - Panic information including message and location
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    console::println!("PANIC: {:?}", info);
+    let mut msg: StaticString<256> = StaticString::new();
+    let _ = write!(msg, "PANIC: {:?}", info);
+    console::write_line(msg.as_str());
     unsafe {
         let qemu_exit = 0x100000 as *mut u32;
         qemu_exit.write_volatile(0x5555);
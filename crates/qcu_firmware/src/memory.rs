@@ -1,12 +1,13 @@
 //! Kernel heap allocator for firmware dynamic memory allocation.
 //!
 //! Implements a simple bump allocator that manages a contiguous region of
-//! memory from 0x8050_0000 to 0x8800_0000. Allocations are never freed,
+//! memory ending at the platform heap end (0x8800_0000 on QEMU virt). Allocations are never freed,
 //! making this suitable for long-lived data structures. The allocator uses
 //! atomic operations to support concurrent allocation from multiple threads.
 
 use core::alloc::{GlobalAlloc, Layout};
 use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::board::PLATFORM;
 
 /// Bump allocator for kernel heap memory.
 ///
@@ -29,7 +30,8 @@ unsafe impl GlobalAlloc for KernelHeap {
     /// Performs alignment and size calculations, then atomically updates
     /// the heap pointer to reserve the memory. Uses compare-and-swap to
     /// handle concurrent allocations safely. Returns null if the allocation
-    /// would exceed the heap bounds (0x8800_0000).
+    /// would exceed the platform's heap end or if the alignment arithmetic
+    /// overflows.
     ///
     /// # Arguments
     ///
@@ -41,10 +43,14 @@ unsafe impl GlobalAlloc for KernelHeap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let mut prev = self.heap_curr.load(Ordering::Relaxed);
         loop {
-            let aligned = (prev + layout.align() - 1) & !(layout.align() - 1);
-            let next = aligned + layout.size();
+            let Some(next) = prev
+                .checked_next_multiple_of(layout.align())
+                .and_then(|aligned| aligned.checked_add(layout.size()))
+            else {
+                return core::ptr::null_mut();
+            };
 
-            if next >= 0x8800_0000 {
+            if next >= PLATFORM.heap_end {
                 return core::ptr::null_mut();
             }
 
@@ -54,7 +60,7 @@ unsafe impl GlobalAlloc for KernelHeap {
                 Ordering::Relaxed,
                 Ordering::Relaxed,
             ) {
-                Ok(_) => return aligned as *mut u8,
+                Ok(_) => return (next - layout.size()) as *mut u8,
                 Err(e) => prev = e,
             }
         }
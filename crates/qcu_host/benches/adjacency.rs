@@ -0,0 +1,82 @@
+//! Graph load time on the d=21 lattice, as done at firmware boot.
+//!
+//! Loads the distance-21, nine-round phenomenological lattice used by the
+//! `reorder` bench into a `CompactDecodingGraph` drawn from a bump arena, as
+//! the firmware does, and builds its CSR adjacency. The standard graph, whose
+//! adjacency lives on the global heap, is timed alongside for comparison.
+//! Each iteration rewinds the arena so every load starts from the same
+//! address.
+
+#![feature(allocator_api)]
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use qcu_core::allocator::BumpAllocator;
+use qcu_core::compact_graph::CompactDecodingGraph;
+use qcu_core::graph::DecodingGraph;
+use std::hint::black_box;
+
+/// Code distance, as in the d=21 benchmark data.
+const DISTANCE: usize = 21;
+
+/// Measurement rounds, as in the `reorder` bench.
+const ROUNDS: usize = 9;
+
+/// Size of the bump arena the compact graph is loaded into.
+const ARENA_SIZE: usize = 1 << 20;
+
+/// Returns the edges of the d x d x rounds phenomenological lattice.
+fn lattice_edges() -> Vec<(usize, usize)> {
+    let layer = DISTANCE * DISTANCE;
+    let mut edges = Vec::new();
+    for t in 0..ROUNDS {
+        for r in 0..DISTANCE {
+            for c in 0..DISTANCE {
+                let u = t * layer + r * DISTANCE + c;
+                if c + 1 < DISTANCE {
+                    edges.push((u, u + 1));
+                }
+                if r + 1 < DISTANCE {
+                    edges.push((u, u + DISTANCE));
+                }
+                if t + 1 < ROUNDS {
+                    edges.push((u, u + layer));
+                }
+            }
+        }
+    }
+    edges
+}
+
+fn bench_adjacency(c: &mut Criterion) {
+    let edges = lattice_edges();
+    let arena = BumpAllocator::from_slice(Box::leak(vec![0u8; ARENA_SIZE].into_boxed_slice()));
+
+    let mut group = c.benchmark_group("load_d21");
+    group.bench_function("compact_bump", |b| {
+        b.iter(|| {
+            let mut graph = CompactDecodingGraph::new_in(edges.len(), &arena);
+            for &(u, v) in black_box(&edges) {
+                graph.add_edge(u, v, 1.0).unwrap();
+            }
+            graph.build_adjacency().unwrap();
+            black_box(graph.adj_targets.len());
+            drop(graph);
+            // The graph, the only user of the arena, has been dropped.
+            unsafe { arena.reset() };
+        })
+    });
+    group.bench_function("standard_global", |b| {
+        b.iter(|| {
+            let mut graph = DecodingGraph::new(edges.len());
+            for &(u, v) in black_box(&edges) {
+                graph.add_edge(u, v, 1.0).unwrap();
+            }
+            graph.build_adjacency();
+            black_box(graph.adj_targets.len())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_adjacency);
+criterion_main!(benches);
@@ -0,0 +1,183 @@
+//! Decode throughput on a d=21 graph with and without locality reordering.
+//!
+//! Builds a distance-21 phenomenological surface code over nine rounds
+//! (3969 detectors, just under the decoder capacity used by `run`), samples
+//! a fixed batch of shots, and times decoding the batch against the graph
+//! as built and after `reorder_for_locality`. The graph is built twice: with
+//! the generator's row-major labelling, and with detector labels scrambled
+//! the way a circuit-level error model numbers them. The reordered runs
+//! include translating each syndrome with `map_syndrome_in`, as
+//! `run --reorder` does.
+//!
+//! So far this has not shown the reordering to be faster: the decoder scans
+//! the whole edge list on every pass, and the reordered runs have measured
+//! the same as or slower than the graph as built.
+
+#![feature(allocator_api)]
+#![feature(generic_const_exprs)]
+#![allow(incomplete_features)]
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use qcu_core::decoder::UnionFindDecoder;
+use qcu_core::graph::DecodingGraph;
+use std::hint::black_box;
+
+/// Code distance, as in the d=21 benchmark data.
+const DISTANCE: usize = 21;
+
+/// Measurement rounds, chosen so the graph fits in `MAX_NODES`.
+const ROUNDS: usize = 9;
+
+/// Decoder capacity, matching `throughput::MAX_NODES`.
+const MAX_NODES: usize = 4096;
+
+/// Shots decoded per benchmark iteration.
+const SHOTS: usize = 256;
+
+/// Physical error rate per edge.
+const P: f64 = 0.005;
+
+/// Xorshift generator, as in the data generator, so runs are repeatable.
+///
+/// A copy of the one in `qcu_core`'s unit tests: that one is test-only and
+/// this crate has no library target to share it from.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn float(&mut self) -> f64 {
+        self.next() as f64 / u64::MAX as f64
+    }
+}
+
+/// Returns the edges of the d x d x rounds phenomenological lattice.
+fn lattice_edges() -> Vec<(usize, usize)> {
+    let layer = DISTANCE * DISTANCE;
+    let mut edges = Vec::new();
+    for t in 0..ROUNDS {
+        for r in 0..DISTANCE {
+            for c in 0..DISTANCE {
+                let u = t * layer + r * DISTANCE + c;
+                if c + 1 < DISTANCE {
+                    edges.push((u, u + 1));
+                }
+                if r + 1 < DISTANCE {
+                    edges.push((u, u + DISTANCE));
+                }
+                if t + 1 < ROUNDS {
+                    edges.push((u, u + layer));
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Returns a random permutation of the detector labels.
+fn scrambled_labels(n: usize, rng: &mut Rng) -> Vec<usize> {
+    let mut labels: Vec<usize> = (0..n).collect();
+    for i in (1..n).rev() {
+        labels.swap(i, rng.next() as usize % (i + 1));
+    }
+    labels
+}
+
+/// Builds the graph with each detector `i` labelled `labels[i]`.
+fn build_graph(edges: &[(usize, usize)], labels: &[usize]) -> DecodingGraph {
+    let mut graph = DecodingGraph::new(edges.len());
+    for &(u, v) in edges {
+        graph.add_edge(labels[u], labels[v], P).unwrap();
+    }
+    graph.build_adjacency();
+    graph
+}
+
+/// Samples shots as sorted lists of fired detector labels.
+fn sample_shots(edges: &[(usize, usize)], labels: &[usize], rng: &mut Rng) -> Vec<Vec<usize>> {
+    let mut fired = vec![false; labels.len()];
+    (0..SHOTS)
+        .map(|_| {
+            fired.fill(false);
+            for &(u, v) in edges {
+                if rng.float() < P {
+                    fired[u] = !fired[u];
+                    fired[v] = !fired[v];
+                }
+            }
+            let mut shot: Vec<usize> = (0..labels.len())
+                .filter(|&i| fired[i])
+                .map(|i| labels[i])
+                .collect();
+            shot.sort_unstable();
+            shot
+        })
+        .collect()
+}
+
+/// Decodes every shot in the batch, returning the number solved.
+fn decode_batch(
+    graph: &DecodingGraph,
+    shots: &[Vec<usize>],
+    decoder: &mut UnionFindDecoder<MAX_NODES>,
+    results: &mut Vec<(usize, usize)>,
+    mapped: &mut Vec<usize>,
+) -> usize {
+    shots
+        .iter()
+        .filter(|shot| {
+            let syndrome = if graph.is_reordered() {
+                mapped.resize(shot.len(), 0);
+                graph.map_syndrome_in(shot, mapped).unwrap();
+                &mapped[..]
+            } else {
+                &shot[..]
+            };
+            decoder.solve_into(graph, syndrome, results).is_ok()
+        })
+        .count()
+}
+
+fn bench_reorder(c: &mut Criterion) {
+    let edges = lattice_edges();
+    let n = DISTANCE * DISTANCE * ROUNDS;
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let labellings = [
+        ("row_major", (0..n).collect::<Vec<_>>()),
+        ("scrambled", scrambled_labels(n, &mut rng)),
+    ];
+
+    let mut group = c.benchmark_group("decode_d21");
+    for (name, labels) in &labellings {
+        let shots = sample_shots(&edges, labels, &mut rng);
+        let plain = build_graph(&edges, labels);
+        let mut reordered = build_graph(&edges, labels);
+        reordered.reorder_for_locality();
+
+        for (variant, graph) in [("as_built", &plain), ("reordered", &reordered)] {
+            let mut decoder = Box::new(UnionFindDecoder::<MAX_NODES>::new());
+            let mut results = Vec::with_capacity(128);
+            let mut mapped = Vec::new();
+            group.bench_function(format!("{}/{}", name, variant), |b| {
+                b.iter(|| {
+                    decode_batch(
+                        black_box(graph),
+                        black_box(&shots),
+                        &mut decoder,
+                        &mut results,
+                        &mut mapped,
+                    )
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_reorder);
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+//! Assembly and disassembly of instruction programs.
+//!
+//! Thin file-handling wrappers around `qcu_io::asm`: assemble a text program
+//! into the binary format executed by `exec` and the firmware, or dump a
+//! binary program back to text. Binary programs may be bare instruction
+//! streams, checksummed streams (`qcu_common::isa::encode_stream`), or
+//! wrapped in a `qcu_common::protocol` Program packet.
+
+use anyhow::{Context, Result, anyhow, bail};
+use qcu_common::isa::{self, InstructionStream, STREAM_MAGIC};
+use qcu_common::protocol::{self, MAGIC, PacketHeader, PacketKind};
+use qcu_io::asm;
+use std::fs;
+
+/// Assembles a text program into the binary instruction format.
+///
+/// The program is validated before it is written, so the output is always
+/// Halt-terminated, has balanced loops, and stays within the given qubit and
+/// detector counts. With `framed`, the program is written as a Program
+/// packet ready to send to the firmware; with `checksummed`, as a
+/// checksummed instruction stream for lossy links.
+///
+/// # Arguments
+///
+/// * `input` - Path to the program text
+/// * `output` - Path to write the binary program to
+/// * `num_qubits` - Number of qubits the program may address
+/// * `num_detectors` - Number of detectors the program may address
+/// * `framed` - Prefix the program with a packet header
+/// * `checksummed` - Write a checksummed stream instead of bare instructions
+///
+/// # Returns
+///
+/// Ok(()) on success, or an error naming the input file (and, for syntax
+/// errors, the line and column) if assembly or validation fails.
+pub fn run_asm(
+    input: &str,
+    output: &str,
+    num_qubits: usize,
+    num_detectors: usize,
+    framed: bool,
+    checksummed: bool,
+) -> Result<()> {
+    let source = fs::read_to_string(input).context("Failed to read program text")?;
+    let program = asm::assemble(&source, num_qubits, num_detectors)
+        .map_err(|e| anyhow!("{}: {}", input, e))?;
+
+    let mut bytes = Vec::new();
+    if checksummed {
+        let instrs: Vec<_> = program.iter().collect();
+        isa::encode_stream(&instrs, &mut bytes);
+    } else {
+        if framed {
+            let header = PacketHeader::new(PacketKind::Program, program.as_bytes());
+            bytes.extend_from_slice(&header.to_bytes());
+        }
+        bytes.extend_from_slice(program.as_bytes());
+    }
+    fs::write(output, &bytes).context("Failed to write binary program")?;
+
+    println!(
+        "Assembled {} instructions ({} bytes) into {}",
+        program.len(),
+        bytes.len(),
+        output
+    );
+    Ok(())
+}
+
+/// Prints a binary program as text.
+///
+/// # Arguments
+///
+/// * `input` - Path to the binary program
+///
+/// # Returns
+///
+/// Ok(()) on success, or an error if the file cannot be read or is not a
+/// valid instruction stream.
+pub fn run_disasm(input: &str) -> Result<()> {
+    let bytes = read_program(input)?;
+    let program = InstructionStream::new(&bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("{}: {:?}", input, e))?;
+    print!("{}", asm::format_program(&program));
+    Ok(())
+}
+
+/// Reads a binary program, unwrapping it if it is framed or checksummed.
+///
+/// A file starting with `protocol::MAGIC` must be exactly one valid Program
+/// packet; its header is checked through `protocol::decode_packet` and the
+/// payload returned. A file starting with `isa::STREAM_MAGIC` must be exactly
+/// one checksummed stream, checked with `isa::decode_stream`. Any other file
+/// is returned as a bare instruction stream.
+///
+/// # Arguments
+///
+/// * `path` - Path to the binary program
+///
+/// # Returns
+///
+/// The encoded instructions, or an error if the file cannot be read or a
+/// framed file fails validation.
+pub fn read_program(path: &str) -> Result<Vec<u8>> {
+    let bytes = fs::read(path).context("Failed to read binary program")?;
+    if bytes.starts_with(&STREAM_MAGIC.to_le_bytes()) {
+        let instrs = isa::decode_stream(&bytes)
+            .map_err(|e| anyhow!("{}: bad checksummed stream: {:?}", path, e))?;
+        return Ok(instrs.iter().flat_map(|i| i.to_bytes()).collect());
+    }
+    if !bytes.starts_with(&MAGIC.to_le_bytes()) {
+        return Ok(bytes);
+    }
+    let (header, payload, rest) =
+        protocol::decode_packet(&bytes).map_err(|e| anyhow!("{}: bad packet: {:?}", path, e))?;
+    if header.kind != PacketKind::Program {
+        bail!("{}: expected a Program packet, found {:?}", path, header.kind);
+    }
+    if !rest.is_empty() {
+        bail!("{}: {} bytes after the packet", path, rest.len());
+    }
+    Ok(payload.to_vec())
+}
@@ -0,0 +1,156 @@
+//! Program execution on the host-side virtual machine.
+//!
+//! Loads a binary instruction program and a decoding graph, runs the program
+//! on a `VirtualMachine`, and reports how execution ended. With tracing
+//! enabled, prints one line per VM event so host runs can be compared line by
+//! line against firmware logs.
+
+use crate::asm;
+use anyhow::{Result, anyhow};
+use qcu_common::isa::{INSTRUCTION_SIZE, Instruction32, Opcode};
+use qcu_core::pauli_frame::PauliFrame;
+use qcu_core::vm::{TraceEvent, VirtualMachine, VmExit};
+use qcu_io::parser;
+use std::alloc::Global;
+
+/// Maximum number of nodes supported by the program VM's decoder.
+///
+/// Matches the throughput benchmark so the same graphs can be used.
+const MAX_NODES: usize = 4096;
+
+/// Runs a binary program on the virtual machine.
+///
+/// The program file is a flat sequence of 6-byte instructions with
+/// little-endian operands, decoded by `VirtualMachine::run_bytes`, optionally
+/// framed as a Program packet (see `asm::read_program`). Measurement
+/// results are taken from `measurements`, a string of '0' and '1' characters
+/// consumed in program order; when absent, every measurement reports 0.
+///
+/// If `switch` is given, the second graph is loaded up front and installed
+/// with `VirtualMachine::set_graph` before the instruction at the given
+/// index executes, mimicking a calibration update between rounds.
+///
+/// # Arguments
+///
+/// * `dem_path` - Path to the decoding graph (.dem file)
+/// * `program_path` - Path to the binary program
+/// * `num_qubits` - Number of qubits tracked by the Pauli frame
+/// * `measurements` - Optional measurement results as a 0/1 string
+/// * `trace` - Print one line per VM event
+/// * `switch` - Optional (graph path, instruction index) to switch graphs at
+///
+/// # Returns
+///
+/// Ok(()) on success, or an error if loading fails or the VM rejects an
+/// instruction.
+pub fn run_exec(
+    dem_path: &str,
+    program_path: &str,
+    num_qubits: usize,
+    measurements: Option<String>,
+    trace: bool,
+    switch: Option<(String, usize)>,
+) -> Result<()> {
+    let graph = parser::load_dem_file(dem_path)?;
+    let program = asm::read_program(program_path)?;
+    let switch_graph = match &switch {
+        Some((path, _)) => Some(parser::load_dem_file(path)?),
+        None => None,
+    };
+
+    let results: Vec<bool> = match measurements {
+        Some(bits) => bits
+            .chars()
+            .map(|c| match c {
+                '0' => Ok(false),
+                '1' => Ok(true),
+                other => Err(anyhow!("invalid measurement character {:?}", other)),
+            })
+            .collect::<Result<_>>()?,
+        None => Vec::new(),
+    };
+    let has_results = !results.is_empty();
+    let mut results = results.into_iter();
+    let mut measurement_iter =
+        std::iter::from_fn(|| if has_results { results.next() } else { Some(false) });
+
+    let frame = PauliFrame::new_in(&Global, num_qubits).map_err(|e| anyhow!("{:?}", e))?;
+    let mut vm = VirtualMachine::<MAX_NODES>::with_frame(frame, &graph)
+        .map_err(|e| anyhow!("graph does not fit the decoder: {:?}", e))?;
+    if trace {
+        vm.set_trace_hook(print_trace, 0);
+    }
+
+    let split = match (&switch, &switch_graph) {
+        (Some((_, at)), Some(_)) => (at * INSTRUCTION_SIZE).min(program.len()),
+        _ => program.len(),
+    };
+    let (head, tail) = program.split_at(split);
+
+    let mut exit = vm
+        .run_bytes(head, &mut measurement_iter)
+        .map_err(|e| anyhow!("execution failed: {:?}", e))?;
+    if let (VmExit::RanOffEnd { executed, .. }, Some(second)) = (exit, &switch_graph) {
+        vm.set_graph(second)
+            .map_err(|e| anyhow!("graph switch failed: {:?}", e))?;
+        println!("Switched decoding graph at pc {}", executed);
+        exit = match vm
+            .run_bytes(tail, &mut measurement_iter)
+            .map_err(|e| anyhow!("execution failed: {:?}", e))?
+        {
+            VmExit::Halted {
+                pc,
+                executed: n,
+                measurements,
+            } => VmExit::Halted {
+                pc: executed + pc,
+                executed: executed + n,
+                measurements,
+            },
+            VmExit::RanOffEnd {
+                executed: n,
+                measurements,
+            } => VmExit::RanOffEnd {
+                executed: executed + n,
+                measurements,
+            },
+        };
+    }
+
+    match exit {
+        VmExit::Halted { pc, .. } => println!("Halted at pc {}", pc),
+        VmExit::RanOffEnd { .. } => println!("Ran off end of program"),
+    }
+    println!("Instructions executed: {}", exit.executed());
+    println!("Measurements recorded: {}", exit.measurements());
+    println!("Residual error weight: {}", vm.frame.total_weight());
+    println!("Observables:           {:#018x}", vm.observables());
+
+    let stats = vm.stats();
+    println!("--- VM Stats ---");
+    println!("Gates:        {}", stats.gates);
+    println!("Measurements: {}", stats.measurements);
+    println!("Conditionals: {}", stats.conditionals);
+    println!("Control:      {}", stats.control);
+    println!("Syndromes:    {}", stats.syndromes_recorded);
+    println!("Decodes:      {} ({:?})", stats.decodes, stats.decode_time);
+    println!("Barriers:     {} (last tag {:?})", stats.barriers, stats.last_barrier_tag);
+    println!("Corrections:  {}", stats.corrections_applied);
+    Ok(())
+}
+
+/// Trace hook printing one line per VM event.
+///
+/// # Arguments
+///
+/// * `_context` - Unused hook context
+/// * `instr` - Instruction that produced the event
+/// * `event` - Event to print
+fn print_trace(_context: usize, instr: &Instruction32, event: TraceEvent) {
+    let (opcode, op1, op2) = (instr.opcode, instr.operand_1, instr.operand_2);
+    let mnemonic = Opcode::try_from(opcode).map_or("?", |op| op.mnemonic());
+    println!(
+        "[TRACE] op=0x{:02X} {:<11} {:>5} {:>5}  {:?}",
+        opcode, mnemonic, op1, op2, event
+    );
+}
@@ -3,9 +3,11 @@
 //! Generates phenomenological noise model data by creating surface code
 //! topologies and simulating error propagation. Outputs decoding graphs
 //! (.dem files) and syndrome measurement data (.b8 files) for use in
-//! performance benchmarks and correctness testing.
+//! performance benchmarks and correctness testing, plus a JSON manifest
+//! recording the parameters and checksums of both.
 
-use anyhow::Result;
+use anyhow::{Result, ensure};
+use qcu_io::loader::{self, B8Writer, MANIFEST_VERSION, Manifest};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 
@@ -16,7 +18,8 @@ use std::io::{BufWriter, Write};
 /// a grid of detector nodes connected by edges representing possible error
 /// locations. Errors are applied probabilistically, and syndrome bits are
 /// recorded when detectors fire. Optionally injects uncorrectable monopole
-/// errors to test decoder robustness.
+/// errors to test decoder robustness. Finally writes a manifest next to the
+/// .b8 file so later runs can check they use the same data.
 ///
 /// # Arguments
 ///
@@ -26,6 +29,7 @@ use std::io::{BufWriter, Write};
 /// * `num_shots` - Number of measurement shots to generate
 /// * `p` - Physical error rate per edge (probability of error occurrence)
 /// * `inject_failures` - If true, inject uncorrectable monopole errors in ~10% of shots
+/// * `seed` - Initial state of the shot sampler; must be non-zero
 ///
 /// # Returns
 ///
@@ -37,7 +41,9 @@ pub fn generate_phenomenological_data(
     num_shots: usize,
     p: f64,
     inject_failures: bool,
+    seed: u64,
 ) -> Result<()> {
+    ensure!(seed != 0, "seed must be non-zero");
     println!("Generating {}x{} Surface Code (p={})...", size, size, p);
     if inject_failures {
         println!("WARNING: Injecting ~10% invalid shots (Monopoles).");
@@ -66,9 +72,9 @@ pub fn generate_phenomenological_data(
     dem_file.flush()?;
 
     println!("Simulating {} shots...", num_shots);
-    let mut b8_file = BufWriter::new(File::create(b8_path)?);
+    let mut b8_file = B8Writer::create(b8_path, num_nodes)?;
 
-    let mut state: u64 = 12345;
+    let mut state = seed;
     let mut rng_float = move || {
         state ^= state >> 12;
         state ^= state << 25;
@@ -77,8 +83,6 @@ pub fn generate_phenomenological_data(
         (result as f64) / (u64::MAX as f64)
     };
 
-    let bytes_per_shot = num_nodes.div_ceil(8);
-    let mut buffer = Vec::with_capacity(bytes_per_shot * 1024);
     let mut detector_state = vec![false; num_nodes];
 
     for _ in 0..num_shots {
@@ -95,22 +99,27 @@ pub fn generate_phenomenological_data(
             detector_state[0] = !detector_state[0];
         }
 
-        for chunk in detector_state.chunks(8) {
-            let mut byte = 0u8;
-            for (i, &triggered) in chunk.iter().enumerate() {
-                if triggered {
-                    byte |= 1 << i;
-                }
-            }
-            buffer.push(byte);
-        }
-
-        if buffer.len() >= 1024 * 1024 {
-            b8_file.write_all(&buffer)?;
-            buffer.clear();
-        }
+        b8_file.write_shot(&detector_state)?;
     }
-    b8_file.write_all(&buffer)?;
+    b8_file.finish()?;
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        generator_version: env!("CARGO_PKG_VERSION").to_string(),
+        size,
+        shots: num_shots,
+        p,
+        seed,
+        inject_failures,
+        detectors: num_nodes,
+        dem_sha256: loader::sha256_file(dem_path)?,
+        b8_sha256: loader::sha256_file(b8_path)?,
+    };
+    manifest.write(b8_path)?;
+    println!(
+        "Wrote manifest to {}.",
+        loader::manifest_path(b8_path).display()
+    );
 
     println!("Done.");
     Ok(())
@@ -0,0 +1,93 @@
+//! Decoding graph inspection and comparison.
+//!
+//! Loads detector error model files and reports their structure, or compares
+//! two graphs edge by edge. Used to confirm that parser or generator changes
+//! leave the resulting decoding graph unchanged, which edge counts alone
+//! cannot show (a duplicated edge and a missing edge cancel out).
+
+use anyhow::Result;
+use qcu_io::parser;
+
+/// Maximum number of differing edges printed per category.
+///
+/// Keeps the report readable when comparing unrelated graphs. The totals
+/// are always printed in full.
+const MAX_LISTED: usize = 20;
+
+/// Prints a summary of a decoding graph, or its differences from another.
+///
+/// Without `diff_path`, reports the node count, stored edge count, and the
+/// range of edge weights. With `diff_path`, loads the second graph and
+/// prints edges present in only one of them, edges stored a different
+/// number of times, and edges whose weights differ by more than `tolerance`.
+///
+/// # Arguments
+///
+/// * `dem_path` - Path to the decoding graph (.dem file)
+/// * `diff_path` - Optional path to a second graph to compare against
+/// * `tolerance` - Maximum absolute weight difference treated as equal
+///
+/// # Returns
+///
+/// Ok(()) on success, or an error if either file cannot be loaded.
+pub fn run_inspect(dem_path: &str, diff_path: Option<String>, tolerance: f64) -> Result<()> {
+    let graph = parser::load_dem_file(dem_path)?;
+
+    let Some(other_path) = diff_path else {
+        let (min_w, max_w) = graph
+            .edges()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, _, w)| {
+                (lo.min(w), hi.max(w))
+            });
+        println!("Graph: {}", dem_path);
+        println!("Nodes: {}", graph.num_nodes());
+        println!("Edges: {}", graph.fast_edges.len());
+        if !graph.fast_edges.is_empty() {
+            println!("Weights: {:.4} .. {:.4}", min_w, max_w);
+        }
+        return Ok(());
+    };
+
+    let other = parser::load_dem_file(&other_path)?;
+    let diff = graph.diff(&other, tolerance);
+
+    println!(
+        "A: {} ({} nodes, {} edges)",
+        dem_path,
+        diff.num_nodes.0,
+        graph.fast_edges.len()
+    );
+    println!(
+        "B: {} ({} nodes, {} edges)",
+        other_path,
+        diff.num_nodes.1,
+        other.fast_edges.len()
+    );
+
+    if diff.is_empty() {
+        println!("Graphs are identical.");
+        return Ok(());
+    }
+
+    println!("Only in A: {}", diff.only_in_self.len());
+    for (u, v) in diff.only_in_self.iter().take(MAX_LISTED) {
+        println!("  D{} D{}", u, v);
+    }
+    println!("Only in B: {}", diff.only_in_other.len());
+    for (u, v) in diff.only_in_other.iter().take(MAX_LISTED) {
+        println!("  D{} D{}", u, v);
+    }
+    println!(
+        "Multiplicity mismatches: {}",
+        diff.multiplicity_mismatches.len()
+    );
+    for ((u, v), a, b) in diff.multiplicity_mismatches.iter().take(MAX_LISTED) {
+        println!("  D{} D{}: {} vs {}", u, v, a, b);
+    }
+    println!("Weight mismatches: {}", diff.weight_mismatches.len());
+    for ((u, v), a, b) in diff.weight_mismatches.iter().take(MAX_LISTED) {
+        println!("  D{} D{}: {:.6} vs {:.6}", u, v, a, b);
+    }
+
+    Ok(())
+}
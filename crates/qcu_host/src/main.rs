@@ -9,6 +9,17 @@
 #![feature(generic_const_exprs)]
 #![allow(incomplete_features)]
 
+/// Assembly and disassembly of instruction programs.
+///
+/// Converts text programs to the binary instruction format and back.
+mod asm;
+
+/// Program execution on the host-side virtual machine.
+///
+/// Runs binary instruction programs against a decoding graph and reports
+/// the exit state, optionally tracing every VM event.
+mod exec;
+
 /// Test data generation for quantum error correction benchmarks.
 ///
 /// Generates phenomenological noise models and syndrome measurement data
@@ -23,6 +34,12 @@ mod generator;
 /// qubit states, error detection, and correction operations.
 mod hil;
 
+/// Decoding graph inspection and comparison.
+///
+/// Reports the structure of a detector error model and compares two graphs
+/// edge by edge, listing missing, duplicated, and re-weighted edges.
+mod inspect;
+
 /// Statistics tracking and reporting for decoder performance metrics.
 ///
 /// Collects and analyzes latency, throughput, and error rate statistics
@@ -46,6 +63,8 @@ mod throughput;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use qcu_io::loader::{ShotFilter, ShotFormat};
+use std::ops::Range;
 
 /// Command-line interface structure.
 ///
@@ -53,7 +72,8 @@ use clap::{Parser, Subcommand};
 /// handler. Uses clap for argument parsing and validation.
 #[derive(Parser)]
 struct Cli {
-    /// Subcommand to execute (gen, run, stream, or hil).
+    /// Subcommand to execute (gen, run, stream, inspect, exec, asm, disasm,
+    /// or hil).
     #[command(subcommand)]
     command: Commands,
 }
@@ -93,6 +113,10 @@ enum Commands {
         /// Inject uncorrectable monopole errors in ~10% of shots.
         #[arg(long)]
         inject_failures: bool,
+
+        /// Seed for the shot sampler (must be non-zero).
+        #[arg(long, default_value_t = 12345)]
+        seed: u64,
     },
 
     /// Run a throughput benchmark on decoding performance.
@@ -112,6 +136,41 @@ enum Commands {
         /// Override the number of detectors (defaults to graph node count).
         #[arg(short, long)]
         detectors: Option<usize>,
+
+        /// Relabel graph nodes in reverse Cuthill-McKee order before decoding.
+        #[arg(long)]
+        reorder: bool,
+
+        /// Read shots from the file while decoding instead of loading it
+        /// all up front, keeping memory flat for large files.
+        #[arg(long, conflicts_with = "mmap")]
+        stream_io: bool,
+
+        /// Memory-map the file and decode shots straight from the mapping
+        /// (needs the `mmap` feature).
+        #[arg(long)]
+        mmap: bool,
+
+        /// Decode only the shots with indices in START..END; either bound
+        /// may be omitted.
+        #[arg(long, value_parser = throughput::parse_shot_range)]
+        shot_range: Option<Range<usize>>,
+
+        /// Decode every Nth shot of the range.
+        #[arg(long, default_value_t = 1, conflicts_with = "mmap")]
+        stride: usize,
+
+        /// Decode only shots with at least this many fired detectors.
+        #[arg(long, default_value_t = 0, conflicts_with = "mmap")]
+        min_defects: usize,
+
+        /// Stop after decoding this many shots.
+        #[arg(long, conflicts_with = "mmap")]
+        max_shots: Option<usize>,
+
+        /// Shot file format, b8, 01 or dets (defaults from the file extension).
+        #[arg(long)]
+        format: Option<ShotFormat>,
     },
 
     /// Run a streaming simulation with real-time throughput monitoring.
@@ -139,6 +198,126 @@ enum Commands {
         /// Override the number of detectors (defaults to graph node count).
         #[arg(long)]
         detectors: Option<usize>,
+
+        /// Number of packets moved through the queue per push or pop.
+        #[arg(long, default_value_t = 1)]
+        batch: usize,
+
+        /// Discard packets that waited in the queue longer than this (us).
+        #[arg(long)]
+        max_age_us: Option<u64>,
+
+        /// Read shots from the .b8 file as the producer needs them,
+        /// replaying from the start at the end, instead of loading it all.
+        #[arg(long)]
+        stream_io: bool,
+
+        /// Replay only the shots with indices in START..END; either bound
+        /// may be omitted.
+        #[arg(long, value_parser = throughput::parse_shot_range)]
+        shot_range: Option<Range<usize>>,
+
+        /// Replay every Nth shot of the range.
+        #[arg(long, default_value_t = 1)]
+        stride: usize,
+
+        /// Replay only shots with at least this many fired detectors.
+        #[arg(long, default_value_t = 0)]
+        min_defects: usize,
+
+        /// Replay at most this many shots before starting over.
+        #[arg(long)]
+        max_shots: Option<usize>,
+
+        /// Shot file format, b8, 01 or dets (defaults from the file extension).
+        #[arg(long)]
+        format: Option<ShotFormat>,
+    },
+
+    /// Inspect a decoding graph or compare two graphs.
+    ///
+    /// Prints node, edge, and weight statistics for a .dem file. With
+    /// `--diff`, compares it against a second file and lists edges present
+    /// in only one graph, edges duplicated in one graph, and weight changes.
+    Inspect {
+        /// Path to the decoding graph (.dem file).
+        dem: String,
+
+        /// Path to a second decoding graph to compare against.
+        #[arg(long)]
+        diff: Option<String>,
+
+        /// Maximum absolute weight difference treated as equal.
+        #[arg(long, default_value_t = 1e-9)]
+        tolerance: f64,
+    },
+
+    /// Execute a binary instruction program on the virtual machine.
+    ///
+    /// Loads the decoding graph and program, runs until Halt or the end of
+    /// the program, and prints the exit state. With `--trace`, prints one
+    /// line per VM event. With `--switch-dem`, swaps to a second decoding
+    /// graph before the instruction at `--switch-at`.
+    Exec {
+        /// Path to the binary program (6 bytes per instruction).
+        program: String,
+
+        /// Path to the decoding graph (.dem file).
+        #[arg(long, default_value = "bench.dem")]
+        dem: String,
+
+        /// Number of qubits tracked by the Pauli frame.
+        #[arg(long, default_value_t = 64)]
+        qubits: usize,
+
+        /// Measurement results as a string of 0/1 characters, in program order.
+        #[arg(long)]
+        measurements: Option<String>,
+
+        /// Print one line per VM event.
+        #[arg(long)]
+        trace: bool,
+
+        /// Decoding graph to switch to partway through the program.
+        #[arg(long)]
+        switch_dem: Option<String>,
+
+        /// Instruction index at which to switch to `--switch-dem`.
+        #[arg(long, default_value_t = 0)]
+        switch_at: usize,
+    },
+
+    /// Assemble a text program into the binary instruction format.
+    Asm {
+        /// Path to the program text.
+        #[arg(long = "in")]
+        input: String,
+
+        /// Path to write the binary program to.
+        #[arg(long = "out")]
+        output: String,
+
+        /// Number of qubits the program may address.
+        #[arg(long, default_value_t = 65536)]
+        qubits: usize,
+
+        /// Number of detectors the program may address.
+        #[arg(long, default_value_t = 65536)]
+        detectors: usize,
+
+        /// Wrap the program in a protocol packet header.
+        #[arg(long)]
+        framed: bool,
+
+        /// Write a checksummed instruction stream for lossy links.
+        #[arg(long, conflicts_with = "framed")]
+        checksummed: bool,
+    },
+
+    /// Print a binary instruction program as text.
+    Disasm {
+        /// Path to the binary program.
+        program: String,
     },
 
     /// Run hardware-in-the-loop demonstration.
@@ -168,11 +347,41 @@ fn main() -> Result<()> {
             shots,
             p,
             inject_failures,
+            seed,
         } => {
-            generator::generate_phenomenological_data(&dem, &b8, size, shots, p, inject_failures)?;
+            generator::generate_phenomenological_data(
+                &dem,
+                &b8,
+                size,
+                shots,
+                p,
+                inject_failures,
+                seed,
+            )?;
         }
-        Commands::Run { dem, b8, detectors } => {
-            throughput::run_benchmark(&dem, &b8, detectors)?;
+        Commands::Run {
+            dem,
+            b8,
+            detectors,
+            reorder,
+            stream_io,
+            mmap,
+            shot_range,
+            stride,
+            min_defects,
+            max_shots,
+            format,
+        } => {
+            let format = format.unwrap_or_else(|| ShotFormat::from_path(&b8));
+            let filter = ShotFilter {
+                stride,
+                max_shots,
+                min_defects,
+                shot_range,
+            };
+            throughput::run_benchmark(
+                &dem, &b8, format, detectors, reorder, stream_io, mmap, filter,
+            )?;
         }
         Commands::Stream {
             dem,
@@ -180,8 +389,60 @@ fn main() -> Result<()> {
             freq,
             duration,
             detectors,
+            batch,
+            max_age_us,
+            stream_io,
+            shot_range,
+            stride,
+            min_defects,
+            max_shots,
+            format,
         } => {
-            stream::run_stream(&dem, b8, freq, duration, detectors)?;
+            let filter = ShotFilter {
+                stride,
+                max_shots,
+                min_defects,
+                shot_range,
+            };
+            let b8 = b8.map(|path| {
+                let format = format.unwrap_or_else(|| ShotFormat::from_path(&path));
+                (path, format)
+            });
+            stream::run_stream(
+                &dem, b8, freq, duration, detectors, batch, max_age_us, stream_io, filter,
+            )?;
+        }
+        Commands::Inspect {
+            dem,
+            diff,
+            tolerance,
+        } => {
+            inspect::run_inspect(&dem, diff, tolerance)?;
+        }
+        Commands::Exec {
+            program,
+            dem,
+            qubits,
+            measurements,
+            trace,
+            switch_dem,
+            switch_at,
+        } => {
+            let switch = switch_dem.map(|path| (path, switch_at));
+            exec::run_exec(&dem, &program, qubits, measurements, trace, switch)?;
+        }
+        Commands::Asm {
+            input,
+            output,
+            qubits,
+            detectors,
+            framed,
+            checksummed,
+        } => {
+            asm::run_asm(&input, &output, qubits, detectors, framed, checksummed)?;
+        }
+        Commands::Disasm { program } => {
+            asm::run_disasm(&program)?;
         }
         Commands::Hil => {
             hil::run_hil_demo()?;
@@ -6,10 +6,14 @@
 //! performance under continuous load conditions.
 
 use crate::stats::LatencyStats;
-use anyhow::Result;
+use anyhow::{Result, bail};
+use qcu_core::bit_utils::BitPack;
 use qcu_core::decoder::UnionFindDecoder;
-use qcu_core::ring_buffer::RingBuffer;
+use qcu_core::ring_buffer::{PopError, RingBuffer};
+use qcu_core::static_vec::StaticVec;
+use qcu_io::loader::{ShotFilter, ShotFormat};
 use qcu_io::{loader, parser};
+use std::io;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
@@ -25,6 +29,10 @@ use std::time::{Duration, Instant};
 pub struct TaskPacket {
     pub syndrome_len: u32,
     pub syndrome_buffer: [u32; 64],
+
+    /// Time the packet was generated, in nanoseconds since the stream
+    /// started.
+    pub timestamp_ns: u64,
 }
 
 impl Default for TaskPacket {
@@ -37,6 +45,7 @@ impl Default for TaskPacket {
         Self {
             syndrome_len: 0,
             syndrome_buffer: [0; 64],
+            timestamp_ns: 0,
         }
     }
 }
@@ -45,12 +54,15 @@ impl Default for TaskPacket {
 ///
 /// Maintains atomic counters for monitoring throughput, dropped packets,
 /// and latency in the producer-consumer decoding pipeline. All fields are
-/// shared between threads via Arc for concurrent access.
+/// shared between threads via Arc for concurrent access. Latency samples go
+/// through a small overwrite-oldest ring so the monitor sees the most recent
+/// decodes even if it falls behind.
 pub struct StreamStats {
     pub processed: Arc<AtomicU64>,
     pub generated: Arc<AtomicU64>,
     pub dropped: Arc<AtomicU64>,
-    pub latency_us: Arc<AtomicU64>,
+    pub stale: Arc<AtomicU64>,
+    pub latency_ns: Arc<RingBuffer<u64>>,
 }
 
 /// Maximum number of nodes supported by the streaming decoder.
@@ -62,37 +74,128 @@ pub struct StreamStats {
 /// codes.
 const MAX_NODES: usize = 4096;
 
+/// Number of packets the producer-consumer queue can hold.
+///
+/// Must be a power of two, as required by `RingBuffer`. Also the upper
+/// bound on the `--batch` size.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Number of recent decode latencies kept for the per-second monitor line.
+///
+/// Must be a power of two. Older samples are overwritten when the monitor
+/// does not drain them in time.
+const LATENCY_SAMPLES: usize = 256;
+
+/// Where the producer takes its syndrome patterns from.
+enum ShotSource {
+    /// Patterns held in memory, replayed in order.
+    Memory {
+        /// Packed shots.
+        shots: Vec<Vec<u64>>,
+
+        /// Index of the next shot to hand out.
+        next: usize,
+    },
+
+    /// Patterns read from a .b8 file as they are needed, replaying from the
+    /// start once the file runs out.
+    File(loader::ShotReader),
+}
+
+impl ShotSource {
+    /// Replaces `out` with the fired detectors of the next pattern.
+    ///
+    /// Shots with more defects than `out` can hold are truncated to the
+    /// first N; an empty source yields no defects.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Receives the detector indices
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or the error from reading the .b8 file.
+    fn next_indices<const N: usize>(&mut self, out: &mut StaticVec<usize, N>) -> io::Result<()> {
+        out.clear();
+        let shot = match self {
+            ShotSource::Memory { shots, next } => {
+                if shots.is_empty() {
+                    return Ok(());
+                }
+                let shot = &shots[*next];
+                *next = (*next + 1) % shots.len();
+                shot.as_slice()
+            }
+            ShotSource::File(reader) => {
+                if reader.read_shot()?.is_none() {
+                    reader.rewind()?;
+                }
+                match reader.read_shot()? {
+                    Some(shot) => shot,
+                    None => return Ok(()),
+                }
+            }
+        };
+        let _ = BitPack::extract_indices(shot, out);
+        Ok(())
+    }
+}
+
 /// Runs a real-time streaming QEC decoder benchmark.
 ///
 /// Spawns separate producer and consumer threads connected via a ring buffer.
 /// The producer generates syndrome packets at the specified frequency, while
 /// the consumer decodes them and records latency statistics. Runs for the
 /// specified duration, printing throughput and latency metrics every second.
-/// If a .b8 file path is provided, loads pre-generated syndrome patterns;
-/// otherwise uses a single empty pattern for continuous testing.
+/// If a .b8 file path is provided, loads pre-generated syndrome patterns,
+/// after checking them against their manifest if one exists; otherwise
+/// uses a single empty pattern for continuous testing.
 ///
 /// # Arguments
 ///
 /// * `dem_path` - Path to the detector error model (.dem) file
-/// * `b8_path` - Optional path to binary measurement data (.b8) file
+/// * `b8_path` - Optional path to measurement data, with its format (.b8,
+///   .01 or .dets); `stream_io` needs .b8
 /// * `freq` - Target frequency in Hz for syndrome packet generation
 /// * `duration_secs` - Duration to run the benchmark in seconds
 /// * `user_detectors` - Optional override for number of detectors
+/// * `batch` - Packets moved per queue operation, using the ring buffer's
+///   `push_slice` and `pop_into` to amortise the atomic updates
+/// * `max_age_us` - If set, the consumer discards packets that have waited
+///   in the queue longer than this instead of decoding them
+/// * `stream_io` - Read the .b8 file a shot at a time as the producer needs
+///   it instead of loading every shot up front
+/// * `filter` - Shots of the file to replay; ignored without a file
 ///
 /// # Returns
 ///
 /// Ok(()) on success, or an error if file loading or thread operations fail.
+#[allow(clippy::too_many_arguments)]
 pub fn run_stream(
     dem_path: &str,
-    b8_path: Option<String>,
+    b8_path: Option<(String, ShotFormat)>,
     freq: u64,
     duration_secs: u64,
     user_detectors: Option<usize>,
+    batch: usize,
+    max_age_us: Option<u64>,
+    stream_io: bool,
+    filter: ShotFilter,
 ) -> Result<()> {
+    if batch == 0 || batch > QUEUE_CAPACITY {
+        bail!("batch size must be between 1 and {}", QUEUE_CAPACITY);
+    }
+
     println!("QEC STREAMING");
     println!("Graph: {}", dem_path);
+    if let Some((path, _)) = &b8_path
+        && let Some(manifest) = loader::verify_manifest(dem_path, path)?
+    {
+        println!("Dataset: {} (manifest verified)", manifest);
+    }
     println!("Target Freq: {} Hz", freq);
     println!("Duration: {} s", duration_secs);
+    println!("Batch: {} packets", batch);
     println!("-------------------------------");
 
     let running = Arc::new(AtomicBool::new(true));
@@ -100,7 +203,8 @@ pub fn run_stream(
         processed: Arc::new(AtomicU64::new(0)),
         generated: Arc::new(AtomicU64::new(0)),
         dropped: Arc::new(AtomicU64::new(0)),
-        latency_us: Arc::new(AtomicU64::new(0)),
+        stale: Arc::new(AtomicU64::new(0)),
+        latency_ns: Arc::new(RingBuffer::new(LATENCY_SAMPLES)),
     };
 
     let graph = parser::load_dem_file(dem_path)?;
@@ -111,31 +215,103 @@ pub fn run_stream(
         graph.fast_edges.len()
     );
 
-    let shots = if let Some(path) = b8_path {
-        println!("Loading shots from {}...", path);
-        let raw_bits = loader::load_b8_file(&path)?;
-        loader::slice_shots(&raw_bits, num_detectors)
-    } else {
-        vec![vec![false; num_detectors]]
+    if let Some((path, ShotFormat::B8)) = &b8_path {
+        // Refuse a detector count that does not divide the file, rather
+        // than replay mis-sliced shots.
+        loader::validate_b8(path, num_detectors)?;
+    }
+
+    let mut source = match b8_path {
+        Some((path, ShotFormat::B8)) if stream_io => {
+            let count = loader::count_shots(&path, num_detectors)?;
+            let range = filter.resolve_range(count)?;
+            if filter.is_sampling() {
+                println!(
+                    "Streaming a sample of shots {}..{} of {} from {}.",
+                    range.start, range.end, count, path
+                );
+            } else {
+                println!(
+                    "Streaming {} unique error patterns from {}.",
+                    range.len(),
+                    path
+                );
+            }
+            let filter = ShotFilter {
+                shot_range: Some(range),
+                ..filter
+            };
+            let reader = loader::ShotReader::open(&path, num_detectors)?.with_filter(filter);
+            ShotSource::File(reader)
+        }
+        Some((_, format)) if stream_io => {
+            bail!("--stream-io needs a .b8 file, not .{}", format);
+        }
+        Some((path, format)) => {
+            println!("Loading shots from {}...", path);
+            let shots = loader::load_shots_packed(&path, format, num_detectors)?;
+            let filter = ShotFilter {
+                shot_range: Some(filter.resolve_range(shots.len())?),
+                ..filter
+            };
+            let shots: Vec<_> = filter
+                .select(shots, |shot| BitPack::count_ones(shot))
+                .collect();
+            println!("Loaded {} unique error patterns.", shots.len());
+            ShotSource::Memory { shots, next: 0 }
+        }
+        None => {
+            let shots = vec![vec![0u64; num_detectors.div_ceil(64)]];
+            println!("Loaded {} unique error patterns.", shots.len());
+            ShotSource::Memory { shots, next: 0 }
+        }
     };
-    println!("Loaded {} unique error patterns.", shots.len());
 
     let graph_arc = Arc::new(graph);
-    let ring_buffer = Arc::new(RingBuffer::<TaskPacket>::new(1024));
+    let ring_buffer = Arc::new(RingBuffer::<TaskPacket>::new(QUEUE_CAPACITY));
 
     let rb_cons = ring_buffer.clone();
     let s_cons = stats.processed.clone();
-    let l_cons = stats.latency_us.clone();
+    let l_cons = stats.latency_ns.clone();
     let r_cons = running.clone();
+    let st_cons = stats.stale.clone();
+    let epoch = Instant::now();
+    let max_age_ns = max_age_us.map(|us| us * 1000);
 
     let consumer = thread::spawn(move || {
         let mut decoder = UnionFindDecoder::<MAX_NODES>::new();
         let mut lat_stats = LatencyStats::new();
         let mut results = Vec::with_capacity(1024);
         let mut indices = Vec::with_capacity(64);
+        let mut packets = vec![TaskPacket::default(); batch];
 
         while r_cons.load(Ordering::Relaxed) {
-            if let Some(packet) = rb_cons.pop() {
+            if let Some(max_age) = max_age_ns {
+                // Look at the oldest packet before committing to it, and
+                // skip it if it is too old to be worth decoding.
+                let now = epoch.elapsed().as_nanos() as u64;
+                let is_stale = |p: &TaskPacket| now.saturating_sub(p.timestamp_ns) > max_age;
+                while rb_cons.peek_with(is_stale) == Some(true) {
+                    rb_cons.pop();
+                    st_cons.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            let mut popped = rb_cons.pop_into(&mut packets);
+            if popped == 0 {
+                // Nothing queued: wait for the producer without spinning, but
+                // wake up periodically to notice the end of the run.
+                match rb_cons.pop_blocking(Duration::from_millis(10)) {
+                    Ok(packet) => {
+                        packets[0] = packet;
+                        popped = 1;
+                    }
+                    Err(PopError::Empty) => continue,
+                    Err(PopError::Closed) => break,
+                }
+            }
+
+            for packet in &packets[..popped] {
                 let len = packet.syndrome_len as usize;
                 indices.clear();
                 for i in 0..len {
@@ -143,86 +319,113 @@ pub fn run_stream(
                 }
 
                 let start = Instant::now();
-                let _ = decoder.solve_into(&graph_arc, &indices, &mut results);
+                let _ = decoder.solve_into(&*graph_arc, &indices, &mut results);
                 let lat_ns = start.elapsed().as_nanos() as u64;
 
-                s_cons.fetch_add(1, Ordering::Relaxed);
-                l_cons.store(lat_ns / 1000, Ordering::Relaxed);
+                // A sample refused while the monitor is reading is lost, just
+                // like an evicted one.
+                let _ = l_cons.force_push(lat_ns);
                 lat_stats.update(lat_ns);
-            } else {
-                std::hint::spin_loop();
             }
+            s_cons.fetch_add(popped as u64, Ordering::Relaxed);
         }
         lat_stats.print_report();
+
+        // The producer closes the queue once it has stopped; whatever it
+        // pushed after we stopped decoding is counted rather than abandoned.
+        while !rb_cons.is_closed() {
+            thread::yield_now();
+        }
+        rb_cons.drain().count()
     });
 
     let rb_prod = ring_buffer.clone();
     let s_gen = stats.generated.clone();
     let s_drop = stats.dropped.clone();
     let r_prod = running.clone();
-    let producer_shots = shots.clone();
 
-    let producer = thread::spawn(move || {
-        let interval = Duration::from_micros(1_000_000 / freq);
-        let num_patterns = producer_shots.len();
-        let mut idx = 0;
+    let producer = thread::spawn(move || -> io::Result<()> {
+        let interval = Duration::from_nanos(1_000_000_000 * batch as u64 / freq);
+        let mut indices: StaticVec<usize, 64> = StaticVec::new();
+        let mut result = Ok(());
+        let mut packets = vec![TaskPacket::default(); batch];
 
-        while r_prod.load(Ordering::Relaxed) {
+        'run: while r_prod.load(Ordering::Relaxed) {
             let start = Instant::now();
-            let mut packet = TaskPacket::default();
-            let mut count = 0;
-
-            if num_patterns > 0 {
-                for (det_id, &triggered) in producer_shots[idx].iter().enumerate() {
-                    if triggered && count < 64 {
-                        packet.syndrome_buffer[count] = det_id as u32;
-                        count += 1;
-                    }
+
+            let now = epoch.elapsed().as_nanos() as u64;
+            for packet in packets.iter_mut() {
+                *packet = TaskPacket::default();
+                packet.timestamp_ns = now;
+                // Shots with more than 64 defects are truncated to the first 64.
+                if let Err(e) = source.next_indices(&mut indices) {
+                    result = Err(e);
+                    break 'run;
+                }
+                for (slot, &det_id) in packet.syndrome_buffer.iter_mut().zip(&indices) {
+                    *slot = det_id as u32;
                 }
-                idx = (idx + 1) % num_patterns;
+                packet.syndrome_len = indices.len() as u32;
             }
-            packet.syndrome_len = count as u32;
 
-            if rb_prod.push(packet) {
-                s_gen.fetch_add(1, Ordering::Relaxed);
-            } else {
-                s_drop.fetch_add(1, Ordering::Relaxed);
-            }
+            let pushed = rb_prod.push_slice(&packets);
+            s_gen.fetch_add(pushed as u64, Ordering::Relaxed);
+            s_drop.fetch_add((batch - pushed) as u64, Ordering::Relaxed);
 
             while start.elapsed() < interval {
                 std::hint::spin_loop();
             }
         }
+        // Close even on a read error, so the consumer does not wait forever.
+        rb_prod.close();
+        result
     });
 
     let start_time = Instant::now();
     let mut last_processed = 0;
+    let mut samples = vec![0u64; LATENCY_SAMPLES];
 
     while start_time.elapsed().as_secs() < duration_secs {
         thread::sleep(Duration::from_secs(1));
         let proc = stats.processed.load(Ordering::Relaxed);
         let r#gen = stats.generated.load(Ordering::Relaxed);
         let drop = stats.dropped.load(Ordering::Relaxed);
-        let lat = stats.latency_us.load(Ordering::Relaxed);
+        let stale = stats.stale.load(Ordering::Relaxed);
+        let depth = ring_buffer.len();
+        let n = stats.latency_ns.pop_into(&mut samples);
+        let lat = samples[..n].iter().max().copied().unwrap_or(0) / 1000;
 
         let tput = proc - last_processed;
         last_processed = proc;
 
         println!(
-            "T={:2}s | Gen: {:8} | Proc: {:8} ({:5}/s) | Drop: {:5} | Latency: {:3} us",
+            "T={:2}s | Gen: {:8} | Proc: {:8} ({:5}/s) | Drop: {:5} | Stale: {:5} \
+             | Queue: {:4}/{} | Max latency: {:3} us",
             start_time.elapsed().as_secs(),
             r#gen,
             proc,
             tput,
             drop,
+            stale,
+            depth,
+            ring_buffer.capacity(),
             lat
         );
     }
 
     running.store(false, Ordering::Relaxed);
     thread::sleep(Duration::from_millis(100));
-    consumer.join().unwrap();
-    producer.join().unwrap();
+    let leftover = consumer.join().unwrap();
+    producer.join().unwrap()?;
+
+    let queue = ring_buffer.stats();
+    println!("--- Queue ---");
+    println!("Capacity:   {}", ring_buffer.capacity());
+    println!("High water: {}", queue.high_water);
+    println!("Pushes:     {}", queue.pushes);
+    println!("Pops:       {}", queue.pops);
+    println!("Rejects:    {}", queue.rejects);
+    println!("Leftover:   {}", leftover);
 
     println!("Done.");
     Ok(())
@@ -5,11 +5,15 @@
 //! validates that all shots are successfully decoded. Used for performance
 //! regression testing and optimization validation.
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail, ensure};
+use qcu_core::bit_utils::BitPack;
 use qcu_core::decoder::UnionFindDecoder;
+use qcu_core::graph::DecodingGraph;
+use qcu_io::loader::{ShotFilter, ShotFormat};
 use qcu_io::{loader, parser};
 use rayon::prelude::*;
-use std::time::Instant;
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
 /// Maximum number of nodes supported by the benchmark decoder.
 ///
@@ -20,23 +24,51 @@ const MAX_NODES: usize = 4096;
 
 /// Runs a throughput benchmark on decoding performance.
 ///
-/// Loads a decoding graph and syndrome data, then processes all shots in
+/// Checks the data against its manifest if the generator wrote one, then
+/// loads a decoding graph and syndrome data and processes all shots in
 /// parallel using Rayon. Measures the total time and computes throughput.
 /// Reports results including total time, shots per second, and success rate.
 ///
 /// # Arguments
 ///
 /// * `dem_path` - Path to the decoding graph (.dem file)
-/// * `b8_path` - Path to the syndrome data (.b8 file)
+/// * `b8_path` - Path to the syndrome data (.b8, .01 or .dets file)
+/// * `format` - Layout of the syndrome data file; `stream_io` and `mmap`
+///   need .b8
 /// * `user_detectors` - Optional override for detector count (defaults to graph size)
+/// * `reorder` - Relabel graph nodes in reverse Cuthill-McKee order before
+///   decoding
+/// * `stream_io` - Read shots from the file as they are decoded instead of
+///   loading them all first, keeping memory flat for large files; the
+///   reported time then includes reading the file
+/// * `mmap` - Memory-map the file and decode shots straight from the
+///   mapping; requires the `mmap` feature
+/// * `filter` - Shots to decode; a .b8 file is streamed whenever the filter
+///   does more than pick a range, and `mmap` supports only a range
 ///
 /// # Returns
 ///
-/// Ok(()) on success, or an error if file loading or decoding fails.
-pub fn run_benchmark(dem_path: &str, b8_path: &str, user_detectors: Option<usize>) -> Result<()> {
+/// Ok(()) on success, or an error if the data does not match its manifest
+/// or file loading or decoding fails. The
+/// reported shot count is the number of shots the filter kept.
+#[allow(clippy::too_many_arguments)]
+pub fn run_benchmark(
+    dem_path: &str,
+    b8_path: &str,
+    format: ShotFormat,
+    user_detectors: Option<usize>,
+    reorder: bool,
+    stream_io: bool,
+    mmap: bool,
+    filter: ShotFilter,
+) -> Result<()> {
+    if let Some(manifest) = loader::verify_manifest(dem_path, b8_path)? {
+        println!("Dataset: {} (manifest verified)", manifest);
+    }
+
     println!("Loading Graph from {}...", dem_path);
     let start_load = Instant::now();
-    let graph = parser::load_dem_file(dem_path)?;
+    let mut graph = parser::load_dem_file(dem_path)?;
     println!(
         "Graph loaded in {:?}. Nodes: {}, Edges: {}",
         start_load.elapsed(),
@@ -44,47 +76,312 @@ pub fn run_benchmark(dem_path: &str, b8_path: &str, user_detectors: Option<usize
         graph.fast_edges.len()
     );
 
+    if reorder {
+        let start_reorder = Instant::now();
+        graph.reorder_for_locality();
+        println!("Graph reordered in {:?}.", start_reorder.elapsed());
+    }
+
     let num_detectors = user_detectors.unwrap_or(graph.num_nodes());
+    let b8_info = if format == ShotFormat::B8 {
+        // Refuse a detector count that does not divide the file, rather
+        // than decode mis-sliced shots.
+        Some(loader::validate_b8(b8_path, num_detectors)?)
+    } else if stream_io || mmap {
+        bail!("--stream-io and --mmap need a .b8 file, not .{}", format);
+    } else {
+        None
+    };
+    if filter.is_sampling() {
+        ensure!(!mmap, "--mmap supports --shot-range only");
+        println!(
+            "Filter: every {} shot(s), at least {} defect(s), at most {} shot(s).",
+            filter.stride.max(1),
+            filter.min_defects,
+            filter
+                .max_shots
+                .map_or("all".to_string(), |n| n.to_string())
+        );
+    }
 
-    println!("Loading Shots from {}...", b8_path);
-    let raw_bits = loader::load_b8_file(b8_path)?;
-    let shots = loader::slice_shots(&raw_bits, num_detectors);
-    println!("Loaded {} shots.", shots.len());
+    let (solved_count, num_shots, duration) = if mmap {
+        decode_mapped(&graph, b8_path, num_detectors, &filter)?
+    } else if let Some(info) = b8_info.filter(|_| stream_io || filter.is_sampling()) {
+        let range = filter.resolve_range(info.shots)?;
+        let filter = ShotFilter {
+            shot_range: Some(range.clone()),
+            ..filter
+        };
+        let reader = loader::ShotReader::open(b8_path, num_detectors)?.with_filter(filter);
+        println!(
+            "Streaming shots {}..{} of {} from {}.",
+            range.start, range.end, info.shots, b8_path
+        );
 
-    println!("Starting Benchmark (Parallel - Rayon)...");
-    let start_bench = Instant::now();
+        println!("Starting Benchmark (Parallel - Rayon, streamed I/O)...");
+        let start_bench = Instant::now();
 
-    let solved_count: usize = shots
-        .par_iter()
-        .map(|shot| {
-            let mut local_decoder = UnionFindDecoder::<MAX_NODES>::new();
-            let mut local_results = Vec::with_capacity(128);
-
-            let syndrome: Vec<usize> = shot
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &triggered)| if triggered { Some(i) } else { None })
-                .collect();
-
-            if local_decoder
-                .solve_into(&graph, &syndrome, &mut local_results)
-                .is_ok()
-            {
-                1
-            } else {
-                0
-            }
-        })
-        .sum();
+        let (solved_count, num_shots) = reader
+            .par_bridge()
+            .map_init(
+                Scratch::default,
+                |scratch, shot| -> Result<(usize, usize)> {
+                    scratch.syndrome.clear();
+                    BitPack::extract_indices_vec(&shot?, &mut scratch.syndrome);
+                    Ok((scratch.solve(&graph)?, 1))
+                },
+            )
+            .try_reduce(|| (0, 0), |a, b| Ok((a.0 + b.0, a.1 + b.1)))?;
+
+        (solved_count, num_shots, start_bench.elapsed())
+    } else if let Some(info) = b8_info {
+        let range = filter.resolve_range(info.shots)?;
+        println!(
+            "Reading {} shots from {} in blocks of {}.",
+            range.len(),
+            b8_path,
+            loader::SHOTS_PER_BLOCK
+        );
+
+        println!("Starting Benchmark (Parallel - Rayon, block reads)...");
+        let start_bench = Instant::now();
+
+        let solved_count = loader::par_shot_range(b8_path, num_detectors, range.clone())?
+            .map(|block| solve_block(&graph, &block?))
+            .try_reduce(|| 0, |a, b| Ok(a + b))?;
+
+        (solved_count, range.len(), start_bench.elapsed())
+    } else {
+        println!("Loading Shots from {}...", b8_path);
+        let shots = loader::load_shots_sparse(b8_path, format, num_detectors)?;
+        let range = filter.resolve_range(shots.len())?;
+        let filter = ShotFilter {
+            shot_range: Some(range),
+            ..filter
+        };
+        let shots: Vec<_> = filter.select(shots, Vec::len).collect();
+        println!("Loaded {} shots.", shots.len());
+
+        println!("Starting Benchmark (Parallel - Rayon)...");
+        let start_bench = Instant::now();
+
+        let solved_count = shots
+            .par_iter()
+            .map_init(Scratch::default, |scratch, shot| {
+                scratch.syndrome.clear();
+                scratch.syndrome.extend(shot.iter().map(|&i| i as usize));
+                scratch.solve(&graph)
+            })
+            .try_reduce(|| 0, |a, b| Ok(a + b))?;
+
+        (solved_count, shots.len(), start_bench.elapsed())
+    };
 
-    let duration = start_bench.elapsed();
     let seconds = duration.as_secs_f64();
-    let throughput = shots.len() as f64 / seconds;
+    let throughput = num_shots as f64 / seconds;
 
     println!("Results");
     println!("Time: {:.4} s", seconds);
     println!("Throughput: {:.2} shots/s", throughput);
-    println!("Solved: {}/{}", solved_count, shots.len());
+    println!("Solved: {}/{}", solved_count, num_shots);
 
     Ok(())
 }
+
+/// Decodes a block of shots with one decoder.
+///
+/// The decoder and its buffers are set up once for the whole block rather
+/// than once per shot.
+///
+/// # Arguments
+///
+/// * `graph` - Decoding graph, possibly reordered
+/// * `block` - Packed shots in the original detector labelling
+///
+/// # Returns
+///
+/// The number of shots in the block for which the decoder found a
+/// correction, or an error if a syndrome could not be translated into the
+/// graph's node numbering.
+fn solve_block(graph: &DecodingGraph, block: &loader::ShotBlock) -> Result<usize> {
+    let mut decoder = UnionFindDecoder::<MAX_NODES>::new();
+    let mut results = Vec::with_capacity(128);
+    let mut original = Vec::new();
+    let mut mapped = Vec::new();
+    let mut solved = 0;
+
+    for shot in block.iter() {
+        original.clear();
+        BitPack::extract_indices_vec(shot, &mut original);
+        let syndrome = internal_syndrome(graph, &original, &mut mapped)?;
+        if decoder.solve_into(graph, syndrome, &mut results).is_ok() {
+            solved += 1;
+        }
+    }
+    Ok(solved)
+}
+
+/// Per-worker buffers for decoding shots one at a time.
+///
+/// Created once per Rayon work split via `map_init`, so the hot loop reuses
+/// its syndrome buffers instead of allocating for every shot.
+#[derive(Default)]
+struct Scratch {
+    /// Packed detector words of the current shot, for memory-mapped input.
+    #[cfg(feature = "mmap")]
+    words: Vec<u64>,
+
+    /// Fired detector indices of the current shot, in the original labelling.
+    syndrome: Vec<usize>,
+
+    /// The same indices translated for a reordered graph.
+    mapped: Vec<usize>,
+}
+
+impl Scratch {
+    /// Decodes the shot held in `syndrome` with a fresh decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - Decoding graph, possibly reordered
+    ///
+    /// # Returns
+    ///
+    /// 1 if the decoder found a correction, 0 otherwise, for summing, or an
+    /// error if the syndrome could not be translated.
+    fn solve(&mut self, graph: &DecodingGraph) -> Result<usize> {
+        let mut local_decoder = UnionFindDecoder::<MAX_NODES>::new();
+        let mut local_results = Vec::with_capacity(128);
+
+        let syndrome = internal_syndrome(graph, &self.syndrome, &mut self.mapped)?;
+        if local_decoder
+            .solve_into(graph, syndrome, &mut local_results)
+            .is_ok()
+        {
+            Ok(1)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+/// Returns a syndrome in the graph's internal node numbering.
+///
+/// # Arguments
+///
+/// * `graph` - Decoding graph, possibly reordered
+/// * `original` - Fired detector indices in the original labelling
+/// * `mapped` - Buffer the translated indices are written to when the graph
+///   has been reordered
+///
+/// # Returns
+///
+/// `original` itself if the graph keeps the original labelling, the
+/// translated indices in `mapped` otherwise, or an error if the translation
+/// fails, so a shot is never decoded against a stale syndrome.
+fn internal_syndrome<'a>(
+    graph: &DecodingGraph,
+    original: &'a [usize],
+    mapped: &'a mut Vec<usize>,
+) -> Result<&'a [usize]> {
+    if !graph.is_reordered() {
+        return Ok(original);
+    }
+    mapped.resize(original.len(), 0);
+    graph
+        .map_syndrome_in(original, mapped)
+        .map_err(|e| anyhow!("failed to map syndrome into reordered graph: {:?}", e))?;
+    Ok(mapped)
+}
+
+/// Decodes shots straight from a memory map of the .b8 file.
+///
+/// Shots are packed on the worker threads as Rayon indexes them, so
+/// nothing is read ahead of time and any sub-range costs only its own
+/// pages.
+///
+/// # Arguments
+///
+/// * `graph` - Decoding graph, possibly reordered
+/// * `b8_path` - Path to the syndrome data (.b8 file)
+/// * `num_detectors` - Number of detector bits per shot
+/// * `filter` - Shots to decode; only its range is used
+///
+/// # Returns
+///
+/// The number of shots solved, the number decoded, and the decode time.
+#[cfg(feature = "mmap")]
+fn decode_mapped(
+    graph: &DecodingGraph,
+    b8_path: &str,
+    num_detectors: usize,
+    filter: &ShotFilter,
+) -> Result<(usize, usize, Duration)> {
+    let map = loader::load_b8_mmap(b8_path)?;
+    let range = filter.resolve_range(map.num_shots(num_detectors))?;
+    println!("Mapped {} shots from {}.", range.len(), b8_path);
+
+    println!("Starting Benchmark (Parallel - Rayon, memory-mapped)...");
+    let start_bench = Instant::now();
+
+    let solved_count = range
+        .clone()
+        .into_par_iter()
+        .map_init(Scratch::default, |scratch, idx| {
+            scratch.words.resize(num_detectors.div_ceil(64), 0);
+            loader::pack_shot(
+                map.shot(idx, num_detectors),
+                num_detectors,
+                &mut scratch.words,
+            );
+            scratch.syndrome.clear();
+            BitPack::extract_indices_vec(&scratch.words, &mut scratch.syndrome);
+            scratch.solve(graph)
+        })
+        .try_reduce(|| 0, |a, b| Ok(a + b))?;
+
+    Ok((solved_count, range.len(), start_bench.elapsed()))
+}
+
+/// Stand-in for `decode_mapped` when built without the `mmap` feature.
+#[cfg(not(feature = "mmap"))]
+fn decode_mapped(
+    _graph: &DecodingGraph,
+    _b8_path: &str,
+    _num_detectors: usize,
+    _filter: &ShotFilter,
+) -> Result<(usize, usize, Duration)> {
+    bail!("--mmap needs qcu_host built with the `mmap` feature")
+}
+
+/// Parses a `--shot-range` argument of the form `START..END`.
+///
+/// Either bound may be omitted, as in `..100` or `500..`. An open end is
+/// returned as `usize::MAX` and resolved against the file once it is
+/// loaded.
+///
+/// # Arguments
+///
+/// * `arg` - Command-line text
+///
+/// # Returns
+///
+/// The half-open range, or a message describing the parse error.
+pub fn parse_shot_range(arg: &str) -> std::result::Result<Range<usize>, String> {
+    let (start, end) = arg
+        .split_once("..")
+        .ok_or_else(|| format!("expected START..END, got `{}`", arg))?;
+    let bound = |text: &str, default: usize| {
+        if text.is_empty() {
+            Ok(default)
+        } else {
+            text.parse::<usize>()
+                .map_err(|e| format!("invalid shot index `{}`: {}", text, e))
+        }
+    };
+    let range = bound(start, 0)?..bound(end, usize::MAX)?;
+    if range.start > range.end {
+        return Err(format!("shot range `{}` ends before it starts", arg));
+    }
+    Ok(range)
+}
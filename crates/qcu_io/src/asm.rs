@@ -0,0 +1,546 @@
+//! Text assembler and disassembler for instruction programs.
+//!
+//! Translates between a line-oriented text form ("H 3", "CNOT 3 4",
+//! "M 4 D12", "DECODE", "HALT") and the binary `Instruction` format executed
+//! by the VM. Mnemonics are those returned by `Opcode::mnemonic` and are
+//! matched case-insensitively. Comments start with `#` and run to the end of
+//! the line.
+//!
+//! Loops can be written with the raw `LOOP n` / `ENDLOOP` instructions or
+//! with the block form `REPEAT n {` ... `}`. A `LOOP` may carry a label
+//! (`round: LOOP 21`), in which case the closing `ENDLOOP round` must name
+//! the same label; labels exist only in the text and are resolved to the
+//! plain loop instructions.
+//!
+//! Operands up to `u32::MAX` are accepted; an instruction with an operand
+//! above 65535 assembles to an `Opcode::Wide` prefix followed by the
+//! instruction, and the disassembler folds such pairs back into one line.
+
+use anyhow::anyhow;
+use qcu_common::isa::{Instruction, Instruction32, Opcode};
+use qcu_core::program::Program;
+use std::fmt;
+
+/// Error produced while assembling a program.
+///
+/// Carries the 1-based line and column of the offending token so the
+/// message can be shown next to the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// Line number of the error, starting at 1.
+    pub line: usize,
+
+    /// Column of the offending token, starting at 1.
+    pub column: usize,
+
+    /// What went wrong.
+    pub kind: AsmErrorKind,
+}
+
+/// Kinds of assembly errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    /// The mnemonic does not name any opcode.
+    UnknownMnemonic(String),
+
+    /// The instruction has the wrong number of operands.
+    OperandCount {
+        /// Operands required by the mnemonic.
+        expected: usize,
+        /// Operands found on the line.
+        found: usize,
+    },
+
+    /// An operand is not a valid number for its position.
+    InvalidOperand(String),
+
+    /// An `ENDLOOP` or `}` has no open loop to close.
+    UnmatchedLoopEnd,
+
+    /// A loop is still open at the end of the program.
+    UnclosedLoop,
+
+    /// An `ENDLOOP` label does not match the innermost open loop.
+    LabelMismatch {
+        /// Label of the innermost open loop, if it has one.
+        expected: Option<String>,
+        /// Label named by the `ENDLOOP`.
+        found: String,
+    },
+
+    /// A label is defined twice.
+    DuplicateLabel(String),
+
+    /// A label is attached to something other than a loop start.
+    MisplacedLabel(String),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(m) => write!(f, "unknown mnemonic '{}'", m),
+            AsmErrorKind::OperandCount { expected, found } => {
+                write!(f, "expected {} operand(s), found {}", expected, found)
+            }
+            AsmErrorKind::InvalidOperand(op) => write!(f, "invalid operand '{}'", op),
+            AsmErrorKind::UnmatchedLoopEnd => write!(f, "loop end without a matching loop"),
+            AsmErrorKind::UnclosedLoop => write!(f, "loop is never closed"),
+            AsmErrorKind::LabelMismatch { expected, found } => match expected {
+                Some(label) => write!(f, "ENDLOOP '{}' closes loop '{}'", found, label),
+                None => write!(f, "ENDLOOP '{}' closes an unlabelled loop", found),
+            },
+            AsmErrorKind::DuplicateLabel(label) => write!(f, "label '{}' defined twice", label),
+            AsmErrorKind::MisplacedLabel(label) => {
+                write!(f, "label '{}' must be attached to a LOOP", label)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Returns the operand prefixes of an opcode, one entry per operand.
+///
+/// A `Some` prefix marks an operand that the disassembler writes with a
+/// letter prefix (detector `D`, observable `L`); the assembler accepts such
+/// operands with or without the prefix.
+fn operand_prefixes(opcode: Opcode) -> &'static [Option<char>] {
+    match opcode {
+        Opcode::GateH
+        | Opcode::GateS
+        | Opcode::GateSdg
+        | Opcode::GateX
+        | Opcode::GateZ
+        | Opcode::Barrier
+        | Opcode::LoopBegin
+        | Opcode::ShiftDetectors => &[None],
+        Opcode::GateCNOT | Opcode::CondPauliX | Opcode::CondPauliZ | Opcode::Wide => {
+            &[None, None]
+        }
+        Opcode::Measure | Opcode::MeasureX => &[None, Some('D')],
+        Opcode::ObservableInclude => &[None, Some('L')],
+        Opcode::Decode | Opcode::Reset | Opcode::LoopEnd | Opcode::Halt => &[],
+    }
+}
+
+/// Splits a line into whitespace- or comma-separated tokens.
+///
+/// Stops at the first `#`. Each token is returned with its 1-based column.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let code = line.split('#').next().unwrap_or("");
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in code.char_indices() {
+        let separator = c.is_whitespace() || c == ',';
+        match (start, separator) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                tokens.push((s + 1, &code[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s + 1, &code[s..]));
+    }
+    tokens
+}
+
+/// Looks up an opcode by mnemonic, ignoring case.
+fn opcode_for(mnemonic: &str) -> Option<Opcode> {
+    Opcode::ALL
+        .iter()
+        .copied()
+        .find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+}
+
+/// Parses a program from its text form.
+///
+/// Each non-empty line holds at most one instruction, optionally preceded
+/// by a label. Operands are decimal or `0x`-prefixed hexadecimal `u32`
+/// values; detector and observable operands may carry a `D` or `L` prefix.
+/// An instruction with an operand above 65535 is emitted as a Wide prefix
+/// and the instruction, so it occupies two entries of the result.
+///
+/// # Arguments
+///
+/// * `source` - Program text
+///
+/// # Returns
+///
+/// The assembled instructions, or the first error with its line and column.
+pub fn parse_program(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut program = Vec::new();
+    let mut labels: Vec<String> = Vec::new();
+    let mut open_loops: Vec<(Option<String>, usize, usize)> = Vec::new();
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_no = line_idx + 1;
+        let err = |column: usize, kind: AsmErrorKind| AsmError {
+            line: line_no,
+            column,
+            kind,
+        };
+
+        let mut tokens = tokenize(line);
+        if tokens.is_empty() {
+            continue;
+        }
+
+        let mut label = None;
+        if let Some(name) = tokens[0].1.strip_suffix(':') {
+            let column = tokens[0].0;
+            if labels.iter().any(|l| l == name) {
+                return Err(err(column, AsmErrorKind::DuplicateLabel(name.to_string())));
+            }
+            labels.push(name.to_string());
+            label = Some((column, name.to_string()));
+            tokens.remove(0);
+        }
+
+        let Some(&(column, mnemonic)) = tokens.first() else {
+            let (column, name) = label.expect("label-only line has a label");
+            return Err(err(column, AsmErrorKind::MisplacedLabel(name)));
+        };
+        let operands = &tokens[1..];
+
+        let (opcode, operands) = if mnemonic.eq_ignore_ascii_case("REPEAT") {
+            match operands {
+                [count, (_, "{")] => (Opcode::LoopBegin, core::slice::from_ref(count)),
+                _ => {
+                    return Err(err(
+                        column,
+                        AsmErrorKind::OperandCount {
+                            expected: 1,
+                            found: operands.len(),
+                        },
+                    ));
+                }
+            }
+        } else if mnemonic == "}" {
+            (Opcode::LoopEnd, operands)
+        } else {
+            let opcode = opcode_for(mnemonic)
+                .ok_or_else(|| err(column, AsmErrorKind::UnknownMnemonic(mnemonic.to_string())))?;
+            (opcode, operands)
+        };
+
+        if let Some((label_column, name)) = &label
+            && opcode != Opcode::LoopBegin
+        {
+            return Err(err(*label_column, AsmErrorKind::MisplacedLabel(name.clone())));
+        }
+
+        let mut values = [0u32; 2];
+        match opcode {
+            Opcode::LoopEnd => {
+                let Some((name, _, _)) = open_loops.pop() else {
+                    return Err(err(column, AsmErrorKind::UnmatchedLoopEnd));
+                };
+                match operands {
+                    [] => {}
+                    [(col, found)] if mnemonic != "}" => {
+                        if name.as_deref() != Some(*found) {
+                            return Err(err(
+                                *col,
+                                AsmErrorKind::LabelMismatch {
+                                    expected: name,
+                                    found: found.to_string(),
+                                },
+                            ));
+                        }
+                    }
+                    _ => {
+                        return Err(err(
+                            column,
+                            AsmErrorKind::OperandCount {
+                                expected: 0,
+                                found: operands.len(),
+                            },
+                        ));
+                    }
+                }
+            }
+            _ => {
+                let prefixes = operand_prefixes(opcode);
+                if operands.len() != prefixes.len() {
+                    return Err(err(
+                        column,
+                        AsmErrorKind::OperandCount {
+                            expected: prefixes.len(),
+                            found: operands.len(),
+                        },
+                    ));
+                }
+                for (i, (&(col, text), prefix)) in operands.iter().zip(prefixes).enumerate() {
+                    values[i] = parse_operand(text, *prefix)
+                        .filter(|&v| opcode != Opcode::Wide || v <= u16::MAX as u32)
+                        .ok_or_else(|| err(col, AsmErrorKind::InvalidOperand(text.to_string())))?;
+                }
+                if opcode == Opcode::LoopBegin {
+                    open_loops.push((label.map(|(_, name)| name), line_no, column));
+                }
+            }
+        }
+
+        let instr = Instruction32::new(opcode, values[0], values[1]);
+        match instr.narrow() {
+            Some(narrow) => program.push(narrow),
+            None => {
+                let (prefix, base) = instr.split();
+                program.extend([prefix, base]);
+            }
+        }
+    }
+
+    if let Some((_, line, column)) = open_loops.pop() {
+        return Err(AsmError {
+            line,
+            column,
+            kind: AsmErrorKind::UnclosedLoop,
+        });
+    }
+    Ok(program)
+}
+
+/// Assembles program text into a validated `Program`.
+///
+/// Runs `parse_program` and then `Program::validate`, so the result is
+/// Halt-terminated, has balanced loops, and stays within the given qubit
+/// and detector counts.
+///
+/// # Arguments
+///
+/// * `source` - Program text
+/// * `num_qubits` - Number of qubits the program may address
+/// * `num_detectors` - Number of detectors the program may address
+///
+/// # Returns
+///
+/// The validated program, or an error describing the assembly or
+/// validation failure.
+pub fn assemble(source: &str, num_qubits: usize, num_detectors: usize) -> anyhow::Result<Program> {
+    let instrs = parse_program(source)?;
+    Program::validate(&instrs, num_qubits, num_detectors)
+        .map_err(|e| anyhow!("program failed validation: {:?}", e))
+}
+
+/// Parses a single numeric operand.
+///
+/// # Arguments
+///
+/// * `text` - Operand token
+/// * `prefix` - Optional letter prefix accepted before the number
+///
+/// # Returns
+///
+/// The operand value, or None if it is not a valid `u32`.
+fn parse_operand(text: &str, prefix: Option<char>) -> Option<u32> {
+    let digits = match prefix {
+        Some(p) => text
+            .strip_prefix(p)
+            .or_else(|| text.strip_prefix(p.to_ascii_lowercase()))
+            .unwrap_or(text),
+        None => text,
+    };
+    match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => digits.parse().ok(),
+    }
+}
+
+/// Formats a program as text.
+///
+/// Writes one instruction per line using the canonical mnemonics, with
+/// loop bodies indented by four spaces. The output assembles back to the
+/// same instructions with `parse_program`. Instructions whose opcode byte
+/// is not recognised are written as comments so the dump stays readable.
+/// A Wide prefix is folded into the following instruction when the result
+/// needs it, and written as a raw `WIDE` line otherwise so the round trip
+/// stays exact.
+///
+/// # Arguments
+///
+/// * `program` - Instructions to format
+///
+/// # Returns
+///
+/// The program text, ending in a newline if non-empty.
+pub fn format_program(program: &[Instruction]) -> String {
+    let mut out = String::new();
+    let mut depth = 0usize;
+
+    let mut pc = 0;
+    while pc < program.len() {
+        let mut instr = Instruction32::from(program[pc]);
+        pc += 1;
+        if instr.opcode == Opcode::Wide as u8
+            && let Some(&base) = program.get(pc)
+            && let Ok(wide) = Instruction32::from_parts(Some(program[pc - 1]), base)
+            && wide.needs_prefix()
+        {
+            instr = wide;
+            pc += 1;
+        }
+
+        let opcode = Opcode::try_from(instr.opcode);
+        if opcode == Ok(Opcode::LoopEnd) {
+            depth = depth.saturating_sub(1);
+        }
+        for _ in 0..depth {
+            out.push_str("    ");
+        }
+
+        match opcode {
+            Ok(op) => {
+                out.push_str(op.mnemonic());
+                let values = [instr.operand_1, instr.operand_2];
+                for (value, prefix) in values.iter().zip(operand_prefixes(op)) {
+                    out.push(' ');
+                    if let Some(p) = prefix {
+                        out.push(*p);
+                    }
+                    out.push_str(&value.to_string());
+                }
+                if op == Opcode::LoopBegin {
+                    depth += 1;
+                }
+            }
+            Err(_) => {
+                out.push_str(&format!(
+                    "# unknown opcode 0x{:02X} {} {}",
+                    instr.opcode, instr.operand_1, instr.operand_2
+                ));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(program: &[Instruction]) -> Vec<[u8; 6]> {
+        program.iter().map(Instruction::to_bytes).collect()
+    }
+
+    fn error_at(source: &str) -> (usize, usize, AsmErrorKind) {
+        let err = parse_program(source).unwrap_err();
+        (err.line, err.column, err.kind)
+    }
+
+    #[test]
+    fn every_opcode_round_trips_through_text() {
+        let mut program = Vec::new();
+        for (i, &op) in Opcode::ALL.iter().enumerate() {
+            let arity = operand_prefixes(op).len();
+            let op1 = if arity >= 1 { i as u16 + 1 } else { 0 };
+            let op2 = if arity == 2 { i as u16 + 100 } else { 0 };
+            match op {
+                Opcode::LoopEnd => {}
+                Opcode::LoopBegin => {
+                    program.push(Instruction::new(op, op1, op2));
+                    program.push(Instruction::new(Opcode::GateX, 2, 0));
+                    program.push(Instruction::new(Opcode::LoopEnd, 0, 0));
+                }
+                // A prefix whose operands fit 16 bits stays a raw WIDE line.
+                Opcode::Wide => {
+                    program.push(Instruction::new(op, 0, 0));
+                    program.push(Instruction::new(Opcode::GateH, 7, 0));
+                }
+                _ => program.push(Instruction::new(op, op1, op2)),
+            }
+        }
+        for op in Opcode::ALL {
+            assert!(program.iter().any(|i| i.opcode == *op as u8), "{op:?}");
+        }
+
+        let text = format_program(&program);
+        assert!(text.contains("    X 2\n"), "{text}");
+        assert!(text.contains("WIDE 0 0\nH 7\n"), "{text}");
+        assert_eq!(bytes(&parse_program(&text).unwrap()), bytes(&program));
+    }
+
+    #[test]
+    fn wide_operands_fold_into_one_line() {
+        let program = parse_program("M 70000 D0x12345\nobs_include 1, l2\n").unwrap();
+        assert_eq!(program.len(), 3);
+        assert_eq!(program[0].opcode, Opcode::Wide as u8);
+        let text = format_program(&program);
+        assert_eq!(text, "M 70000 D74565\nOBS_INCLUDE 1 L2\n");
+        assert_eq!(bytes(&parse_program(&text).unwrap()), bytes(&program));
+    }
+
+    #[test]
+    fn labelled_and_block_loops_assemble_alike() {
+        let labelled = parse_program("round: LOOP 3\n  H 0\nENDLOOP round\nHALT").unwrap();
+        let block = parse_program("REPEAT 3 {\n  H 0 # comment\n}\nHALT").unwrap();
+        assert_eq!(bytes(&labelled), bytes(&block));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(
+            error_at("H 0\n  FOO 1"),
+            (2, 3, AsmErrorKind::UnknownMnemonic("FOO".into()))
+        );
+        assert_eq!(
+            error_at("CNOT 1 x2"),
+            (1, 8, AsmErrorKind::InvalidOperand("x2".into()))
+        );
+        assert_eq!(
+            error_at("M 1 D99999999999"),
+            (1, 5, AsmErrorKind::InvalidOperand("D99999999999".into()))
+        );
+        assert_eq!(
+            error_at("WIDE 1 70000"),
+            (1, 8, AsmErrorKind::InvalidOperand("70000".into()))
+        );
+        assert_eq!(
+            error_at("\n\n   CNOT 1"),
+            (3, 4, AsmErrorKind::OperandCount { expected: 2, found: 1 })
+        );
+        assert_eq!(
+            error_at("HALT 0"),
+            (1, 1, AsmErrorKind::OperandCount { expected: 0, found: 1 })
+        );
+    }
+
+    #[test]
+    fn loop_errors_are_reported() {
+        assert_eq!(error_at("H 0\nENDLOOP"), (2, 1, AsmErrorKind::UnmatchedLoopEnd));
+        assert_eq!(error_at("H 0\n LOOP 2\nH 1"), (2, 2, AsmErrorKind::UnclosedLoop));
+        assert_eq!(
+            error_at("a: LOOP 2\nENDLOOP b"),
+            (
+                2,
+                9,
+                AsmErrorKind::LabelMismatch {
+                    expected: Some("a".into()),
+                    found: "b".into()
+                }
+            )
+        );
+        assert_eq!(
+            error_at("a: LOOP 2\nENDLOOP\na: LOOP 2"),
+            (3, 1, AsmErrorKind::DuplicateLabel("a".into()))
+        );
+        assert_eq!(
+            error_at("x: H 0"),
+            (1, 1, AsmErrorKind::MisplacedLabel("x".into()))
+        );
+    }
+
+    #[test]
+    fn unknown_opcodes_are_written_as_comments() {
+        let mut junk = Instruction::new(Opcode::Halt, 1, 2);
+        junk.opcode = 0x13;
+        let text = format_program(&[junk, Instruction::new(Opcode::Halt, 0, 0)]);
+        assert_eq!(text, "# unknown opcode 0x13 1 2\nHALT\n");
+        assert_eq!(parse_program(&text).unwrap().len(), 1);
+    }
+}
@@ -5,6 +5,12 @@
 //! correction system. These utilities handle file format parsing and
 //! conversion to internal data structures.
 
+/// Text assembler and disassembler for instruction programs.
+///
+/// Converts between a human-readable program text and the binary
+/// instruction format, reporting assembly errors by line and column.
+pub mod asm;
+
 /// File loading utilities for quantum error correction data formats.
 ///
 /// Provides functions for reading binary syndrome data (.b8 files) and
@@ -18,3 +24,6 @@ pub mod loader;
 /// structure of quantum error correction codes. Constructs DecodingGraph
 /// instances from the parsed edge and node information.
 pub mod parser;
+
+#[cfg(test)]
+mod test_util;
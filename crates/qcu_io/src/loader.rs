@@ -3,13 +3,96 @@
 //! Provides functions for reading Stim .b8 files, which contain packed binary
 //! data representing syndrome measurements from multiple quantum shots. The
 //! loader unpacks the binary data into per-shot boolean vectors for processing
-//! by the decoder.
+//! by the decoder. For files too large to hold in memory, `ShotReader`
+//! reads them a chunk at a time and hands out one packed shot at a time,
+//! and with the `mmap` feature `load_b8_mmap` maps them for zero-copy
+//! random access. Stim's ASCII `.01` format, one line of '0'/'1' per shot,
+//! is read by `load_01_file` and `Shot01Reader`, and the sparse `.dets`
+//! format, one `shot D3 D17 L0` line per shot, by `load_dets_file` and
+//! `DetsReader`. With the `parallel` feature, `par_shots` hands .b8 shots
+//! to Rayon in blocks read straight from the file. `ShotFilter` picks a
+//! subset of shots by index range, stride, defect count and limit.
+//! `Manifest` records how a dataset was generated, in a JSON sidecar next
+//! to the .b8 file, and `verify_manifest` checks the data still matches it.
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail, ensure};
 use bitvec::prelude::*;
+use qcu_core::bit_utils::BitPack;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// On-disk layout of a shot file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShotFormat {
+    /// Stim .b8: bits packed little-endian, each shot padded to a byte.
+    B8,
+
+    /// Stim .01: one line of '0' and '1' characters per shot.
+    Ascii01,
+
+    /// Stim .dets: one line per shot listing the detectors that fired.
+    Dets,
+}
+
+impl ShotFormat {
+    /// Guesses the format from a file extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the shot file
+    ///
+    /// # Returns
+    ///
+    /// Ascii01 for a `.01` extension, Dets for `.dets`, B8 for anything
+    /// else.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension() {
+            Some(ext) if ext == "01" => ShotFormat::Ascii01,
+            Some(ext) if ext == "dets" => ShotFormat::Dets,
+            _ => ShotFormat::B8,
+        }
+    }
+}
+
+impl FromStr for ShotFormat {
+    type Err = String;
+
+    /// Parses a format name as given on the command line: `b8`, `01` or
+    /// `dets`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "b8" => Ok(ShotFormat::B8),
+            "01" => Ok(ShotFormat::Ascii01),
+            "dets" => Ok(ShotFormat::Dets),
+            _ => Err(format!(
+                "unknown shot format `{}` (expected b8, 01 or dets)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ShotFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShotFormat::B8 => "b8",
+            ShotFormat::Ascii01 => "01",
+            ShotFormat::Dets => "dets",
+        })
+    }
+}
+
+/// Size of the read buffer used by `ShotReader`.
+///
+/// Large enough that per-read overhead is negligible next to decoding, and
+/// small enough that memory use stays flat however large the file is.
+const CHUNK_BYTES: usize = 1 << 20;
 
 /// Loads a Stim .b8 file containing binary measurement data.
 ///
@@ -67,3 +150,2343 @@ pub fn slice_shots(raw_bits: &BitVec<u8, Lsb0>, bits_per_shot: usize) -> Vec<Vec
 
     shots
 }
+
+/// Splits raw bit data into per-shot packed word vectors.
+///
+/// Same shot layout as `slice_shots`, but each shot is packed into words
+/// straight from the file bytes, without an intermediate boolean vector:
+/// detector `i` is bit `i % 64` of word `i / 64`, and the padding bits of
+/// the byte-aligned stride are cleared.
+///
+/// # Arguments
+///
+/// * `raw_bits` - Packed bit vector from load_b8_file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// A vector of packed shots, each `bits_per_shot.div_ceil(64)` words long.
+pub fn slice_shots_packed(raw_bits: &BitVec<u8, Lsb0>, bits_per_shot: usize) -> Vec<Vec<u64>> {
+    shot_records(raw_bits, bits_per_shot)
+        .map(|record| {
+            let mut words = vec![0; bits_per_shot.div_ceil(64)];
+            pack_shot(record, bits_per_shot, &mut words);
+            words
+        })
+        .collect()
+}
+
+/// Splits raw bit data into per-shot lists of fired detectors.
+///
+/// Same shot layout as `slice_shots`, but each shot is reduced to the
+/// indices of its set bits, in increasing order, which is the form the
+/// decoder takes. Sparse shots cost a few bytes each instead of one per
+/// detector.
+///
+/// # Arguments
+///
+/// * `raw_bits` - Packed bit vector from load_b8_file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// A vector holding, for each shot, the indices of the detectors that
+/// fired.
+pub fn slice_shots_sparse(raw_bits: &BitVec<u8, Lsb0>, bits_per_shot: usize) -> Vec<Vec<u32>> {
+    let mut words = vec![0; bits_per_shot.div_ceil(64)];
+    shot_records(raw_bits, bits_per_shot)
+        .map(|record| {
+            pack_shot(record, bits_per_shot, &mut words);
+            BitPack::iter_ones(&words).map(|i| i as u32).collect()
+        })
+        .collect()
+}
+
+/// Returns the raw bytes of each complete shot in the file data.
+///
+/// Shots start on byte boundaries, `bits_per_shot.div_ceil(8)` bytes
+/// apart; a trailing partial shot is skipped.
+fn shot_records(raw_bits: &BitVec<u8, Lsb0>, bits_per_shot: usize) -> impl Iterator<Item = &[u8]> {
+    let bytes_per_shot = bits_per_shot.div_ceil(8);
+    let num_shots = raw_bits.len() / (bytes_per_shot * 8);
+    raw_bits.as_raw_slice()[..num_shots * bytes_per_shot].chunks_exact(bytes_per_shot)
+}
+
+/// Packs the bytes of one shot into little-endian words.
+///
+/// Uses the layout of `slice_shots_packed`, for shots taken from other
+/// sources such as `B8Mmap::shot`. A thin wrapper over
+/// `BitPack::pack_bytes`; `words` is left unchanged if either buffer is
+/// shorter than given below.
+///
+/// # Arguments
+///
+/// * `record` - The shot's `bits_per_shot.div_ceil(8)` bytes
+/// * `bits_per_shot` - Number of detector bits in the shot
+/// * `words` - Receives the packed shot; must be
+///   `bits_per_shot.div_ceil(64)` words long
+pub fn pack_shot(record: &[u8], bits_per_shot: usize, words: &mut [u64]) {
+    let _ = BitPack::pack_bytes(record, bits_per_shot, words);
+}
+
+/// Selection of the shots to decode out of a file, for quick iteration on
+/// a subset.
+///
+/// A shot is kept if its index lies in `shot_range`, is a multiple of
+/// `stride` past the start of the range, and has at least `min_defects`
+/// fired detectors; selection stops once `max_shots` shots have been kept.
+/// The default filter keeps every shot. `ShotReader::with_filter` applies
+/// it while streaming a .b8 file, skipping unselected indices without
+/// unpacking them; `select` applies it to shots already in memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShotFilter {
+    /// Keep every `stride`-th shot of the range; 0 and 1 keep every shot.
+    pub stride: usize,
+
+    /// Stop after this many shots have been kept.
+    pub max_shots: Option<usize>,
+
+    /// Keep only shots with at least this many fired detectors.
+    pub min_defects: usize,
+
+    /// Indices of the shots to consider, or None for every shot. An end of
+    /// `usize::MAX` stands for the end of the file.
+    pub shot_range: Option<Range<usize>>,
+}
+
+impl ShotFilter {
+    /// Returns true if the filter does more than pick a contiguous range,
+    /// so that which shots it keeps depends on reading them in order.
+    pub fn is_sampling(&self) -> bool {
+        self.stride > 1 || self.max_shots.is_some() || self.min_defects > 0
+    }
+
+    /// Returns true if the shot at `index` passes the range and stride
+    /// checks.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the shot in the file
+    pub fn keeps_index(&self, index: usize) -> bool {
+        let range = self.range();
+        range.contains(&index) && (index - range.start).is_multiple_of(self.stride.max(1))
+    }
+
+    /// Returns true if the shot at `index`, with `defects` fired detectors,
+    /// passes the range, stride and defect checks.
+    ///
+    /// `max_shots` is not checked here, as it depends on the shots kept
+    /// before this one.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the shot in the file
+    /// * `defects` - Number of fired detectors in the shot
+    pub fn keeps(&self, index: usize, defects: usize) -> bool {
+        defects >= self.min_defects && self.keeps_index(index)
+    }
+
+    /// Applies the filter to shots already in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `shots` - Every shot of the file, in order
+    /// * `defects` - Counts the fired detectors of a shot
+    ///
+    /// # Returns
+    ///
+    /// The kept shots, in order.
+    pub fn select<T>(
+        &self,
+        shots: impl IntoIterator<Item = T>,
+        defects: impl Fn(&T) -> usize,
+    ) -> impl Iterator<Item = T> {
+        let filter = self.clone();
+        shots
+            .into_iter()
+            .enumerate()
+            .filter(move |(index, shot)| filter.keeps(*index, defects(shot)))
+            .map(|(_, shot)| shot)
+            .take(self.max_shots.unwrap_or(usize::MAX))
+    }
+
+    /// Checks the filter's shot range against the number of shots
+    /// available.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - Number of shots in the file
+    ///
+    /// # Returns
+    ///
+    /// The range to consider, with an open end resolved to `total`, or an
+    /// error if it reaches past the last shot.
+    pub fn resolve_range(&self, total: usize) -> Result<Range<usize>> {
+        let Some(range) = self.shot_range.clone() else {
+            return Ok(0..total);
+        };
+        let end = if range.end == usize::MAX {
+            total
+        } else {
+            range.end
+        };
+        ensure!(
+            range.start <= end && end <= total,
+            "shot range {}..{} reaches past the {} shots in the file",
+            range.start,
+            end,
+            total
+        );
+        Ok(range.start..end)
+    }
+
+    /// Returns the range of indices considered, open-ended if unset.
+    fn range(&self) -> Range<usize> {
+        self.shot_range.clone().unwrap_or(0..usize::MAX)
+    }
+
+    /// Returns the first index at or after `index` that passes the range
+    /// and stride checks, or None if there is none.
+    fn next_candidate(&self, index: usize) -> Option<usize> {
+        let range = self.range();
+        let stride = self.stride.max(1);
+        let candidate = if index <= range.start {
+            range.start
+        } else {
+            range.start + (index - range.start).div_ceil(stride) * stride
+        };
+        (candidate < range.end).then_some(candidate)
+    }
+}
+
+/// Streaming reader over the shots of a Stim .b8 file.
+///
+/// Reads the file through a fixed-size buffer and unpacks one shot at a
+/// time into packed words, using the same layout as `slice_shots_packed`:
+/// detector `i` is bit `i % 64` of word `i / 64`, and the padding bits of
+/// the byte-aligned stride are cleared. Memory use is independent of the
+/// file size. A partial shot at the end of the file is ignored, as
+/// `slice_shots` does.
+///
+/// `read_shot` lends the shot from an internal buffer; the `Iterator`
+/// implementation copies each shot into its own vector for callers that
+/// need to own it, such as parallel iterators. With a `ShotFilter`
+/// attached, only the shots it keeps are returned.
+pub struct ShotReader {
+    /// Buffered file handle.
+    reader: BufReader<File>,
+
+    /// Raw bytes of the current shot.
+    record: Vec<u8>,
+
+    /// Current shot as packed words.
+    words: Vec<u64>,
+
+    /// Number of detector bits per shot.
+    bits_per_shot: usize,
+
+    /// Shots to return.
+    filter: ShotFilter,
+
+    /// Index of the shot the file is positioned at.
+    next: usize,
+
+    /// Number of shots returned since the last rewind.
+    kept: usize,
+
+    /// Index of the shot most recently returned.
+    last: Option<usize>,
+}
+
+impl ShotReader {
+    /// Opens a .b8 file for streaming.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the .b8 file
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    ///
+    /// # Returns
+    ///
+    /// The reader, positioned at the first shot, or an error if the file
+    /// cannot be opened or `bits_per_shot` is zero.
+    pub fn open<P: AsRef<Path>>(path: P, bits_per_shot: usize) -> Result<Self> {
+        ensure!(bits_per_shot > 0, "bits per shot must be non-zero");
+        let file = File::open(path).context("Failed to open .b8 file")?;
+        Ok(Self {
+            reader: BufReader::with_capacity(CHUNK_BYTES, file),
+            record: vec![0; bits_per_shot.div_ceil(8)],
+            words: vec![0; bits_per_shot.div_ceil(64)],
+            bits_per_shot,
+            filter: ShotFilter::default(),
+            next: 0,
+            kept: 0,
+            last: None,
+        })
+    }
+
+    /// Restricts the reader to the shots kept by `filter`.
+    ///
+    /// Takes effect from the current position; call on a fresh or rewound
+    /// reader to filter the whole file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Shots to return
+    ///
+    /// # Returns
+    ///
+    /// The reader, now filtered.
+    pub fn with_filter(mut self, filter: ShotFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Reads the next shot kept by the filter.
+    ///
+    /// Shots skipped by range or stride are seeked over without being read;
+    /// shots with too few defects are read, counted and dropped.
+    ///
+    /// # Returns
+    ///
+    /// The shot as packed words, valid until the next call, or None once
+    /// no complete shot remains or the filter has kept `max_shots` shots.
+    pub fn read_shot(&mut self) -> io::Result<Option<&[u64]>> {
+        loop {
+            if self.filter.max_shots.is_some_and(|max| self.kept >= max) {
+                return Ok(None);
+            }
+            let Some(index) = self.filter.next_candidate(self.next) else {
+                return Ok(None);
+            };
+            if index > self.next {
+                let skip = (index - self.next) * self.record.len();
+                self.reader.seek_relative(skip as i64)?;
+                self.next = index;
+            }
+            if !self.read_record()? {
+                return Ok(None);
+            }
+            self.next += 1;
+
+            pack_shot(&self.record, self.bits_per_shot, &mut self.words);
+            if BitPack::count_ones(&self.words) >= self.filter.min_defects {
+                self.kept += 1;
+                self.last = Some(index);
+                return Ok(Some(&self.words));
+            }
+        }
+    }
+
+    /// Reads the raw bytes of the shot at the current position.
+    ///
+    /// # Returns
+    ///
+    /// True if a complete shot was read, false at the end of the file.
+    fn read_record(&mut self) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < self.record.len() {
+            match self.reader.read(&mut self.record[filled..]) {
+                Ok(0) => return Ok(false),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Moves back to the first shot, so the file can be replayed.
+    ///
+    /// The filter starts over too, so `max_shots` counts from here.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.next = 0;
+        self.kept = 0;
+        self.last = None;
+        self.reader.rewind()
+    }
+
+    /// Returns the file index of the shot most recently returned by
+    /// `read_shot`, or None if none has been since the last rewind.
+    pub fn last_index(&self) -> Option<usize> {
+        self.last
+    }
+
+    /// Returns the number of detector bits per shot.
+    pub fn bits_per_shot(&self) -> usize {
+        self.bits_per_shot
+    }
+}
+
+impl Iterator for ShotReader {
+    /// An owned copy of one packed shot, or the read error that ended the
+    /// stream.
+    type Item = io::Result<Vec<u64>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_shot()
+            .map(|shot| shot.map(<[u64]>::to_vec))
+            .transpose()
+    }
+}
+
+/// Writer for Stim .b8 files.
+///
+/// Packs each shot into `bits_per_shot.div_ceil(8)` bytes, detector `i`
+/// being bit `i % 8` of byte `i / 8` with the padding bits of the last byte
+/// cleared, and buffers the output so shots can be written one at a time.
+/// Files written here read back unchanged through `load_b8_file` and
+/// `slice_shots`. Call `finish` to flush and see write errors; dropping
+/// the writer flushes too, but discards any error.
+pub struct B8Writer {
+    /// Buffered output file.
+    writer: BufWriter<File>,
+
+    /// Bytes of the shot being written.
+    record: Vec<u8>,
+
+    /// Number of detector bits per shot.
+    bits_per_shot: usize,
+
+    /// Number of shots written so far.
+    shots: usize,
+}
+
+impl B8Writer {
+    /// Creates or truncates a .b8 file for writing.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the .b8 file
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    ///
+    /// # Returns
+    ///
+    /// The writer, or an error if the file cannot be created or
+    /// `bits_per_shot` is zero.
+    pub fn create<P: AsRef<Path>>(path: P, bits_per_shot: usize) -> Result<Self> {
+        ensure!(bits_per_shot > 0, "bits per shot must be non-zero");
+        let file = File::create(path).context("Failed to create .b8 file")?;
+        Ok(Self {
+            writer: BufWriter::with_capacity(CHUNK_BYTES, file),
+            record: vec![0; bits_per_shot.div_ceil(8)],
+            bits_per_shot,
+            shots: 0,
+        })
+    }
+
+    /// Appends one shot given as one boolean per detector.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - Detector outcomes; must hold exactly `bits_per_shot`
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or an error on a length mismatch or write failure.
+    pub fn write_shot(&mut self, bits: &[bool]) -> Result<()> {
+        ensure!(
+            bits.len() == self.bits_per_shot,
+            "shot has {} bits, expected {}",
+            bits.len(),
+            self.bits_per_shot
+        );
+        self.record.fill(0);
+        for (i, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+            self.record[i / 8] |= 1 << (i % 8);
+        }
+        self.write_record()
+    }
+
+    /// Appends one shot given as packed words, in the layout of
+    /// `slice_shots_packed`.
+    ///
+    /// Bits past `bits_per_shot` in the last word are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - Packed shot; must be `bits_per_shot.div_ceil(64)` words
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or an error on a length mismatch or write failure.
+    pub fn write_shot_packed(&mut self, words: &[u64]) -> Result<()> {
+        ensure!(
+            words.len() == self.bits_per_shot.div_ceil(64),
+            "shot has {} words, expected {}",
+            words.len(),
+            self.bits_per_shot.div_ceil(64)
+        );
+        for (i, byte) in self.record.iter_mut().enumerate() {
+            *byte = words[i / 8].to_le_bytes()[i % 8];
+        }
+        let tail = self.bits_per_shot % 8;
+        if tail != 0 {
+            // The record is non-empty because bits_per_shot is.
+            let last = self.record.len() - 1;
+            self.record[last] &= (1 << tail) - 1;
+        }
+        self.write_record()
+    }
+
+    /// Returns the number of shots written so far.
+    pub fn shots(&self) -> usize {
+        self.shots
+    }
+
+    /// Flushes the buffered shots to the file.
+    ///
+    /// # Returns
+    ///
+    /// The number of shots written, or the error from the final flush.
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.flush().context("Failed to write .b8 file")?;
+        Ok(self.shots)
+    }
+
+    /// Appends the packed record of the current shot.
+    fn write_record(&mut self) -> Result<()> {
+        self.writer
+            .write_all(&self.record)
+            .context("Failed to write .b8 file")?;
+        self.shots += 1;
+        Ok(())
+    }
+}
+
+/// Size summary of a .b8 file that holds a whole number of shots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct B8Info {
+    /// Number of shots in the file.
+    pub shots: usize,
+
+    /// Bytes per shot, `bits_per_shot.div_ceil(8)`.
+    pub bytes_per_shot: usize,
+
+    /// File size in bytes.
+    pub file_len: u64,
+}
+
+/// Error produced by `validate_b8`.
+#[derive(Debug)]
+pub enum B8Error {
+    /// The file metadata could not be read.
+    Io(io::Error),
+
+    /// The shot width was zero.
+    ZeroWidth,
+
+    /// The file size is not a whole number of shots, so the detector count
+    /// does not match the file.
+    PartialShot {
+        /// File size in bytes.
+        file_len: u64,
+
+        /// Detector count the file was checked against.
+        bits_per_shot: usize,
+
+        /// Number of whole shots that fit.
+        shots: u64,
+
+        /// Bytes left over after the whole shots.
+        trailing_bytes: u64,
+
+        /// Nearby detector counts for which the file size divides evenly,
+        /// closest first. Each range shares one byte width, so any count
+        /// in it fits equally well.
+        suggestions: Vec<RangeInclusive<usize>>,
+    },
+}
+
+impl fmt::Display for B8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            B8Error::Io(e) => write!(f, "cannot read .b8 file metadata: {}", e),
+            B8Error::ZeroWidth => write!(f, "bits per shot must be non-zero"),
+            B8Error::PartialShot {
+                file_len,
+                bits_per_shot,
+                shots,
+                trailing_bytes,
+                suggestions,
+            } => {
+                write!(
+                    f,
+                    "{}-byte .b8 file is not a whole number of {}-byte shots for {} detectors \
+                     ({} shots and {} stray bytes)",
+                    file_len,
+                    bits_per_shot.div_ceil(8),
+                    bits_per_shot,
+                    shots,
+                    trailing_bytes
+                )?;
+                if suggestions.is_empty() {
+                    return write!(f, "; no nearby detector count fits the file size");
+                }
+                write!(f, "; detector counts that fit:")?;
+                for (i, range) in suggestions.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}-{}", sep, range.start(), range.end())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for B8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            B8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Number of byte widths either side of the expected one that
+/// `validate_b8` tries when suggesting detector counts.
+const SUGGESTION_SPAN: usize = 8;
+
+/// Maximum number of detector-count ranges `validate_b8` suggests.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Checks that a .b8 file holds a whole number of shots of the given width.
+///
+/// A wrong detector count usually leaves a partial shot at the end of the
+/// file; `slice_shots` would drop it silently and mis-slice every shot
+/// before it. A count with the right byte width but the wrong bit count
+/// cannot be told apart from the size alone.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .b8 file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// The shot count and sizes, or `B8Error::PartialShot` listing detector
+/// counts near `bits_per_shot` that would fit the file.
+pub fn validate_b8<P: AsRef<Path>>(path: P, bits_per_shot: usize) -> Result<B8Info, B8Error> {
+    if bits_per_shot == 0 {
+        return Err(B8Error::ZeroWidth);
+    }
+    let file_len = std::fs::metadata(path).map_err(B8Error::Io)?.len();
+    let bytes_per_shot = bits_per_shot.div_ceil(8);
+    let trailing_bytes = file_len % bytes_per_shot as u64;
+    if trailing_bytes == 0 {
+        return Ok(B8Info {
+            shots: (file_len / bytes_per_shot as u64) as usize,
+            bytes_per_shot,
+            file_len,
+        });
+    }
+
+    let low = bytes_per_shot.saturating_sub(SUGGESTION_SPAN).max(1);
+    let mut widths: Vec<usize> = (low..=bytes_per_shot + SUGGESTION_SPAN)
+        .filter(|&width| file_len % width as u64 == 0)
+        .collect();
+    widths.sort_by_key(|&width| width.abs_diff(bytes_per_shot));
+    let suggestions = widths
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|width| (width - 1) * 8 + 1..=width * 8)
+        .collect();
+
+    Err(B8Error::PartialShot {
+        file_len,
+        bits_per_shot,
+        shots: file_len / bytes_per_shot as u64,
+        trailing_bytes,
+        suggestions,
+    })
+}
+
+/// Counts the complete shots in a .b8 file from its size, without reading
+/// it.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .b8 file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// The number of shots `ShotReader` would yield, or an error if the file
+/// metadata cannot be read or `bits_per_shot` is zero.
+pub fn count_shots<P: AsRef<Path>>(path: P, bits_per_shot: usize) -> Result<usize> {
+    ensure!(bits_per_shot > 0, "bits per shot must be non-zero");
+    let len = std::fs::metadata(path)
+        .context("Failed to read .b8 file metadata")?
+        .len();
+    Ok((len / bits_per_shot.div_ceil(8) as u64) as usize)
+}
+
+/// Read-only memory map of a Stim .b8 file.
+///
+/// Gives zero-copy access to the raw bytes of any shot by index, so large
+/// files can be processed, or individual shots replayed, without reading
+/// them into memory first. The operating system pages the file in as shots
+/// are touched.
+///
+/// # Platform caveats
+///
+/// The mapping assumes the file is not changed while it is mapped. If
+/// another process truncates it, touching the lost pages raises SIGBUS on
+/// Unix; if it rewrites it, the shots seen here change underneath the
+/// caller. On Windows the file cannot be deleted or truncated while
+/// mapped. On 32-bit targets the file must fit in the address space.
+#[cfg(feature = "mmap")]
+pub struct B8Mmap {
+    /// The mapping, covering the whole file.
+    map: memmap2::Mmap,
+}
+
+/// Maps a Stim .b8 file into memory.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .b8 file
+///
+/// # Returns
+///
+/// The mapping, or an error if the file cannot be opened or mapped.
+#[cfg(feature = "mmap")]
+pub fn load_b8_mmap<P: AsRef<Path>>(path: P) -> Result<B8Mmap> {
+    let file = File::open(path).context("Failed to open .b8 file")?;
+    // Mapping is unsafe only because the file could change while mapped;
+    // see the caveats on B8Mmap.
+    let map = unsafe { memmap2::Mmap::map(&file) }.context("Failed to map .b8 file")?;
+    Ok(B8Mmap { map })
+}
+
+#[cfg(feature = "mmap")]
+impl B8Mmap {
+    /// Returns the whole file as bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Returns the number of complete shots in the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    pub fn num_shots(&self, bits_per_shot: usize) -> usize {
+        self.map.len() / bits_per_shot.div_ceil(8)
+    }
+
+    /// Returns the raw bytes of one shot.
+    ///
+    /// Feed the result to `pack_shot` for the packed form, or follow that
+    /// with `BitPack::iter_ones` for fired-detector indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `idx` - Shot index
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    ///
+    /// # Returns
+    ///
+    /// The shot's `bits_per_shot.div_ceil(8)` bytes, borrowed from the
+    /// mapping.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than `num_shots(bits_per_shot)`.
+    pub fn shot(&self, idx: usize, bits_per_shot: usize) -> &[u8] {
+        let bytes_per_shot = bits_per_shot.div_ceil(8);
+        let start = idx * bytes_per_shot;
+        &self.map[start..start + bytes_per_shot]
+    }
+
+    /// Iterates over the raw bytes of every complete shot, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    pub fn shots(&self, bits_per_shot: usize) -> std::slice::ChunksExact<'_, u8> {
+        self.map.chunks_exact(bits_per_shot.div_ceil(8))
+    }
+
+    /// Iterates over every complete shot in packed form, as
+    /// `slice_shots_packed` would return them but one at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    pub fn packed_shots(&self, bits_per_shot: usize) -> impl Iterator<Item = Vec<u64>> + '_ {
+        self.shots(bits_per_shot).map(move |record| {
+            let mut words = vec![0; bits_per_shot.div_ceil(64)];
+            pack_shot(record, bits_per_shot, &mut words);
+            words
+        })
+    }
+
+    /// Iterates over every complete shot as fired-detector indices, as
+    /// `slice_shots_sparse` would return them but one at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_shot` - Number of detector bits per measurement shot
+    pub fn sparse_shots(&self, bits_per_shot: usize) -> impl Iterator<Item = Vec<u32>> + '_ {
+        self.packed_shots(bits_per_shot)
+            .map(|words| BitPack::iter_ones(&words).map(|i| i as u32).collect())
+    }
+}
+
+/// Loads a Stim .01 file, one shot per line.
+///
+/// See `Shot01Reader` for the accepted syntax.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .01 file
+///
+/// # Returns
+///
+/// One bit vector per shot, or an error naming the offending line if the
+/// file cannot be read, holds a character other than '0' or '1', or has
+/// lines of different lengths.
+pub fn load_01_file<P: AsRef<Path>>(path: P) -> Result<Vec<BitVec<u8, Lsb0>>> {
+    Shot01Reader::open(path)?.collect()
+}
+
+/// Streaming reader over the shots of a Stim .01 file.
+///
+/// Each line is one shot, detector `i` being character `i`. Trailing
+/// whitespace, including a Windows line ending, is ignored; every line must
+/// then hold the same number of '0' and '1' characters and nothing else.
+/// Lines are read one at a time, so memory use does not grow with the
+/// file.
+pub struct Shot01Reader {
+    /// Lines of the file.
+    lines: io::Lines<BufReader<File>>,
+
+    /// Number of the last line read, counting from 1.
+    line_no: usize,
+
+    /// Bits per shot, fixed by the first line.
+    width: Option<usize>,
+}
+
+impl Shot01Reader {
+    /// Opens a .01 file for streaming.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the .01 file
+    ///
+    /// # Returns
+    ///
+    /// The reader, positioned at the first shot, or an error if the file
+    /// cannot be opened.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path).context("Failed to open .01 file")?;
+        Ok(Self {
+            lines: BufReader::with_capacity(CHUNK_BYTES, file).lines(),
+            line_no: 0,
+            width: None,
+        })
+    }
+
+    /// Returns the number of bits per shot, once the first shot is read.
+    pub fn bits_per_shot(&self) -> Option<usize> {
+        self.width
+    }
+
+    /// Parses one line into a shot, checking it against the first line.
+    fn parse_line(&mut self, line: &str) -> Result<BitVec<u8, Lsb0>> {
+        let line = line.trim_end();
+        let mut bits = BitVec::with_capacity(line.len());
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                '0' => bits.push(false),
+                '1' => bits.push(true),
+                _ => bail!(
+                    "line {}, column {}: expected '0' or '1', found {:?}",
+                    self.line_no,
+                    col + 1,
+                    c
+                ),
+            }
+        }
+        match self.width {
+            Some(width) if width != bits.len() => bail!(
+                "line {}: shot has {} bits, but line 1 has {}",
+                self.line_no,
+                bits.len(),
+                width
+            ),
+            Some(_) => {}
+            None => self.width = Some(bits.len()),
+        }
+        Ok(bits)
+    }
+}
+
+impl Iterator for Shot01Reader {
+    /// One shot, or the read or syntax error at the current line.
+    type Item = Result<BitVec<u8, Lsb0>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        self.line_no += 1;
+        Some(match line {
+            Ok(line) => self.parse_line(&line),
+            Err(e) => Err(e).with_context(|| format!("line {}: read failed", self.line_no)),
+        })
+    }
+}
+
+/// Loads a shot file in either format as packed words, in the layout of
+/// `slice_shots_packed`.
+///
+/// # Arguments
+///
+/// * `path` - Path to the shot file
+/// * `format` - Layout of the file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// The packed shots, or an error if the file cannot be loaded or, for a
+/// .01 file, its lines do not hold `bits_per_shot` bits, or for a .dets
+/// file, a detector index is not below `bits_per_shot`.
+pub fn load_shots_packed<P: AsRef<Path>>(
+    path: P,
+    format: ShotFormat,
+    bits_per_shot: usize,
+) -> Result<Vec<Vec<u64>>> {
+    match format {
+        ShotFormat::B8 => Ok(slice_shots_packed(&load_b8_file(path)?, bits_per_shot)),
+        ShotFormat::Ascii01 => {
+            let shots = load_01_file(path)?;
+            check_01_width(&shots, bits_per_shot)?;
+            Ok(shots
+                .iter()
+                .map(|bits| {
+                    let mut words = vec![0; bits_per_shot.div_ceil(64)];
+                    pack_shot(bits.as_raw_slice(), bits_per_shot, &mut words);
+                    words
+                })
+                .collect())
+        }
+        ShotFormat::Dets => Ok(load_dets_file(path, bits_per_shot)?
+            .iter()
+            .map(|detectors| {
+                let mut words = vec![0; bits_per_shot.div_ceil(64)];
+                for &d in detectors {
+                    BitPack::set(&mut words, d as usize, true);
+                }
+                words
+            })
+            .collect()),
+    }
+}
+
+/// Loads a shot file in either format as fired-detector indices, in the
+/// layout of `slice_shots_sparse`.
+///
+/// # Arguments
+///
+/// * `path` - Path to the shot file
+/// * `format` - Layout of the file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// The sparse shots, or an error if the file cannot be loaded or, for a
+/// .01 file, its lines do not hold `bits_per_shot` bits, or for a .dets
+/// file, a detector index is not below `bits_per_shot`.
+pub fn load_shots_sparse<P: AsRef<Path>>(
+    path: P,
+    format: ShotFormat,
+    bits_per_shot: usize,
+) -> Result<Vec<Vec<u32>>> {
+    match format {
+        ShotFormat::B8 => Ok(slice_shots_sparse(&load_b8_file(path)?, bits_per_shot)),
+        ShotFormat::Ascii01 => {
+            let shots = load_01_file(path)?;
+            check_01_width(&shots, bits_per_shot)?;
+            Ok(shots
+                .iter()
+                .map(|bits| bits.iter_ones().map(|i| i as u32).collect())
+                .collect())
+        }
+        ShotFormat::Dets => load_dets_file(path, bits_per_shot),
+    }
+}
+
+/// Checks that the shots of a .01 file have the expected width.
+///
+/// Unlike .b8, the width is recorded in the file, so a wrong detector
+/// count can be caught instead of mis-slicing every shot.
+fn check_01_width(shots: &[BitVec<u8, Lsb0>], bits_per_shot: usize) -> Result<()> {
+    if let Some(first) = shots.first() {
+        ensure!(
+            first.len() == bits_per_shot,
+            ".01 file has {} bits per shot, expected {}",
+            first.len(),
+            bits_per_shot
+        );
+    }
+    Ok(())
+}
+
+/// One shot of a Stim .dets file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DetsShot {
+    /// Indices of the detectors that fired (`D` targets), in increasing
+    /// order.
+    pub detectors: Vec<u32>,
+
+    /// Indices of the observables that flipped (`L` targets), in increasing
+    /// order.
+    pub observables: Vec<u32>,
+}
+
+/// Loads the detection events of a Stim .dets file.
+///
+/// Observable flips are checked but dropped; use
+/// `load_dets_with_observables` to keep them.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .dets file
+/// * `num_detectors` - Number of detectors; every `D` index must be below it
+///
+/// # Returns
+///
+/// The fired detectors of each shot, or an error naming the offending line.
+pub fn load_dets_file<P: AsRef<Path>>(path: P, num_detectors: usize) -> Result<Vec<Vec<u32>>> {
+    DetsReader::open(path, num_detectors)?
+        .map(|shot| shot.map(|shot| shot.detectors))
+        .collect()
+}
+
+/// Contents of a Stim .dets file, split into parallel per-shot lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DetsShots {
+    /// Fired detectors of each shot, in the form `slice_shots_sparse`
+    /// returns.
+    pub detectors: Vec<Vec<u32>>,
+
+    /// Flipped observables of each shot; entry `i` belongs to shot `i`.
+    pub observables: Vec<Vec<u32>>,
+}
+
+/// Loads a Stim .dets file, keeping the observable flips.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .dets file
+/// * `num_detectors` - Number of detectors; every `D` index must be below it
+///
+/// # Returns
+///
+/// The detectors and observables of every shot, or an error naming the
+/// offending line.
+pub fn load_dets_with_observables<P: AsRef<Path>>(
+    path: P,
+    num_detectors: usize,
+) -> Result<DetsShots> {
+    let mut shots = DetsShots::default();
+    for shot in DetsReader::open(path, num_detectors)? {
+        let shot = shot?;
+        shots.detectors.push(shot.detectors);
+        shots.observables.push(shot.observables);
+    }
+    Ok(shots)
+}
+
+/// Streaming reader over the shots of a Stim .dets file.
+///
+/// Each non-blank line is one shot: the word `shot` followed by
+/// whitespace-separated targets, `D<n>` for a detector that fired and
+/// `L<n>` for an observable that flipped, in any order. A shot with no
+/// events is a bare `shot`. Lines are read one at a time, so memory use
+/// does not grow with the file.
+pub struct DetsReader {
+    /// Lines of the file.
+    lines: io::Lines<BufReader<File>>,
+
+    /// Number of the last line read, counting from 1.
+    line_no: usize,
+
+    /// Exclusive upper bound on detector indices.
+    num_detectors: usize,
+}
+
+impl DetsReader {
+    /// Opens a .dets file for streaming.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the .dets file
+    /// * `num_detectors` - Number of detectors; every `D` index must be
+    ///   below it
+    ///
+    /// # Returns
+    ///
+    /// The reader, positioned at the first shot, or an error if the file
+    /// cannot be opened.
+    pub fn open<P: AsRef<Path>>(path: P, num_detectors: usize) -> Result<Self> {
+        let file = File::open(path).context("Failed to open .dets file")?;
+        Ok(Self {
+            lines: BufReader::with_capacity(CHUNK_BYTES, file).lines(),
+            line_no: 0,
+            num_detectors,
+        })
+    }
+
+    /// Parses one non-blank line into a shot.
+    fn parse_line(&self, line: &str) -> Result<DetsShot> {
+        let mut tokens = line.split_whitespace();
+        let line_no = self.line_no;
+        match tokens.next() {
+            Some("shot") => {}
+            Some(other) => bail!("line {}: expected `shot`, found `{}`", line_no, other),
+            None => unreachable!("blank lines are skipped"),
+        }
+
+        let mut shot = DetsShot::default();
+        for token in tokens {
+            let (list, index) = match token.split_at_checked(1) {
+                Some(("D", index)) => (&mut shot.detectors, index),
+                Some(("L", index)) => (&mut shot.observables, index),
+                _ => bail!("line {}: unknown target `{}`", line_no, token),
+            };
+            let index: u32 = index
+                .parse()
+                .with_context(|| format!("line {}: bad index in `{}`", line_no, token))?;
+            list.push(index);
+        }
+
+        shot.detectors.sort_unstable();
+        shot.observables.sort_unstable();
+        if let Some(&last) = shot.detectors.last() {
+            ensure!(
+                (last as usize) < self.num_detectors,
+                "line {}: detector D{} out of range for {} detectors",
+                line_no,
+                last,
+                self.num_detectors
+            );
+        }
+        for (kind, list) in [("D", &shot.detectors), ("L", &shot.observables)] {
+            if let Some(pair) = list.windows(2).find(|pair| pair[0] == pair[1]) {
+                bail!("line {}: {}{} listed twice", line_no, kind, pair[0]);
+            }
+        }
+        Ok(shot)
+    }
+}
+
+impl Iterator for DetsReader {
+    /// One shot, or the read or syntax error at the current line.
+    type Item = Result<DetsShot>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = self.lines.next()?;
+            self.line_no += 1;
+            match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => return Some(self.parse_line(&line)),
+                Err(e) => {
+                    let line_no = self.line_no;
+                    return Some(Err(e).with_context(|| format!("line {}: read failed", line_no)));
+                }
+            }
+        }
+    }
+}
+
+/// Writer for Stim .dets files.
+///
+/// Writes one `shot` line per call, detectors first, in the form
+/// `DetsReader` reads back. Call `finish` to flush and see write errors;
+/// dropping the writer flushes too, but discards any error.
+pub struct DetsWriter {
+    /// Buffered output file.
+    writer: BufWriter<File>,
+
+    /// Number of shots written so far.
+    shots: usize,
+}
+
+impl DetsWriter {
+    /// Creates or truncates a .dets file for writing.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the .dets file
+    ///
+    /// # Returns
+    ///
+    /// The writer, or an error if the file cannot be created.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::create(path).context("Failed to create .dets file")?;
+        Ok(Self {
+            writer: BufWriter::with_capacity(CHUNK_BYTES, file),
+            shots: 0,
+        })
+    }
+
+    /// Appends one shot.
+    ///
+    /// # Arguments
+    ///
+    /// * `detectors` - Detectors that fired, written as `D` targets
+    /// * `observables` - Observables that flipped, written as `L` targets
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or the write error.
+    pub fn write_shot(&mut self, detectors: &[u32], observables: &[u32]) -> Result<()> {
+        let w = &mut self.writer;
+        write!(w, "shot")
+            .and_then(|()| {
+                for d in detectors {
+                    write!(w, " D{}", d)?;
+                }
+                for l in observables {
+                    write!(w, " L{}", l)?;
+                }
+                writeln!(w)
+            })
+            .context("Failed to write .dets file")?;
+        self.shots += 1;
+        Ok(())
+    }
+
+    /// Returns the number of shots written so far.
+    pub fn shots(&self) -> usize {
+        self.shots
+    }
+
+    /// Flushes the buffered shots to the file.
+    ///
+    /// # Returns
+    ///
+    /// The number of shots written, or the error from the final flush.
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.flush().context("Failed to write .dets file")?;
+        Ok(self.shots)
+    }
+}
+
+/// Format version written to and expected in `Manifest::version`.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Parameters and checksums of a generated dataset.
+///
+/// Written by the generator as a JSON sidecar at `manifest_path(b8)`, so
+/// results from different machines can be checked to come from the same
+/// data. The checksums cover the exact bytes of both files, so any
+/// regeneration with different parameters, or any corruption, shows up as
+/// a mismatch in `verify_manifest`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Manifest format version, `MANIFEST_VERSION` when written.
+    pub version: u32,
+
+    /// Version of the tool that generated the data.
+    pub generator_version: String,
+
+    /// Surface code size (size x size detectors).
+    pub size: usize,
+
+    /// Number of shots in the .b8 file.
+    pub shots: usize,
+
+    /// Physical error rate per edge.
+    pub p: f64,
+
+    /// Seed of the shot sampler.
+    pub seed: u64,
+
+    /// Whether uncorrectable monopole errors were injected.
+    pub inject_failures: bool,
+
+    /// Number of detectors per shot.
+    pub detectors: usize,
+
+    /// SHA-256 of the .dem file, as lowercase hex.
+    pub dem_sha256: String,
+
+    /// SHA-256 of the .b8 file, as lowercase hex.
+    pub b8_sha256: String,
+}
+
+impl Manifest {
+    /// Writes the manifest next to a .b8 file, at `manifest_path(b8_path)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `b8_path` - Path to the .b8 file the manifest describes
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or an error if the file cannot be written.
+    pub fn write<P: AsRef<Path>>(&self, b8_path: P) -> Result<()> {
+        let file = File::create(manifest_path(b8_path)).context("Failed to create manifest")?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self).context("Failed to write manifest")?;
+        writeln!(writer).context("Failed to write manifest")?;
+        writer.flush().context("Failed to write manifest")
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} surface code, {} detectors, {} shots, p={}, seed {}",
+            self.size, self.size, self.detectors, self.shots, self.p, self.seed
+        )?;
+        if self.inject_failures {
+            write!(f, ", injected failures")?;
+        }
+        write!(f, " (generator {})", self.generator_version)
+    }
+}
+
+/// Error produced by `verify_manifest`.
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The manifest or a data file could not be read.
+    Io(io::Error),
+
+    /// The manifest is not valid JSON or lacks a field.
+    Parse(serde_json::Error),
+
+    /// The manifest was written in a format this version cannot check.
+    UnsupportedVersion {
+        /// Version found in the manifest.
+        found: u32,
+    },
+
+    /// A data file does not have the checksum recorded in the manifest.
+    Mismatch {
+        /// Path of the file that differs.
+        path: PathBuf,
+
+        /// Checksum recorded in the manifest.
+        expected: String,
+
+        /// Checksum of the file on disk.
+        actual: String,
+    },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Io(e) => write!(f, "cannot read dataset for manifest check: {}", e),
+            ManifestError::Parse(e) => write!(f, "malformed dataset manifest: {}", e),
+            ManifestError::UnsupportedVersion { found } => write!(
+                f,
+                "dataset manifest version {} is not supported (expected {})",
+                found, MANIFEST_VERSION
+            ),
+            ManifestError::Mismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} does not match its manifest: SHA-256 is {}, manifest records {}",
+                path.display(),
+                actual,
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManifestError::Io(e) => Some(e),
+            ManifestError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the path of the manifest for a .b8 file: the same path with
+/// `.json` appended, as in `bench.b8.json`.
+///
+/// # Arguments
+///
+/// * `b8_path` - Path to the .b8 file
+pub fn manifest_path<P: AsRef<Path>>(b8_path: P) -> PathBuf {
+    let mut path = b8_path.as_ref().as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Computes the SHA-256 of a file, reading it a chunk at a time.
+///
+/// # Arguments
+///
+/// * `path` - Path to the file
+///
+/// # Returns
+///
+/// The digest as lowercase hex, or the error from reading the file.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_BYTES];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Checks a dataset against the manifest the generator wrote next to it.
+///
+/// Both files are hashed in full, which costs one read of each.
+///
+/// # Arguments
+///
+/// * `dem_path` - Path to the .dem file
+/// * `b8_path` - Path to the .b8 file; the manifest is looked for at
+///   `manifest_path(b8_path)`
+///
+/// # Returns
+///
+/// The manifest if one exists and both files match it, None if there is no
+/// manifest, or `ManifestError::Mismatch` naming the first file whose
+/// checksum differs.
+pub fn verify_manifest<P: AsRef<Path>, Q: AsRef<Path>>(
+    dem_path: P,
+    b8_path: Q,
+) -> Result<Option<Manifest>, ManifestError> {
+    let text = match std::fs::read_to_string(manifest_path(&b8_path)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ManifestError::Io(e)),
+    };
+    let manifest: Manifest = serde_json::from_str(&text).map_err(ManifestError::Parse)?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(ManifestError::UnsupportedVersion {
+            found: manifest.version,
+        });
+    }
+
+    let files = [
+        (dem_path.as_ref(), &manifest.dem_sha256),
+        (b8_path.as_ref(), &manifest.b8_sha256),
+    ];
+    for (path, expected) in files {
+        let actual = sha256_file(path).map_err(ManifestError::Io)?;
+        if actual != *expected {
+            return Err(ManifestError::Mismatch {
+                path: path.to_path_buf(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(Some(manifest))
+}
+
+/// Number of shots in each block handed out by `par_shots`.
+///
+/// Large enough that per-block costs, such as opening the file and setting
+/// up a decoder, are spread over many shots, and small enough that a block
+/// of wide shots stays a few megabytes.
+#[cfg(feature = "parallel")]
+pub const SHOTS_PER_BLOCK: usize = 1024;
+
+/// Contiguous run of packed shots read from a .b8 file.
+///
+/// Shots use the layout of `slice_shots_packed` and are stored back to
+/// back, `bits_per_shot.div_ceil(64)` words apart.
+#[cfg(feature = "parallel")]
+pub struct ShotBlock {
+    /// Index of the first shot in the file.
+    first: usize,
+
+    /// Words per shot.
+    words_per_shot: usize,
+
+    /// Packed shots, back to back.
+    words: Vec<u64>,
+}
+
+#[cfg(feature = "parallel")]
+impl ShotBlock {
+    /// Returns the file index of the first shot in the block.
+    pub fn first(&self) -> usize {
+        self.first
+    }
+
+    /// Returns the number of shots in the block.
+    pub fn len(&self) -> usize {
+        self.words.len() / self.words_per_shot
+    }
+
+    /// Returns true if the block holds no shots.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Returns shot `i` of the block, counting from `first()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is not less than `len()`.
+    pub fn shot(&self, i: usize) -> &[u64] {
+        &self.words[i * self.words_per_shot..(i + 1) * self.words_per_shot]
+    }
+
+    /// Iterates over the shots of the block, in file order.
+    pub fn iter(&self) -> std::slice::ChunksExact<'_, u64> {
+        self.words.chunks_exact(self.words_per_shot)
+    }
+}
+
+/// Returns a Rayon parallel iterator over every shot of a .b8 file, in
+/// blocks.
+///
+/// See `par_shot_range`.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .b8 file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+///
+/// # Returns
+///
+/// The iterator, or an error if the file size cannot be read or
+/// `bits_per_shot` is zero.
+#[cfg(feature = "parallel")]
+pub fn par_shots<P: AsRef<Path>>(
+    path: P,
+    bits_per_shot: usize,
+) -> Result<impl rayon::iter::IndexedParallelIterator<Item = io::Result<ShotBlock>>> {
+    let total = count_shots(&path, bits_per_shot)?;
+    par_shot_range(path, bits_per_shot, 0..total)
+}
+
+/// Returns a Rayon parallel iterator over a range of shots of a .b8 file,
+/// in blocks.
+///
+/// Each item is a block of up to `SHOTS_PER_BLOCK` consecutive shots that
+/// the worker reads from the file itself when it picks the block up, so
+/// only the blocks being decoded are ever in memory and a worker can reuse
+/// one decoder across a whole block. The iterator is indexed, so Rayon
+/// splits it evenly without a shared queue.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .b8 file
+/// * `bits_per_shot` - Number of detector bits per measurement shot
+/// * `shots` - Indices of the shots to visit
+///
+/// # Returns
+///
+/// The iterator, or an error if the file size cannot be read,
+/// `bits_per_shot` is zero, or the range reaches past the last shot.
+#[cfg(feature = "parallel")]
+pub fn par_shot_range<P: AsRef<Path>>(
+    path: P,
+    bits_per_shot: usize,
+    shots: Range<usize>,
+) -> Result<impl rayon::iter::IndexedParallelIterator<Item = io::Result<ShotBlock>>> {
+    use rayon::prelude::*;
+
+    let total = count_shots(&path, bits_per_shot)?;
+    ensure!(
+        shots.start <= shots.end && shots.end <= total,
+        "shot range {}..{} reaches past the {} shots in the file",
+        shots.start,
+        shots.end,
+        total
+    );
+    let path = path.as_ref().to_path_buf();
+    let num_blocks = shots.len().div_ceil(SHOTS_PER_BLOCK);
+    Ok((0..num_blocks).into_par_iter().map(move |block| {
+        let start = shots.start + block * SHOTS_PER_BLOCK;
+        let end = (start + SHOTS_PER_BLOCK).min(shots.end);
+        read_block(&path, bits_per_shot, start..end)
+    }))
+}
+
+/// Reads and packs a run of shots from a .b8 file.
+///
+/// Opens its own handle, so blocks can be read from several threads at
+/// once.
+#[cfg(feature = "parallel")]
+fn read_block(path: &Path, bits_per_shot: usize, shots: Range<usize>) -> io::Result<ShotBlock> {
+    let bytes_per_shot = bits_per_shot.div_ceil(8);
+    let words_per_shot = bits_per_shot.div_ceil(64);
+
+    let mut file = File::open(path)?;
+    file.seek(io::SeekFrom::Start((shots.start * bytes_per_shot) as u64))?;
+    let mut bytes = vec![0; shots.len() * bytes_per_shot];
+    file.read_exact(&mut bytes)?;
+
+    let mut words = vec![0; shots.len() * words_per_shot];
+    for (record, shot) in bytes
+        .chunks_exact(bytes_per_shot)
+        .zip(words.chunks_exact_mut(words_per_shot))
+    {
+        pack_shot(record, bits_per_shot, shot);
+    }
+    Ok(ShotBlock {
+        first: shots.start,
+        words_per_shot,
+        words,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Scratch, random_bytes};
+
+    /// Widths around byte and word boundaries, plus a d=21-sized shot.
+    const WIDTHS: [usize; 8] = [1, 7, 8, 9, 63, 64, 65, 441];
+
+    #[test]
+    fn shot_reader_matches_slice_shots_packed_and_skips_a_partial_tail() {
+        for (seed, &bits) in WIDTHS.iter().enumerate() {
+            let bytes_per_shot = bits.div_ceil(8);
+            // 50 whole shots plus all but one byte of another, if there is
+            // room for a partial shot at all.
+            let len = 50 * bytes_per_shot + bytes_per_shot - 1;
+            let file = Scratch::with_bytes("stream.b8", &random_bytes(seed as u64 + 1, len));
+
+            let expected = slice_shots_packed(&load_b8_file(file.path()).unwrap(), bits);
+            assert_eq!(expected.len(), 50);
+
+            let streamed: Vec<Vec<u64>> = ShotReader::open(file.path(), bits)
+                .unwrap()
+                .collect::<io::Result<_>>()
+                .unwrap();
+            assert_eq!(streamed, expected, "bits_per_shot = {bits}");
+            assert_eq!(count_shots(file.path(), bits).unwrap(), 50);
+        }
+    }
+
+    #[test]
+    fn shot_reader_rewinds_and_reports_the_last_index() {
+        let bits = 65;
+        let file = Scratch::with_bytes("rewind.b8", &random_bytes(7, 9 * 20));
+        let mut reader = ShotReader::open(file.path(), bits).unwrap();
+        assert_eq!(reader.bits_per_shot(), bits);
+        assert_eq!(reader.last_index(), None);
+
+        let first = reader.read_shot().unwrap().unwrap().to_vec();
+        assert_eq!(reader.last_index(), Some(0));
+        let rest = reader.by_ref().count();
+        assert_eq!(rest, 19);
+        assert_eq!(reader.last_index(), Some(19));
+        assert!(reader.read_shot().unwrap().is_none());
+
+        reader.rewind().unwrap();
+        assert_eq!(reader.last_index(), None);
+        assert_eq!(reader.read_shot().unwrap().unwrap(), first.as_slice());
+    }
+
+    #[test]
+    fn packed_and_sparse_slicing_match_the_bool_slices() {
+        for (seed, &bits) in WIDTHS.iter().enumerate() {
+            let len = 37 * bits.div_ceil(8) + 3;
+            let raw = BitVec::<u8, Lsb0>::from_vec(random_bytes(seed as u64 + 100, len));
+            let bools = slice_shots(&raw, bits);
+            let packed = slice_shots_packed(&raw, bits);
+            let sparse = slice_shots_sparse(&raw, bits);
+            assert_eq!(packed.len(), bools.len());
+            assert_eq!(sparse.len(), bools.len());
+
+            for ((shot, words), fired) in bools.iter().zip(&packed).zip(&sparse) {
+                let mut expected = vec![0u64; bits.div_ceil(64)];
+                for (i, _) in shot.iter().enumerate().filter(|(_, bit)| **bit) {
+                    expected[i / 64] |= 1 << (i % 64);
+                }
+                assert_eq!(*words, expected, "bits_per_shot = {bits}");
+
+                let ones: Vec<u32> = (0..bits as u32).filter(|&i| shot[i as usize]).collect();
+                assert_eq!(*fired, ones, "bits_per_shot = {bits}");
+            }
+        }
+    }
+
+    #[test]
+    fn pack_shot_clears_the_padding_bits() {
+        let mut words = [u64::MAX; 2];
+        pack_shot(&[0xff; 9], 67, &mut words);
+        assert_eq!(words, [u64::MAX, 0b111]);
+
+        let mut words = [u64::MAX];
+        pack_shot(&[0b1010_1010], 5, &mut words);
+        assert_eq!(words, [0b0_1010]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_shots_match_the_loaded_slices() {
+        for (seed, &bits) in WIDTHS.iter().enumerate() {
+            let len = 23 * bits.div_ceil(8) + bits.div_ceil(8) / 2;
+            let file = Scratch::with_bytes("map.b8", &random_bytes(seed as u64 + 200, len));
+            let raw = load_b8_file(file.path()).unwrap();
+            let map = load_b8_mmap(file.path()).unwrap();
+
+            assert_eq!(map.bytes(), raw.as_raw_slice());
+            assert_eq!(map.num_shots(bits), 23);
+            assert_eq!(map.shots(bits).len(), 23);
+            let packed: Vec<Vec<u64>> = map.packed_shots(bits).collect();
+            assert_eq!(
+                packed,
+                slice_shots_packed(&raw, bits),
+                "bits_per_shot = {bits}"
+            );
+            let sparse: Vec<Vec<u32>> = map.sparse_shots(bits).collect();
+            assert_eq!(
+                sparse,
+                slice_shots_sparse(&raw, bits),
+                "bits_per_shot = {bits}"
+            );
+
+            let bytes_per_shot = bits.div_ceil(8);
+            let last = &raw.as_raw_slice()[22 * bytes_per_shot..23 * bytes_per_shot];
+            assert_eq!(map.shot(22, bits), last);
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_of_an_empty_file_has_no_shots() {
+        let file = Scratch::with_bytes("empty.b8", &[]);
+        let map = load_b8_mmap(file.path()).unwrap();
+        assert!(map.bytes().is_empty());
+        assert_eq!(map.num_shots(441), 0);
+        assert_eq!(map.packed_shots(441).count(), 0);
+    }
+
+    /// Returns the error message of a failed load, with its context.
+    fn error_of<T: fmt::Debug>(result: Result<T>) -> String {
+        format!("{:#}", result.unwrap_err())
+    }
+
+    #[test]
+    fn ascii_01_file_loads_the_same_shots_as_its_b8_twin() {
+        let bits = 75;
+        let b8 = Scratch::with_bytes("twin.b8", &random_bytes(300, 10 * 41));
+        let bools = slice_shots(&load_b8_file(b8.path()).unwrap(), bits);
+        // Mixed line endings and trailing blanks are allowed.
+        let text: String = bools
+            .iter()
+            .enumerate()
+            .map(|(i, shot)| {
+                let line: String = shot.iter().map(|&b| if b { '1' } else { '0' }).collect();
+                let end = ["\n", "\r\n", "  \n"][i % 3];
+                line + end
+            })
+            .collect();
+        let ascii = Scratch::with_bytes("twin.01", text.as_bytes());
+
+        let loaded = load_01_file(ascii.path()).unwrap();
+        assert_eq!(loaded.len(), bools.len());
+        for (shot, expected) in loaded.iter().zip(&bools) {
+            assert!(shot.iter().by_vals().eq(expected.iter().copied()));
+        }
+
+        let format = ShotFormat::from_path(ascii.path());
+        assert_eq!(format, ShotFormat::Ascii01);
+        assert_eq!(
+            load_shots_packed(ascii.path(), format, bits).unwrap(),
+            load_shots_packed(b8.path(), ShotFormat::B8, bits).unwrap()
+        );
+        assert_eq!(
+            load_shots_sparse(ascii.path(), format, bits).unwrap(),
+            load_shots_sparse(b8.path(), ShotFormat::B8, bits).unwrap()
+        );
+
+        let mut reader = Shot01Reader::open(ascii.path()).unwrap();
+        assert_eq!(reader.bits_per_shot(), None);
+        reader.next().unwrap().unwrap();
+        assert_eq!(reader.bits_per_shot(), Some(bits));
+    }
+
+    #[test]
+    fn ascii_01_errors_name_the_offending_line() {
+        let bad_char = Scratch::with_bytes("bad.01", b"0101\n0110\n01x1\n");
+        assert_eq!(
+            error_of(load_01_file(bad_char.path())),
+            "line 3, column 3: expected '0' or '1', found 'x'"
+        );
+
+        let ragged = Scratch::with_bytes("ragged.01", b"0101\n010\n");
+        assert_eq!(
+            error_of(load_01_file(ragged.path())),
+            "line 2: shot has 3 bits, but line 1 has 4"
+        );
+
+        let narrow = Scratch::with_bytes("narrow.01", b"0101\n1111\n");
+        assert_eq!(
+            error_of(load_shots_packed(narrow.path(), ShotFormat::Ascii01, 5)),
+            ".01 file has 4 bits per shot, expected 5"
+        );
+        assert!(load_shots_sparse(narrow.path(), ShotFormat::Ascii01, 4).is_ok());
+    }
+
+    #[test]
+    fn shot_format_is_guessed_from_the_extension_and_parsed_by_name() {
+        assert_eq!(ShotFormat::from_path("shots.01"), ShotFormat::Ascii01);
+        assert_eq!(ShotFormat::from_path("shots.b8"), ShotFormat::B8);
+        assert_eq!(ShotFormat::from_path("shots"), ShotFormat::B8);
+        for format in [ShotFormat::B8, ShotFormat::Ascii01] {
+            assert_eq!(format.to_string().parse::<ShotFormat>(), Ok(format));
+        }
+        assert!("b9".parse::<ShotFormat>().is_err());
+    }
+
+    #[test]
+    fn b8_writer_round_trips_through_either_write_method() {
+        for (seed, &bits) in [1usize, 3, 8, 9, 63, 64, 65, 441, 1000].iter().enumerate() {
+            let raw = BitVec::<u8, Lsb0>::from_vec(random_bytes(
+                seed as u64 + 400,
+                12 * bits.div_ceil(8),
+            ));
+            let bools = slice_shots(&raw, bits);
+            let packed = slice_shots_packed(&raw, bits);
+
+            let from_bools = Scratch::new("bools.b8");
+            let mut writer = B8Writer::create(from_bools.path(), bits).unwrap();
+            for shot in &bools {
+                writer.write_shot(shot).unwrap();
+            }
+            assert_eq!(writer.shots(), 12);
+            assert_eq!(writer.finish().unwrap(), 12);
+
+            let from_words = Scratch::new("words.b8");
+            let mut writer = B8Writer::create(from_words.path(), bits).unwrap();
+            for shot in &packed {
+                writer.write_shot_packed(shot).unwrap();
+            }
+            assert_eq!(writer.finish().unwrap(), 12);
+
+            let written = std::fs::read(from_bools.path()).unwrap();
+            assert_eq!(
+                written,
+                std::fs::read(from_words.path()).unwrap(),
+                "bits_per_shot = {bits}"
+            );
+            let reloaded = load_b8_file(from_bools.path()).unwrap();
+            assert_eq!(
+                slice_shots(&reloaded, bits),
+                bools,
+                "bits_per_shot = {bits}"
+            );
+        }
+    }
+
+    #[test]
+    fn b8_writer_ignores_bits_past_the_width_of_a_packed_shot() {
+        let file = Scratch::new("masked.b8");
+        let mut writer = B8Writer::create(file.path(), 12).unwrap();
+        writer.write_shot_packed(&[u64::MAX]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read(file.path()).unwrap(), [0xff, 0x0f]);
+    }
+
+    #[test]
+    fn b8_writer_rejects_shots_of_the_wrong_length() {
+        let file = Scratch::new("lengths.b8");
+        assert!(B8Writer::create(file.path(), 0).is_err());
+
+        let mut writer = B8Writer::create(file.path(), 65).unwrap();
+        assert_eq!(
+            error_of(writer.write_shot(&[true; 64])),
+            "shot has 64 bits, expected 65"
+        );
+        assert_eq!(
+            error_of(writer.write_shot_packed(&[0])),
+            "shot has 1 words, expected 2"
+        );
+        assert_eq!(writer.shots(), 0);
+        assert_eq!(writer.finish().unwrap(), 0);
+        assert!(std::fs::read(file.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn validate_b8_accepts_a_whole_number_of_shots() {
+        let file = Scratch::with_bytes("whole.b8", &[0; 35 * 56]);
+        let info = validate_b8(file.path(), 441).unwrap();
+        assert_eq!(
+            info,
+            B8Info {
+                shots: 35,
+                bytes_per_shot: 56,
+                file_len: 1960,
+            }
+        );
+        // Any width rounding up to the same byte count fits as well.
+        assert_eq!(validate_b8(file.path(), 448).unwrap(), info);
+    }
+
+    #[test]
+    fn validate_b8_suggests_the_detector_counts_that_fit() {
+        let file = Scratch::with_bytes("short.b8", &[0; 35 * 56]);
+        let err = validate_b8(file.path(), 440).unwrap_err();
+        let B8Error::PartialShot {
+            shots,
+            trailing_bytes,
+            ref suggestions,
+            ..
+        } = err
+        else {
+            panic!("expected PartialShot, got {err:?}");
+        };
+        assert_eq!((shots, trailing_bytes), (35, 35));
+        assert_eq!(*suggestions, [441..=448, 385..=392]);
+        assert_eq!(
+            err.to_string(),
+            "1960-byte .b8 file is not a whole number of 55-byte shots for 440 detectors \
+             (35 shots and 35 stray bytes); detector counts that fit: 441-448, 385-392"
+        );
+
+        for len in [35 * 56 - 1, 35 * 56 + 1] {
+            let file = Scratch::with_bytes("off_by_one.b8", &vec![0; len]);
+            assert!(matches!(
+                validate_b8(file.path(), 441),
+                Err(B8Error::PartialShot { trailing_bytes, .. }) if trailing_bytes != 0
+            ));
+        }
+    }
+
+    #[test]
+    fn validate_b8_says_when_nothing_nearby_fits() {
+        // 1009 is prime, so no width from 92 to 108 bytes divides it.
+        let file = Scratch::with_bytes("prime.b8", &[0; 1009]);
+        let err = validate_b8(file.path(), 800).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("; no nearby detector count fits the file size")
+        );
+    }
+
+    #[test]
+    fn validate_b8_reports_a_zero_width_and_a_missing_file() {
+        let file = Scratch::with_bytes("tiny.b8", &[0; 4]);
+        assert!(matches!(
+            validate_b8(file.path(), 0),
+            Err(B8Error::ZeroWidth)
+        ));
+
+        let missing = Scratch::new("missing.b8");
+        let err = validate_b8(missing.path(), 8).unwrap_err();
+        assert!(matches!(err, B8Error::Io(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn dets_round_trip_matches_the_b8_it_was_written_from() {
+        let bits = 100;
+        let b8 = Scratch::with_bytes("source.b8", &random_bytes(500, 40 * 13));
+        let sparse = load_shots_sparse(b8.path(), ShotFormat::B8, bits).unwrap();
+        let observables: Vec<Vec<u32>> = (0..sparse.len())
+            .map(|i| if i % 3 == 0 { vec![0, 2] } else { vec![] })
+            .collect();
+
+        let dets = Scratch::new("round_trip.dets");
+        let mut writer = DetsWriter::create(dets.path()).unwrap();
+        for (detectors, flips) in sparse.iter().zip(&observables) {
+            writer.write_shot(detectors, flips).unwrap();
+        }
+        assert_eq!(writer.shots(), 40);
+        assert_eq!(writer.finish().unwrap(), 40);
+
+        let format = ShotFormat::from_path(dets.path());
+        assert_eq!(format, ShotFormat::Dets);
+        assert_eq!(load_dets_file(dets.path(), bits).unwrap(), sparse);
+        assert_eq!(
+            load_dets_with_observables(dets.path(), bits).unwrap(),
+            DetsShots {
+                detectors: sparse.clone(),
+                observables,
+            }
+        );
+        assert_eq!(
+            load_shots_sparse(dets.path(), format, bits).unwrap(),
+            sparse
+        );
+        assert_eq!(
+            load_shots_packed(dets.path(), format, bits).unwrap(),
+            load_shots_packed(b8.path(), ShotFormat::B8, bits).unwrap()
+        );
+    }
+
+    #[test]
+    fn dets_targets_sort_in_any_order_and_blank_lines_are_skipped() {
+        let file = Scratch::with_bytes(
+            "mixed.dets",
+            b"shot L1 D7 D2 L0\n\n   \nshot\n\tshot  D0\r\n",
+        );
+        let shots: Vec<DetsShot> = DetsReader::open(file.path(), 8)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            shots,
+            [
+                DetsShot {
+                    detectors: vec![2, 7],
+                    observables: vec![0, 1],
+                },
+                DetsShot::default(),
+                DetsShot {
+                    detectors: vec![0],
+                    observables: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dets_errors_name_the_offending_line() {
+        let cases: [(&[u8], &str); 8] = [
+            (b"shots D1\n", "line 1: expected `shot`, found `shots`"),
+            (b"D1\n", "line 1: expected `shot`, found `D1`"),
+            (b"shot\n\nshot M3\n", "line 3: unknown target `M3`"),
+            (b"shot D\n", "line 1: bad index in `D`"),
+            (b"shot L-1\n", "line 1: bad index in `L-1`"),
+            (
+                b"shot D1 D10\n",
+                "line 1: detector D10 out of range for 10 detectors",
+            ),
+            (b"shot D4 D3 D4\n", "line 1: D4 listed twice"),
+            (b"shot\nshot L2 L2\n", "line 2: L2 listed twice"),
+        ];
+        for (text, message) in cases {
+            let file = Scratch::with_bytes("bad.dets", text);
+            let err = error_of(load_dets_file(file.path(), 10));
+            assert!(
+                err.starts_with(message),
+                "{err:?} should start with {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn dets_format_is_guessed_from_the_extension_and_parsed_by_name() {
+        assert_eq!(ShotFormat::from_path("events.dets"), ShotFormat::Dets);
+        assert_eq!("dets".parse::<ShotFormat>(), Ok(ShotFormat::Dets));
+        assert_eq!(ShotFormat::Dets.to_string(), "dets");
+    }
+
+    /// Collects the blocks of a parallel read, in file order, as one list
+    /// of shots, checking that each block starts where the last ended.
+    #[cfg(feature = "parallel")]
+    fn gather_blocks(
+        blocks: impl rayon::iter::IndexedParallelIterator<Item = io::Result<ShotBlock>>,
+        first: usize,
+    ) -> Vec<Vec<u64>> {
+        let blocks: Vec<ShotBlock> = blocks.collect::<io::Result<_>>().unwrap();
+        let mut next = first;
+        let mut shots = Vec::new();
+        for block in &blocks {
+            assert_eq!(block.first(), next);
+            assert!(!block.is_empty() && block.len() <= SHOTS_PER_BLOCK);
+            assert_eq!(block.shot(block.len() - 1), block.iter().last().unwrap());
+            shots.extend(block.iter().map(<[u64]>::to_vec));
+            next += block.len();
+        }
+        shots
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_shots_match_slice_shots_packed_over_several_blocks() {
+        let bits = 70;
+        let count = 2 * SHOTS_PER_BLOCK + 37;
+        let file = Scratch::with_bytes("blocks.b8", &random_bytes(600, count * 9 + 4));
+        let expected = slice_shots_packed(&load_b8_file(file.path()).unwrap(), bits);
+        assert_eq!(expected.len(), count);
+
+        assert_eq!(
+            gather_blocks(par_shots(file.path(), bits).unwrap(), 0),
+            expected
+        );
+        for range in [0..0, 5..6, 1000..1100, 100..count] {
+            let blocks = par_shot_range(file.path(), bits, range.clone()).unwrap();
+            assert_eq!(gather_blocks(blocks, range.start), expected[range]);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_shot_range_rejects_ranges_past_the_end() {
+        let file = Scratch::with_bytes("short_range.b8", &[0; 10]);
+        assert!(par_shot_range(file.path(), 8, 0..10).is_ok());
+        assert_eq!(
+            error_of(par_shot_range(file.path(), 8, 3..11).map(|_| ())),
+            "shot range 3..11 reaches past the 10 shots in the file"
+        );
+        assert!(par_shots(file.path(), 0).is_err());
+    }
+
+    #[test]
+    fn shot_reader_filter_matches_select_on_the_loaded_shots() {
+        let bits = 30;
+        let file = Scratch::with_bytes("filter.b8", &random_bytes(11, 4 * 200));
+        let shots = slice_shots_packed(&load_b8_file(file.path()).unwrap(), bits);
+
+        let filters = [
+            ShotFilter::default(),
+            ShotFilter {
+                shot_range: Some(13..170),
+                stride: 4,
+                ..Default::default()
+            },
+            ShotFilter {
+                shot_range: Some(5..usize::MAX),
+                min_defects: 17,
+                max_shots: Some(9),
+                ..Default::default()
+            },
+        ];
+        for filter in filters {
+            let expected: Vec<Vec<u64>> = filter
+                .select(shots.iter().cloned(), |shot| BitPack::count_ones(shot))
+                .collect();
+            let mut reader = ShotReader::open(file.path(), bits)
+                .unwrap()
+                .with_filter(filter.clone());
+            let mut streamed = Vec::new();
+            let mut indices = Vec::new();
+            while let Some(shot) = reader.read_shot().unwrap() {
+                streamed.push(shot.to_vec());
+                indices.push(reader.last_index().unwrap());
+            }
+            assert_eq!(streamed, expected, "{filter:?}");
+            assert!(indices.iter().all(|&i| filter.keeps_index(i)));
+            assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+
+            // A rewind replays the same selection, max_shots included.
+            reader.rewind().unwrap();
+            assert_eq!(reader.count(), expected.len());
+        }
+    }
+
+    #[test]
+    fn select_keeps_the_expected_indices() {
+        let filter = ShotFilter {
+            shot_range: Some(3..20),
+            stride: 5,
+            min_defects: 1,
+            max_shots: None,
+        };
+        // Index i has i % 4 defects, so 4, 8, 12 and 16 are dropped.
+        let kept: Vec<usize> = filter.select(0..30, |&i| i % 4).collect();
+        assert_eq!(kept, [3, 13, 18]);
+
+        let limited = ShotFilter {
+            max_shots: Some(2),
+            ..filter
+        };
+        assert_eq!(
+            limited.select(0..30, |&i| i % 4).collect::<Vec<_>>(),
+            [3, 13]
+        );
+        assert_eq!(ShotFilter::default().select(0..4, |_| 0).count(), 4);
+    }
+
+    #[test]
+    fn next_candidate_agrees_with_keeps_index() {
+        for stride in 0..5 {
+            for range in [None, Some(0..17), Some(6..30), Some(9..9)] {
+                let filter = ShotFilter {
+                    stride,
+                    shot_range: range,
+                    ..Default::default()
+                };
+                for index in 0..40 {
+                    let brute = (index..45).find(|&i| filter.keeps_index(i));
+                    let found = filter.next_candidate(index).filter(|&i| i < 45);
+                    assert_eq!(found, brute, "{filter:?} from {index}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_stride_defects_and_limits_count_as_sampling() {
+        assert!(!ShotFilter::default().is_sampling());
+        let range_only = ShotFilter {
+            shot_range: Some(10..20),
+            stride: 1,
+            ..Default::default()
+        };
+        assert!(!range_only.is_sampling());
+        for filter in [
+            ShotFilter {
+                stride: 2,
+                ..Default::default()
+            },
+            ShotFilter {
+                min_defects: 1,
+                ..Default::default()
+            },
+            ShotFilter {
+                max_shots: Some(100),
+                ..Default::default()
+            },
+        ] {
+            assert!(filter.is_sampling(), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn resolve_range_opens_the_end_and_rejects_overruns() {
+        let open = ShotFilter {
+            shot_range: Some(4..usize::MAX),
+            ..Default::default()
+        };
+        assert_eq!(open.resolve_range(10).unwrap(), 4..10);
+        assert_eq!(ShotFilter::default().resolve_range(10).unwrap(), 0..10);
+
+        let past = ShotFilter {
+            shot_range: Some(4..11),
+            ..Default::default()
+        };
+        assert_eq!(
+            error_of(past.resolve_range(10)),
+            "shot range 4..11 reaches past the 10 shots in the file"
+        );
+        let backwards = ShotFilter {
+            shot_range: Some(12..usize::MAX),
+            ..Default::default()
+        };
+        assert!(backwards.resolve_range(10).is_err());
+    }
+
+    #[test]
+    fn sha256_file_matches_known_digests_and_a_one_shot_hash() {
+        let empty = Scratch::with_bytes("empty.bin", b"");
+        assert_eq!(
+            sha256_file(empty.path()).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let abc = Scratch::with_bytes("abc.bin", b"abc");
+        assert_eq!(
+            sha256_file(abc.path()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        // Longer than one read chunk, so the digest spans several updates.
+        let bytes = random_bytes(700, 2 * CHUNK_BYTES + 123);
+        let big = Scratch::with_bytes("big.bin", &bytes);
+        let expected: String = Sha256::digest(&bytes)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_eq!(sha256_file(big.path()).unwrap(), expected);
+    }
+
+    /// Writes a small dataset and its manifest, returning the .dem, the
+    /// .b8 and the manifest files.
+    fn dataset() -> (Scratch, Scratch, Scratch, Manifest) {
+        let dem = Scratch::with_bytes("set.dem", b"error(0.01) D0 D1\n");
+        let b8 = Scratch::with_bytes("set.b8", &random_bytes(800, 64));
+        let json = Scratch(manifest_path(b8.path()));
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            generator_version: "0.1.0".into(),
+            size: 2,
+            shots: 64,
+            p: 0.01,
+            seed: 12345,
+            inject_failures: false,
+            detectors: 4,
+            dem_sha256: sha256_file(dem.path()).unwrap(),
+            b8_sha256: sha256_file(b8.path()).unwrap(),
+        };
+        manifest.write(b8.path()).unwrap();
+        (dem, b8, json, manifest)
+    }
+
+    #[test]
+    fn manifest_round_trips_and_verifies_against_its_dataset() {
+        let (dem, b8, json, manifest) = dataset();
+        assert!(json.path().to_str().unwrap().ends_with("set.b8.json"));
+        assert_eq!(
+            verify_manifest(dem.path(), b8.path()).unwrap(),
+            Some(manifest.clone())
+        );
+        assert_eq!(
+            manifest.to_string(),
+            "2x2 surface code, 4 detectors, 64 shots, p=0.01, seed 12345 (generator 0.1.0)"
+        );
+
+        std::fs::remove_file(json.path()).unwrap();
+        assert_eq!(verify_manifest(dem.path(), b8.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn manifest_names_the_file_that_changed() {
+        let (dem, b8, _json, manifest) = dataset();
+        let mut bytes = std::fs::read(b8.path()).unwrap();
+        bytes[10] ^= 1;
+        std::fs::write(b8.path(), &bytes).unwrap();
+
+        let err = verify_manifest(dem.path(), b8.path()).unwrap_err();
+        let ManifestError::Mismatch {
+            ref path,
+            ref expected,
+            ref actual,
+        } = err
+        else {
+            panic!("expected Mismatch, got {err:?}");
+        };
+        assert_eq!(path, b8.path());
+        assert_eq!(*expected, manifest.b8_sha256);
+        assert_eq!(*actual, sha256_file(b8.path()).unwrap());
+
+        std::fs::write(dem.path(), b"error(0.02) D0 D1\n").unwrap();
+        let err = verify_manifest(dem.path(), b8.path()).unwrap_err();
+        assert!(matches!(err, ManifestError::Mismatch { ref path, .. } if path == dem.path()));
+    }
+
+    #[test]
+    fn manifest_rejects_other_versions_and_malformed_json() {
+        let (dem, b8, json, manifest) = dataset();
+        let bumped = Manifest {
+            version: MANIFEST_VERSION + 1,
+            ..manifest
+        };
+        bumped.write(b8.path()).unwrap();
+        assert!(matches!(
+            verify_manifest(dem.path(), b8.path()),
+            Err(ManifestError::UnsupportedVersion { found }) if found == MANIFEST_VERSION + 1
+        ));
+
+        let text = std::fs::read_to_string(json.path()).unwrap();
+        std::fs::write(json.path(), &text[..text.len() / 2]).unwrap();
+        let err = verify_manifest(dem.path(), b8.path()).unwrap_err();
+        assert!(matches!(err, ManifestError::Parse(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn shot_reader_rejects_zero_width_shots() {
+        let file = Scratch::with_bytes("zero.b8", &[0xff; 4]);
+        assert!(ShotReader::open(file.path(), 0).is_err());
+    }
+}
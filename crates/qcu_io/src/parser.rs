@@ -3,47 +3,307 @@
 //! Provides functions for parsing Stim .dem (Detector Error Model) files,
 //! which describe the error model topology for stabilizer codes. The parser
 //! extracts edges between detector nodes and constructs a DecodingGraph structure
-//! for use by the decoder.
+//! for use by the decoder. Error mechanisms that flip three or more detectors
+//! are handled according to `ParserOptions`, and the logical observables
+//! (`L#` targets) each error flips are kept as per-edge bitmasks. `repeat`
+//! blocks and `shift_detectors` are expanded, so multi-round models load
+//! with one node per detector of every round, and `detector` declarations
+//! record coordinates and count detectors that no error touches.
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use qcu_core::graph::DecodingGraph;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::path::Path;
 
+/// How the parser treats an error mechanism that flips three or more
+/// detectors.
+///
+/// Such a mechanism is a hyperedge: no single edge of a matching graph
+/// represents it. Stim marks a suggested split into graph-like pieces with
+/// `^` separators (`decompose_errors=True`), and the parser always honours
+/// those, so the policy only applies to a piece that still has three or
+/// more detectors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HyperedgePolicy {
+    /// Fail with an error naming the line.
+    #[default]
+    Reject,
+
+    /// Split the detectors into consecutive pairs, each added as an
+    /// ordinary edge; with an odd count the last detector is left over as a
+    /// boundary piece and dropped, like single-detector errors. The pairs
+    /// flip the same detectors as the mechanism when all of them fire, but
+    /// they are weighted as independent errors, so this is an
+    /// approximation.
+    Decompose,
+
+    /// Keep the mechanism whole with `DecodingGraph::add_hyperedge`. The
+    /// union-find decoder does not use hyperedges.
+    Store,
+}
+
+/// Options for `load_dem_file_with`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParserOptions {
+    /// Treatment of error mechanisms that flip three or more detectors.
+    pub hyperedges: HyperedgePolicy,
+}
+
 /// Loads a Stim .dem file and constructs a DecodingGraph.
 ///
+/// Uses the default `ParserOptions`, which reject hyperedges; see
+/// `load_dem_file_with`.
+///
+/// # Arguments
+///
+/// * `path` - Path to the .dem file
+///
+/// # Returns
+///
+/// A DecodingGraph containing all edges from the file, or an error if parsing fails.
+pub fn load_dem_file<P: AsRef<Path>>(path: P) -> Result<DecodingGraph> {
+    load_dem_file_with(path, ParserOptions::default())
+}
+
+/// Loads a Stim .dem file with explicit parser options.
+///
 /// Parses the DEM file format, which specifies error probabilities and detector
 /// node connections. Each "error" line defines an edge in the decoding graph
 /// with an associated error probability. The probability is converted to a
 /// weight using negative log probability for use in weighted decoding algorithms.
+/// A line whose targets are split by `^` adds one piece per part, each with
+/// the line's weight. `L#` targets set bits in the piece's observable mask,
+/// and `logical_observable L#` declarations raise the graph's observable
+/// count. A piece with observables but no detectors is recorded with
+/// `add_observable_error`. Pieces with a single detector are boundary
+/// errors, which the graph cannot represent yet, and are skipped along with
+/// their observables.
+///
+/// `repeat N { ... }` blocks are expanded N times, and `shift_detectors k`
+/// adds k to every detector index that follows, so multi-round models
+/// produce one set of nodes per round. Its coordinate arguments, as in
+/// `shift_detectors(0, 0, 1) 4`, are added to the coordinates of every
+/// later `detector(x, y, t) D#` declaration, which are stored with
+/// `set_detector_coords`. Declared detectors count towards the node count
+/// even if no error touches them.
 ///
 /// # Arguments
 ///
 /// * `path` - Path to the .dem file
+/// * `options` - Treatment of hyperedges
 ///
 /// # Returns
 ///
-/// A DecodingGraph containing all edges from the file, or an error if parsing fails.
-#[allow(clippy::collapsible_if)]
-pub fn load_dem_file<P: AsRef<Path>>(path: P) -> Result<DecodingGraph> {
+/// A DecodingGraph containing all edges from the file, or an error if parsing
+/// fails, braces are unbalanced, an observable index exceeds 63, or a
+/// hyperedge is found under `HyperedgePolicy::Reject`.
+pub fn load_dem_file_with<P: AsRef<Path>>(
+    path: P,
+    options: ParserOptions,
+) -> Result<DecodingGraph> {
     let file = File::open(path).context("Failed to open .dem file")?;
-    let reader = BufReader::new(file);
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<io::Result<Vec<String>>>()
+        .context("Failed to read .dem file")?;
+    let program = parse_blocks(&lines)?;
 
     let mut graph = DecodingGraph::new(1024);
+    let mut state = DemState {
+        graph: &mut graph,
+        options,
+        detector_offset: 0,
+        coord_offset: Vec::new(),
+    };
+    state.run(&program, &lines)?;
+
+    graph.build_adjacency();
+
+    Ok(graph)
+}
+
+/// Node of a parsed .dem file.
+enum Block {
+    /// Instruction on the line with this index.
+    Line(usize),
+
+    /// `repeat` block, run `count` times.
+    Repeat {
+        /// Number of repetitions.
+        count: usize,
 
-    for line in reader.lines() {
-        let line = line?;
-        let trimmed = line.trim();
+        /// Blocks inside the braces.
+        body: Vec<Block>,
+    },
+}
+
+/// Returns the instruction text of a line, without comment or surrounding
+/// whitespace.
+fn instruction(line: &str) -> &str {
+    line.split('#').next().unwrap_or("").trim()
+}
+
+/// Groups the lines of a .dem file into nested `repeat` blocks.
+///
+/// # Arguments
+///
+/// * `lines` - Lines of the file
+///
+/// # Returns
+///
+/// The top-level blocks, or an error naming the line of a malformed
+/// `repeat`, a `}` with no open block, or a block that is never closed.
+fn parse_blocks(lines: &[String]) -> Result<Vec<Block>> {
+    // Each open block keeps the line it was opened on, its count, and the
+    // blocks of its parent collected so far.
+    let mut open: Vec<(usize, usize, Vec<Block>)> = Vec::new();
+    let mut current = Vec::new();
 
-        if trimmed.is_empty() || trimmed.starts_with('#') {
+    for (line_no, line) in lines.iter().enumerate() {
+        let text = instruction(line);
+        if text.is_empty() {
             continue;
         }
 
-        if trimmed.starts_with("error") {
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if parts.len() < 3 {
-                continue;
+        if let Some(rest) = text.strip_prefix("repeat") {
+            let count = rest
+                .strip_suffix('{')
+                .map(|count| count.trim().parse::<usize>());
+            let Some(Ok(count)) = count else {
+                bail!(
+                    "line {}: expected `repeat N {{`, got `{}`",
+                    line_no + 1,
+                    text
+                );
+            };
+            open.push((line_no, count, std::mem::take(&mut current)));
+        } else if text == "}" {
+            let Some((_, count, parent)) = open.pop() else {
+                bail!("line {}: `}}` without a matching `repeat`", line_no + 1);
+            };
+            let body = std::mem::replace(&mut current, parent);
+            current.push(Block::Repeat { count, body });
+        } else {
+            current.push(Block::Line(line_no));
+        }
+    }
+
+    if let Some(&(line_no, _, _)) = open.last() {
+        bail!("line {}: `repeat` block is never closed", line_no + 1);
+    }
+    Ok(current)
+}
+
+/// Interpreter state while a .dem file is being expanded into a graph.
+struct DemState<'a> {
+    /// Graph under construction.
+    graph: &'a mut DecodingGraph,
+
+    /// Parser options.
+    options: ParserOptions,
+
+    /// Sum of all `shift_detectors` offsets run so far, added to every
+    /// detector index. As in Stim, shifts persist past the end of the block
+    /// that made them, which is what makes each repetition of a block
+    /// address the next round's detectors.
+    detector_offset: usize,
+
+    /// Sum of all `shift_detectors` coordinate arguments run so far, added
+    /// to declared detector coordinates, component by component.
+    coord_offset: Vec<f64>,
+}
+
+impl DemState<'_> {
+    /// Runs a list of blocks in order, expanding repeats.
+    ///
+    /// # Arguments
+    ///
+    /// * `blocks` - Blocks to run
+    /// * `lines` - Lines of the file, indexed by `Block::Line`
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or the first error, tagged with its line.
+    fn run(&mut self, blocks: &[Block], lines: &[String]) -> Result<()> {
+        for block in blocks {
+            match block {
+                Block::Line(line_no) => {
+                    let text = instruction(&lines[*line_no]);
+                    self.line(text)
+                        .with_context(|| format!("line {}: {}", line_no + 1, text))?;
+                }
+                Block::Repeat { count, body } => {
+                    for _ in 0..*count {
+                        self.run(body, lines)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies one instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Instruction text, without comment
+    ///
+    /// # Returns
+    ///
+    /// Ok(()) on success, or an error describing what is wrong with it.
+    #[allow(clippy::collapsible_if)]
+    fn line(&mut self, text: &str) -> Result<()> {
+        if let Some(targets) = text.strip_prefix("logical_observable") {
+            for target in targets.split_whitespace() {
+                let k = parse_observable(target)?;
+                self.graph.declare_observables(k + 1);
+            }
+            return Ok(());
+        }
+
+        if let Some(rest) = text.strip_prefix("shift_detectors") {
+            let (coords, shift) = split_arguments(rest)?;
+            let shift = shift.trim();
+            let shift: usize = shift
+                .parse()
+                .with_context(|| format!("invalid detector shift `{}`", shift))?;
+            self.detector_offset += shift;
+            if self.coord_offset.len() < coords.len() {
+                self.coord_offset.resize(coords.len(), 0.0);
+            }
+            for (offset, coord) in self.coord_offset.iter_mut().zip(coords) {
+                *offset += coord;
+            }
+            return Ok(());
+        }
+
+        if let Some(rest) = text.strip_prefix("detector")
+            && (rest.starts_with('(') || rest.starts_with(char::is_whitespace))
+        {
+            let (mut coords, targets) = split_arguments(rest)?;
+            for (coord, offset) in coords.iter_mut().zip(&self.coord_offset) {
+                *coord += offset;
+            }
+            for target in targets.split_whitespace() {
+                let idx: usize = target
+                    .strip_prefix('D')
+                    .and_then(|idx| idx.parse().ok())
+                    .with_context(|| format!("invalid detector target `{}`", target))?;
+                let detector = idx + self.detector_offset;
+                if coords.is_empty() {
+                    self.graph.declare_detector(detector);
+                } else {
+                    self.graph.set_detector_coords(detector, &coords);
+                }
+            }
+            return Ok(());
+        }
+
+        if text.starts_with("error") {
+            let parts: Vec<&str> = text.split_whitespace().collect();
+            if parts.len() < 2 {
+                return Ok(());
             }
 
             let p_part = parts[0];
@@ -54,24 +314,452 @@ pub fn load_dem_file<P: AsRef<Path>>(path: P) -> Result<DecodingGraph> {
                 if let Ok(p) = p_part[s + 1..e].parse::<f64>() {
                     let weight = -p.ln();
 
-                    let mut detectors = Vec::new();
-                    for part in &parts[1..] {
-                        if let Some(Ok(idx)) = part.strip_prefix('D').map(|s| s.parse::<usize>()) {
-                            detectors.push(idx);
-                        }
-                    }
-
-                    if detectors.len() >= 2 {
-                        for i in 0..detectors.len() - 1 {
-                            let _ = graph.add_edge(detectors[i], detectors[i + 1], weight);
+                    for piece in parts[1..].split(|&part| part == "^") {
+                        let mut detectors = Vec::new();
+                        let mut observables = 0u64;
+                        for part in piece {
+                            if let Some(Ok(idx)) = part.strip_prefix('D').map(str::parse::<usize>) {
+                                detectors.push(idx + self.detector_offset);
+                            } else if part.starts_with('L') {
+                                observables ^= 1 << parse_observable(part)?;
+                            }
                         }
+                        let policy = self.options.hyperedges;
+                        add_piece(self.graph, &detectors, observables, weight, policy)?;
                     }
                 }
             }
         }
+        Ok(())
     }
+}
 
-    graph.build_adjacency();
+/// Splits a parenthesised argument list off the start of `text`, if any.
+///
+/// # Arguments
+///
+/// * `text` - Instruction text after its name
+///
+/// # Returns
+///
+/// The arguments and the text after the closing parenthesis, no arguments
+/// and `text` itself if it does not start with one, or an error if the list
+/// is unclosed or holds something other than numbers.
+fn split_arguments(text: &str) -> Result<(Vec<f64>, &str)> {
+    let Some(args) = text.strip_prefix('(') else {
+        return Ok((Vec::new(), text));
+    };
+    let (args, rest) = args
+        .split_once(')')
+        .context("argument list is missing its `)`")?;
+    let args = args
+        .split(',')
+        .map(|arg| {
+            let arg = arg.trim();
+            arg.parse()
+                .with_context(|| format!("invalid argument `{}`", arg))
+        })
+        .collect::<Result<_>>()?;
+    Ok((args, rest))
+}
 
-    Ok(graph)
+/// Adds one graph-like piece of an error mechanism to the graph.
+///
+/// # Arguments
+///
+/// * `graph` - Graph under construction
+/// * `detectors` - Detectors flipped by the piece
+/// * `observables` - Observables flipped by the piece, bit `k` for `Lk`
+/// * `weight` - Weight of the mechanism
+/// * `policy` - Treatment of pieces with three or more detectors
+///
+/// # Returns
+///
+/// Ok(()) on success, or an error for a hyperedge under `Reject`.
+fn add_piece(
+    graph: &mut DecodingGraph,
+    detectors: &[usize],
+    observables: u64,
+    weight: f64,
+    policy: HyperedgePolicy,
+) -> Result<()> {
+    match (detectors, policy) {
+        ([], _) => {
+            if observables != 0 {
+                graph.add_observable_error(observables, weight);
+            }
+        }
+        ([_], _) => {}
+        ([u, v], _) => {
+            let _ = graph.add_edge_with_observables(*u, *v, weight, observables);
+        }
+        (_, HyperedgePolicy::Reject) => bail!(
+            "error mechanism flips {} detectors, which is a hyperedge; decompose it \
+             (Stim's decompose_errors=True) or choose another hyperedge policy",
+            detectors.len()
+        ),
+        (_, HyperedgePolicy::Decompose) => {
+            // The first pair carries the observables, so the pairs together
+            // flip them once.
+            let mut mask = observables;
+            for pair in detectors.chunks_exact(2) {
+                let _ = graph.add_edge_with_observables(pair[0], pair[1], weight, mask);
+                mask = 0;
+            }
+        }
+        (_, HyperedgePolicy::Store) => graph.add_hyperedge(detectors, weight, observables),
+    }
+    Ok(())
+}
+
+/// Parses an `L#` observable target.
+///
+/// # Arguments
+///
+/// * `target` - Target text, such as `L0`
+///
+/// # Returns
+///
+/// The observable index, or an error if the target is malformed or the
+/// index does not fit the 64-bit observable masks.
+fn parse_observable(target: &str) -> Result<usize> {
+    let k: usize = target
+        .strip_prefix('L')
+        .and_then(|index| index.parse().ok())
+        .with_context(|| format!("invalid observable target `{}`", target))?;
+    if k >= u64::BITS as usize {
+        bail!("observable L{} exceeds the supported 64 observables", k);
+    }
+    Ok(k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Scratch;
+
+    /// Parses `text` as the contents of a .dem file.
+    fn parse(text: &str, hyperedges: HyperedgePolicy) -> Result<DecodingGraph> {
+        let file = Scratch::with_bytes("model.dem", text.as_bytes());
+        load_dem_file_with(file.path(), ParserOptions { hyperedges })
+    }
+
+    /// Parses `text`, expecting it to fail, and returns the message with
+    /// its context.
+    fn parse_error(text: &str, hyperedges: HyperedgePolicy) -> String {
+        match parse(text, hyperedges) {
+            Ok(_) => panic!("{text:?} should not parse"),
+            Err(err) => format!("{:#}", err),
+        }
+    }
+
+    /// Returns the edges of a graph as sorted (min, max) detector pairs.
+    fn pairs(graph: &DecodingGraph) -> Vec<(usize, usize)> {
+        let mut pairs: Vec<_> = graph
+            .edges()
+            .map(|(u, v, _)| (u.min(v), u.max(v)))
+            .collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn caret_pieces_become_separate_edges() {
+        let graph = parse(
+            "error(0.1) D1 D3 ^ D5\nerror(0.2) D0 D2 ^ D4 D6\nerror(0.3) D7\n",
+            HyperedgePolicy::Reject,
+        )
+        .unwrap();
+        // D3-D5 would mean one error flipping both, which the model does
+        // not say; single-detector pieces are dropped.
+        assert_eq!(pairs(&graph), [(0, 2), (1, 3), (4, 6)]);
+        assert_eq!(graph.num_hyperedges(), 0);
+        assert_eq!(graph.edge_weight(0), -0.1f64.ln());
+    }
+
+    #[test]
+    fn reject_names_the_line_of_a_hyperedge() {
+        let text = "error(0.1) D0 D1\nerror(0.1) D1 D5 D9\n";
+        let err = parse_error(text, HyperedgePolicy::Reject);
+        assert!(
+            err.starts_with("line 2: error(0.1) D1 D5 D9: error mechanism flips 3 detectors"),
+            "{err}"
+        );
+        assert!(parse(text, HyperedgePolicy::default()).is_err());
+    }
+
+    #[test]
+    fn decompose_adds_consecutive_pairs_and_drops_an_odd_one_out() {
+        let graph = parse(
+            "error(0.1) D1 D5 D9\nerror(0.2) D2 D4 D6 D8\n",
+            HyperedgePolicy::Decompose,
+        )
+        .unwrap();
+        assert_eq!(pairs(&graph), [(1, 5), (2, 4), (6, 8)]);
+        assert_eq!(graph.num_hyperedges(), 0);
+    }
+
+    #[test]
+    fn store_keeps_hyperedges_whole_through_a_reorder() {
+        let mut graph = parse(
+            "error(0.1) D4 D0\nerror(0.1) D0 D3\nerror(0.1) D3 D1\nerror(0.1) D1 D2\n\
+             error(0.25) D4 D1 D2 ^ D0 D3\n",
+            HyperedgePolicy::Store,
+        )
+        .unwrap();
+        // The graph-like piece after the ^ is an ordinary edge.
+        assert_eq!(pairs(&graph), [(0, 3), (0, 3), (0, 4), (1, 2), (1, 3)]);
+        let hyperedges: Vec<_> = graph
+            .hyperedges()
+            .map(|(nodes, weight, _)| (nodes.to_vec(), weight))
+            .collect();
+        assert_eq!(hyperedges, [(vec![4, 1, 2], -0.25f64.ln())]);
+
+        graph.reorder_for_locality();
+        assert!(graph.is_reordered());
+        let (nodes, _, _) = graph.hyperedges().next().unwrap();
+        let original: Vec<u32> = nodes.iter().map(|&n| graph.node_perm[n as usize]).collect();
+        assert_eq!(original, [4, 1, 2]);
+    }
+
+    /// Returns each edge as a sorted detector pair with its observable mask.
+    fn masks(graph: &DecodingGraph) -> Vec<((usize, usize), u64)> {
+        let mut masks: Vec<_> = graph
+            .edges()
+            .enumerate()
+            .map(|(i, (u, v, _))| ((u.min(v), u.max(v)), graph.edge_observables(i)))
+            .collect();
+        masks.sort_unstable();
+        masks
+    }
+
+    #[test]
+    fn each_piece_carries_the_observables_it_flips() {
+        let graph = parse(
+            "logical_observable L2\n\
+             error(0.1) D0 D1 L0 ^ D2 D3 L1 L0\n\
+             error(0.1) D4 D5 L1 L1\n\
+             error(0.1) D6 D7\n",
+            HyperedgePolicy::Reject,
+        )
+        .unwrap();
+        // L1 listed twice flips it twice, which is no flip.
+        assert_eq!(
+            masks(&graph),
+            [((0, 1), 0b01), ((2, 3), 0b11), ((4, 5), 0), ((6, 7), 0)]
+        );
+        assert_eq!(graph.num_observables(), 3);
+        // Masks past the last edge that has one are not stored.
+        assert_eq!(graph.edge_observables.len(), 2);
+    }
+
+    #[test]
+    fn observable_only_errors_are_kept_and_boundary_pieces_dropped() {
+        let graph = parse(
+            "error(0.2) L1\nerror(0.1) D5 L0\nerror(0.1) D0 D1\n",
+            HyperedgePolicy::Reject,
+        )
+        .unwrap();
+        assert_eq!(graph.observable_errors, [(0b10, -0.2f64.ln())]);
+        assert_eq!(masks(&graph), [((0, 1), 0)]);
+        assert_eq!(graph.num_observables(), 2);
+    }
+
+    #[test]
+    fn hyperedge_observables_follow_the_policy() {
+        let text = "error(0.1) D0 D1 D2 D3 L3\n";
+        let stored = parse(text, HyperedgePolicy::Store).unwrap();
+        let (nodes, _, observables) = stored.hyperedges().next().unwrap();
+        assert_eq!((nodes, observables), (&[0, 1, 2, 3][..], 0b1000));
+        assert_eq!(stored.num_observables(), 4);
+
+        // Only the first pair flips the observable, so the pairs together
+        // flip it once.
+        let decomposed = parse(text, HyperedgePolicy::Decompose).unwrap();
+        assert_eq!(masks(&decomposed), [((0, 1), 0b1000), ((2, 3), 0)]);
+    }
+
+    #[test]
+    fn masks_follow_edges_through_reorder_and_subgraph() {
+        let text = "error(0.1) D5 D0 L0\nerror(0.1) D0 D3\nerror(0.1) D3 D1 L1\n\
+                    error(0.1) D1 D4\nerror(0.1) D4 D2 L0 L1\n";
+        let mut graph = parse(text, HyperedgePolicy::Reject).unwrap();
+        let expected = masks(&graph);
+        graph.reorder_for_locality();
+        assert!(graph.is_reordered());
+        assert_eq!(masks(&graph), expected);
+
+        let graph = parse(text, HyperedgePolicy::Reject).unwrap();
+        let (window, _) = graph.subgraph(1..5, std::alloc::Global);
+        let local: Vec<_> = (0..window.fast_edges.len())
+            .map(|i| (window.fast_edges[i], window.edge_observables(i)))
+            .collect();
+        assert_eq!(local, [((2, 0), 0b10), ((0, 3), 0), ((3, 1), 0b11)]);
+    }
+
+    #[test]
+    fn observables_past_l63_are_rejected_with_the_line() {
+        let err = parse_error(
+            "error(0.1) D0 D1\nerror(0.1) D0 D1 L64\n",
+            HyperedgePolicy::Reject,
+        );
+        assert_eq!(
+            err,
+            "line 2: error(0.1) D0 D1 L64: observable L64 exceeds the supported 64 observables"
+        );
+        let err = parse_error("logical_observable L1 Lx\n", HyperedgePolicy::Reject);
+        assert!(err.ends_with("invalid observable target `Lx`"), "{err}");
+        assert!(parse("error(0.1) D0 D1 L63\n", HyperedgePolicy::Reject).is_ok());
+    }
+
+    #[test]
+    fn repeat_blocks_shift_each_round_onto_the_next_detectors() {
+        let graph = parse(
+            "error(0.1) D0 D1\n\
+             repeat 3 {\n\
+             \x20   error(0.1) D0 D2\n\
+             \x20   error(0.1) D1 D3\n\
+             \x20   error(0.1) D2 D3  # D4 would make this a hyperedge\n\
+             \x20   shift_detectors 2\n\
+             }\n",
+            HyperedgePolicy::Reject,
+        )
+        .unwrap();
+        let mut expected = vec![(0, 1)];
+        for k in 0..3 {
+            let o = 2 * k;
+            expected.extend([(o, o + 2), (o + 1, o + 3), (o + 2, o + 3)]);
+        }
+        expected.sort_unstable();
+        assert_eq!(pairs(&graph), expected);
+        assert_eq!(graph.num_nodes(), 8);
+    }
+
+    #[test]
+    fn nested_repeats_accumulate_shifts_that_outlive_their_block() {
+        let graph = parse(
+            "repeat 2 {\n\
+             \x20   repeat 3 {\n\
+             \x20       error(0.1) D0 D1\n\
+             \x20       shift_detectors 1\n\
+             \x20   }\n\
+             \x20   shift_detectors 10\n\
+             }\n\
+             error(0.1) D0 D1\n\
+             repeat 0 {\n\
+             \x20   error(0.1) D0 D1\n\
+             }\n",
+            HyperedgePolicy::Reject,
+        )
+        .unwrap();
+        assert_eq!(
+            pairs(&graph),
+            [
+                (0, 1),
+                (1, 2),
+                (2, 3),
+                (13, 14),
+                (14, 15),
+                (15, 16),
+                (26, 27)
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_blocks_name_their_line() {
+        let cases = [
+            (
+                "repeat x {\n}\n",
+                "line 1: expected `repeat N {`, got `repeat x {`",
+            ),
+            (
+                "repeat 3\nerror(0.1) D0 D1\n",
+                "line 1: expected `repeat N {`, got `repeat 3`",
+            ),
+            (
+                "error(0.1) D0 D1\n}\n",
+                "line 2: `}` without a matching `repeat`",
+            ),
+            (
+                "repeat 2 {\n# comment\nrepeat 2 {\n}\n",
+                "line 1: `repeat` block is never closed",
+            ),
+            (
+                "shift_detectors x\n",
+                "line 1: shift_detectors x: invalid detector shift `x`",
+            ),
+        ];
+        for (text, message) in cases {
+            let err = parse_error(text, HyperedgePolicy::Reject);
+            assert!(
+                err.starts_with(message),
+                "{err:?} should start with {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn detector_coordinates_add_up_the_shifts_before_them() {
+        let mut graph = parse(
+            "detector(0, 0, 0) D0\n\
+             detector(2, 0, 0) D1\n\
+             error(0.1) D0 D1\n\
+             repeat 2 {\n\
+             \x20   error(0.1) D0 D2\n\
+             \x20   error(0.1) D1 D3\n\
+             \x20   shift_detectors(0, 0, 1) 2\n\
+             \x20   detector(0, 0, 0) D0\n\
+             \x20   detector(2, 0, 0) D1\n\
+             \x20   error(0.1) D0 D1\n\
+             }\n\
+             detector(1, 1) D2\n\
+             detector D3\n",
+            HyperedgePolicy::Reject,
+        )
+        .unwrap();
+        let expected: [Option<&[f64]>; 9] = [
+            Some(&[0.0, 0.0, 0.0]),
+            Some(&[2.0, 0.0, 0.0]),
+            Some(&[0.0, 0.0, 1.0]),
+            Some(&[2.0, 0.0, 1.0]),
+            Some(&[0.0, 0.0, 2.0]),
+            Some(&[2.0, 0.0, 2.0]),
+            Some(&[1.0, 1.0]),
+            None,
+            None,
+        ];
+        // D6 and D7 touch no error, but the declarations still count them.
+        assert_eq!(graph.num_nodes(), 8);
+        for (detector, coords) in expected.iter().enumerate() {
+            assert_eq!(graph.detector_coords(detector), *coords, "D{detector}");
+        }
+
+        // Coordinates stay in detector numbering when the nodes move.
+        graph.reorder_for_locality();
+        for (detector, coords) in expected.iter().enumerate() {
+            assert_eq!(graph.detector_coords(detector), *coords, "D{detector}");
+        }
+    }
+
+    #[test]
+    fn malformed_detector_declarations_name_their_line() {
+        let cases = [
+            (
+                "detector(0, x) D0\n",
+                "line 1: detector(0, x) D0: invalid argument `x`: invalid float literal",
+            ),
+            (
+                "detector(0 D0\n",
+                "line 1: detector(0 D0: argument list is missing its `)`",
+            ),
+            (
+                "error(0.1) D0 D1\ndetector(0) X3\n",
+                "line 2: detector(0) X3: invalid detector target `X3`",
+            ),
+        ];
+        for (text, message) in cases {
+            assert_eq!(parse_error(text, HyperedgePolicy::Reject), message);
+        }
+    }
 }
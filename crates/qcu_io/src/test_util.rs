@@ -0,0 +1,54 @@
+//! Helpers shared by the unit tests of the loader and parser modules.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A file under the system temp directory, removed when dropped.
+///
+/// Wrap an existing path to have it cleaned up too, such as a sidecar the
+/// code under test writes next to a scratch file.
+pub(crate) struct Scratch(pub(crate) PathBuf);
+
+impl Scratch {
+    /// Returns a fresh path ending in `name`, unique within the test run.
+    pub(crate) fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let file = format!("qcu_io-{}-{n}-{name}", std::process::id());
+        Self(std::env::temp_dir().join(file))
+    }
+
+    /// Creates the file with the given contents.
+    pub(crate) fn with_bytes(name: &str, bytes: &[u8]) -> Self {
+        let scratch = Self::new(name);
+        std::fs::write(&scratch.0, bytes).unwrap();
+        scratch
+    }
+
+    /// Returns the path of the file.
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Returns `len` bytes from a seeded xorshift generator.
+///
+/// The same generator as `qcu_core`'s test `Rng`, which is test-only and so
+/// not visible from this crate.
+pub(crate) fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}